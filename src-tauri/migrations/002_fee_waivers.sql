@@ -0,0 +1,28 @@
+-- =====================================================
+-- Astana - Migration 002: Fee waivers
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: fee_waivers
+-- Description: Annual fees waived (fully or partially) per grave
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS fee_waivers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    grave_id INTEGER NOT NULL,                 -- Reference to graves table
+    year INTEGER NOT NULL,                     -- Waived fee year
+    amount INTEGER NOT NULL,                   -- Waived amount (Rupiah)
+    reason TEXT,                               -- Reason for waiver
+    approved_by TEXT,                          -- Name of approving board member
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    UNIQUE(grave_id, year)                     -- One waiver per year per grave
+);
+
+CREATE INDEX IF NOT EXISTS idx_fee_waivers_grave_id ON fee_waivers(grave_id);
+
+CREATE TRIGGER IF NOT EXISTS update_fee_waivers_timestamp 
+AFTER UPDATE ON fee_waivers
+BEGIN
+    UPDATE fee_waivers SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
@@ -0,0 +1,21 @@
+-- =====================================================
+-- Astana - Migration 003: Import mapping profiles
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: import_profiles
+-- Description: Saved column mappings for bulk import from Excel/CSV
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS import_profiles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,                 -- Profile name (e.g. "Buku Induk Blok A")
+    mapping TEXT NOT NULL,                     -- Column mapping as JSON
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER IF NOT EXISTS update_import_profiles_timestamp 
+AFTER UPDATE ON import_profiles
+BEGIN
+    UPDATE import_profiles SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
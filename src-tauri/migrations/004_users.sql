@@ -0,0 +1,26 @@
+-- =====================================================
+-- Astana - Migration 004: User accounts and roles
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: users
+-- Description: Local staff accounts for login and access control
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE COLLATE NOCASE, -- Login name
+    full_name TEXT NOT NULL,                   -- Display name
+    password_hash TEXT NOT NULL,               -- Argon2 PHC string (includes salt)
+    role TEXT NOT NULL DEFAULT 'viewer'        -- admin, cashier, clerk, viewer
+        CHECK (role IN ('admin', 'cashier', 'clerk', 'viewer')),
+    is_active INTEGER NOT NULL DEFAULT 1,      -- 0 = disabled, 1 = active
+    last_login TIMESTAMP,                      -- Last successful login
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER IF NOT EXISTS update_users_timestamp 
+AFTER UPDATE ON users
+BEGIN
+    UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
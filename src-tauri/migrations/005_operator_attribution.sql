@@ -0,0 +1,20 @@
+-- =====================================================
+-- Astana - Migration 005: Operator attribution
+-- =====================================================
+-- Records which staff member (users.id) created or last changed a row.
+-- Rows created before this migration keep NULL.
+
+ALTER TABLE blocks ADD COLUMN created_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE blocks ADD COLUMN updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+ALTER TABLE graves ADD COLUMN created_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE graves ADD COLUMN updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+ALTER TABLE heirs ADD COLUMN created_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE heirs ADD COLUMN updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+ALTER TABLE payments ADD COLUMN created_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE payments ADD COLUMN updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Cash reconciliation per cashier
+CREATE INDEX IF NOT EXISTS idx_payments_created_by ON payments(created_by, payment_date);
@@ -0,0 +1,41 @@
+-- =====================================================
+-- Astana - Migration 006: Cash sessions (daily cash closing)
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: cash_sessions
+-- Description: Cash collected by one cashier between opening and closing
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS cash_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    opened_by INTEGER NOT NULL,                -- Cashier (users.id)
+    opened_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    opening_float INTEGER NOT NULL DEFAULT 0,  -- Change money at opening
+    status TEXT NOT NULL DEFAULT 'open'        -- open, closed
+        CHECK (status IN ('open', 'closed')),
+    closed_by INTEGER,                         -- User who closed the session
+    closed_at TIMESTAMP,
+    expected_amount INTEGER,                   -- Opening float + cash payments
+    counted_amount INTEGER,                    -- Cash counted at closing
+    difference INTEGER,                        -- counted - expected
+    notes TEXT,                                -- Closing notes
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (opened_by) REFERENCES users(id) ON DELETE RESTRICT,
+    FOREIGN KEY (closed_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- One open session per cashier
+CREATE UNIQUE INDEX IF NOT EXISTS idx_cash_sessions_open
+    ON cash_sessions(opened_by) WHERE status = 'open';
+
+CREATE TRIGGER IF NOT EXISTS update_cash_sessions_timestamp 
+AFTER UPDATE ON cash_sessions
+BEGIN
+    UPDATE cash_sessions SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Cash payments are attached to the cashier's open session
+ALTER TABLE payments ADD COLUMN cash_session_id INTEGER REFERENCES cash_sessions(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_payments_cash_session ON payments(cash_session_id);
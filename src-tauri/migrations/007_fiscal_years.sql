@@ -0,0 +1,49 @@
+-- =====================================================
+-- Astana - Migration 007: Fiscal year closing
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: closed_years
+-- Description: Closed fiscal years with the signed-off report snapshot
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS closed_years (
+    year INTEGER PRIMARY KEY,                  -- Closed fiscal year
+    closed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    closed_by INTEGER,                         -- User who closed the year
+    report_snapshot TEXT NOT NULL,             -- YearlyReport as JSON
+    total_arrears INTEGER NOT NULL DEFAULT 0,  -- Arrears carried into next year
+    notes TEXT,
+    FOREIGN KEY (closed_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- -----------------------------------------------------
+-- Table: carried_arrears
+-- Description: Outstanding balance per grave carried into a new year
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS carried_arrears (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    year INTEGER NOT NULL,                     -- Year the balance is carried into
+    grave_id INTEGER NOT NULL,
+    amount INTEGER NOT NULL,                   -- Arrears up to and including year - 1
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    UNIQUE(year, grave_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_carried_arrears_grave ON carried_arrears(grave_id);
+
+-- -----------------------------------------------------
+-- Table: payment_lock_overrides
+-- Description: Audit log of admin changes to payments in closed years
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS payment_lock_overrides (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    payment_id INTEGER NOT NULL,               -- No FK: payment may be deleted
+    year INTEGER NOT NULL,                     -- Closed year of the payment
+    action TEXT NOT NULL CHECK (action IN ('update', 'delete')),
+    reason TEXT NOT NULL,
+    payment_snapshot TEXT NOT NULL,            -- Payment as JSON before the change
+    overridden_by INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (overridden_by) REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,28 @@
+-- =====================================================
+-- Astana - Migration 008: Plot layout per block
+-- =====================================================
+-- Blocks get an optional grid (rows x columns) and graves an optional
+-- position in that grid. Positions are 1-based.
+
+ALTER TABLE blocks ADD COLUMN layout_rows INTEGER;       -- NULL = no layout
+ALTER TABLE blocks ADD COLUMN layout_columns INTEGER;
+
+ALTER TABLE graves ADD COLUMN plot_row INTEGER;          -- NULL = not placed
+ALTER TABLE graves ADD COLUMN plot_column INTEGER;
+
+-- One grave per cell
+CREATE UNIQUE INDEX IF NOT EXISTS idx_graves_plot
+    ON graves(block_id, plot_row, plot_column) WHERE plot_row IS NOT NULL;
+
+-- -----------------------------------------------------
+-- Table: block_unusable_cells
+-- Description: Grid cells that cannot hold a grave (paths, trees, walls)
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS block_unusable_cells (
+    block_id INTEGER NOT NULL,                 -- Reference to blocks table
+    plot_row INTEGER NOT NULL,
+    plot_column INTEGER NOT NULL,
+    notes TEXT,                                -- e.g. "Jalan setapak"
+    PRIMARY KEY (block_id, plot_row, plot_column),
+    FOREIGN KEY (block_id) REFERENCES blocks(id) ON DELETE CASCADE
+);
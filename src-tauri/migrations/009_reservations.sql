@@ -0,0 +1,46 @@
+-- =====================================================
+-- Astana - Migration 009: Plot reservations
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: reservations
+-- Description: Plots held for living people (e.g. next to a spouse)
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS reservations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_id INTEGER NOT NULL,                 -- Reference to blocks table
+    grave_number TEXT,                         -- Reserved grave number (optional)
+    plot_row INTEGER,                          -- Reserved cell on the block map (optional)
+    plot_column INTEGER,
+    reserved_for TEXT,                         -- Living person the plot is held for
+    reserver_name TEXT NOT NULL,               -- Contact person who made the reservation
+    phone_number TEXT,                         -- Contact phone
+    address TEXT,
+    relationship TEXT,                         -- Reserver's relationship to `reserved_for`
+    reserved_until DATE,                       -- NULL = no expiry
+    deposit_amount INTEGER NOT NULL DEFAULT 0, -- Deposit paid (Rupiah)
+    status TEXT NOT NULL DEFAULT 'active'      -- active, converted, cancelled, expired
+        CHECK (status IN ('active', 'converted', 'cancelled', 'expired')),
+    grave_id INTEGER,                          -- Grave created when converted
+    notes TEXT,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (block_id) REFERENCES blocks(id) ON DELETE RESTRICT,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE SET NULL,
+    CHECK (grave_number IS NOT NULL OR plot_row IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_reservations_block ON reservations(block_id, status);
+
+-- One active reservation per cell
+CREATE UNIQUE INDEX IF NOT EXISTS idx_reservations_plot
+    ON reservations(block_id, plot_row, plot_column)
+    WHERE status = 'active' AND plot_row IS NOT NULL;
+
+CREATE TRIGGER IF NOT EXISTS update_reservations_timestamp
+AFTER UPDATE ON reservations
+BEGIN
+    UPDATE reservations SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
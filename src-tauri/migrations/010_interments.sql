@@ -0,0 +1,59 @@
+-- =====================================================
+-- Astana - Migration 010: Multiple interments per grave (tumpang)
+-- =====================================================
+-- A grave (plot) can hold several people buried over time. Fees, heirs and
+-- payments stay on the grave. The first interment mirrors the grave's own
+-- deceased_name / date_of_death / burial_date, which keep driving lists,
+-- search and the first billable year.
+
+-- -----------------------------------------------------
+-- Table: interments
+-- Description: People buried in a grave, in burial order
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS interments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    grave_id INTEGER NOT NULL,                 -- Reference to graves table
+    sequence INTEGER NOT NULL,                 -- 1 = first person buried
+    deceased_name TEXT NOT NULL,
+    date_of_death DATE NOT NULL,
+    burial_date DATE,
+    notes TEXT,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    UNIQUE(grave_id, sequence)
+);
+
+CREATE INDEX IF NOT EXISTS idx_interments_name ON interments(deceased_name);
+
+-- Existing graves become their own first interment
+INSERT INTO interments (grave_id, sequence, deceased_name, date_of_death, burial_date, created_by, created_at)
+SELECT id, 1, deceased_name, date_of_death, burial_date, created_by, created_at FROM graves;
+
+CREATE TRIGGER IF NOT EXISTS update_interments_timestamp
+AFTER UPDATE ON interments
+BEGIN
+    UPDATE interments SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Every new grave starts with its first interment
+CREATE TRIGGER IF NOT EXISTS create_first_interment
+AFTER INSERT ON graves
+BEGIN
+    INSERT INTO interments (grave_id, sequence, deceased_name, date_of_death, burial_date, created_by)
+    VALUES (NEW.id, 1, NEW.deceased_name, NEW.date_of_death, NEW.burial_date, NEW.created_by);
+END;
+
+-- Keep the first interment in step with the grave
+CREATE TRIGGER IF NOT EXISTS sync_first_interment
+AFTER UPDATE OF deceased_name, date_of_death, burial_date ON graves
+BEGIN
+    UPDATE interments SET
+        deceased_name = NEW.deceased_name,
+        date_of_death = NEW.date_of_death,
+        burial_date = NEW.burial_date
+    WHERE id = (SELECT id FROM interments WHERE grave_id = NEW.id ORDER BY sequence LIMIT 1);
+END;
+
@@ -0,0 +1,30 @@
+-- =====================================================
+-- Astana - Migration 011: Grave relocation history
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: grave_relocations
+-- Description: Moves of a grave to another block, number or plot cell
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS grave_relocations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    grave_id INTEGER NOT NULL,                 -- Reference to graves table
+    from_block_id INTEGER NOT NULL,
+    from_number TEXT NOT NULL,
+    from_plot_row INTEGER,
+    from_plot_column INTEGER,
+    to_block_id INTEGER NOT NULL,
+    to_number TEXT NOT NULL,
+    to_plot_row INTEGER,
+    to_plot_column INTEGER,
+    relocation_date DATE NOT NULL,             -- Date of the move
+    reason TEXT NOT NULL,
+    authorized_by TEXT NOT NULL,               -- Name of authorizing board member
+    recorded_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    FOREIGN KEY (from_block_id) REFERENCES blocks(id) ON DELETE RESTRICT,
+    FOREIGN KEY (to_block_id) REFERENCES blocks(id) ON DELETE RESTRICT
+);
+
+CREATE INDEX IF NOT EXISTS idx_grave_relocations_grave ON grave_relocations(grave_id, relocation_date);
@@ -0,0 +1,23 @@
+-- =====================================================
+-- Astana - Migration 012: Block capacity enforcement
+-- =====================================================
+
+-- -----------------------------------------------------
+-- Table: capacity_overrides
+-- Description: Audit log of admin placements into full or inactive blocks
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS capacity_overrides (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_id INTEGER NOT NULL,                 -- Block the grave was placed in
+    grave_id INTEGER,                          -- NULL once the grave is deleted
+    action TEXT NOT NULL CHECK (action IN ('create', 'relocate')),
+    violation TEXT NOT NULL CHECK (violation IN ('full', 'inactive')),
+    reason TEXT NOT NULL,
+    overridden_by INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (block_id) REFERENCES blocks(id) ON DELETE CASCADE,
+    FOREIGN KEY (grave_id) REFERENCES graves(id) ON DELETE SET NULL,
+    FOREIGN KEY (overridden_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_capacity_overrides_block ON capacity_overrides(block_id);
@@ -0,0 +1,10 @@
+-- =====================================================
+-- Astana - Migration 013: Grave numbering scheme per block
+-- =====================================================
+-- numeric: 1, 2, 3 ...
+-- padded:  zero-padded to number_width digits (001, 002 ...)
+-- suffix:  padded base with a letter (05A, 05B ...); new plots start at A
+
+ALTER TABLE blocks ADD COLUMN number_scheme TEXT NOT NULL DEFAULT 'numeric'
+    CHECK (number_scheme IN ('numeric', 'padded', 'suffix'));
+ALTER TABLE blocks ADD COLUMN number_width INTEGER NOT NULL DEFAULT 0;  -- Digits, 0 = no padding
@@ -0,0 +1,85 @@
+-- =====================================================
+-- Astana - Migration 014: Shared persons for heirs
+-- =====================================================
+-- One person can be heir of several graves. Heir rows keep the per-grave
+-- relationship and order plus a copy of the person's name, phone and
+-- address; `person_id` links them. New heirs are linked to an existing
+-- person with the same match key, otherwise a new person is created.
+--
+-- Match key: the lower-cased name with the phone number, or (without a
+-- phone number) with the lower-cased address. A family often
+-- shares one phone number, so the phone alone does not make a person.
+-- The phone number counts in its E.164 form (`phone_e164`, filled from
+-- migration 015 on) and otherwise as typed without separators.
+
+-- -----------------------------------------------------
+-- Table: persons
+-- Description: Heirs and contacts shared across graves
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS persons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    full_name TEXT NOT NULL,
+    phone_number TEXT,                         -- Phone/WhatsApp number as typed
+    phone_e164 TEXT,                           -- Same number as +62..., set by the application
+    address TEXT,
+    notes TEXT,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    match_key TEXT GENERATED ALWAYS AS (
+        CASE
+            WHEN phone_e164 IS NOT NULL THEN 'p:' || phone_e164 || '|' || lower(trim(full_name))
+            WHEN replace(replace(replace(replace(replace(replace(COALESCE(phone_number, ''), ' ', ''), '-', ''), '.', ''), '+', ''), '(', ''), ')', '') <> ''
+            THEN 'p:' || replace(replace(replace(replace(replace(replace(phone_number, ' ', ''), '-', ''), '.', ''), '+', ''), '(', ''), ')', '') || '|' || lower(trim(full_name))
+            ELSE 'n:' || lower(trim(full_name)) || '|' || lower(trim(COALESCE(address, '')))
+        END
+    ) VIRTUAL
+);
+
+CREATE INDEX IF NOT EXISTS idx_persons_name ON persons(full_name);
+CREATE INDEX IF NOT EXISTS idx_persons_match_key ON persons(match_key);
+
+ALTER TABLE heirs ADD COLUMN person_id INTEGER REFERENCES persons(id) ON DELETE SET NULL;
+ALTER TABLE heirs ADD COLUMN phone_e164 TEXT;
+ALTER TABLE heirs ADD COLUMN person_key TEXT GENERATED ALWAYS AS (
+    CASE
+        WHEN phone_e164 IS NOT NULL THEN 'p:' || phone_e164 || '|' || lower(trim(full_name))
+        WHEN replace(replace(replace(replace(replace(replace(COALESCE(phone_number, ''), ' ', ''), '-', ''), '.', ''), '+', ''), '(', ''), ')', '') <> ''
+        THEN 'p:' || replace(replace(replace(replace(replace(replace(phone_number, ' ', ''), '-', ''), '.', ''), '+', ''), '(', ''), ')', '') || '|' || lower(trim(full_name))
+        ELSE 'n:' || lower(trim(full_name)) || '|' || lower(trim(COALESCE(address, '')))
+    END
+) VIRTUAL;
+CREATE INDEX IF NOT EXISTS idx_heirs_person ON heirs(person_id);
+
+-- Existing heirs: one person per match key, reusing the lowest heir ID of
+-- the group as person ID
+INSERT INTO persons (id, full_name, phone_number, address, created_by, created_at)
+SELECT h.id, h.full_name, NULLIF(h.phone_number, ''), NULLIF(h.address, ''), h.created_by, h.created_at
+FROM heirs h
+WHERE h.id IN (SELECT MIN(id) FROM heirs GROUP BY person_key);
+
+UPDATE heirs SET person_id = (
+    SELECT p.id FROM persons p WHERE p.match_key = heirs.person_key ORDER BY p.id LIMIT 1
+);
+
+CREATE TRIGGER IF NOT EXISTS update_persons_timestamp
+AFTER UPDATE ON persons
+BEGIN
+    UPDATE persons SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Link every new heir to a person
+CREATE TRIGGER IF NOT EXISTS link_heir_person
+AFTER INSERT ON heirs
+WHEN NEW.person_id IS NULL
+BEGIN
+    INSERT INTO persons (full_name, phone_number, phone_e164, address, created_by)
+    SELECT NEW.full_name, NULLIF(NEW.phone_number, ''), NEW.phone_e164, NULLIF(NEW.address, ''), NEW.created_by
+    WHERE NOT EXISTS (SELECT 1 FROM persons p WHERE p.match_key = NEW.person_key);
+
+    UPDATE heirs SET person_id = (
+        SELECT p.id FROM persons p WHERE p.match_key = NEW.person_key ORDER BY p.id LIMIT 1
+    )
+    WHERE id = NEW.id;
+END;
@@ -0,0 +1,29 @@
+-- =====================================================
+-- Astana - Migration 015: Phone number normalization
+-- =====================================================
+-- Heirs and persons keep the phone number as typed for display and its
+-- E.164 form (+628...) in `phone_e164` (added by migration 014). Existing
+-- numbers are normalized by the data migration that runs right after this
+-- script; heir numbers it cannot parse are listed in `phone_number_issues`.
+--
+-- Person matching prefers the E.164 number, so "0812..." and "+62 812..."
+-- are the same person; persons migration 014 grouped apart by the typed
+-- number are merged by the data migration.
+
+DROP INDEX IF EXISTS idx_heirs_phone;
+CREATE INDEX IF NOT EXISTS idx_heirs_phone ON heirs(phone_e164);
+CREATE INDEX IF NOT EXISTS idx_persons_phone ON persons(phone_e164);
+
+-- -----------------------------------------------------
+-- Table: phone_number_issues
+-- Description: Stored heir phone numbers that could not be normalized
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS phone_number_issues (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    heir_id INTEGER NOT NULL REFERENCES heirs(id) ON DELETE CASCADE,
+    phone_number TEXT NOT NULL,                -- Number as stored
+    error TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_phone_number_issues_heir ON phone_number_issues(heir_id);
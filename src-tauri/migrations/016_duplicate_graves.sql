@@ -0,0 +1,39 @@
+-- =====================================================
+-- Astana - Migration 016: Duplicate grave review
+-- =====================================================
+-- Candidate duplicates are computed on request. Pairs reviewed as "not a
+-- duplicate" are remembered so they are not suggested again, and every
+-- merge keeps a snapshot of the removed grave.
+
+-- -----------------------------------------------------
+-- Table: duplicate_dismissals
+-- Description: Grave pairs marked as not duplicates
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS duplicate_dismissals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    first_grave_id INTEGER NOT NULL,           -- Lower grave ID of the pair
+    second_grave_id INTEGER NOT NULL,
+    dismissed_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (first_grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    FOREIGN KEY (second_grave_id) REFERENCES graves(id) ON DELETE CASCADE,
+    CHECK (first_grave_id < second_grave_id),
+    UNIQUE(first_grave_id, second_grave_id)
+);
+
+-- -----------------------------------------------------
+-- Table: grave_merges
+-- Description: Audit log of duplicate graves merged into another grave
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS grave_merges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kept_grave_id INTEGER REFERENCES graves(id) ON DELETE SET NULL,
+    removed_grave_id INTEGER NOT NULL,         -- No FK: the grave is deleted
+    removed_snapshot TEXT NOT NULL,            -- Removed grave as JSON
+    heirs_moved INTEGER NOT NULL DEFAULT 0,
+    payments_moved INTEGER NOT NULL DEFAULT 0,
+    merged_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_grave_merges_kept ON grave_merges(kept_grave_id);
@@ -0,0 +1,158 @@
+-- =====================================================
+-- Astana - Migration 017: Full-text search
+-- =====================================================
+-- FTS5 indexes over graves, heirs and payments. The rowid of each index row
+-- is the ID of the record it indexes; triggers keep them in sync.
+--
+-- Phone numbers are indexed as typed, in E.164 and in national form
+-- (0812...), so any of them can be searched by prefix.
+
+-- -----------------------------------------------------
+-- Index: graves_fts
+-- Description: Deceased names (all interments), grave number and notes
+-- -----------------------------------------------------
+CREATE VIRTUAL TABLE IF NOT EXISTS graves_fts USING fts5(
+    deceased_name,
+    number,
+    notes,
+    interments,                                -- Names of later interments
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- -----------------------------------------------------
+-- Index: heirs_fts
+-- Description: Heir names, phone numbers and addresses
+-- -----------------------------------------------------
+CREATE VIRTUAL TABLE IF NOT EXISTS heirs_fts USING fts5(
+    full_name,
+    phone,
+    address,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- -----------------------------------------------------
+-- Index: payments_fts
+-- Description: Payer names and payment notes
+-- -----------------------------------------------------
+CREATE VIRTUAL TABLE IF NOT EXISTS payments_fts USING fts5(
+    paid_by,
+    notes,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Existing data
+INSERT INTO graves_fts (rowid, deceased_name, number, notes, interments)
+SELECT g.id, g.deceased_name, g.number, g.notes,
+       (SELECT group_concat(i.deceased_name, ' ') FROM interments i WHERE i.grave_id = g.id AND i.sequence > 1)
+FROM graves g;
+
+INSERT INTO heirs_fts (rowid, full_name, phone, address)
+SELECT id, full_name,
+       trim(COALESCE(phone_number, '') || ' ' || COALESCE(phone_e164 || ' 0' || substr(phone_e164, 4), '')),
+       address
+FROM heirs;
+
+INSERT INTO payments_fts (rowid, paid_by, notes)
+SELECT id, paid_by, notes FROM payments;
+
+-- Graves
+CREATE TRIGGER IF NOT EXISTS graves_fts_insert
+AFTER INSERT ON graves
+BEGIN
+    INSERT INTO graves_fts (rowid, deceased_name, number, notes, interments)
+    VALUES (NEW.id, NEW.deceased_name, NEW.number, NEW.notes, NULL);
+END;
+
+CREATE TRIGGER IF NOT EXISTS graves_fts_update
+AFTER UPDATE OF deceased_name, number, notes ON graves
+BEGIN
+    UPDATE graves_fts SET deceased_name = NEW.deceased_name, number = NEW.number, notes = NEW.notes
+    WHERE rowid = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS graves_fts_delete
+AFTER DELETE ON graves
+BEGIN
+    DELETE FROM graves_fts WHERE rowid = OLD.id;
+END;
+
+-- Later interments are part of their grave's row
+CREATE TRIGGER IF NOT EXISTS interments_fts_insert
+AFTER INSERT ON interments
+WHEN NEW.sequence > 1
+BEGIN
+    UPDATE graves_fts SET interments = (
+        SELECT group_concat(deceased_name, ' ') FROM interments WHERE grave_id = NEW.grave_id AND sequence > 1
+    )
+    WHERE rowid = NEW.grave_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS interments_fts_update
+AFTER UPDATE OF grave_id, sequence, deceased_name ON interments
+BEGIN
+    UPDATE graves_fts SET interments = (
+        SELECT group_concat(deceased_name, ' ') FROM interments WHERE grave_id = graves_fts.rowid AND sequence > 1
+    )
+    WHERE rowid IN (OLD.grave_id, NEW.grave_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS interments_fts_delete
+AFTER DELETE ON interments
+WHEN OLD.sequence > 1
+BEGIN
+    UPDATE graves_fts SET interments = (
+        SELECT group_concat(deceased_name, ' ') FROM interments WHERE grave_id = OLD.grave_id AND sequence > 1
+    )
+    WHERE rowid = OLD.grave_id;
+END;
+
+-- Heirs
+CREATE TRIGGER IF NOT EXISTS heirs_fts_insert
+AFTER INSERT ON heirs
+BEGIN
+    INSERT INTO heirs_fts (rowid, full_name, phone, address)
+    VALUES (
+        NEW.id,
+        NEW.full_name,
+        trim(COALESCE(NEW.phone_number, '') || ' ' || COALESCE(NEW.phone_e164 || ' 0' || substr(NEW.phone_e164, 4), '')),
+        NEW.address
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS heirs_fts_update
+AFTER UPDATE OF full_name, phone_number, phone_e164, address ON heirs
+BEGIN
+    UPDATE heirs_fts SET
+        full_name = NEW.full_name,
+        phone = trim(COALESCE(NEW.phone_number, '') || ' ' || COALESCE(NEW.phone_e164 || ' 0' || substr(NEW.phone_e164, 4), '')),
+        address = NEW.address
+    WHERE rowid = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS heirs_fts_delete
+AFTER DELETE ON heirs
+BEGIN
+    DELETE FROM heirs_fts WHERE rowid = OLD.id;
+END;
+
+-- Payments
+CREATE TRIGGER IF NOT EXISTS payments_fts_insert
+AFTER INSERT ON payments
+BEGIN
+    INSERT INTO payments_fts (rowid, paid_by, notes) VALUES (NEW.id, NEW.paid_by, NEW.notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS payments_fts_update
+AFTER UPDATE OF paid_by, notes ON payments
+BEGIN
+    UPDATE payments_fts SET paid_by = NEW.paid_by, notes = NEW.notes WHERE rowid = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS payments_fts_delete
+AFTER DELETE ON payments
+BEGIN
+    DELETE FROM payments_fts WHERE rowid = OLD.id;
+END;
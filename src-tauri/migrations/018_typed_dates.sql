@@ -0,0 +1,47 @@
+-- =====================================================
+-- Astana - Migration 018: Typed dates and local timezone
+-- =====================================================
+-- Calendar dates are stored as ISO `YYYY-MM-DD`. Older rows may hold other
+-- notations (31/12/2023, 2023-12-31 00:00:00, 5 Maret 2020, ...); they are
+-- rewritten by the data migration that runs right after this script. Dates
+-- it cannot read are listed in `date_issues` together with the value they
+-- were replaced with.
+--
+-- Timestamps (`created_at`, `updated_at`, ...) stay SQLite
+-- `CURRENT_TIMESTAMP` values, which are UTC. "Today", "this month" and
+-- "this year" are computed in the timezone configured in `settings`.
+
+-- Indonesian timezone (IANA name): Asia/Jakarta (WIB), Asia/Makassar (WITA),
+-- Asia/Jayapura (WIT)
+ALTER TABLE settings ADD COLUMN timezone TEXT NOT NULL DEFAULT 'Asia/Jakarta';
+
+-- -----------------------------------------------------
+-- View: local_clock
+-- Description: Current time in the configured timezone, for SQL that
+-- compares against "today". Offsets must match `TIMEZONES` in db/dates.rs.
+-- -----------------------------------------------------
+CREATE VIEW IF NOT EXISTS local_clock AS
+SELECT
+    datetime('now', CASE timezone
+        WHEN 'Asia/Makassar' THEN '+8 hours'
+        WHEN 'Asia/Jayapura' THEN '+9 hours'
+        ELSE '+7 hours'
+    END) AS now
+FROM settings
+WHERE id = 1;
+
+-- -----------------------------------------------------
+-- Table: date_issues
+-- Description: Stored dates that could not be read as a date
+-- -----------------------------------------------------
+CREATE TABLE IF NOT EXISTS date_issues (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,                  -- graves, payments, interments, ...
+    row_id INTEGER NOT NULL,
+    column_name TEXT NOT NULL,
+    stored_value TEXT NOT NULL,                -- Value before the migration
+    replaced_with TEXT,                        -- Fallback date written, NULL = cleared
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_date_issues_row ON date_issues(table_name, row_id);
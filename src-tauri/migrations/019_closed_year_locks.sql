@@ -0,0 +1,26 @@
+-- =====================================================
+-- Astana - Migration 019: Closed year locks on every payment write
+-- =====================================================
+-- Closed years also lock new payments and fee waivers, not only changes
+-- to existing payments. The override log now records which kind of record
+-- an admin wrote and includes creations.
+
+CREATE TABLE IF NOT EXISTS payment_lock_overrides_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    record_type TEXT NOT NULL DEFAULT 'payment' CHECK (record_type IN ('payment', 'fee_waiver')),
+    record_id INTEGER NOT NULL,                -- No FK: record may be deleted
+    year INTEGER NOT NULL,                     -- Closed year of the record
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    reason TEXT NOT NULL,
+    snapshot TEXT NOT NULL,                    -- Record as JSON before the change (after, for create)
+    overridden_by INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (overridden_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO payment_lock_overrides_new (id, record_type, record_id, year, action, reason, snapshot, overridden_by, created_at)
+SELECT id, 'payment', payment_id, year, action, reason, payment_snapshot, overridden_by, created_at
+FROM payment_lock_overrides;
+
+DROP TABLE payment_lock_overrides;
+ALTER TABLE payment_lock_overrides_new RENAME TO payment_lock_overrides;
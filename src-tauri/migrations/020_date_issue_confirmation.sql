@@ -0,0 +1,10 @@
+-- =====================================================
+-- Astana - Migration 020: Confirming unreadable dates
+-- =====================================================
+-- Dates migration 018 could not read stay open until staff confirm the
+-- date written in their place or enter the right one. A grave whose date
+-- of death was replaced takes no payments, fee waivers or relocations
+-- until then, as its fees start from that date.
+
+ALTER TABLE date_issues ADD COLUMN resolved_at TIMESTAMP;
+ALTER TABLE date_issues ADD COLUMN resolved_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
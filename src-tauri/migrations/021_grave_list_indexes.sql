@@ -0,0 +1,12 @@
+-- =====================================================
+-- Astana - Migration 021: Grave listing sort indexes
+-- =====================================================
+-- One index per sort order of the data makam page, on the same
+-- expressions as its sort key, so a page is read in index order and a
+-- keyset cursor seeks to its position instead of sorting the whole list.
+-- Date of death is covered by idx_graves_date_of_death.
+
+CREATE INDEX IF NOT EXISTS idx_graves_list_number
+    ON graves(block_id, CAST(number AS INTEGER), length(number), number);
+CREATE INDEX IF NOT EXISTS idx_graves_list_name ON graves(deceased_name COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS idx_graves_list_burial ON graves(COALESCE(burial_date, '9999-12-31'));
@@ -0,0 +1,128 @@
+//! Login session and role-based permissions
+//!
+//! The session lives in Tauri managed state; mutating commands call
+//! [`SessionState::require`] with the permission they need.
+
+use std::sync::Mutex;
+
+use crate::db::{Role, User};
+
+/// Actions guarded by role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Manage user accounts
+    ManageUsers,
+    /// Change settings, back up, restore or merge data
+    ManageSettings,
+    /// Delete blocks
+    DeleteBlock,
+    /// Create and update blocks
+    ManageBlocks,
+    /// Create, update and delete graves and heirs, bulk import
+    ManageGraves,
+    /// Record, update and delete payments and fee waivers
+    ManagePayments,
+}
+
+impl Permission {
+    /// Description used in "permission denied" messages
+    fn description(&self) -> &'static str {
+        match self {
+            Permission::ManageUsers => "manage users",
+            Permission::ManageSettings => "change settings or restore data",
+            Permission::DeleteBlock => "delete blocks",
+            Permission::ManageBlocks => "manage blocks",
+            Permission::ManageGraves => "manage grave data",
+            Permission::ManagePayments => "record payments",
+        }
+    }
+}
+
+impl Role {
+    /// Check whether this role grants a permission
+    pub fn allows(&self, permission: Permission) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Cashier => matches!(permission, Permission::ManagePayments),
+            Role::Clerk => matches!(permission, Permission::ManageBlocks | Permission::ManageGraves),
+            Role::Viewer => false,
+        }
+    }
+}
+
+/// Logged-in user
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub user_id: i64,
+    pub username: String,
+    pub full_name: String,
+    pub role: Role,
+    pub started_at: String,
+}
+
+impl Session {
+    pub fn new(user: &User) -> Self {
+        Self {
+            user_id: user.id,
+            username: user.username.clone(),
+            full_name: user.full_name.clone(),
+            role: user.role,
+            started_at: chrono::Local::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Current session (Tauri managed state)
+#[derive(Default)]
+pub struct SessionState(Mutex<Option<Session>>);
+
+impl SessionState {
+    /// Get the current session, if logged in
+    pub fn current(&self) -> Option<Session> {
+        self.0.lock().ok().and_then(|session| session.clone())
+    }
+
+    pub fn set(&self, session: Option<Session>) {
+        if let Ok(mut current) = self.0.lock() {
+            *current = session;
+        }
+    }
+
+    /// Require a logged-in user whose role grants `permission`
+    pub fn require(&self, permission: Permission) -> Result<Session, String> {
+        let session = self
+            .current()
+            .ok_or_else(|| "Not logged in".to_string())?;
+        if !session.role.allows(permission) {
+            return Err(format!(
+                "Permission denied: role {} cannot {}",
+                session.role.as_str(),
+                permission.description()
+            ));
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_permission() {
+        let state = SessionState::default();
+        assert!(state.require(Permission::ManagePayments).is_err());
+
+        state.set(Some(Session {
+            user_id: 2,
+            username: "kasir".to_string(),
+            full_name: "Kasir".to_string(),
+            role: Role::Cashier,
+            started_at: String::new(),
+        }));
+        assert!(state.require(Permission::ManagePayments).is_ok());
+        assert!(state.require(Permission::DeleteBlock).is_err());
+        assert!(!Role::Clerk.allows(Permission::ManagePayments));
+        assert!(Role::Admin.allows(Permission::DeleteBlock));
+    }
+}
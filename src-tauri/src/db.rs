@@ -3,7 +3,7 @@
 //! This module handles SQLite database initialization and connection.
 //! Database is created automatically when the app runs for the first time.
 
-use chrono::Datelike;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use rusqlite::{Connection, OptionalExtension};
 use std::fs;
 use std::path::PathBuf;
@@ -14,12 +14,116 @@ use tauri::Manager;
 /// Database file name
 const DB_FILENAME: &str = "astana.db";
 
-/// Embedded SQL migration script
-const MIGRATION_SQL: &str = include_str!("../migrations/001_initial.sql");
+/// Search filter on graves (`g`): joins the ranked grave IDs from
+/// `grave_search_ids`; `m.key` is the rank.
+const GRAVE_SEARCH_JOIN: &str = " JOIN json_each(?) m ON m.value = g.id";
+
+/// Embedded SQL migration scripts, applied in order.
+/// The applied version is tracked in `PRAGMA user_version`.
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, include_str!("../migrations/001_initial.sql")),
+    (2, include_str!("../migrations/002_fee_waivers.sql")),
+    (3, include_str!("../migrations/003_import_profiles.sql")),
+    (4, include_str!("../migrations/004_users.sql")),
+    (5, include_str!("../migrations/005_operator_attribution.sql")),
+    (6, include_str!("../migrations/006_cash_sessions.sql")),
+    (7, include_str!("../migrations/007_fiscal_years.sql")),
+    (8, include_str!("../migrations/008_plot_layout.sql")),
+    (9, include_str!("../migrations/009_reservations.sql")),
+    (10, include_str!("../migrations/010_interments.sql")),
+    (11, include_str!("../migrations/011_grave_relocations.sql")),
+    (12, include_str!("../migrations/012_capacity_overrides.sql")),
+    (13, include_str!("../migrations/013_grave_numbering.sql")),
+    (14, include_str!("../migrations/014_persons.sql")),
+    (15, include_str!("../migrations/015_phone_numbers.sql")),
+    (16, include_str!("../migrations/016_duplicate_graves.sql")),
+    (17, include_str!("../migrations/017_search_index.sql")),
+    (18, include_str!("../migrations/018_typed_dates.sql")),
+    (19, include_str!("../migrations/019_closed_year_locks.sql")),
+    (20, include_str!("../migrations/020_date_issue_confirmation.sql")),
+    (21, include_str!("../migrations/021_grave_list_indexes.sql")),
+];
+
+/// Migration step written in Rust
+type DataMigration = fn(&Database) -> Result<(), String>;
+
+/// Data migrations run after the SQL script of the same version, inside
+/// its transaction.
+const DATA_MIGRATIONS: &[(i32, DataMigration)] = &[
+    (15, Database::normalize_stored_phone_numbers),
+    (15, Database::merge_persons_sharing_match_key),
+    (18, Database::normalize_stored_dates),
+];
+
+mod bulk_import;
+mod capacity;
+mod cash_sessions;
+mod dates;
+mod duplicates;
+mod fiscal_year;
+mod grave_list;
+mod haul;
+mod interments;
+mod merge;
+mod name_search;
+mod numbering;
+mod operators;
+mod payment_overview;
+mod persons;
+mod phone;
+mod plot_map;
+mod relocations;
+mod reservations;
+mod search;
+mod statement;
+#[cfg(test)]
+pub(crate) mod test_support;
+mod transfer;
+mod users;
+mod validation;
+pub use bulk_import::{ImportHeir, ImportPayment, ImportProfile, ImportReport, ImportRow, ImportRowError};
+pub use capacity::{BlockCapacityForecast, CapacityForecast, CapacityOverride, CapacityViolation};
+pub use cash_sessions::{CashClosingReport, CashSession, CashSessionPayment};
+pub use dates::{parse_loose_date, timezone, DateIssue, Timezone, TIMEZONES};
+pub use duplicates::{DuplicateCandidate, DuplicateGrave, GraveMergeReport};
+pub use fiscal_year::{CarriedArrears, ClosedYear, PaymentLockOverride};
+pub use grave_list::{GraveListQuery, GravePage, GraveSort};
+pub use haul::{HaulAnniversary, HaulContact, HijriDate};
+pub use interments::{CreateIntermentRequest, Interment, UpdateIntermentRequest};
+pub use merge::{BlockMapping, BlockMergeAction, BlockMergePlan, MergeOptions, MergeReport};
+pub use name_search::{NameKind, NameMatch};
+pub use numbering::{parse_grave_number, GraveNumberSuggestion, GraveNumbering, NumberScheme};
+pub use operators::OperatorPaymentSummary;
+pub use payment_overview::{GravePaymentSummary, PaymentOverviewFilter, PaymentStatus, YearPaymentStatus};
+pub use persons::{
+    CombinedPaymentRequest, CombinedPaymentResult, CreatePersonRequest, GraveArrears, LinkPersonRequest, Person,
+    PersonArrears, PersonGrave, UnpaidYear, UpdatePersonRequest,
+};
+pub use phone::{normalize_phone, PhoneNumberIssue};
+pub use plot_map::{
+    BlockLayout, PlotCell, PlotCellState, PlotGrave, PlotMap, PlotPaymentStatus, PlotPosition, PlotReservation,
+    UnusableCell,
+};
+pub use relocations::{GraveRelocation, RelocateGraveRequest};
+pub use reservations::{
+    ConvertReservationRequest, CreateReservationRequest, Reservation, UpdateReservationRequest,
+};
+pub use search::{GlobalSearchResults, GraveSearchHit, HeirSearchHit, PaymentSearchHit};
+pub use statement::{GraveStatement, StatementLine, StatementPayment};
+pub use transfer::{
+    ConflictStrategy, DataExport, ExportCounts, ExportManifest, GraveConflict, PlacementViolation, TransferCounts,
+    TransferReport,
+};
+pub use users::{CreateUserRequest, Role, UpdateUserRequest, User};
+pub use validation::{
+    parse_validation_errors, validate_grave_with_heirs, validate_heirs, FieldError, BLOCK_STATUSES, PAYMENT_METHODS,
+};
 
 /// Database management structure
 pub struct Database {
     conn: Connection,
+    /// User ID recorded as `created_by`/`updated_by` on data changes
+    operator: Option<i64>,
 }
 
 impl Database {
@@ -45,7 +149,7 @@ impl Database {
         let conn =
             Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
 
-        let db = Self { conn };
+        let db = Self { conn, operator: None };
 
         // Run migrations
         db.run_migrations()?;
@@ -62,13 +166,19 @@ impl Database {
         let conn =
             Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
 
-        let db = Self { conn };
+        let db = Self { conn, operator: None };
         db.run_migrations()?;
 
         log::info!("Database successfully initialized at: {:?}", db_path);
         Ok(db)
     }
 
+    /// Attribute data changes made through this connection to a user
+    pub fn with_operator(mut self, user_id: i64) -> Self {
+        self.operator = Some(user_id);
+        self
+    }
+
     /// Get database path based on platform
     ///
     /// Windows: %LOCALAPPDATA%/com.perogeremmer.astana/astana.db
@@ -89,11 +199,36 @@ impl Database {
         Ok(path.to_string_lossy().to_string())
     }
 
-    /// Run SQL migrations
+    /// Run pending SQL migrations
+    ///
+    /// Each migration runs in its own transaction together with the
+    /// `user_version` bump, so a failed migration leaves the schema untouched.
     fn run_migrations(&self) -> Result<(), String> {
-        self.conn
-            .execute_batch(MIGRATION_SQL)
-            .map_err(|e| format!("Failed to run migrations: {}", e))?;
+        let current_version: i32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+        for (version, sql) in MIGRATIONS {
+            if *version <= current_version {
+                continue;
+            }
+
+            let tx = self
+                .conn
+                .unchecked_transaction()
+                .map_err(|e| format!("Failed to start migration {}: {}", version, e))?;
+            tx.execute_batch(sql)
+                .map_err(|e| format!("Failed to run migration {}: {}", version, e))?;
+            for (_, migrate) in DATA_MIGRATIONS.iter().filter(|(v, _)| v == version) {
+                migrate(self).map_err(|e| format!("Failed to run migration {}: {}", version, e))?;
+            }
+            tx.pragma_update(None, "user_version", version)
+                .map_err(|e| format!("Failed to update schema version: {}", e))?;
+            tx.commit()
+                .map_err(|e| format!("Failed to commit migration {}: {}", version, e))?;
+        }
+
         Ok(())
     }
 
@@ -237,15 +372,17 @@ impl Database {
 
     /// Create new block
     pub fn create_block(&self, block: &CreateBlockRequest) -> Result<i64, String> {
+        validation::create_block(block)?;
         self.conn
             .execute(
-                "INSERT INTO blocks (code, description, total_capacity, annual_fee, status) VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO blocks (code, description, total_capacity, annual_fee, status, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
                 [
                     &block.code as &dyn rusqlite::ToSql,
                     &block.description.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
                     &block.total_capacity as &dyn rusqlite::ToSql,
                     &block.annual_fee as &dyn rusqlite::ToSql,
                     &block.status as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to create block: {}", e))?;
@@ -255,6 +392,7 @@ impl Database {
 
     /// Update block
     pub fn update_block(&self, id: i64, block: &UpdateBlockRequest) -> Result<(), String> {
+        validation::update_block(block)?;
         self.conn
             .execute(
                 "UPDATE blocks SET 
@@ -262,7 +400,8 @@ impl Database {
                     description = COALESCE(?2, description),
                     total_capacity = COALESCE(?3, total_capacity),
                     annual_fee = COALESCE(?4, annual_fee),
-                    status = COALESCE(?5, status)
+                    status = COALESCE(?5, status),
+                    updated_by = ?7
                     WHERE id = ?6",
                 [
                     &block.code as &dyn rusqlite::ToSql,
@@ -271,6 +410,7 @@ impl Database {
                     &block.annual_fee as &dyn rusqlite::ToSql,
                     &block.status as &dyn rusqlite::ToSql,
                     &id as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to update block: {}", e))?;
@@ -297,6 +437,14 @@ impl Database {
             ));
         }
 
+        let reservation_count = self.count_active_reservations(id)?;
+        if reservation_count > 0 {
+            return Err(format!(
+                "Cannot delete block: {} active reservation(s)",
+                reservation_count
+            ));
+        }
+
         self.conn
             .execute("DELETE FROM blocks WHERE id = ?1", [id])
             .map_err(|e| format!("Failed to delete block: {}", e))?;
@@ -304,7 +452,7 @@ impl Database {
         Ok(())
     }
 
-    /// Get block stats (occupied count)
+    /// Get block stats (occupied and reserved count)
     pub fn get_block_stats(&self, block_id: i64) -> Result<BlockStats, String> {
         let total_capacity: i64 = self
             .conn
@@ -324,91 +472,45 @@ impl Database {
             )
             .map_err(|e| format!("Failed to count graves: {}", e))?;
 
+        let reserved = self.count_active_reservations(block_id)?;
+
         Ok(BlockStats {
             total_capacity,
             occupied,
-            available: total_capacity - occupied,
+            reserved,
+            // Admin overrides can fill a block past its capacity
+            available: (total_capacity - occupied - reserved).max(0),
         })
     }
 
     // ==================== GRAVES CRUD ====================
 
-    /// Get graves with pagination and search
-    pub fn get_graves(
-        &self,
-        search: Option<String>,
-        block_id: Option<i64>,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<GraveWithBlock>, String> {
-        let mut query = String::from(
-            "SELECT g.id, g.deceased_name, g.block_id, g.number, g.date_of_death, g.burial_date, g.notes, g.created_at, g.updated_at,
-                    b.code, b.annual_fee
-                    FROM graves g
-                    JOIN blocks b ON g.block_id = b.id
-                    WHERE 1=1"
-        );
-
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-        if let Some(s) = search {
-            query.push_str(" AND (g.deceased_name LIKE ? OR g.number LIKE ?)");
-            let pattern = format!("%{}%", s);
-            params.push(Box::new(pattern.clone()));
-            params.push(Box::new(pattern));
-        }
-
-        if let Some(bid) = block_id {
-            query.push_str(" AND g.block_id = ?");
-            params.push(Box::new(bid));
-        }
-
-        query.push_str(" ORDER BY g.created_at DESC LIMIT ? OFFSET ?");
-        params.push(Box::new(limit));
-        params.push(Box::new(offset));
-
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-
-        let mut stmt = self
-            .conn
-            .prepare(&query)
-            .map_err(|e| format!("Failed to prepare query: {}", e))?;
-
-        let graves = stmt
-            .query_map(param_refs.as_slice(), |row| {
-                Ok(GraveWithBlock {
-                    id: row.get(0)?,
-                    deceased_name: row.get(1)?,
-                    block_id: row.get(2)?,
-                    number: row.get(3)?,
-                    date_of_death: row.get(4)?,
-                    burial_date: row.get(5)?,
-                    notes: row.get(6)?,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                    code: row.get(9)?,
-                    annual_fee: row.get(10)?,
-                })
-            })
-            .map_err(|e| format!("Failed to query graves: {}", e))?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to collect graves: {}", e))?;
-
-        Ok(graves)
-    }
-
     /// Create new grave
+    ///
+    /// The block must be active and have a free plot; see
+    /// `override_create_grave` for the admin override.
     pub fn create_grave(&self, grave: &CreateGraveRequest) -> Result<i64, String> {
+        validation::create_grave(grave, self.local_today()?)?;
+        self.check_block_capacity(grave.block_id)?;
+        self.insert_grave(grave)
+    }
+
+    fn insert_grave(&self, grave: &CreateGraveRequest) -> Result<i64, String> {
+        let number = self.normalized_grave_number(grave.block_id, &grave.number)?;
+        self.check_number_free(grave.block_id, &number, None)?;
+        self.check_number_not_reserved(grave.block_id, &number)?;
+
         self.conn
             .execute(
-                "INSERT INTO graves (deceased_name, block_id, number, date_of_death, burial_date, notes) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                "INSERT INTO graves (deceased_name, block_id, number, date_of_death, burial_date, notes, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
                 [
                     &grave.deceased_name as &dyn rusqlite::ToSql,
                     &grave.block_id as &dyn rusqlite::ToSql,
-                    &grave.number as &dyn rusqlite::ToSql,
+                    &number as &dyn rusqlite::ToSql,
                     &grave.date_of_death as &dyn rusqlite::ToSql,
-                    &grave.burial_date.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
-                    &grave.notes.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &validation::non_empty(grave.burial_date.as_deref()) as &dyn rusqlite::ToSql,
+                    &validation::non_empty(grave.notes.as_deref()) as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to create grave: {}", e))?;
@@ -449,7 +551,20 @@ impl Database {
     }
 
     /// Update grave
+    ///
+    /// Block and number can only be changed through `relocate_grave`, which
+    /// keeps the location history.
     pub fn update_grave(&self, id: i64, grave: &UpdateGraveRequest) -> Result<(), String> {
+        let current = self
+            .get_grave_by_id(id)?
+            .ok_or_else(|| format!("Grave {} not found", id))?;
+        let moves_block = grave.block_id.is_some_and(|block_id| block_id != current.block_id);
+        let renumbers = grave.number.as_ref().is_some_and(|number| *number != current.number);
+        if moves_block || renumbers {
+            return Err("Use relocation to move or renumber a grave".to_string());
+        }
+        validation::update_grave(grave, &current, self.local_today()?)?;
+
         self.conn
             .execute(
                 "UPDATE graves SET 
@@ -458,16 +573,18 @@ impl Database {
                     number = COALESCE(?3, number),
                     date_of_death = COALESCE(?4, date_of_death),
                     burial_date = COALESCE(?5, burial_date),
-                    notes = COALESCE(?6, notes)
+                    notes = COALESCE(?6, notes),
+                    updated_by = ?8
                  WHERE id = ?7",
                 [
                     &grave.deceased_name as &dyn rusqlite::ToSql,
                     &grave.block_id.map(|v| v.to_string()) as &dyn rusqlite::ToSql,
                     &grave.number as &dyn rusqlite::ToSql,
                     &grave.date_of_death as &dyn rusqlite::ToSql,
-                    &grave.burial_date as &dyn rusqlite::ToSql,
+                    &validation::non_empty(grave.burial_date.as_deref()) as &dyn rusqlite::ToSql,
                     &grave.notes as &dyn rusqlite::ToSql,
                     &id as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to update grave: {}", e))?;
@@ -476,44 +593,39 @@ impl Database {
     }
 
     /// Delete grave (will cascade delete heirs and payments)
+    ///
+    /// Payments of closed cash sessions keep the grave; payments and fee
+    /// waivers of closed years need `override_delete_grave`.
     pub fn delete_grave(&self, id: i64) -> Result<(), String> {
-        self.conn
-            .execute("DELETE FROM graves WHERE id = ?1", [id])
-            .map_err(|e| format!("Failed to delete grave: {}", e))?;
-
-        Ok(())
+        self.remove_grave(id, None)
     }
 
-    /// Count graves for pagination
-    pub fn count_graves(
-        &self,
-        search: Option<String>,
-        block_id: Option<i64>,
-    ) -> Result<i64, String> {
-        let mut query = String::from("SELECT COUNT(*) FROM graves g WHERE 1=1");
-
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-        if let Some(s) = search {
-            query.push_str(" AND (g.deceased_name LIKE ? OR g.number LIKE ?)");
-            let pattern = format!("%{}%", s);
-            params.push(Box::new(pattern.clone()));
-            params.push(Box::new(pattern));
+    fn remove_grave(&self, id: i64, override_reason: Option<&str>) -> Result<(), String> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        // The cascade would rewrite closed sessions and years; log what an
+        // override removes before it goes
+        for payment in self.get_payments_by_grave(id)? {
+            self.check_cash_session_lock(payment.id)?;
+            if let Some(reason) = self.lock_override_for(payment.year, override_reason)? {
+                self.log_payment_override(payment.id, "delete", reason)?;
+            }
         }
-
-        if let Some(bid) = block_id {
-            query.push_str(" AND g.block_id = ?");
-            params.push(Box::new(bid));
+        for waiver in self.get_waivers_by_grave(id)? {
+            if let Some(reason) = self.lock_override_for(waiver.year, override_reason)? {
+                self.log_waiver_override(waiver.id, "delete", reason)?;
+            }
         }
 
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-
-        let count: i64 = self
-            .conn
-            .query_row(&query, param_refs.as_slice(), |row| row.get(0))
-            .map_err(|e| format!("Failed to count graves: {}", e))?;
+        self.conn
+            .execute("DELETE FROM graves WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete grave: {}", e))?;
 
-        Ok(count)
+        tx.commit()
+            .map_err(|e| format!("Failed to commit grave deletion: {}", e))
     }
 
     /// Get all graves with heirs for export (no pagination)
@@ -527,25 +639,23 @@ impl Database {
             "SELECT g.id, g.deceased_name, g.block_id, g.number, g.date_of_death, g.burial_date, g.notes, g.created_at, g.updated_at,
                     b.code, b.annual_fee
                     FROM graves g
-                    JOIN blocks b ON g.block_id = b.id
-                    WHERE 1=1"
+                    JOIN blocks b ON g.block_id = b.id"
         );
 
         let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
         if let Some(s) = &search {
-            query.push_str(" AND (g.deceased_name LIKE ? OR g.number LIKE ?)");
-            let pattern = format!("%{}%", s);
-            params.push(Box::new(pattern.clone()));
-            params.push(Box::new(pattern));
+            query.push_str(GRAVE_SEARCH_JOIN);
+            params.push(Box::new(self.grave_search_ids(s)?));
         }
+        query.push_str(" WHERE 1=1");
 
         if let Some(bid) = block_id {
             query.push_str(" AND g.block_id = ?");
             params.push(Box::new(bid));
         }
 
-        query.push_str(" ORDER BY b.code, g.number");
+        query.push_str(&format!(" ORDER BY b.code, {}", numbering::GRAVE_NUMBER_ORDER_SQL));
 
         let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
 
@@ -574,11 +684,12 @@ impl Database {
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| format!("Failed to collect graves: {}", e))?;
 
-        // Now get heirs and payments for each grave
+        // Now get heirs, payments and waivers for each grave
         let mut result = Vec::new();
         for grave in graves {
             let heirs = self.get_heirs_by_grave(grave.id)?;
             let payments = self.get_payments_by_grave(grave.id)?;
+            let waivers = self.get_waivers_by_grave(grave.id)?;
             result.push(GraveExportData {
                 id: grave.id,
                 deceased_name: grave.deceased_name,
@@ -590,6 +701,7 @@ impl Database {
                 annual_fee: grave.annual_fee,
                 heirs,
                 payments,
+                waivers,
             });
         }
 
@@ -627,18 +739,25 @@ impl Database {
     }
 
     /// Create new heir
+    ///
+    /// The phone number must be a valid Indonesian number; it is stored as
+    /// typed and in E.164 form.
     pub fn create_heir(&self, heir: &CreateHeirRequest) -> Result<i64, String> {
+        let phone_e164 = validation::create_heir(heir)?;
+        let phone = heir.phone_number.as_deref().unwrap_or("").trim();
         self.conn
             .execute(
-                "INSERT INTO heirs (grave_id, order_number, full_name, phone_number, relationship, address, is_primary) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                "INSERT INTO heirs (grave_id, order_number, full_name, phone_number, relationship, address, is_primary, created_by, updated_by, phone_e164) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?9)",
                 [
                     &heir.grave_id as &dyn rusqlite::ToSql,
                     &heir.order_number as &dyn rusqlite::ToSql,
                     &heir.full_name as &dyn rusqlite::ToSql,
-                    &heir.phone_number.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &phone as &dyn rusqlite::ToSql,
                     &heir.relationship.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
                     &heir.address.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
                     &(if heir.is_primary { 1 } else { 0 }) as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
+                    &phone_e164 as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to create heir: {}", e))?;
@@ -676,27 +795,45 @@ impl Database {
 
     /// Update heir
     pub fn update_heir(&self, id: i64, heir: &UpdateHeirRequest) -> Result<(), String> {
+        let phone_e164 = validation::update_heir(heir)?;
+        let phone = heir.phone_number.as_deref().map(str::trim);
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
         self.conn
             .execute(
                 "UPDATE heirs SET 
                     full_name = COALESCE(?1, full_name),
                     phone_number = COALESCE(?2, phone_number),
+                    phone_e164 = CASE WHEN ?2 IS NULL THEN phone_e164 ELSE ?8 END,
                     relationship = COALESCE(?3, relationship),
                     address = COALESCE(?4, address),
-                    is_primary = COALESCE(?5, is_primary)
+                    is_primary = COALESCE(?5, is_primary),
+                    updated_by = ?7
                  WHERE id = ?6",
                 [
                     &heir.full_name,
-                    &heir.phone_number,
+                    &phone as &dyn rusqlite::ToSql,
                     &heir.relationship,
                     &heir.address,
                     &heir
                         .is_primary
                         .map(|b| if b { "1" } else { "0" }.to_string()),
                     &id as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
+                    &phone_e164 as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to update heir: {}", e))?;
+        if phone.is_some() {
+            self.conn
+                .execute("DELETE FROM phone_number_issues WHERE heir_id = ?1", [id])
+                .map_err(|e| format!("Failed to clear phone number issue: {}", e))?;
+        }
+        self.relink_heir_person(id)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit heir update: {}", e))?;
 
         Ok(())
     }
@@ -782,20 +919,85 @@ impl Database {
         Ok(payment)
     }
 
+    /// Get payment by ID
+    pub fn get_payment_by_id(&self, id: i64) -> Result<Option<Payment>, String> {
+        let payment = self.conn
+            .query_row(
+                "SELECT id, grave_id, year, payment_date, amount, payment_method, payment_proof, paid_by, notes, created_at, updated_at FROM payments WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok(Payment {
+                        id: row.get(0)?,
+                        grave_id: row.get(1)?,
+                        year: row.get(2)?,
+                        payment_date: row.get(3)?,
+                        amount: row.get(4)?,
+                        payment_method: row.get(5)?,
+                        payment_proof: row.get(6)?,
+                        paid_by: row.get(7)?,
+                        notes: row.get(8)?,
+                        created_at: row.get(9)?,
+                        updated_at: row.get(10)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get payment: {}", e))?;
+
+        Ok(payment)
+    }
+
     /// Create new payment
+    ///
+    /// Closed years are locked; see `override_create_payment`.
     pub fn create_payment(&self, payment: &CreatePaymentRequest) -> Result<i64, String> {
+        validation::payment(payment)?;
+        self.check_year_open(payment.year)?;
+        self.insert_payment(payment)
+    }
+
+    /// Create a payment taken outside the app, e.g. from an old ledger
+    ///
+    /// The method is kept as given (NULL when unknown) and no cash session
+    /// takes the payment, as its money was never in today's cash.
+    pub(super) fn create_ledger_payment(&self, payment: &CreatePaymentRequest) -> Result<i64, String> {
+        validation::payment(payment)?;
+        self.check_year_open(payment.year)?;
+        self.write_new_payment(payment, payment.payment_method.as_deref(), None)
+    }
+
+    fn insert_payment(&self, payment: &CreatePaymentRequest) -> Result<i64, String> {
+        // Cash goes into the cashier's open cash session (if any)
+        let payment_method = payment.payment_method.as_deref().unwrap_or("cash");
+        let cash_session_id = if payment_method == "cash" {
+            self.open_cash_session_id()?
+        } else {
+            None
+        };
+        self.write_new_payment(payment, Some(payment_method), cash_session_id)
+    }
+
+    fn write_new_payment(
+        &self,
+        payment: &CreatePaymentRequest,
+        payment_method: Option<&str>,
+        cash_session_id: Option<i64>,
+    ) -> Result<i64, String> {
+        self.check_grave_dates_confirmed(payment.grave_id)?;
         self.conn
             .execute(
-                "INSERT INTO payments (grave_id, year, payment_date, amount, payment_method, payment_proof, paid_by, notes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                "INSERT INTO payments (grave_id, year, payment_date, amount, payment_method, payment_proof, paid_by, notes, created_by, updated_by, cash_session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9, ?10)",
                 [
                     &payment.grave_id as &dyn rusqlite::ToSql,
                     &payment.year as &dyn rusqlite::ToSql,
                     &payment.payment_date as &dyn rusqlite::ToSql,
                     &payment.amount as &dyn rusqlite::ToSql,
-                    &payment.payment_method.as_deref().unwrap_or("cash") as &dyn rusqlite::ToSql,
+                    &payment_method as &dyn rusqlite::ToSql,
                     &payment.payment_proof.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
                     &payment.paid_by.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
                     &payment.notes.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
+                    &cash_session_id as &dyn rusqlite::ToSql,
                 ],
             )
             .map_err(|e| format!("Failed to create payment: {}", e))?;
@@ -803,21 +1005,177 @@ impl Database {
         Ok(self.conn.last_insert_rowid())
     }
 
+    /// Update payment
+    pub fn update_payment(&self, id: i64, payment: &CreatePaymentRequest) -> Result<(), String> {
+        self.check_payment_locks(id, Some(payment.year))?;
+        self.write_payment_update(id, payment)
+    }
+
+    fn write_payment_update(&self, id: i64, payment: &CreatePaymentRequest) -> Result<(), String> {
+        validation::payment(payment)?;
+        // A payment changed to cash joins the open cash session, as a new one
+        let open_session_id = self.open_cash_session_id()?;
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE payments SET
+                    grave_id = ?1,
+                    year = ?2,
+                    payment_date = ?3,
+                    amount = ?4,
+                    payment_method = ?5,
+                    payment_proof = ?6,
+                    paid_by = ?7,
+                    notes = ?8,
+                    updated_by = ?9,
+                    cash_session_id = CASE WHEN ?5 = 'cash' THEN COALESCE(cash_session_id, ?11) END
+                 WHERE id = ?10",
+                [
+                    &payment.grave_id as &dyn rusqlite::ToSql,
+                    &payment.year as &dyn rusqlite::ToSql,
+                    &payment.payment_date as &dyn rusqlite::ToSql,
+                    &payment.amount as &dyn rusqlite::ToSql,
+                    &payment.payment_method.as_deref().unwrap_or("cash") as &dyn rusqlite::ToSql,
+                    &payment.payment_proof.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &payment.paid_by.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &payment.notes.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &self.operator as &dyn rusqlite::ToSql,
+                    &id as &dyn rusqlite::ToSql,
+                    &open_session_id as &dyn rusqlite::ToSql,
+                ],
+            )
+            .map_err(|e| format!("Failed to update payment: {}", e))?;
+
+        if updated == 0 {
+            return Err(format!("Payment {} not found", id));
+        }
+        Ok(())
+    }
+
     /// Delete payment
     pub fn delete_payment(&self, id: i64) -> Result<(), String> {
+        self.check_payment_locks(id, None)?;
+        self.remove_payment(id)
+    }
+
+    fn remove_payment(&self, id: i64) -> Result<(), String> {
         self.conn
             .execute("DELETE FROM payments WHERE id = ?1", [id])
             .map_err(|e| format!("Failed to delete payment: {}", e))?;
         Ok(())
     }
 
+    // ==================== FEE WAIVERS ====================
+
+    /// Get fee waivers by grave ID
+    pub fn get_waivers_by_grave(&self, grave_id: i64) -> Result<Vec<FeeWaiver>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT id, grave_id, year, amount, reason, approved_by, created_at, updated_at FROM fee_waivers WHERE grave_id = ?1 ORDER BY year DESC")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let waivers = stmt
+            .query_map([grave_id], |row| {
+                Ok(FeeWaiver {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    year: row.get(2)?,
+                    amount: row.get(3)?,
+                    reason: row.get(4)?,
+                    approved_by: row.get(5)?,
+                    created_at: row.get(6)?,
+                    updated_at: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query fee waivers: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect fee waivers: {}", e))?;
+
+        Ok(waivers)
+    }
+
+    /// Get fee waiver by ID
+    pub fn get_waiver_by_id(&self, id: i64) -> Result<Option<FeeWaiver>, String> {
+        self.conn
+            .query_row(
+                "SELECT id, grave_id, year, amount, reason, approved_by, created_at, updated_at FROM fee_waivers WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok(FeeWaiver {
+                        id: row.get(0)?,
+                        grave_id: row.get(1)?,
+                        year: row.get(2)?,
+                        amount: row.get(3)?,
+                        reason: row.get(4)?,
+                        approved_by: row.get(5)?,
+                        created_at: row.get(6)?,
+                        updated_at: row.get(7)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get fee waiver: {}", e))
+    }
+
+    fn waiver_id_for_year(&self, grave_id: i64, year: i32) -> Result<Option<i64>, String> {
+        self.conn
+            .query_row(
+                "SELECT id FROM fee_waivers WHERE grave_id = ?1 AND year = ?2",
+                [grave_id, i64::from(year)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get fee waiver: {}", e))
+    }
+
+    /// Create new fee waiver
+    ///
+    /// Closed years are locked; see `override_create_waiver`.
+    pub fn create_waiver(&self, waiver: &CreateWaiverRequest) -> Result<i64, String> {
+        validation::create_waiver(waiver)?;
+        self.check_year_open(waiver.year)?;
+        self.insert_waiver(waiver)
+    }
+
+    fn insert_waiver(&self, waiver: &CreateWaiverRequest) -> Result<i64, String> {
+        self.check_grave_dates_confirmed(waiver.grave_id)?;
+        self.conn
+            .execute(
+                "INSERT INTO fee_waivers (grave_id, year, amount, reason, approved_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+                [
+                    &waiver.grave_id as &dyn rusqlite::ToSql,
+                    &waiver.year as &dyn rusqlite::ToSql,
+                    &waiver.amount as &dyn rusqlite::ToSql,
+                    &waiver.reason.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                    &waiver.approved_by.as_deref().unwrap_or("") as &dyn rusqlite::ToSql,
+                ],
+            )
+            .map_err(|e| format!("Failed to create fee waiver: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Delete fee waiver
+    pub fn delete_waiver(&self, id: i64) -> Result<(), String> {
+        if let Some(waiver) = self.get_waiver_by_id(id)? {
+            self.check_year_open(waiver.year)?;
+        }
+        self.remove_waiver(id)
+    }
+
+    fn remove_waiver(&self, id: i64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM fee_waivers WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete fee waiver: {}", e))?;
+        Ok(())
+    }
+
     // ==================== SETTINGS ====================
 
     /// Get settings
     pub fn get_settings(&self) -> Result<Settings, String> {
         let settings = self.conn
             .query_row(
-                "SELECT id, foundation_name, address, phone, email, logo_path, active_year, last_backup, auto_backup, created_at, updated_at FROM settings WHERE id = 1",
+                "SELECT id, foundation_name, address, phone, email, logo_path, active_year, last_backup, auto_backup, timezone, created_at, updated_at FROM settings WHERE id = 1",
                 [],
                 |row| {
                     Ok(Settings {
@@ -830,8 +1188,9 @@ impl Database {
                         active_year: row.get(6)?,
                         last_backup: row.get(7)?,
                         auto_backup: row.get::<_, i64>(8)? != 0,
-                        created_at: row.get(9)?,
-                        updated_at: row.get(10)?,
+                        timezone: row.get(9)?,
+                        created_at: row.get(10)?,
+                        updated_at: row.get(11)?,
                     })
                 },
             )
@@ -842,9 +1201,10 @@ impl Database {
 
     /// Update settings
     pub fn update_settings(&self, settings: &UpdateSettingsRequest) -> Result<(), String> {
+        validation::update_settings(settings, self.last_closed_year()?)?;
         self.conn
             .execute(
-                "UPDATE settings SET foundation_name = COALESCE(?1, foundation_name), address = COALESCE(?2, address), phone = COALESCE(?3, phone), email = COALESCE(?4, email), logo_path = COALESCE(?5, logo_path), active_year = COALESCE(?6, active_year), auto_backup = COALESCE(?7, auto_backup) WHERE id = 1",
+                "UPDATE settings SET foundation_name = COALESCE(?1, foundation_name), address = COALESCE(?2, address), phone = COALESCE(?3, phone), email = COALESCE(?4, email), logo_path = COALESCE(?5, logo_path), active_year = COALESCE(?6, active_year), auto_backup = COALESCE(?7, auto_backup), timezone = COALESCE(?8, timezone) WHERE id = 1",
                 [
                     &settings.foundation_name,
                     &settings.address,
@@ -853,6 +1213,7 @@ impl Database {
                     &settings.logo_path,
                     &settings.active_year.map(|y| y.to_string()),
                     &settings.auto_backup.map(|b| if b { "1" } else { "0" }.to_string()),
+                    &settings.timezone,
                 ],
             )
             .map_err(|e| format!("Failed to update settings: {}", e))?;
@@ -991,27 +1352,12 @@ impl Database {
             )
             .unwrap_or(0);
 
-        // Get new graves this month
-        let new_graves_this_month: i64 = self
-            .conn
-            .query_row(
-                "SELECT COUNT(*) FROM graves 
-                 WHERE strftime('%Y-%m', created_at) = strftime('%Y-%m', 'now')",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
-
-        // Get new graves this year
-        let new_graves_this_year: i64 = self
-            .conn
-            .query_row(
-                "SELECT COUNT(*) FROM graves 
-                 WHERE strftime('%Y', created_at) = strftime('%Y', 'now')",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+        // Get new graves this month and this year (in the configured timezone)
+        let today = self.local_today()?;
+        let (month_start, month_end) = dates::month_range(today);
+        let new_graves_this_month = self.count_graves_created(month_start, month_end)?;
+        let (year_start, year_end) = dates::year_range(today.year());
+        let new_graves_this_year = self.count_graves_created(year_start, year_end)?;
 
         Ok(FinancialSummary {
             year,
@@ -1023,6 +1369,18 @@ impl Database {
         })
     }
 
+    /// Count graves registered from local date `from` up to (not including) `until`
+    fn count_graves_created(&self, from: NaiveDate, until: NaiveDate) -> Result<i64, String> {
+        let (created_from, created_until) = self.timestamp_range(from, until)?;
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM graves WHERE created_at >= ?1 AND created_at < ?2",
+                [created_from, created_until],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count new graves: {}", e))
+    }
+
     /// Get dashboard statistics
     pub fn get_dashboard_stats(&self) -> Result<DashboardStats, String> {
         // Get active year from settings
@@ -1155,14 +1513,9 @@ impl Database {
         let total_expected: i64 = block_reports.iter().map(|r| r.expected_revenue).sum();
 
         // Get new graves count for the year
-        let new_graves_count: i64 = self
-            .conn
-            .query_row(
-                "SELECT COUNT(*) FROM graves WHERE strftime('%Y', created_at) = ?1",
-                [year.to_string()],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+        let (year_start, year_end) = dates::year_range(year);
+        let new_graves_count = self.count_graves_created(year_start, year_end)?;
+        let (created_from, created_until) = self.timestamp_range(year_start, year_end)?;
 
         // Get new graves per block
         let mut stmt_new = self
@@ -1173,14 +1526,14 @@ impl Database {
                 COUNT(g.id) as new_count
              FROM blocks b
              LEFT JOIN graves g ON b.id = g.block_id 
-                AND strftime('%Y', g.created_at) = ?1
+                AND g.created_at >= ?1 AND g.created_at < ?2
              GROUP BY b.id
              ORDER BY b.code",
             )
             .map_err(|e| format!("Failed to prepare new graves query: {}", e))?;
 
         let new_graves_per_block: Vec<(i64, i64)> = stmt_new
-            .query_map([year.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))
+            .query_map([&created_from, &created_until], |row| Ok((row.get(0)?, row.get(1)?)))
             .map_err(|e| format!("Failed to query new graves: {}", e))?
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| format!("Failed to collect new graves: {}", e))?;
@@ -1230,14 +1583,14 @@ impl Database {
         let mut stmt2 = self
             .conn
             .prepare(
-                "SELECT DISTINCT CAST(strftime('%Y', created_at) AS INTEGER) 
+                "SELECT DISTINCT CAST(strftime('%Y', created_at, ?1) AS INTEGER) 
              FROM graves 
              ORDER BY created_at DESC",
             )
             .map_err(|e| format!("Failed to prepare grave years query: {}", e))?;
 
         let grave_years: Vec<i32> = stmt2
-            .query_map([], |row| row.get(0))
+            .query_map([self.local_time_modifier()?], |row| row.get(0))
             .map_err(|e| format!("Failed to query grave years: {}", e))?
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| format!("Failed to collect grave years: {}", e))?;
@@ -1245,7 +1598,7 @@ impl Database {
         years.extend(grave_years);
 
         // Add current year and active year
-        let current_year: i32 = chrono::Local::now().year();
+        let current_year: i32 = self.local_today()?.year();
         years.push(current_year);
 
         let active_year: i32 = self
@@ -1282,7 +1635,7 @@ pub struct RecentPayment {
     pub id: i64,
     pub grave_id: i64,
     pub year: i32,
-    pub payment_date: String,
+    pub payment_date: NaiveDate,
     pub amount: i64,
     pub deceased_name: String,
     pub block_code: String,
@@ -1294,8 +1647,8 @@ pub struct RecentPayment {
 pub struct RecentGrave {
     pub id: i64,
     pub deceased_name: String,
-    pub date_of_death: String,
-    pub created_at: String,
+    pub date_of_death: NaiveDate,
+    pub created_at: DateTime<Utc>,
     pub block_code: String,
     pub grave_number: String,
     pub has_paid_current_year: bool,
@@ -1354,8 +1707,8 @@ pub struct Block {
     pub total_capacity: i64,
     pub annual_fee: i64,
     pub status: String,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1380,6 +1733,8 @@ pub struct UpdateBlockRequest {
 pub struct BlockStats {
     pub total_capacity: i64,
     pub occupied: i64,
+    /// Active reservations
+    pub reserved: i64,
     pub available: i64,
 }
 
@@ -1390,11 +1745,11 @@ pub struct Grave {
     pub deceased_name: String,
     pub block_id: i64,
     pub number: String,
-    pub date_of_death: String,
-    pub burial_date: Option<String>,
+    pub date_of_death: NaiveDate,
+    pub burial_date: Option<NaiveDate>,
     pub notes: Option<String>,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1403,11 +1758,11 @@ pub struct GraveWithBlock {
     pub deceased_name: String,
     pub block_id: i64,
     pub number: String,
-    pub date_of_death: String,
-    pub burial_date: Option<String>,
+    pub date_of_death: NaiveDate,
+    pub burial_date: Option<NaiveDate>,
     pub notes: Option<String>,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub code: String,
     pub annual_fee: i64,
 }
@@ -1439,12 +1794,13 @@ pub struct GraveExportData {
     pub deceased_name: String,
     pub block_code: String,
     pub number: String,
-    pub date_of_death: String,
-    pub burial_date: Option<String>,
+    pub date_of_death: NaiveDate,
+    pub burial_date: Option<NaiveDate>,
     pub notes: Option<String>,
     pub annual_fee: i64,
     pub heirs: Vec<Heir>,
     pub payments: Vec<Payment>,
+    pub waivers: Vec<FeeWaiver>,
 }
 
 /// Heir data structure
@@ -1458,8 +1814,8 @@ pub struct Heir {
     pub relationship: Option<String>,
     pub address: Option<String>,
     pub is_primary: bool,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1488,14 +1844,14 @@ pub struct Payment {
     pub id: i64,
     pub grave_id: i64,
     pub year: i32,
-    pub payment_date: String,
+    pub payment_date: NaiveDate,
     pub amount: i64,
     pub payment_method: Option<String>,
     pub payment_proof: Option<String>,
     pub paid_by: Option<String>,
     pub notes: Option<String>,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1510,6 +1866,28 @@ pub struct CreatePaymentRequest {
     pub notes: Option<String>,
 }
 
+/// Fee waiver data structure
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FeeWaiver {
+    pub id: i64,
+    pub grave_id: i64,
+    pub year: i32,
+    pub amount: i64,
+    pub reason: Option<String>,
+    pub approved_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreateWaiverRequest {
+    pub grave_id: i64,
+    pub year: i32,
+    pub amount: i64,
+    pub reason: Option<String>,
+    pub approved_by: Option<String>,
+}
+
 /// Settings data structure
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Settings {
@@ -1520,10 +1898,12 @@ pub struct Settings {
     pub email: Option<String>,
     pub logo_path: Option<String>,
     pub active_year: i32,
-    pub last_backup: Option<String>,
+    pub last_backup: Option<DateTime<Utc>>,
     pub auto_backup: bool,
-    pub created_at: String,
-    pub updated_at: String,
+    /// IANA name of the local timezone (see `TIMEZONES`)
+    pub timezone: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1535,6 +1915,7 @@ pub struct UpdateSettingsRequest {
     pub logo_path: Option<String>,
     pub active_year: Option<i32>,
     pub auto_backup: Option<bool>,
+    pub timezone: Option<String>,
 }
 
 // ==================== REPORT DATA STRUCTURES ====================
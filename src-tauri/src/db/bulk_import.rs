@@ -0,0 +1,570 @@
+//! Bulk import of graves, heirs and historical payments
+//!
+//! Rows come from `crate::import` (Excel/CSV reader) already mapped to
+//! fields. This module validates them against the database and inserts
+//! everything in one transaction. Historical payments keep the mapped
+//! method and stay out of cash sessions.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::OptionalExtension;
+
+use super::numbering::grave_number_key;
+use super::phone::normalize_phone;
+use super::{validation, CreateGraveRequest, CreateHeirRequest, CreatePaymentRequest, Database};
+
+impl Database {
+    /// Validate rows and import them atomically
+    ///
+    /// With `dry_run` (or when any row is invalid) nothing is written and the
+    /// report only lists the row-level errors.
+    pub fn import_graves(&self, rows: &[ImportRow], dry_run: bool) -> Result<ImportReport, String> {
+        let block_ids: HashMap<String, i64> = self
+            .get_all_blocks()?
+            .into_iter()
+            .map(|b| (b.code.trim().to_uppercase(), b.id))
+            .collect();
+        let mut taken_numbers = self.get_grave_numbers()?;
+        let mut places_left = self.places_left_per_block()?;
+        let today = self.local_today()?;
+
+        let mut errors = Vec::new();
+        let mut valid_rows = 0;
+        for row in rows {
+            let row_errors = self.validate_row(row, today, &block_ids, &mut taken_numbers, &mut places_left);
+            if row_errors.is_empty() {
+                valid_rows += 1;
+            }
+            errors.extend(row_errors);
+        }
+
+        let mut report = ImportReport {
+            dry_run,
+            total_rows: rows.len() as i64,
+            valid_rows,
+            imported_graves: 0,
+            imported_heirs: 0,
+            imported_payments: 0,
+            errors,
+        };
+
+        if dry_run || !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        // Any failure drops the transaction, which rolls everything back
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start import: {}", e))?;
+
+        for row in rows {
+            let block_id = block_ids[&row.block_code.trim().to_uppercase()];
+            let grave_id = self
+                .create_grave(&CreateGraveRequest {
+                    deceased_name: row.deceased_name.trim().to_string(),
+                    block_id,
+                    number: row.number.trim().to_string(),
+                    date_of_death: row.date_of_death.clone(),
+                    burial_date: row.burial_date.clone(),
+                    notes: row.notes.clone(),
+                })
+                .map_err(|e| format!("Row {}: {}", row.row_number, e))?;
+            report.imported_graves += 1;
+
+            for heir in &row.heirs {
+                self.create_heir(&CreateHeirRequest {
+                    grave_id,
+                    order_number: heir.order_number,
+                    full_name: heir.full_name.trim().to_string(),
+                    phone_number: heir.phone_number.clone(),
+                    relationship: heir.relationship.clone(),
+                    address: heir.address.clone(),
+                    is_primary: heir.order_number == 1,
+                })
+                .map_err(|e| format!("Row {}: {}", row.row_number, e))?;
+                report.imported_heirs += 1;
+            }
+
+            for payment in &row.payments {
+                self.create_ledger_payment(&CreatePaymentRequest {
+                    grave_id,
+                    year: payment.year,
+                    payment_date: payment.payment_date.clone(),
+                    amount: payment.amount,
+                    payment_method: payment.payment_method.clone(),
+                    payment_proof: None,
+                    paid_by: None,
+                    notes: Some("Import data lama".to_string()),
+                })
+                .map_err(|e| format!("Row {}: {}", row.row_number, e))?;
+                report.imported_payments += 1;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit import: {}", e))?;
+
+        log::info!(
+            "Imported {} graves, {} heirs, {} payments",
+            report.imported_graves,
+            report.imported_heirs,
+            report.imported_payments
+        );
+        Ok(report)
+    }
+
+    /// Existing (block_id, number key) pairs, see `grave_number_key`
+    fn get_grave_numbers(&self) -> Result<HashSet<(i64, String)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT block_id, number FROM graves")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let numbers = stmt
+            .query_map([], |row| Ok((row.get(0)?, grave_number_key(&row.get::<_, String>(1)?))))
+            .map_err(|e| format!("Failed to query grave numbers: {}", e))?
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(|e| format!("Failed to collect grave numbers: {}", e))?;
+
+        Ok(numbers)
+    }
+
+    // ==================== IMPORT PROFILES ====================
+
+    /// Get all saved import mapping profiles
+    pub fn get_import_profiles(&self) -> Result<Vec<ImportProfile>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, mapping, created_at, updated_at FROM import_profiles ORDER BY name")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let profiles = stmt
+            .query_map([], |row| {
+                let mapping: String = row.get(2)?;
+                Ok(ImportProfile {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    mapping: serde_json::from_str(&mapping).unwrap_or(serde_json::Value::Null),
+                    created_at: row.get(3)?,
+                    updated_at: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query import profiles: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect import profiles: {}", e))?;
+
+        Ok(profiles)
+    }
+
+    /// Save import mapping profile (overwrites profile with the same name)
+    pub fn save_import_profile(&self, name: &str, mapping: &serde_json::Value) -> Result<i64, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile name is required".to_string());
+        }
+
+        self.conn
+            .execute(
+                "INSERT INTO import_profiles (name, mapping) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET mapping = excluded.mapping",
+                rusqlite::params![name, mapping.to_string()],
+            )
+            .map_err(|e| format!("Failed to save import profile: {}", e))?;
+
+        let id = self
+            .conn
+            .query_row("SELECT id FROM import_profiles WHERE name = ?1", [name], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to get import profile: {}", e))?
+            .ok_or("Import profile was not saved")?;
+
+        Ok(id)
+    }
+
+    /// Delete import mapping profile
+    pub fn delete_import_profile(&self, id: i64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM import_profiles WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete import profile: {}", e))?;
+        Ok(())
+    }
+
+    /// Validate one row; a valid row takes its grave number and a place in
+    /// its block, so later rows of the same file see them as used
+    fn validate_row(
+        &self,
+        row: &ImportRow,
+        today: NaiveDate,
+        block_ids: &HashMap<String, i64>,
+        taken_numbers: &mut HashSet<(i64, String)>,
+        places_left: &mut HashMap<i64, Option<i64>>,
+    ) -> Vec<ImportRowError> {
+        let mut errors = row.errors.clone();
+        let error = |field: &str, message: String| ImportRowError {
+            row_number: row.row_number,
+            field: field.to_string(),
+            message,
+        };
+
+        if row.deceased_name.trim().is_empty() {
+            errors.push(error("deceased_name", "Deceased name is required".to_string()));
+        }
+        if row.date_of_death.is_empty() && !row.errors.iter().any(|e| e.field == "date_of_death") {
+            errors.push(error("date_of_death", "Date of death is required".to_string()));
+        }
+
+        let number = row.number.trim().to_string();
+        if number.is_empty() {
+            errors.push(error("number", "Grave number is required".to_string()));
+        }
+
+        // Same checks as a grave entered by hand (dates, burial after death)
+        let grave = CreateGraveRequest {
+            deceased_name: row.deceased_name.clone(),
+            block_id: 0,
+            number: row.number.clone(),
+            date_of_death: row.date_of_death.clone(),
+            burial_date: row.burial_date.clone(),
+            notes: None,
+        };
+        for e in validation::field_errors(validation::create_grave(&grave, today)) {
+            if !errors.iter().any(|existing| existing.field == e.field) {
+                errors.push(error(&e.field, e.message));
+            }
+        }
+
+        match block_ids.get(&row.block_code.trim().to_uppercase()) {
+            None => errors.push(error(
+                "block_code",
+                format!("Block '{}' not found", row.block_code.trim()),
+            )),
+            Some(&block_id) => {
+                let key = (block_id, grave_number_key(&number));
+                if !number.is_empty() {
+                    if taken_numbers.contains(&key) {
+                        errors.push(error(
+                            "number",
+                            format!("Number {} already used in block {}", number, row.block_code.trim()),
+                        ));
+                    } else if let Err(e) = self.check_number_not_reserved(block_id, &number) {
+                        errors.push(error("number", e));
+                    }
+                }
+
+                // Same capacity and status check as `create_grave`
+                match places_left.get_mut(&block_id) {
+                    Some(None) => errors.push(error(
+                        "block_code",
+                        format!("Block {} is inactive", row.block_code.trim()),
+                    )),
+                    Some(Some(left)) if *left <= 0 => errors.push(error(
+                        "block_code",
+                        format!("Block {} is full", row.block_code.trim()),
+                    )),
+                    Some(Some(left)) if errors.is_empty() => {
+                        *left -= 1;
+                        taken_numbers.insert(key);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut orders = HashSet::new();
+        for heir in &row.heirs {
+            if !(1..=validation::MAX_HEIR_ORDER).contains(&heir.order_number) {
+                errors.push(error(
+                    "heirs",
+                    format!(
+                        "Invalid heir order {} (must be 1-{})",
+                        heir.order_number,
+                        validation::MAX_HEIR_ORDER
+                    ),
+                ));
+            } else if !orders.insert(heir.order_number) {
+                errors.push(error(
+                    "heirs",
+                    format!("Heir order {} used more than once", heir.order_number),
+                ));
+            }
+            if heir.full_name.trim().is_empty() {
+                errors.push(error(
+                    "heirs",
+                    format!("Name of heir {} is required", heir.order_number),
+                ));
+            }
+            if let Err(e) = normalize_phone(heir.phone_number.as_deref().unwrap_or("")) {
+                errors.push(error("heirs", format!("Heir {}: {}", heir.order_number, e)));
+            }
+        }
+
+        let mut years = HashSet::new();
+        for payment in &row.payments {
+            if payment.amount <= 0 {
+                errors.push(error(
+                    "payments",
+                    format!("Payment amount for {} must be greater than 0", payment.year),
+                ));
+            }
+            let request = CreatePaymentRequest {
+                grave_id: 0,
+                year: payment.year,
+                payment_date: payment.payment_date.clone(),
+                amount: payment.amount,
+                payment_method: payment.payment_method.clone(),
+                payment_proof: None,
+                paid_by: None,
+                notes: None,
+            };
+            for e in validation::field_errors(validation::payment(&request)) {
+                // Amount is checked above
+                if e.field != "amount" {
+                    errors.push(error("payments", format!("Payment for {}: {}", payment.year, e.message)));
+                }
+            }
+            if let Err(e) = self.check_year_open(payment.year) {
+                errors.push(error("payments", format!("Payment for {}: {}", payment.year, e)));
+            }
+            if !years.insert(payment.year) {
+                errors.push(error(
+                    "payments",
+                    format!("Payment for {} listed more than once", payment.year),
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Places left per block (active reservations count as taken); None for
+    /// inactive blocks
+    fn places_left_per_block(&self) -> Result<HashMap<i64, Option<i64>>, String> {
+        let mut places_left = HashMap::new();
+        for block in self.get_all_blocks()? {
+            let left = if block.status == "active" {
+                let stats = self.get_block_stats(block.id)?;
+                Some(stats.total_capacity - stats.occupied - stats.reserved)
+            } else {
+                None
+            };
+            places_left.insert(block.id, left);
+        }
+        Ok(places_left)
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Row to import, already mapped from the source file
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ImportRow {
+    /// Row number in the source file (header = row 1)
+    pub row_number: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    /// ISO date (YYYY-MM-DD)
+    pub date_of_death: String,
+    pub burial_date: Option<String>,
+    pub notes: Option<String>,
+    pub heirs: Vec<ImportHeir>,
+    pub payments: Vec<ImportPayment>,
+    /// Errors found while parsing the source cells
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportHeir {
+    pub order_number: i64,
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub relationship: Option<String>,
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportPayment {
+    pub year: i32,
+    pub payment_date: String,
+    pub amount: i64,
+    pub payment_method: Option<String>,
+}
+
+/// Row-level import error
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportRowError {
+    pub row_number: i64,
+    pub field: String,
+    pub message: String,
+}
+
+/// Result of an import or dry run
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: i64,
+    pub valid_rows: i64,
+    pub imported_graves: i64,
+    pub imported_heirs: i64,
+    pub imported_payments: i64,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Saved column mapping profile
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportProfile {
+    pub id: i64,
+    pub name: String,
+    pub mapping: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, create_block, test_db};
+    use crate::db::{CreateBlockRequest, CreateReservationRequest, CreateUserRequest, Role, UpdateSettingsRequest};
+
+    fn row(row_number: i64, block_code: &str, number: &str) -> ImportRow {
+        ImportRow {
+            row_number,
+            deceased_name: format!("Almarhum {}", row_number),
+            block_code: block_code.to_string(),
+            number: number.to_string(),
+            date_of_death: "2020-01-01".to_string(),
+            heirs: vec![ImportHeir {
+                order_number: 1,
+                full_name: "Budi".to_string(),
+                phone_number: None,
+                relationship: None,
+                address: None,
+            }],
+            payments: vec![ImportPayment {
+                year: 2021,
+                payment_date: "2021-05-01".to_string(),
+                amount: 100000,
+                payment_method: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_import_with_errors_writes_nothing() {
+        let (_dir, db) = test_db();
+        create_block(&db, "A");
+
+        // Unknown block and duplicate number within the file
+        let rows = vec![row(2, "a", "1"), row(3, "A", "1"), row(4, "Z", "2")];
+        let report = db.import_graves(&rows, false).unwrap();
+        assert_eq!(report.valid_rows, 1);
+        assert_eq!(report.imported_graves, 0);
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.errors[0].row_number, 3);
+        assert_eq!(db.get_stats().unwrap().graves_count, 0);
+    }
+
+    #[test]
+    fn test_import_after_dry_run() {
+        let (_dir, db) = test_db();
+        create_block(&db, "A");
+
+        let rows = vec![row(2, "A", "1"), row(3, "A", "2")];
+        let report = db.import_graves(&rows, true).unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(db.get_stats().unwrap().graves_count, 0);
+
+        let report = db.import_graves(&rows, false).unwrap();
+        assert_eq!(report.imported_graves, 2);
+        assert_eq!(report.imported_heirs, 2);
+        assert_eq!(report.imported_payments, 2);
+
+        // Numbers now exist in the database, also written another way
+        let rows = vec![row(2, "A", "1"), row(3, "A", "02")];
+        let report = db.import_graves(&rows, true).unwrap();
+        assert_eq!(report.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_import_dry_run_reports_full_block_and_reserved_number() {
+        let (_dir, db) = test_db();
+        let block_id = db
+            .create_block(&CreateBlockRequest {
+                total_capacity: 3,
+                ..block_request("A")
+            })
+            .unwrap();
+        db.create_reservation(&CreateReservationRequest {
+            block_id,
+            grave_number: Some("9".to_string()),
+            position: None,
+            reserved_for: None,
+            reserver_name: "Pak Harun".to_string(),
+            phone_number: None,
+            address: None,
+            relationship: None,
+            reserved_until: None,
+            deposit_amount: 0,
+            notes: None,
+        })
+        .unwrap();
+
+        // One place is reserved, so only two of the three rows fit
+        let rows = vec![row(2, "A", "1"), row(3, "A", "9"), row(4, "A", "2"), row(5, "A", "3")];
+        let report = db.import_graves(&rows, true).unwrap();
+        let errors: Vec<(i64, &str)> = report.errors.iter().map(|e| (e.row_number, e.field.as_str())).collect();
+        assert_eq!(errors, [(3, "number"), (5, "block_code")]);
+        assert_eq!(report.valid_rows, 2);
+    }
+
+    #[test]
+    fn test_imported_payments_stay_out_of_cash_session() {
+        let (_dir, db) = test_db();
+        create_block(&db, "A");
+        let cashier_id = db
+            .create_user(&CreateUserRequest {
+                username: "kasir".to_string(),
+                full_name: "Kasir".to_string(),
+                password: "rahasia123".to_string(),
+                role: Role::Cashier,
+            })
+            .unwrap();
+        let db = db.with_operator(cashier_id);
+        let session_id = db.open_cash_session(0).unwrap();
+
+        db.import_graves(&[row(2, "A", "1")], false).unwrap();
+        let graves = db.get_all_graves_with_heirs(None, None).unwrap();
+        let payments = db.get_payments_by_grave(graves[0].id).unwrap();
+        assert_eq!(payments[0].payment_method, None);
+
+        let report = db.close_cash_session(session_id, 0, None).unwrap();
+        assert!(report.payments.is_empty());
+        assert_eq!(report.session.expected_amount, Some(0));
+    }
+
+    #[test]
+    fn test_import_dry_run_reports_closed_year() {
+        let (_dir, db) = test_db();
+        create_block(&db, "A");
+        db.update_settings(&UpdateSettingsRequest {
+            foundation_name: None,
+            address: None,
+            phone: None,
+            email: None,
+            logo_path: None,
+            active_year: Some(2021),
+            auto_backup: None,
+            timezone: None,
+        })
+        .unwrap();
+        db.close_fiscal_year(2021, None).unwrap();
+
+        let report = db.import_graves(&[row(2, "A", "1")], true).unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].field, "payments");
+        assert!(report.errors[0].message.starts_with("Payment for 2021: Year 2021 is closed"));
+    }
+}
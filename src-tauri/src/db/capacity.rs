@@ -0,0 +1,355 @@
+//! Block capacity enforcement and forecast
+//!
+//! New and relocated graves may only go into active blocks that still have
+//! free plots (active reservations count as taken). An admin can place a
+//! grave anyway with a reason, which is written to an audit log.
+
+use chrono::{DateTime, Datelike, Utc};
+
+use super::{CreateGraveRequest, Database};
+
+/// Year of burial, falling back to the date of death
+const BURIAL_YEAR_SQL: &str =
+    "CAST(substr(COALESCE(NULLIF(g.burial_date, ''), g.date_of_death), 1, 4) AS INTEGER)";
+
+impl Database {
+    /// Create a grave in a full or inactive block (admin override, logged)
+    pub fn override_create_grave(&self, grave: &CreateGraveRequest, reason: &str) -> Result<i64, String> {
+        let reason = require_override_reason(reason)?;
+        let violation = self.block_capacity_violation(grave.block_id)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let grave_id = self.insert_grave(grave)?;
+        if let Some(violation) = violation {
+            self.log_capacity_override(grave.block_id, grave_id, "create", violation, reason)?;
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit grave: {}", e))?;
+
+        Ok(grave_id)
+    }
+
+    /// Get audit log of capacity overrides
+    pub fn get_capacity_overrides(&self, limit: i64) -> Result<Vec<CapacityOverride>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT o.id, o.block_id, b.code, o.grave_id, g.deceased_name, o.action, o.violation, o.reason,
+                        o.overridden_by, u.full_name, o.created_at
+                 FROM capacity_overrides o
+                 JOIN blocks b ON o.block_id = b.id
+                 LEFT JOIN graves g ON o.grave_id = g.id
+                 LEFT JOIN users u ON o.overridden_by = u.id
+                 ORDER BY o.created_at DESC, o.id DESC
+                 LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let overrides = stmt
+            .query_map([limit], |row| {
+                Ok(CapacityOverride {
+                    id: row.get(0)?,
+                    block_id: row.get(1)?,
+                    block_code: row.get(2)?,
+                    grave_id: row.get(3)?,
+                    deceased_name: row.get(4)?,
+                    action: row.get(5)?,
+                    violation: row.get(6)?,
+                    reason: row.get(7)?,
+                    overridden_by: row.get(8)?,
+                    overridden_by_name: row.get(9)?,
+                    created_at: row.get(10)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query capacity overrides: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect capacity overrides: {}", e))?;
+
+        Ok(overrides)
+    }
+
+    /// Project when each block will be full
+    ///
+    /// The burial rate is the number of new graves per year over the last
+    /// `years` complete calendar years.
+    pub fn get_capacity_forecast(&self, years: i32) -> Result<CapacityForecast, String> {
+        if years < 1 {
+            return Err("Forecast period must be at least one year".to_string());
+        }
+        let current_year = self.local_today()?.year();
+        let (from_year, to_year) = (current_year - years, current_year - 1);
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT b.id, b.code, b.status, b.total_capacity,
+                        (SELECT COUNT(*) FROM graves g WHERE g.block_id = b.id),
+                        (SELECT COUNT(*) FROM graves g WHERE g.block_id = b.id AND {} BETWEEN ?1 AND ?2)
+                 FROM blocks b
+                 ORDER BY b.code",
+                BURIAL_YEAR_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt
+            .query_map([from_year, to_year], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            })
+            .map_err(|e| format!("Failed to query blocks: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect blocks: {}", e))?;
+
+        let mut blocks = Vec::with_capacity(rows.len());
+        for (block_id, code, status, total_capacity, occupied, burials_in_period) in rows {
+            let reserved = self.count_active_reservations(block_id)?;
+            let available = (total_capacity - occupied - reserved).max(0);
+            let burials_per_year = burials_in_period as f64 / years as f64;
+
+            // Inactive blocks take no new graves, so they never fill up
+            let years_until_full = if status != "active" {
+                None
+            } else if available == 0 {
+                Some(0.0)
+            } else if burials_per_year > 0.0 {
+                Some(available as f64 / burials_per_year)
+            } else {
+                None
+            };
+
+            blocks.push(BlockCapacityForecast {
+                block_id,
+                code,
+                status,
+                total_capacity,
+                occupied,
+                reserved,
+                available,
+                burials_in_period,
+                burials_per_year,
+                years_until_full,
+                projected_full_year: years_until_full.map(|years| current_year + years.ceil() as i32),
+            });
+        }
+
+        Ok(CapacityForecast {
+            from_year,
+            to_year,
+            blocks,
+        })
+    }
+
+    /// Fail unless the block can take another grave
+    pub(super) fn check_block_capacity(&self, block_id: i64) -> Result<(), String> {
+        match self.block_capacity_violation(block_id)? {
+            None => Ok(()),
+            Some(violation) => {
+                let block = self
+                    .get_block_by_id(block_id)?
+                    .ok_or_else(|| format!("Block {} not found", block_id))?;
+                Err(match violation {
+                    CapacityViolation::Full => format!(
+                        "Block {} is full (capacity {}); an admin can override with a reason",
+                        block.code, block.total_capacity
+                    ),
+                    CapacityViolation::Inactive => format!(
+                        "Block {} is inactive; an admin can override with a reason",
+                        block.code
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Why the block cannot take another grave, if it cannot
+    pub(super) fn block_capacity_violation(&self, block_id: i64) -> Result<Option<CapacityViolation>, String> {
+        let block = self
+            .get_block_by_id(block_id)?
+            .ok_or_else(|| format!("Block {} not found", block_id))?;
+        if block.status != "active" {
+            return Ok(Some(CapacityViolation::Inactive));
+        }
+        let stats = self.get_block_stats(block_id)?;
+        if stats.occupied + stats.reserved >= stats.total_capacity {
+            return Ok(Some(CapacityViolation::Full));
+        }
+        Ok(None)
+    }
+
+    pub(super) fn log_capacity_override(
+        &self,
+        block_id: i64,
+        grave_id: i64,
+        action: &str,
+        violation: CapacityViolation,
+        reason: &str,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO capacity_overrides (block_id, grave_id, action, violation, reason, overridden_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![block_id, grave_id, action, violation.as_str(), reason, self.operator],
+            )
+            .map_err(|e| format!("Failed to log capacity override: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Trimmed override reason, which must not be empty
+pub(super) fn require_override_reason(reason: &str) -> Result<&str, String> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("A reason is required to override block capacity".to_string());
+    }
+    Ok(reason)
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CapacityViolation {
+    Full,
+    Inactive,
+}
+
+impl CapacityViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CapacityViolation::Full => "full",
+            CapacityViolation::Inactive => "inactive",
+        }
+    }
+}
+
+/// Grave placed into a full or inactive block by an admin
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CapacityOverride {
+    pub id: i64,
+    pub block_id: i64,
+    pub block_code: String,
+    /// None once the grave is deleted
+    pub grave_id: Option<i64>,
+    pub deceased_name: Option<String>,
+    /// create or relocate
+    pub action: String,
+    /// full or inactive
+    pub violation: String,
+    pub reason: String,
+    pub overridden_by: Option<i64>,
+    pub overridden_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CapacityForecast {
+    /// First year of the burial rate period
+    pub from_year: i32,
+    /// Last year of the burial rate period
+    pub to_year: i32,
+    pub blocks: Vec<BlockCapacityForecast>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockCapacityForecast {
+    pub block_id: i64,
+    pub code: String,
+    pub status: String,
+    pub total_capacity: i64,
+    pub occupied: i64,
+    pub reserved: i64,
+    pub available: i64,
+    /// New graves in the burial rate period
+    pub burials_in_period: i64,
+    pub burials_per_year: f64,
+    /// None when the block takes no new graves at the current rate
+    pub years_until_full: Option<f64>,
+    pub projected_full_year: Option<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, grave_request, test_db};
+    use crate::db::{CreateBlockRequest, RelocateGraveRequest};
+
+    /// Database with active block "A" and inactive block "B", two places each
+    fn db_with_small_blocks() -> (tempfile::TempDir, Database, i64, i64) {
+        let (dir, db) = test_db();
+        let block = |code: &str, status: &str| {
+            db.create_block(&CreateBlockRequest {
+                total_capacity: 2,
+                status: status.to_string(),
+                ..block_request(code)
+            })
+            .unwrap()
+        };
+        let (block_a, block_b) = (block("A", "active"), block("B", "inactive"));
+        (dir, db, block_a, block_b)
+    }
+
+    #[test]
+    fn test_full_and_inactive_blocks_need_override() {
+        let (_dir, db, block_a, block_b) = db_with_small_blocks();
+        db.create_grave(&grave_request(block_a, "1")).unwrap();
+        db.create_grave(&grave_request(block_a, "2")).unwrap();
+
+        assert!(db.create_grave(&grave_request(block_a, "3")).is_err());
+        assert!(db.create_grave(&grave_request(block_b, "1")).is_err());
+        assert!(db.override_create_grave(&grave_request(block_a, "3"), " ").is_err());
+        db.override_create_grave(&grave_request(block_a, "3"), "Makam keluarga").unwrap();
+        assert_eq!(db.get_block_stats(block_a).unwrap().available, 0);
+
+        let overrides = db.get_capacity_overrides(10).unwrap();
+        assert_eq!((overrides[0].action.as_str(), overrides[0].violation.as_str()), ("create", "full"));
+    }
+
+    #[test]
+    fn test_relocation_into_inactive_block_needs_override() {
+        let (_dir, db, block_a, block_b) = db_with_small_blocks();
+        let grave_id = db.create_grave(&grave_request(block_a, "1")).unwrap();
+
+        let to_b = RelocateGraveRequest {
+            to_block_id: block_b,
+            to_number: "1".to_string(),
+            to_position: None,
+            relocation_date: "2026-02-01".to_string(),
+            reason: "Pelebaran jalan".to_string(),
+            authorized_by: "H. Usman".to_string(),
+        };
+        assert!(db.relocate_grave(grave_id, &to_b).is_err());
+        db.override_relocate_grave(grave_id, &to_b, "Persetujuan rapat pengurus").unwrap();
+
+        let overrides = db.get_capacity_overrides(10).unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!((overrides[0].action.as_str(), overrides[0].violation.as_str()), ("relocate", "inactive"));
+    }
+
+    #[test]
+    fn test_capacity_forecast() {
+        let (_dir, db, block_a, _) = db_with_small_blocks();
+        let last_year = db.local_today().unwrap().year() - 1;
+        for number in ["1", "2"] {
+            db.create_grave(&CreateGraveRequest {
+                date_of_death: format!("{}-06-01", last_year),
+                ..grave_request(block_a, number)
+            })
+            .unwrap();
+        }
+
+        let forecast = db.get_capacity_forecast(2).unwrap();
+        let a = forecast.blocks.iter().find(|b| b.code == "A").unwrap();
+        assert_eq!((a.occupied, a.available, a.burials_in_period), (2, 0, 2));
+        assert_eq!(a.projected_full_year, Some(last_year + 1));
+        let b = forecast.blocks.iter().find(|b| b.code == "B").unwrap();
+        assert_eq!(b.projected_full_year, None);
+    }
+}
@@ -0,0 +1,349 @@
+//! Cash sessions for daily cash closing
+//!
+//! A cashier opens a session, cash payments they record are attached to it,
+//! and at the end of the day the session is closed with the counted amount.
+//! Payments of a closed session can no longer be edited or deleted.
+
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+use super::statement::receipt_number;
+use super::{validation, Database, Payment};
+
+const SESSION_COLUMNS: &str = "s.id, s.opened_by, uo.full_name, s.opened_at, s.opening_float, s.status,
+        s.closed_by, uc.full_name, s.closed_at, s.expected_amount, s.counted_amount, s.difference, s.notes,
+        (SELECT COUNT(*) FROM payments p WHERE p.cash_session_id = s.id),
+        (SELECT COALESCE(SUM(p.amount), 0) FROM payments p WHERE p.cash_session_id = s.id)";
+
+const SESSION_JOINS: &str = "FROM cash_sessions s
+        LEFT JOIN users uo ON s.opened_by = uo.id
+        LEFT JOIN users uc ON s.closed_by = uc.id";
+
+impl Database {
+    /// Open a cash session for the current operator
+    pub fn open_cash_session(&self, opening_float: i64) -> Result<i64, String> {
+        let operator = self
+            .operator
+            .ok_or_else(|| "Cash sessions require a logged-in user".to_string())?;
+        validation::open_cash_session(opening_float)?;
+        if self.open_cash_session_id()?.is_some() {
+            return Err("You already have an open cash session".to_string());
+        }
+
+        self.conn
+            .execute(
+                "INSERT INTO cash_sessions (opened_by, opening_float) VALUES (?1, ?2)",
+                rusqlite::params![operator, opening_float],
+            )
+            .map_err(|e| format!("Failed to open cash session: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Get the open cash session of the current operator
+    pub fn get_open_cash_session(&self) -> Result<Option<CashSession>, String> {
+        match self.open_cash_session_id()? {
+            Some(id) => self.get_cash_session_by_id(id),
+            None => Ok(None),
+        }
+    }
+
+    /// Get cash sessions, newest first
+    pub fn get_cash_sessions(&self, limit: i64) -> Result<Vec<CashSession>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} {} ORDER BY s.opened_at DESC, s.id DESC LIMIT ?1",
+                SESSION_COLUMNS, SESSION_JOINS
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let sessions = stmt
+            .query_map([limit], map_session)
+            .map_err(|e| format!("Failed to query cash sessions: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect cash sessions: {}", e))?;
+
+        Ok(sessions)
+    }
+
+    /// Get cash session by ID
+    pub fn get_cash_session_by_id(&self, id: i64) -> Result<Option<CashSession>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} {} WHERE s.id = ?1", SESSION_COLUMNS, SESSION_JOINS),
+                [id],
+                map_session,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get cash session: {}", e))
+    }
+
+    /// Close a cash session with the counted amount
+    ///
+    /// Records the expected amount (opening float + cash payments) and the
+    /// difference. Returns the closing report.
+    pub fn close_cash_session(
+        &self,
+        id: i64,
+        counted_amount: i64,
+        notes: Option<&str>,
+    ) -> Result<CashClosingReport, String> {
+        validation::close_cash_session(counted_amount)?;
+        let session = self
+            .get_cash_session_by_id(id)?
+            .ok_or_else(|| format!("Cash session {} not found", id))?;
+        if session.status != "open" {
+            return Err(format!("Cash session {} is already closed", id));
+        }
+
+        let expected_amount = session.opening_float + session.cash_total;
+        self.conn
+            .execute(
+                "UPDATE cash_sessions SET
+                    status = 'closed',
+                    closed_by = ?1,
+                    closed_at = CURRENT_TIMESTAMP,
+                    expected_amount = ?2,
+                    counted_amount = ?3,
+                    difference = ?4,
+                    notes = ?5
+                 WHERE id = ?6",
+                rusqlite::params![
+                    self.operator,
+                    expected_amount,
+                    counted_amount,
+                    counted_amount - expected_amount,
+                    notes,
+                    id
+                ],
+            )
+            .map_err(|e| format!("Failed to close cash session: {}", e))?;
+
+        self.get_cash_closing_report(id)
+    }
+
+    /// Get closing report (session with its payments)
+    pub fn get_cash_closing_report(&self, id: i64) -> Result<CashClosingReport, String> {
+        let session = self
+            .get_cash_session_by_id(id)?
+            .ok_or_else(|| format!("Cash session {} not found", id))?;
+
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT p.id, p.grave_id, p.year, p.payment_date, p.amount, p.payment_method, p.payment_proof, p.paid_by, p.notes, p.created_at, p.updated_at,
+                        g.deceased_name, b.code, g.number
+                 FROM payments p
+                 JOIN graves g ON p.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE p.cash_session_id = ?1
+                 ORDER BY p.created_at, p.id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let payments = stmt
+            .query_map([id], |row| {
+                let payment = Payment {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    year: row.get(2)?,
+                    payment_date: row.get(3)?,
+                    amount: row.get(4)?,
+                    payment_method: row.get(5)?,
+                    payment_proof: row.get(6)?,
+                    paid_by: row.get(7)?,
+                    notes: row.get(8)?,
+                    created_at: row.get(9)?,
+                    updated_at: row.get(10)?,
+                };
+                Ok(CashSessionPayment {
+                    receipt_number: receipt_number(&payment),
+                    payment_id: payment.id,
+                    deceased_name: row.get(11)?,
+                    block_code: row.get(12)?,
+                    grave_number: row.get(13)?,
+                    year: payment.year,
+                    amount: payment.amount,
+                    paid_by: payment.paid_by,
+                    recorded_at: payment.created_at,
+                })
+            })
+            .map_err(|e| format!("Failed to query session payments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect session payments: {}", e))?;
+
+        Ok(CashClosingReport { session, payments })
+    }
+
+    /// ID of the open cash session of the current operator
+    pub(super) fn open_cash_session_id(&self) -> Result<Option<i64>, String> {
+        let Some(operator) = self.operator else {
+            return Ok(None);
+        };
+        self.conn
+            .query_row(
+                "SELECT id FROM cash_sessions WHERE opened_by = ?1 AND status = 'open'",
+                [operator],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up cash session: {}", e))
+    }
+
+    /// Fail if the payment belongs to a closed cash session
+    pub(super) fn check_cash_session_lock(&self, payment_id: i64) -> Result<(), String> {
+        let closed_session: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT s.id FROM payments p
+                 JOIN cash_sessions s ON p.cash_session_id = s.id
+                 WHERE p.id = ?1 AND s.status = 'closed'",
+                [payment_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to check cash session: {}", e))?;
+
+        match closed_session {
+            Some(session_id) => Err(format!(
+                "Payment {} belongs to closed cash session {} and cannot be changed",
+                payment_id, session_id
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn map_session(row: &rusqlite::Row) -> rusqlite::Result<CashSession> {
+    Ok(CashSession {
+        id: row.get(0)?,
+        opened_by: row.get(1)?,
+        opened_by_name: row.get(2)?,
+        opened_at: row.get(3)?,
+        opening_float: row.get(4)?,
+        status: row.get(5)?,
+        closed_by: row.get(6)?,
+        closed_by_name: row.get(7)?,
+        closed_at: row.get(8)?,
+        expected_amount: row.get(9)?,
+        counted_amount: row.get(10)?,
+        difference: row.get(11)?,
+        notes: row.get(12)?,
+        payment_count: row.get(13)?,
+        cash_total: row.get(14)?,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Cash session of one cashier
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CashSession {
+    pub id: i64,
+    pub opened_by: i64,
+    pub opened_by_name: Option<String>,
+    pub opened_at: DateTime<Utc>,
+    pub opening_float: i64,
+    /// open, closed
+    pub status: String,
+    pub closed_by: Option<i64>,
+    pub closed_by_name: Option<String>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub expected_amount: Option<i64>,
+    pub counted_amount: Option<i64>,
+    /// counted - expected (negative = cash short)
+    pub difference: Option<i64>,
+    pub notes: Option<String>,
+    pub payment_count: i64,
+    pub cash_total: i64,
+}
+
+/// Payment listed on the closing report
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CashSessionPayment {
+    pub payment_id: i64,
+    pub receipt_number: String,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub grave_number: String,
+    pub year: i32,
+    pub amount: i64,
+    pub paid_by: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Closing report of a cash session
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CashClosingReport {
+    pub session: CashSession,
+    pub payments: Vec<CashSessionPayment>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_grave, db_with_grave, payment_request};
+    use crate::db::{CreatePaymentRequest, CreateUserRequest, Role};
+
+    /// Cashier's view of a database with one grave: `(dir, db, grave_id)`
+    fn cashier_db() -> (tempfile::TempDir, Database, i64) {
+        let (dir, db, _, grave_id) = db_with_grave();
+        let cashier_id = db
+            .create_user(&CreateUserRequest {
+                username: "kasir".to_string(),
+                full_name: "Kasir".to_string(),
+                password: "rahasia123".to_string(),
+                role: Role::Cashier,
+            })
+            .unwrap();
+        (dir, db.with_operator(cashier_id), grave_id)
+    }
+
+    fn payment(grave_id: i64, year: i32, method: &str) -> CreatePaymentRequest {
+        CreatePaymentRequest {
+            payment_date: "2026-01-09".to_string(),
+            payment_method: Some(method.to_string()),
+            ..payment_request(grave_id, year)
+        }
+    }
+
+    #[test]
+    fn test_closing_report_counts_cash_payments() {
+        let (_dir, db, grave_id) = cashier_db();
+        let session_id = db.open_cash_session(50000).unwrap();
+        assert!(db.open_cash_session(0).is_err());
+
+        db.create_payment(&payment(grave_id, 2024, "cash")).unwrap();
+        let transfer_id = db.create_payment(&payment(grave_id, 2025, "transfer")).unwrap();
+        // Counted as cash after all
+        db.update_payment(transfer_id, &payment(grave_id, 2025, "cash")).unwrap();
+
+        let errors = validation::field_errors(db.close_cash_session(session_id, -1, None));
+        assert_eq!(errors[0].field, "counted_amount");
+        let report = db.close_cash_session(session_id, 240000, Some("Kurang")).unwrap();
+        assert_eq!(report.payments.len(), 2);
+        assert_eq!(report.session.expected_amount, Some(250000));
+        assert_eq!(report.session.difference, Some(-10000));
+        assert!(db.get_open_cash_session().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_closed_session_locks_its_payments() {
+        let (_dir, db, grave_id) = cashier_db();
+        let session_id = db.open_cash_session(0).unwrap();
+        let cash_id = db.create_payment(&payment(grave_id, 2024, "cash")).unwrap();
+        db.close_cash_session(session_id, 100000, None).unwrap();
+
+        assert!(db.delete_payment(cash_id).is_err());
+        assert!(db.update_payment(cash_id, &payment(grave_id, 2024, "cash")).is_err());
+        // Deleting the grave would cascade to the payment, even for an admin
+        assert!(db.delete_grave(grave_id).is_err());
+        assert!(db.override_delete_grave(grave_id, "Data ganda").is_err());
+
+        // Merging the grave elsewhere would move the locked payment too
+        let block_id = db.get_grave_by_id(grave_id).unwrap().unwrap().block_id;
+        let duplicate_id = create_grave(&db, block_id, "2");
+        assert!(db.merge_graves(duplicate_id, grave_id).unwrap_err().contains("closed cash session"));
+        assert_eq!(db.get_payment_by_id(cash_id).unwrap().unwrap().grave_id, grave_id);
+    }
+}
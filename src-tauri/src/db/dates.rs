@@ -0,0 +1,510 @@
+//! Dates, timestamps and the local timezone
+//!
+//! Calendar dates (date of death, burial, payment, ...) are stored as ISO
+//! `YYYY-MM-DD` text and read as `NaiveDate`. Timestamps (`created_at`,
+//! `updated_at`, ...) are SQLite `CURRENT_TIMESTAMP` values in UTC and read
+//! as `DateTime<Utc>`. "Today", "this month" and "this year" are computed in
+//! the timezone configured in settings, not in UTC or the computer's zone.
+//!
+//! Dates written before validation existed were normalized once by
+//! migration 18; those it could not read are listed in `date_issues` until
+//! staff confirm them.
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Utc};
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::Error::FromSqlConversionFailure;
+use rusqlite::OptionalExtension;
+use rusqlite::Row;
+
+use super::{validation, Database};
+
+/// Timezones that can be configured (Indonesia has no daylight saving time)
+///
+/// The offsets are repeated in the `local_clock` view of migration 18.
+pub const TIMEZONES: &[Timezone] = &[
+    Timezone {
+        name: "Asia/Jakarta",
+        abbreviation: "WIB",
+        utc_offset_hours: 7,
+    },
+    Timezone {
+        name: "Asia/Makassar",
+        abbreviation: "WITA",
+        utc_offset_hours: 8,
+    },
+    Timezone {
+        name: "Asia/Jayapura",
+        abbreviation: "WIT",
+        utc_offset_hours: 9,
+    },
+];
+
+/// Stored timestamp format (SQLite `CURRENT_TIMESTAMP`)
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Stored date columns normalized by migration 18, with whether they may be NULL
+const DATE_COLUMNS: &[(&str, &str, bool)] = &[
+    ("graves", "date_of_death", false),
+    ("graves", "burial_date", true),
+    // After graves: the first interment follows its grave through a trigger
+    ("interments", "date_of_death", false),
+    ("interments", "burial_date", true),
+    ("payments", "payment_date", false),
+    ("reservations", "reserved_until", true),
+    ("grave_relocations", "relocation_date", false),
+];
+
+impl Database {
+    /// Configured timezone
+    pub fn local_timezone(&self) -> Result<&'static Timezone, String> {
+        let name: String = self
+            .conn
+            .query_row("SELECT timezone FROM settings WHERE id = 1", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read timezone: {}", e))?;
+        Ok(timezone(&name).unwrap_or(&TIMEZONES[0]))
+    }
+
+    /// Current time in the configured timezone
+    pub fn local_now(&self) -> Result<DateTime<FixedOffset>, String> {
+        Ok(Utc::now().with_timezone(&self.local_timezone()?.offset()))
+    }
+
+    /// Today's date in the configured timezone
+    pub fn local_today(&self) -> Result<NaiveDate, String> {
+        Ok(self.local_now()?.date_naive())
+    }
+
+    /// Stored timestamps covering the local dates `from` (inclusive) to
+    /// `until` (exclusive), for `created_at >= ?1 AND created_at < ?2`
+    pub(super) fn timestamp_range(&self, from: NaiveDate, until: NaiveDate) -> Result<(String, String), String> {
+        let timezone = self.local_timezone()?;
+        Ok((timezone.day_start_utc(from), timezone.day_start_utc(until)))
+    }
+
+    /// SQLite date modifier turning a stored UTC timestamp into local time
+    pub(super) fn local_time_modifier(&self) -> Result<String, String> {
+        Ok(format!("{:+} hours", self.local_timezone()?.utc_offset_hours))
+    }
+
+    /// Stored dates that could not be read during migration 18 and are not
+    /// confirmed yet
+    pub fn get_date_issues(&self) -> Result<Vec<DateIssue>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, table_name, row_id, column_name, stored_value, replaced_with, created_at
+                 FROM date_issues
+                 WHERE resolved_at IS NULL
+                 ORDER BY table_name, row_id, column_name",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let issues = stmt
+            .query_map([], |row| {
+                Ok(DateIssue {
+                    id: row.get(0)?,
+                    table_name: row.get(1)?,
+                    row_id: row.get(2)?,
+                    column_name: row.get(3)?,
+                    stored_value: row.get(4)?,
+                    replaced_with: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query date issues: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect date issues: {}", e))?;
+
+        Ok(issues)
+    }
+
+    /// Confirm a date that could not be read
+    ///
+    /// `date` is written to the record; without it the date written by the
+    /// migration is kept (or the date stays empty).
+    pub fn resolve_date_issue(&self, id: i64, date: Option<&str>) -> Result<(), String> {
+        let (table, row_id, column): (String, i64, String) = self
+            .conn
+            .query_row(
+                "SELECT table_name, row_id, column_name FROM date_issues WHERE id = ?1 AND resolved_at IS NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get date issue: {}", e))?
+            .ok_or_else(|| format!("Date issue {} not found or already resolved", id))?;
+        let (table, column, _) = DATE_COLUMNS
+            .iter()
+            .find(|(t, c, _)| *t == table && *c == column)
+            .ok_or_else(|| format!("Unknown date column {}.{}", table, column))?;
+        let latest = (*table != "reservations").then(|| self.local_today()).transpose()?;
+        let date = validation::non_empty(date);
+        validation::resolve_date_issue(date, latest)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        if let Some(date) = date {
+            self.conn
+                .execute(
+                    &format!("UPDATE {} SET {} = ?1 WHERE id = ?2", table, column),
+                    rusqlite::params![date, row_id],
+                )
+                .map_err(|e| format!("Failed to store date: {}", e))?;
+        }
+        self.conn
+            .execute(
+                "UPDATE date_issues SET resolved_at = CURRENT_TIMESTAMP, resolved_by = ?1 WHERE id = ?2",
+                rusqlite::params![self.operator, id],
+            )
+            .map_err(|e| format!("Failed to resolve date issue: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit date issue: {}", e))
+    }
+
+    /// Fail while the grave's date of death, which its fees start from, is
+    /// an unconfirmed replacement for an unreadable date
+    ///
+    /// Only writes are blocked; statements are marked instead
+    /// (`GraveStatement::date_unconfirmed`).
+    pub(super) fn check_grave_dates_confirmed(&self, grave_id: i64) -> Result<(), String> {
+        match self.unconfirmed_date_of_death(grave_id)? {
+            Some(stored) => Err(format!(
+                "The date of death of grave {} could not be read ('{}'); confirm it under date issues first",
+                grave_id, stored
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The unreadable stored value while the grave's date of death is an
+    /// unconfirmed replacement
+    pub(super) fn unconfirmed_date_of_death(&self, grave_id: i64) -> Result<Option<String>, String> {
+        self
+            .conn
+            .query_row(
+                "SELECT stored_value FROM date_issues
+                 WHERE table_name = 'graves' AND row_id = ?1 AND column_name = 'date_of_death' AND resolved_at IS NULL",
+                [grave_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to check date issues: {}", e))
+    }
+
+    /// Data migration 18: rewrite stored dates as `YYYY-MM-DD`
+    ///
+    /// Blank optional dates become NULL. Dates that cannot be read are
+    /// cleared when optional, otherwise replaced by the day the record was
+    /// entered; both are recorded as issues with the original value, which
+    /// staff confirm with `resolve_date_issue`. Until then writes to a grave
+    /// with a replaced date of death are blocked (`check_grave_dates_confirmed`).
+    pub(super) fn normalize_stored_dates(&self) -> Result<(), String> {
+        for (table, column, nullable) in DATE_COLUMNS {
+            let mut stmt = self
+                .conn
+                .prepare(&format!(
+                    "SELECT id, CAST({} AS TEXT), created_at FROM {} WHERE {} IS NOT NULL",
+                    column, table, column
+                ))
+                .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            let rows: Vec<(i64, String, Option<String>)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .map_err(|e| format!("Failed to query {}.{}: {}", table, column, e))?
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to collect {}.{}: {}", table, column, e))?;
+
+            for (id, stored, created_at) in rows {
+                if NaiveDate::parse_from_str(&stored, "%Y-%m-%d").is_ok() {
+                    continue;
+                }
+                let date = match parse_loose_date(&stored) {
+                    Some(date) => Some(date),
+                    None if *nullable && stored.trim().is_empty() => None,
+                    None => {
+                        let fallback = if *nullable {
+                            None
+                        } else {
+                            Some(
+                                created_at
+                                    .as_deref()
+                                    .and_then(parse_loose_date)
+                                    .unwrap_or_else(|| Utc::now().date_naive()),
+                            )
+                        };
+                        self.conn
+                            .execute(
+                                "INSERT INTO date_issues (table_name, row_id, column_name, stored_value, replaced_with) VALUES (?1, ?2, ?3, ?4, ?5)",
+                                rusqlite::params![table, id, column, stored, fallback],
+                            )
+                            .map_err(|e| format!("Failed to record date issue: {}", e))?;
+                        fallback
+                    }
+                };
+                self.conn
+                    .execute(
+                        &format!("UPDATE {} SET {} = ?1 WHERE id = ?2", table, column),
+                        rusqlite::params![date, id],
+                    )
+                    .map_err(|e| format!("Failed to store normalized date: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configured timezone by IANA name
+pub fn timezone(name: &str) -> Option<&'static Timezone> {
+    TIMEZONES.iter().find(|timezone| timezone.name == name)
+}
+
+impl Timezone {
+    /// Fixed UTC offset of the timezone
+    pub fn offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_hours * 3600).expect("timezone offset within a day")
+    }
+
+    /// Stored (UTC) timestamp of local midnight at the start of `date`
+    fn day_start_utc(&self, date: NaiveDate) -> String {
+        (date.and_time(NaiveTime::MIN) - Duration::hours(self.utc_offset_hours.into()))
+            .format(TIMESTAMP_FORMAT)
+            .to_string()
+    }
+}
+
+/// First day of the month containing `date` and of the month after it
+pub(super) fn month_range(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = date.with_day(1).unwrap_or(date);
+    let next = if start.month() == 12 {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+    };
+    (start, next.unwrap_or(NaiveDate::MAX))
+}
+
+/// First day of `year` and of the year after it
+pub(super) fn year_range(year: i32) -> (NaiveDate, NaiveDate) {
+    (
+        NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or(NaiveDate::MIN),
+        NaiveDate::from_ymd_opt(year + 1, 1, 1).unwrap_or(NaiveDate::MAX),
+    )
+}
+
+/// Read a stored date column, also accepting the notations of databases
+/// that have not been through migration 18 (e.g. a merge source)
+pub(super) fn loose_date(row: &Row, idx: usize) -> rusqlite::Result<NaiveDate> {
+    loose_optional_date(row, idx)?
+        .ok_or_else(|| FromSqlConversionFailure(idx, Type::Null, "Date is empty".into()))
+}
+
+/// Optional variant of [`loose_date`]; blank values read as `None`
+pub(super) fn loose_optional_date(row: &Row, idx: usize) -> rusqlite::Result<Option<NaiveDate>> {
+    let stored = match row.get_ref(idx)? {
+        ValueRef::Null => return Ok(None),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) => value.to_string(),
+        ValueRef::Text(text) | ValueRef::Blob(text) => String::from_utf8_lossy(text).into_owned(),
+    };
+    if stored.trim().is_empty() {
+        return Ok(None);
+    }
+    parse_loose_date(&stored)
+        .map(Some)
+        .ok_or_else(|| FromSqlConversionFailure(idx, Type::Text, format!("Unreadable date '{}'", stored).into()))
+}
+
+/// Read a date in any of the notations found in older data
+///
+/// Accepts ISO dates (also with a time part), day-first dates (31/12/2023,
+/// 31-12-2023, 31.12.2023), 2023/12/31, Indonesian or English month names
+/// (5 Maret 2020, 5 Agu 2020) and Excel serial numbers.
+pub fn parse_loose_date(raw: &str) -> Option<NaiveDate> {
+    let raw = raw.trim();
+    // Time part, e.g. "2023-12-31 00:00:00" or "2023-12-31T00:00:00"
+    let date_part = match raw.split_once([' ', 'T']) {
+        Some((date, time)) if time.contains(':') => date,
+        _ => raw,
+    };
+
+    const FORMATS: &[&str] = &["%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%Y/%m/%d"];
+    for format in FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(date_part, format) {
+            return Some(date);
+        }
+    }
+
+    // Excel serial date (days since 1899-12-30)
+    if let Ok(serial) = date_part.parse::<i64>() {
+        if (1..=2958465).contains(&serial) {
+            let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)?;
+            return epoch.checked_add_signed(Duration::days(serial));
+        }
+    }
+
+    parse_month_name_date(raw)
+}
+
+/// "5 Maret 2020", "05-Agu-2020", "5 March 2020"
+fn parse_month_name_date(raw: &str) -> Option<NaiveDate> {
+    let parts: Vec<&str> = raw
+        .split(|c: char| c.is_whitespace() || matches!(c, '-' | '/' | '.' | ','))
+        .filter(|part| !part.is_empty())
+        .collect();
+    let [day, month, year] = parts.as_slice() else {
+        return None;
+    };
+    let month = month_number(month)?;
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month, day.parse().ok()?)
+}
+
+/// Month number from an Indonesian or English (short) month name
+fn month_number(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    let month = match name.get(..3)? {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "mei" | "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "agu" | "agt" | "ags" | "aug" => 8,
+        "sep" => 9,
+        "okt" | "oct" => 10,
+        "nov" | "nop" => 11,
+        "des" | "dec" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Timezone that can be configured in settings
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Timezone {
+    /// IANA name stored in settings
+    pub name: &'static str,
+    pub abbreviation: &'static str,
+    pub utc_offset_hours: i32,
+}
+
+/// Stored date that could not be read during migration 18
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DateIssue {
+    pub id: i64,
+    pub table_name: String,
+    pub row_id: i64,
+    pub column_name: String,
+    pub stored_value: String,
+    /// Date written instead, `None` when the date was cleared
+    pub replaced_with: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, create_grave, payment_request, test_db};
+    use crate::db::UpdateSettingsRequest;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_loose_date() {
+        for raw in [
+            "2023-12-31",
+            "31/12/2023",
+            "31-12-2023",
+            "31.12.2023",
+            "2023/12/31",
+            "2023-12-31 00:00:00",
+            "2023-12-31T08:30:00",
+            "31 Desember 2023",
+            "31-Des-2023",
+            "31 December 2023",
+            "45291",
+        ] {
+            assert_eq!(parse_loose_date(raw), Some(date(2023, 12, 31)), "{}", raw);
+        }
+        assert_eq!(parse_loose_date("5 Agustus 2020"), Some(date(2020, 8, 5)));
+        assert_eq!(parse_loose_date("1-2-2020"), Some(date(2020, 2, 1)));
+        for raw in ["", "kemarin", "31/02/2023", "Maret 2020"] {
+            assert_eq!(parse_loose_date(raw), None, "{}", raw);
+        }
+        assert_eq!(month_range(date(2026, 12, 15)), (date(2026, 12, 1), date(2027, 1, 1)));
+    }
+
+    #[test]
+    fn test_stored_dates_are_normalized_and_confirmed() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let (first, second) = (create_grave(&db, block_id, "1"), create_grave(&db, block_id, "2"));
+        // Values as older versions could store them
+        db.connection()
+            .execute_batch(&format!(
+                "UPDATE graves SET date_of_death = '05/03/2020', burial_date = '' WHERE id = {first};
+                 UPDATE graves SET date_of_death = 'lupa', burial_date = 'kemarin', created_at = '2024-02-01 10:00:00' WHERE id = {second};"
+            ))
+            .unwrap();
+
+        db.normalize_stored_dates().unwrap();
+
+        let first = db.get_grave_by_id(first).unwrap().unwrap();
+        assert_eq!((first.date_of_death, first.burial_date), (date(2020, 3, 5), None));
+        let second = db.get_grave_by_id(second).unwrap().unwrap();
+        assert_eq!((second.date_of_death, second.burial_date), (date(2024, 2, 1), None));
+        assert_eq!(db.get_interments(second.id).unwrap()[0].date_of_death, date(2024, 2, 1));
+
+        let issues = db.get_date_issues().unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!((issues[0].column_name.as_str(), issues[0].stored_value.as_str()), ("burial_date", "kemarin"));
+        assert_eq!(issues[0].replaced_with, None);
+        assert_eq!((issues[1].column_name.as_str(), issues[1].replaced_with), ("date_of_death", Some(date(2024, 2, 1))));
+
+        // The replaced date of death blocks writes until it is confirmed;
+        // the statement is only marked
+        assert!(db.get_grave_statement(second.id).unwrap().date_unconfirmed);
+        assert!(db.create_payment(&payment_request(second.id, 2024)).is_err());
+        assert!(db.resolve_date_issue(issues[1].id, Some("05/03/2019")).is_err());
+        db.resolve_date_issue(issues[1].id, Some("2019-03-05")).unwrap();
+        assert_eq!(db.get_grave_by_id(second.id).unwrap().unwrap().date_of_death, date(2019, 3, 5));
+        let statement = db.get_grave_statement(second.id).unwrap();
+        assert_eq!((statement.first_billable_year, statement.date_unconfirmed), (2019, false));
+        assert_eq!(db.get_date_issues().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_local_timezone() {
+        let (_dir, db) = test_db();
+
+        // Local midnight 1 March WIB is 28 February 17:00 UTC
+        assert_eq!(db.local_timezone().unwrap().abbreviation, "WIB");
+        assert_eq!(
+            db.timestamp_range(date(2026, 3, 1), date(2026, 4, 1)).unwrap(),
+            ("2026-02-28 17:00:00".to_string(), "2026-03-31 17:00:00".to_string())
+        );
+
+        db.update_settings(&UpdateSettingsRequest {
+            foundation_name: None,
+            address: None,
+            phone: None,
+            email: None,
+            logo_path: None,
+            active_year: None,
+            auto_backup: None,
+            timezone: Some("Asia/Jayapura".to_string()),
+        })
+        .unwrap();
+        assert_eq!(db.local_time_modifier().unwrap(), "+9 hours");
+        let sql_today: NaiveDate = db
+            .connection()
+            .query_row("SELECT date(now) FROM local_clock", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sql_today, db.local_today().unwrap());
+    }
+}
@@ -0,0 +1,644 @@
+//! Duplicate grave detection and merging
+//!
+//! Graves entered twice (often with a slightly different spelling) are found
+//! by scoring pairs on the phonetic deceased name key, date of death, block
+//! and shared heir phone numbers. Pairs are only compared when they share a
+//! name prefix, date of death or heir phone, so large cemeteries stay fast.
+//! A confirmed duplicate is merged into the grave that is kept: heirs,
+//! payments, fee waivers, carried arrears, relocations and later interments
+//! move over, and a snapshot of the removed grave is logged.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use chrono::{Datelike, NaiveDate};
+
+use super::name_search::{name_key, ratio};
+use super::Database;
+
+/// Pairs below this name similarity are never suggested
+const MIN_NAME_SIMILARITY: f64 = 0.7;
+
+/// Blocking groups larger than this are not compared pairwise
+const MAX_GROUP_SIZE: usize = 1000;
+
+impl Database {
+    /// Find likely duplicate graves, best match first
+    ///
+    /// `min_score` is 0-100; pairs dismissed earlier are left out.
+    pub fn find_duplicate_graves(&self, min_score: i64, limit: usize) -> Result<Vec<DuplicateCandidate>, String> {
+        let graves = self.load_duplicate_graves()?;
+        let phones = self.load_heir_phones()?;
+        let dismissed = self.load_dismissed_pairs()?;
+
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, grave) in graves.iter().enumerate() {
+            let mut keys: HashSet<String> = grave
+                .normalized
+                .split(' ')
+                .filter(|token| !token.is_empty())
+                .map(|token| format!("n:{}", token.chars().take(2).collect::<String>()))
+                .collect();
+            keys.insert(format!("d:{}", grave.grave.date_of_death));
+            for phone in phones.get(&grave.grave.id).into_iter().flatten() {
+                keys.insert(format!("p:{}", phone));
+            }
+            for key in keys {
+                groups.entry(key).or_default().push(index);
+            }
+        }
+
+        let mut compared = HashSet::new();
+        let mut candidates = Vec::new();
+        for members in groups.values().filter(|m| m.len() > 1 && m.len() <= MAX_GROUP_SIZE) {
+            for (i, &a) in members.iter().enumerate() {
+                for &b in &members[i + 1..] {
+                    let (first, second) = (&graves[a.min(b)], &graves[a.max(b)]);
+                    let pair = (first.grave.id, second.grave.id);
+                    if dismissed.contains(&pair) || !compared.insert(pair) {
+                        continue;
+                    }
+                    if let Some(candidate) = score_pair(first, second, &phones) {
+                        if candidate.score >= min_score {
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.first.id.cmp(&b.first.id))
+                .then(a.second.id.cmp(&b.second.id))
+        });
+        candidates.truncate(limit);
+        Ok(candidates)
+    }
+
+    /// Mark a pair of graves as not being duplicates
+    pub fn dismiss_duplicate(&self, grave_id: i64, other_grave_id: i64) -> Result<(), String> {
+        if grave_id == other_grave_id {
+            return Err("A grave cannot be a duplicate of itself".to_string());
+        }
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO duplicate_dismissals (first_grave_id, second_grave_id, dismissed_by) VALUES (?1, ?2, ?3)",
+                rusqlite::params![grave_id.min(other_grave_id), grave_id.max(other_grave_id), self.operator],
+            )
+            .map_err(|e| format!("Failed to dismiss duplicate: {}", e))?;
+        Ok(())
+    }
+
+    /// Merge a duplicate grave into the grave that is kept
+    ///
+    /// Payments, fee waivers, carried arrears, relocation history and
+    /// interments after the first move to the kept grave; heirs move unless
+    /// the same person is already heir of it. Both graves having a payment or
+    /// fee waiver for the same year is refused, as one of them has to be
+    /// corrected first. Everything happens in one transaction. Payments and
+    /// fee waivers of closed years or closed cash sessions are locked; see
+    /// `override_merge_graves`.
+    pub fn merge_graves(&self, keep_id: i64, remove_id: i64) -> Result<GraveMergeReport, String> {
+        self.merge_grave_records(keep_id, remove_id, None)
+    }
+
+    /// Merge graves moving payments or fee waivers of closed years (admin
+    /// override, logged)
+    pub fn override_merge_graves(&self, keep_id: i64, remove_id: i64, reason: &str) -> Result<GraveMergeReport, String> {
+        self.merge_grave_records(keep_id, remove_id, Some(reason))
+    }
+
+    fn merge_grave_records(
+        &self,
+        keep_id: i64,
+        remove_id: i64,
+        override_reason: Option<&str>,
+    ) -> Result<GraveMergeReport, String> {
+        if keep_id == remove_id {
+            return Err("Choose two different graves to merge".to_string());
+        }
+        self.get_grave_by_id(keep_id)?
+            .ok_or_else(|| format!("Grave {} not found", keep_id))?;
+        let removed = self
+            .get_grave_by_id(remove_id)?
+            .ok_or_else(|| format!("Grave {} not found", remove_id))?;
+
+        for (table, label) in [("payments", "payment"), ("fee_waivers", "fee waiver")] {
+            let years = self.overlapping_years(table, keep_id, remove_id)?;
+            if !years.is_empty() {
+                return Err(format!(
+                    "Both graves have a {} for {}; correct one of them before merging",
+                    label,
+                    years.iter().map(|y| y.to_string()).collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start merge: {}", e))?;
+
+        let (heirs_moved, heirs_dropped) = self.move_heirs(keep_id, remove_id)?;
+
+        let last_sequence: i64 = self
+            .conn
+            .query_row(
+                "SELECT COALESCE(MAX(sequence), 0) FROM interments WHERE grave_id = ?1",
+                [keep_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to get interment sequence: {}", e))?;
+        // The first interment is the duplicated deceased itself
+        let interments_moved = self
+            .conn
+            .execute(
+                "UPDATE interments SET grave_id = ?1, sequence = ?2 + sequence - 1, updated_by = ?3
+                 WHERE grave_id = ?4 AND sequence > 1",
+                rusqlite::params![keep_id, last_sequence, self.operator, remove_id],
+            )
+            .map_err(|e| format!("Failed to move interments: {}", e))? as i64;
+
+        // Moving a closed year's payment changes totals the board signed;
+        // payments of a closed cash session cannot move at all
+        for (id, year) in self.records_by_year("payments", remove_id)? {
+            match self.lock_override_for(year, override_reason)? {
+                Some(reason) => {
+                    self.check_cash_session_lock(id)?;
+                    self.log_payment_override(id, "update", reason)?;
+                }
+                None => self.check_payment_locks(id, None)?,
+            }
+        }
+        for (id, year) in self.records_by_year("fee_waivers", remove_id)? {
+            if let Some(reason) = self.lock_override_for(year, override_reason)? {
+                self.log_waiver_override(id, "update", reason)?;
+            }
+        }
+
+        let mut moved = [0i64; 2];
+        for (count, table) in moved.iter_mut().zip(["payments", "fee_waivers"]) {
+            *count = self
+                .conn
+                .execute(
+                    &format!("UPDATE {} SET grave_id = ?1 WHERE grave_id = ?2", table),
+                    [keep_id, remove_id],
+                )
+                .map_err(|e| format!("Failed to move {}: {}", table, e))? as i64;
+        }
+        let [payments_moved, waivers_moved] = moved;
+
+        // Arrears carried into the same year by both graves add up, as in
+        // the totals of the closed year
+        self.conn
+            .execute(
+                "INSERT INTO carried_arrears (year, grave_id, amount)
+                 SELECT year, ?1, amount FROM carried_arrears WHERE grave_id = ?2
+                 ON CONFLICT(year, grave_id) DO UPDATE SET amount = amount + excluded.amount",
+                [keep_id, remove_id],
+            )
+            .map_err(|e| format!("Failed to move carried arrears: {}", e))?;
+
+        for table in ["reservations", "capacity_overrides", "grave_relocations"] {
+            self.conn
+                .execute(
+                    &format!("UPDATE {} SET grave_id = ?1 WHERE grave_id = ?2", table),
+                    [keep_id, remove_id],
+                )
+                .map_err(|e| format!("Failed to update {}: {}", table, e))?;
+        }
+
+        self.conn
+            .execute(
+                "UPDATE graves SET
+                    burial_date = COALESCE(graves.burial_date, r.burial_date),
+                    notes = COALESCE(graves.notes, r.notes),
+                    updated_by = ?3
+                 FROM (SELECT burial_date, notes FROM graves WHERE id = ?2) AS r
+                 WHERE graves.id = ?1",
+                rusqlite::params![keep_id, remove_id, self.operator],
+            )
+            .map_err(|e| format!("Failed to update kept grave: {}", e))?;
+
+        let snapshot = serde_json::to_string(&removed)
+            .map_err(|e| format!("Failed to serialize grave: {}", e))?;
+        self.conn
+            .execute(
+                "INSERT INTO grave_merges (kept_grave_id, removed_grave_id, removed_snapshot, heirs_moved, payments_moved, merged_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![keep_id, remove_id, snapshot, heirs_moved, payments_moved, self.operator],
+            )
+            .map_err(|e| format!("Failed to log merge: {}", e))?;
+
+        self.conn
+            .execute(
+                "UPDATE grave_merges SET kept_grave_id = ?1 WHERE kept_grave_id = ?2",
+                [keep_id, remove_id],
+            )
+            .map_err(|e| format!("Failed to update earlier merges: {}", e))?;
+        self.delete_merged_grave(remove_id)?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit merge: {}", e))?;
+
+        Ok(GraveMergeReport {
+            kept_grave_id: keep_id,
+            removed_grave_id: remove_id,
+            heirs_moved,
+            heirs_dropped,
+            payments_moved,
+            waivers_moved,
+            interments_moved,
+        })
+    }
+
+    /// Move heirs of `remove_id` behind those of `keep_id`, skipping people
+    /// already heir of the kept grave. Returns (moved, dropped).
+    fn move_heirs(&self, keep_id: i64, remove_id: i64) -> Result<(i64, i64), String> {
+        let heir_keys = |grave_id: i64| -> Result<Vec<(i64, Option<i64>, String)>, String> {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, person_id, person_key FROM heirs WHERE grave_id = ?1 ORDER BY order_number")
+                .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            let heirs = stmt
+                .query_map([grave_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .map_err(|e| format!("Failed to query heirs: {}", e))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect heirs: {}", e))?;
+            Ok(heirs)
+        };
+
+        let kept = heir_keys(keep_id)?;
+        let mut next_order: i64 = self
+            .conn
+            .query_row(
+                "SELECT COALESCE(MAX(order_number), 0) FROM heirs WHERE grave_id = ?1",
+                [keep_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to get heir order: {}", e))?;
+
+        let (mut moved, mut dropped) = (0, 0);
+        for (id, person_id, key) in heir_keys(remove_id)? {
+            let known = kept
+                .iter()
+                .any(|(_, kept_person, kept_key)| *kept_key == key || (person_id.is_some() && *kept_person == person_id));
+            if known {
+                dropped += 1;
+                continue;
+            }
+            next_order += 1;
+            self.conn
+                .execute(
+                    "UPDATE heirs SET grave_id = ?1, order_number = ?2, is_primary = ?3, updated_by = ?4 WHERE id = ?5",
+                    rusqlite::params![keep_id, next_order, next_order == 1, self.operator, id],
+                )
+                .map_err(|e| format!("Failed to move heir: {}", e))?;
+            moved += 1;
+        }
+        Ok((moved, dropped))
+    }
+
+    /// Delete a merged grave with the rows still pointing at it: dropped
+    /// heirs, its first interment and open issues. Foreign keys are not
+    /// enforced, so nothing cascades.
+    fn delete_merged_grave(&self, grave_id: i64) -> Result<(), String> {
+        for (sql, what) in [
+            (
+                "DELETE FROM phone_number_issues WHERE heir_id IN (SELECT id FROM heirs WHERE grave_id = ?1)",
+                "phone number issues",
+            ),
+            (
+                "DELETE FROM date_issues WHERE (table_name = 'graves' AND row_id = ?1)
+                    OR (table_name = 'interments' AND row_id IN (SELECT id FROM interments WHERE grave_id = ?1))",
+                "date issues",
+            ),
+            ("DELETE FROM heirs WHERE grave_id = ?1", "heirs"),
+            ("DELETE FROM interments WHERE grave_id = ?1", "interments"),
+            ("DELETE FROM carried_arrears WHERE grave_id = ?1", "carried arrears"),
+            (
+                "DELETE FROM duplicate_dismissals WHERE first_grave_id = ?1 OR second_grave_id = ?1",
+                "duplicate dismissals",
+            ),
+            ("DELETE FROM graves WHERE id = ?1", "merged grave"),
+        ] {
+            self.conn
+                .execute(sql, [grave_id])
+                .map_err(|e| format!("Failed to delete {}: {}", what, e))?;
+        }
+        Ok(())
+    }
+
+    /// (id, year) of a grave's payments or fee waivers
+    fn records_by_year(&self, table: &str, grave_id: i64) -> Result<Vec<(i64, i32)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT id, year FROM {} WHERE grave_id = ?1 ORDER BY year", table))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let records = stmt
+            .query_map([grave_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query {}: {}", table, e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect {}: {}", table, e))?;
+        Ok(records)
+    }
+
+    fn overlapping_years(&self, table: &str, keep_id: i64, remove_id: i64) -> Result<Vec<i32>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT r.year FROM {0} r JOIN {0} k ON k.year = r.year AND k.grave_id = ?1
+                 WHERE r.grave_id = ?2 ORDER BY r.year",
+                table
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let years = stmt
+            .query_map([keep_id, remove_id], |row| row.get(0))
+            .map_err(|e| format!("Failed to check {}: {}", table, e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect years: {}", e))?;
+        Ok(years)
+    }
+
+    fn load_duplicate_graves(&self) -> Result<Vec<ScoredGrave>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT g.id, g.deceased_name, g.block_id, b.code, g.number, g.date_of_death,
+                        (SELECT COUNT(*) FROM payments p WHERE p.grave_id = g.id)
+                 FROM graves g
+                 JOIN blocks b ON g.block_id = b.id
+                 ORDER BY g.id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let graves = stmt
+            .query_map([], |row| {
+                Ok(DuplicateGrave {
+                    id: row.get(0)?,
+                    deceased_name: row.get(1)?,
+                    block_id: row.get(2)?,
+                    block_code: row.get(3)?,
+                    number: row.get(4)?,
+                    date_of_death: row.get(5)?,
+                    payment_count: row.get(6)?,
+                    heir_names: Vec::new(),
+                })
+            })
+            .map_err(|e| format!("Failed to query graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT grave_id, full_name FROM heirs ORDER BY grave_id, order_number")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut heir_names: HashMap<i64, Vec<String>> = HashMap::new();
+        for row in stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query heirs: {}", e))?
+        {
+            let (grave_id, name) = row.map_err(|e| format!("Failed to collect heirs: {}", e))?;
+            heir_names.entry(grave_id).or_default().push(name);
+        }
+
+        Ok(graves
+            .into_iter()
+            .map(|mut grave| {
+                grave.heir_names = heir_names.remove(&grave.id).unwrap_or_default();
+                ScoredGrave {
+                    normalized: name_key(&grave.deceased_name),
+                    grave,
+                }
+            })
+            .collect())
+    }
+
+    /// Normalized heir phone numbers per grave
+    fn load_heir_phones(&self) -> Result<HashMap<i64, Vec<String>>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT grave_id, phone_e164 FROM heirs WHERE phone_e164 IS NOT NULL")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut phones: HashMap<i64, Vec<String>> = HashMap::new();
+        for row in stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query heir phones: {}", e))?
+        {
+            let (grave_id, phone) = row.map_err(|e| format!("Failed to collect heir phones: {}", e))?;
+            phones.entry(grave_id).or_default().push(phone);
+        }
+        Ok(phones)
+    }
+
+    fn load_dismissed_pairs(&self) -> Result<HashSet<(i64, i64)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT first_grave_id, second_grave_id FROM duplicate_dismissals")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let pairs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query dismissed duplicates: {}", e))?
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(|e| format!("Failed to collect dismissed duplicates: {}", e))?;
+        Ok(pairs)
+    }
+}
+
+struct ScoredGrave {
+    grave: DuplicateGrave,
+    normalized: String,
+}
+
+/// Score a pair of graves; None when the names are too far apart
+///
+/// Name similarity gives up to 60 points, the same date of death 25 (same
+/// month 10, same year 5), a shared heir phone number 10 and the same block 5.
+fn score_pair(
+    first: &ScoredGrave,
+    second: &ScoredGrave,
+    phones: &HashMap<i64, Vec<String>>,
+) -> Option<DuplicateCandidate> {
+    let name_similarity = name_similarity(&first.normalized, &second.normalized);
+    if name_similarity < MIN_NAME_SIMILARITY {
+        return None;
+    }
+
+    let (a, b) = (&first.grave, &second.grave);
+    let same_date_of_death = a.date_of_death == b.date_of_death;
+    let date_points = if same_date_of_death {
+        25
+    } else if (a.date_of_death.year(), a.date_of_death.month()) == (b.date_of_death.year(), b.date_of_death.month()) {
+        10
+    } else if a.date_of_death.year() == b.date_of_death.year() {
+        5
+    } else {
+        0
+    };
+
+    let empty = Vec::new();
+    let second_phones = phones.get(&b.id).unwrap_or(&empty);
+    let shared_phones: Vec<String> = phones
+        .get(&a.id)
+        .unwrap_or(&empty)
+        .iter()
+        .filter(|phone| second_phones.contains(phone))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let same_block = a.block_id == b.block_id;
+
+    let score = (name_similarity * 60.0).round() as i64
+        + date_points
+        + if shared_phones.is_empty() { 0 } else { 10 }
+        + if same_block { 5 } else { 0 };
+
+    Some(DuplicateCandidate {
+        score,
+        name_similarity: (name_similarity * 100.0).round() / 100.0,
+        same_date_of_death,
+        same_block,
+        shared_phones,
+        first: a.clone(),
+        second: b.clone(),
+    })
+}
+
+/// Similarity of two name keys (0-1), ignoring word order
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let sorted = |name: &str| {
+        let mut tokens: Vec<&str> = name.split(' ').collect();
+        tokens.sort_unstable();
+        tokens.join(" ")
+    };
+    ratio(a, b).max(ratio(&sorted(a), &sorted(b)))
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Grave as shown in a duplicate review
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DuplicateGrave {
+    pub id: i64,
+    pub deceased_name: String,
+    pub block_id: i64,
+    pub block_code: String,
+    pub number: String,
+    pub date_of_death: NaiveDate,
+    pub heir_names: Vec<String>,
+    pub payment_count: i64,
+}
+
+/// Pair of graves that are probably the same deceased
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DuplicateCandidate {
+    /// 0-100
+    pub score: i64,
+    /// Similarity of the normalized names (0-1)
+    pub name_similarity: f64,
+    pub same_date_of_death: bool,
+    pub same_block: bool,
+    /// Heir phone numbers (E.164) found on both graves
+    pub shared_phones: Vec<String>,
+    /// Grave with the lower ID
+    pub first: DuplicateGrave,
+    pub second: DuplicateGrave,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveMergeReport {
+    pub kept_grave_id: i64,
+    pub removed_grave_id: i64,
+    pub heirs_moved: i64,
+    /// Heirs of the removed grave already heir of the kept grave
+    pub heirs_dropped: i64,
+    pub payments_moved: i64,
+    pub waivers_moved: i64,
+    pub interments_moved: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, create_grave, grave_request, heir_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreateHeirRequest};
+
+    /// Two records of H. Achmad Fauzi and one of Sutrisno, died the same day:
+    /// `(dir, db, original, duplicate, other)`
+    fn db_with_duplicate() -> (tempfile::TempDir, Database, i64, i64, i64) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave = |name: &str, number: &str, phone: &str| {
+            let grave_id = db
+                .create_grave(&CreateGraveRequest {
+                    deceased_name: name.to_string(),
+                    date_of_death: "2020-03-04".to_string(),
+                    ..grave_request(block_id, number)
+                })
+                .unwrap();
+            db.create_heir(&CreateHeirRequest {
+                phone_number: Some(phone.to_string()),
+                ..heir_request(grave_id, "Siti")
+            })
+            .unwrap();
+            grave_id
+        };
+        let original = grave("H. Achmad Fauzi", "1", "0812-3456-789");
+        let duplicate = grave("Alm. Ahmad Fauzi", "7", "+62 812 3456 789");
+        let other = grave("Sutrisno", "2", "0813-0000-111");
+        (dir, db, original, duplicate, other)
+    }
+
+    #[test]
+    fn test_duplicates_found_by_name_and_phone() {
+        let (_dir, db, original, duplicate, other) = db_with_duplicate();
+
+        let candidates = db.find_duplicate_graves(70, 10).unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!((candidates[0].first.id, candidates[0].second.id), (original, duplicate));
+        assert_eq!(candidates[0].shared_phones, ["+628123456789"]);
+        assert!(!candidates.iter().any(|c| c.first.id == other || c.second.id == other));
+    }
+
+    #[test]
+    fn test_merge_moves_records_to_kept_grave() {
+        let (_dir, db, original, duplicate, _) = db_with_duplicate();
+        db.create_payment(&payment_request(original, 2021)).unwrap();
+        let duplicate_payment = db.create_payment(&payment_request(duplicate, 2022)).unwrap();
+        db.conn
+            .execute(
+                "INSERT INTO carried_arrears (year, grave_id, amount) VALUES (2023, ?1, 100000), (2023, ?2, 50000)",
+                [original, duplicate],
+            )
+            .unwrap();
+
+        let report = db.merge_graves(original, duplicate).unwrap();
+        assert_eq!((report.payments_moved, report.heirs_moved, report.heirs_dropped), (1, 0, 1));
+        assert!(db.get_grave_by_id(duplicate).unwrap().is_none());
+        assert_eq!(db.get_payment_by_id(duplicate_payment).unwrap().unwrap().grave_id, original);
+        assert_eq!(db.get_heirs_by_grave(original).unwrap().len(), 1);
+        assert!(db.find_duplicate_graves(0, 10).unwrap().iter().all(|c| c.second.id != duplicate));
+        assert!(db.get_interments(duplicate).unwrap().is_empty());
+        assert!(db.get_heirs_by_grave(duplicate).unwrap().is_empty());
+        let carried: Vec<(i64, i64)> = db
+            .get_carried_arrears(2023)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.grave_id, a.amount))
+            .collect();
+        assert_eq!(carried, [(original, 150000)]);
+    }
+
+    #[test]
+    fn test_merge_refuses_paid_year_on_both_graves() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let (kept, merged) = (create_grave(&db, block_id, "1"), create_grave(&db, block_id, "2"));
+        db.create_payment(&payment_request(kept, 2021)).unwrap();
+        db.create_payment(&payment_request(merged, 2021)).unwrap();
+
+        assert!(db.merge_graves(kept, merged).unwrap_err().contains("Both graves have a payment for 2021"));
+        assert!(db.get_grave_by_id(merged).unwrap().is_some());
+    }
+}
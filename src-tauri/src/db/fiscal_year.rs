@@ -0,0 +1,600 @@
+//! Fiscal year closing
+//!
+//! Closing the active year stores a snapshot of its `YearlyReport`, carries
+//! each grave's outstanding balance into the next year and advances
+//! `settings.active_year`. Payments and fee waivers of closed years are
+//! locked, including new ones; an admin can still write them with a reason,
+//! which is written to an audit log.
+
+use chrono::{DateTime, Datelike, Utc};
+use rusqlite::OptionalExtension;
+
+use super::numbering::GRAVE_NUMBER_ORDER_SQL;
+use super::validation;
+use super::{CreatePaymentRequest, CreateWaiverRequest, Database, FeeWaiver, Payment, YearlyReport};
+
+impl Database {
+    /// Close the active fiscal year
+    pub fn close_fiscal_year(&self, year: i32, notes: Option<&str>) -> Result<ClosedYear, String> {
+        let settings = self.get_settings()?;
+        if year != settings.active_year {
+            return Err(format!(
+                "Only the active year ({}) can be closed",
+                settings.active_year
+            ));
+        }
+        if year >= self.local_today()?.year() {
+            return Err(format!("Year {} has not ended yet", year));
+        }
+        if self.is_year_closed(year)? {
+            return Err(format!("Year {} is already closed", year));
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start year closing: {}", e))?;
+
+        let report = self.get_yearly_report(year)?;
+        let snapshot = serde_json::to_string(&report)
+            .map_err(|e| format!("Failed to serialize yearly report: {}", e))?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM graves ORDER BY id")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let grave_ids: Vec<i64> = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Failed to query graves: {}", e))?
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        let mut total_arrears = 0;
+        for grave_id in grave_ids {
+            let arrears = self.balance_as_of(grave_id, year)?;
+            if arrears <= 0 {
+                continue;
+            }
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO carried_arrears (year, grave_id, amount) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![year + 1, grave_id, arrears],
+                )
+                .map_err(|e| format!("Failed to carry arrears forward: {}", e))?;
+            total_arrears += arrears;
+        }
+
+        self.conn
+            .execute(
+                "INSERT INTO closed_years (year, closed_by, report_snapshot, total_arrears, notes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![year, self.operator, snapshot, total_arrears, notes],
+            )
+            .map_err(|e| format!("Failed to close year: {}", e))?;
+        self.conn
+            .execute("UPDATE settings SET active_year = ?1 WHERE id = 1", [year + 1])
+            .map_err(|e| format!("Failed to advance active year: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit year closing: {}", e))?;
+
+        self.get_closed_year(year)?
+            .ok_or_else(|| format!("Closed year {} not found", year))
+    }
+
+    /// Check whether a fiscal year is closed
+    pub fn is_year_closed(&self, year: i32) -> Result<bool, String> {
+        self.conn
+            .query_row("SELECT COUNT(*) > 0 FROM closed_years WHERE year = ?1", [year], |row| row.get(0))
+            .map_err(|e| format!("Failed to check closed year: {}", e))
+    }
+
+    /// Last closed fiscal year, if any
+    pub(super) fn last_closed_year(&self) -> Result<Option<i32>, String> {
+        self.conn
+            .query_row("SELECT MAX(year) FROM closed_years", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to get last closed year: {}", e))
+    }
+
+    /// Fail if `year` is closed
+    pub(super) fn check_year_open(&self, year: i32) -> Result<(), String> {
+        if self.is_year_closed(year)? {
+            return Err(closed_year_error(year));
+        }
+        Ok(())
+    }
+
+    /// Check a payment or fee waiver written by an import or merge
+    ///
+    /// Returns the reason to log when `year` is closed and an override
+    /// reason was given; fails when it is closed without one.
+    pub(super) fn lock_override_for<'a>(
+        &self,
+        year: i32,
+        override_reason: Option<&'a str>,
+    ) -> Result<Option<&'a str>, String> {
+        if !self.is_year_closed(year)? {
+            return Ok(None);
+        }
+        override_reason.map(Some).ok_or_else(|| closed_year_error(year))
+    }
+
+    /// Get closed years, newest first
+    pub fn get_closed_years(&self) -> Result<Vec<ClosedYear>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT c.year, c.closed_at, c.closed_by, u.full_name, c.report_snapshot, c.total_arrears, c.notes,
+                        (SELECT COUNT(*) FROM carried_arrears a WHERE a.year = c.year + 1)
+                 FROM closed_years c
+                 LEFT JOIN users u ON c.closed_by = u.id
+                 ORDER BY c.year DESC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let years = stmt
+            .query_map([], map_closed_year)
+            .map_err(|e| format!("Failed to query closed years: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect closed years: {}", e))?;
+
+        Ok(years)
+    }
+
+    /// Get a closed year with its report snapshot
+    pub fn get_closed_year(&self, year: i32) -> Result<Option<ClosedYear>, String> {
+        self.conn
+            .query_row(
+                "SELECT c.year, c.closed_at, c.closed_by, u.full_name, c.report_snapshot, c.total_arrears, c.notes,
+                        (SELECT COUNT(*) FROM carried_arrears a WHERE a.year = c.year + 1)
+                 FROM closed_years c
+                 LEFT JOIN users u ON c.closed_by = u.id
+                 WHERE c.year = ?1",
+                [year],
+                map_closed_year,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get closed year: {}", e))
+    }
+
+    /// Get arrears carried into a year
+    pub fn get_carried_arrears(&self, year: i32) -> Result<Vec<CarriedArrears>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT a.grave_id, g.deceased_name, b.code, g.number, a.amount
+                 FROM carried_arrears a
+                 JOIN graves g ON a.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE a.year = ?1
+                 ORDER BY b.code, {}",
+                GRAVE_NUMBER_ORDER_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let arrears = stmt
+            .query_map([year], |row| {
+                Ok(CarriedArrears {
+                    grave_id: row.get(0)?,
+                    deceased_name: row.get(1)?,
+                    block_code: row.get(2)?,
+                    number: row.get(3)?,
+                    amount: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query carried arrears: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect carried arrears: {}", e))?;
+
+        Ok(arrears)
+    }
+
+    /// Create a payment in a closed year (admin override, logged)
+    pub fn override_create_payment(&self, payment: &CreatePaymentRequest, reason: &str) -> Result<i64, String> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let id = self.insert_payment_override(payment, reason)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit payment: {}", e))?;
+        Ok(id)
+    }
+
+    /// Insert a payment and log the override; must run inside a transaction
+    pub(super) fn insert_payment_override(&self, payment: &CreatePaymentRequest, reason: &str) -> Result<i64, String> {
+        validation::payment(payment)?;
+        let id = self.insert_payment(payment)?;
+        self.log_payment_override(id, "create", reason)?;
+        Ok(id)
+    }
+
+    /// Update a payment of a closed year (admin override, logged)
+    pub fn override_update_payment(
+        &self,
+        id: i64,
+        payment: &CreatePaymentRequest,
+        reason: &str,
+    ) -> Result<(), String> {
+        self.check_cash_session_lock(id)?;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        self.log_payment_override(id, "update", reason)?;
+        self.write_payment_update(id, payment)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit payment update: {}", e))
+    }
+
+    /// Delete a payment of a closed year (admin override, logged)
+    pub fn override_delete_payment(&self, id: i64, reason: &str) -> Result<(), String> {
+        self.check_cash_session_lock(id)?;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        self.log_payment_override(id, "delete", reason)?;
+        self.remove_payment(id)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit payment deletion: {}", e))
+    }
+
+    /// Create a fee waiver in a closed year (admin override, logged)
+    pub fn override_create_waiver(&self, waiver: &CreateWaiverRequest, reason: &str) -> Result<i64, String> {
+        validation::create_waiver(waiver)?;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let id = self.insert_waiver(waiver)?;
+        self.log_waiver_override(id, "create", reason)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit fee waiver: {}", e))?;
+        Ok(id)
+    }
+
+    /// Delete a fee waiver of a closed year (admin override, logged)
+    pub fn override_delete_waiver(&self, id: i64, reason: &str) -> Result<(), String> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        self.log_waiver_override(id, "delete", reason)?;
+        self.remove_waiver(id)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit fee waiver deletion: {}", e))
+    }
+
+    /// Delete a grave with payments or fee waivers in closed years (admin
+    /// override, logged per record)
+    pub fn override_delete_grave(&self, id: i64, reason: &str) -> Result<(), String> {
+        self.remove_grave(id, Some(reason))
+    }
+
+    /// Get audit log of payment lock overrides
+    pub fn get_payment_lock_overrides(&self, limit: i64) -> Result<Vec<PaymentLockOverride>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT o.id, o.record_type, o.record_id, o.year, o.action, o.reason, o.snapshot, o.overridden_by, u.full_name, o.created_at
+                 FROM payment_lock_overrides o
+                 LEFT JOIN users u ON o.overridden_by = u.id
+                 ORDER BY o.created_at DESC, o.id DESC
+                 LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let overrides = stmt
+            .query_map([limit], |row| {
+                let record_type: String = row.get(1)?;
+                let snapshot: String = row.get(6)?;
+                let is_payment = record_type == "payment";
+                Ok(PaymentLockOverride {
+                    id: row.get(0)?,
+                    record_id: row.get(2)?,
+                    year: row.get(3)?,
+                    action: row.get(4)?,
+                    reason: row.get(5)?,
+                    payment: is_payment.then(|| serde_json::from_str(&snapshot).ok()).flatten(),
+                    fee_waiver: (!is_payment).then(|| serde_json::from_str(&snapshot).ok()).flatten(),
+                    record_type,
+                    overridden_by: row.get(7)?,
+                    overridden_by_name: row.get(8)?,
+                    created_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query lock overrides: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect lock overrides: {}", e))?;
+
+        Ok(overrides)
+    }
+
+    /// Fail if a payment is locked by a closed cash session or closed year
+    ///
+    /// `new_year` is the year a payment is being moved to, if any.
+    pub(super) fn check_payment_locks(&self, payment_id: i64, new_year: Option<i32>) -> Result<(), String> {
+        self.check_cash_session_lock(payment_id)?;
+
+        let payment = self
+            .get_payment_by_id(payment_id)?
+            .ok_or_else(|| format!("Payment {} not found", payment_id))?;
+        for year in std::iter::once(payment.year).chain(new_year) {
+            self.check_year_open(year)?;
+        }
+        Ok(())
+    }
+
+    /// Log an override on a payment; call before updating or deleting it,
+    /// after creating it
+    pub(super) fn log_payment_override(&self, payment_id: i64, action: &str, reason: &str) -> Result<(), String> {
+        let payment = self
+            .get_payment_by_id(payment_id)?
+            .ok_or_else(|| format!("Payment {} not found", payment_id))?;
+        self.log_lock_override(LockedRecord::Payment(payment), action, reason)
+    }
+
+    /// Log an override on a fee waiver, as `log_payment_override`
+    pub(super) fn log_waiver_override(&self, waiver_id: i64, action: &str, reason: &str) -> Result<(), String> {
+        let waiver = self
+            .get_waiver_by_id(waiver_id)?
+            .ok_or_else(|| format!("Fee waiver {} not found", waiver_id))?;
+        self.log_lock_override(LockedRecord::FeeWaiver(waiver), action, reason)
+    }
+
+    fn log_lock_override(&self, record: LockedRecord, action: &str, reason: &str) -> Result<(), String> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err("A reason is required to change a locked payment".to_string());
+        }
+        let (record_type, record_id, year, snapshot) = match &record {
+            LockedRecord::Payment(p) => ("payment", p.id, p.year, serde_json::to_string(p)),
+            LockedRecord::FeeWaiver(w) => ("fee_waiver", w.id, w.year, serde_json::to_string(w)),
+        };
+        let snapshot = snapshot.map_err(|e| format!("Failed to serialize {}: {}", record_type, e))?;
+
+        self.conn
+            .execute(
+                "INSERT INTO payment_lock_overrides (record_type, record_id, year, action, reason, snapshot, overridden_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![record_type, record_id, year, action, reason, snapshot, self.operator],
+            )
+            .map_err(|e| format!("Failed to log lock override: {}", e))?;
+        Ok(())
+    }
+
+    /// Outstanding balance of a grave up to and including `year`
+    fn balance_as_of(&self, grave_id: i64, year: i32) -> Result<i64, String> {
+        let statement = self.get_grave_statement(grave_id)?;
+        Ok(statement
+            .lines
+            .iter()
+            .take_while(|line| line.year <= year)
+            .last()
+            .map(|line| line.balance)
+            .unwrap_or(0))
+    }
+}
+
+fn closed_year_error(year: i32) -> String {
+    format!(
+        "Year {} is closed; changing its payments requires an admin override with a reason",
+        year
+    )
+}
+
+/// Payment or fee waiver written through a closed-year override
+enum LockedRecord {
+    Payment(Payment),
+    FeeWaiver(FeeWaiver),
+}
+
+fn map_closed_year(row: &rusqlite::Row) -> rusqlite::Result<ClosedYear> {
+    let snapshot: String = row.get(4)?;
+    let report = serde_json::from_str(&snapshot).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(ClosedYear {
+        year: row.get(0)?,
+        closed_at: row.get(1)?,
+        closed_by: row.get(2)?,
+        closed_by_name: row.get(3)?,
+        report,
+        total_arrears: row.get(5)?,
+        notes: row.get(6)?,
+        graves_in_arrears: row.get(7)?,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Closed fiscal year
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClosedYear {
+    pub year: i32,
+    pub closed_at: DateTime<Utc>,
+    pub closed_by: Option<i64>,
+    pub closed_by_name: Option<String>,
+    /// Yearly report as it was when the year was closed
+    pub report: YearlyReport,
+    pub total_arrears: i64,
+    pub graves_in_arrears: i64,
+    pub notes: Option<String>,
+}
+
+/// Arrears of one grave carried into a new year
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CarriedArrears {
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub amount: i64,
+}
+
+/// Audit entry for an admin change to a locked payment or fee waiver
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PaymentLockOverride {
+    pub id: i64,
+    /// payment, fee_waiver
+    pub record_type: String,
+    pub record_id: i64,
+    pub year: i32,
+    /// create, update, delete
+    pub action: String,
+    pub reason: String,
+    /// Payment before the change (after, for create)
+    pub payment: Option<Payment>,
+    /// Fee waiver before the change (after, for create)
+    pub fee_waiver: Option<FeeWaiver>,
+    pub overridden_by: Option<i64>,
+    pub overridden_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, UpdateSettingsRequest};
+
+    fn active_year(year: i32) -> UpdateSettingsRequest {
+        UpdateSettingsRequest {
+            foundation_name: None,
+            address: None,
+            phone: None,
+            email: None,
+            logo_path: None,
+            active_year: Some(year),
+            auto_backup: None,
+            timezone: None,
+        }
+    }
+
+    /// Year 2023 closed with grave "1" paid through 2023 and grave "2"
+    /// unpaid, both died 2022: `(dir, db, paid_grave, unpaid_grave, payment_id)`
+    fn db_with_closed_year() -> (tempfile::TempDir, Database, i64, i64, i64) {
+        let (dir, db) = test_db();
+        db.update_settings(&active_year(2023)).unwrap();
+        let block_id = create_block(&db, "A");
+        let grave = |number: &str| {
+            db.create_grave(&CreateGraveRequest {
+                date_of_death: "2022-05-01".to_string(),
+                ..grave_request(block_id, number)
+            })
+            .unwrap()
+        };
+        let (paid_grave, unpaid_grave) = (grave("1"), grave("2"));
+        db.create_payment(&payment_request(paid_grave, 2022)).unwrap();
+        let payment_id = db.create_payment(&payment_request(paid_grave, 2023)).unwrap();
+
+        assert!(db.close_fiscal_year(2024, None).is_err());
+        let closed = db.close_fiscal_year(2023, Some("Disahkan pengurus")).unwrap();
+        assert_eq!(closed.report.year, 2023);
+        assert_eq!(closed.total_arrears, 200000);
+        (dir, db, paid_grave, unpaid_grave, payment_id)
+    }
+
+    #[test]
+    fn test_close_year_carries_arrears() {
+        let (_dir, db, _, unpaid_grave, _) = db_with_closed_year();
+        assert_eq!(db.get_settings().unwrap().active_year, 2024);
+
+        let carried = db.get_carried_arrears(2024).unwrap();
+        assert_eq!(carried.len(), 1);
+        assert_eq!(carried[0].grave_id, unpaid_grave);
+
+        // The active year cannot go back to a closed year
+        assert!(db.update_settings(&active_year(2023)).is_err());
+    }
+
+    #[test]
+    fn test_close_year_with_unconfirmed_date_of_death() {
+        let (_dir, db) = test_db();
+        db.update_settings(&active_year(2023)).unwrap();
+        let block_id = create_block(&db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                date_of_death: "2022-05-01".to_string(),
+                ..grave_request(block_id, "1")
+            })
+            .unwrap();
+        db.connection()
+            .execute(
+                "INSERT INTO date_issues (table_name, row_id, column_name, stored_value, replaced_with)
+                 VALUES ('graves', ?1, 'date_of_death', 'lupa', '2022-05-01')",
+                [grave_id],
+            )
+            .unwrap();
+
+        assert_eq!(db.close_fiscal_year(2023, None).unwrap().total_arrears, 200000);
+    }
+
+    #[test]
+    fn test_closed_year_payments_need_override() {
+        let (_dir, db, paid_grave, _, payment_id) = db_with_closed_year();
+
+        assert!(db.update_payment(payment_id, &payment_request(paid_grave, 2023)).is_err());
+        assert!(db.delete_payment(payment_id).is_err());
+        assert!(db.override_delete_payment(payment_id, " ").is_err());
+        db.override_delete_payment(payment_id, "Salah input").unwrap();
+
+        let log = db.get_payment_lock_overrides(10).unwrap();
+        assert_eq!(log[0].action, "delete");
+        assert_eq!(log[0].payment.as_ref().unwrap().amount, 100000);
+    }
+
+    #[test]
+    fn test_new_records_of_closed_year_need_override() {
+        let (_dir, db, paid_grave, unpaid_grave, _) = db_with_closed_year();
+
+        let late = payment_request(unpaid_grave, 2023);
+        assert!(db.create_payment(&late).is_err());
+        db.override_create_payment(&late, "Bukti transfer baru ditemukan").unwrap();
+        let waiver = CreateWaiverRequest {
+            grave_id: paid_grave,
+            year: 2023,
+            amount: 50000,
+            reason: None,
+            approved_by: None,
+        };
+        assert!(db.create_waiver(&waiver).is_err());
+        let waiver_id = db.override_create_waiver(&waiver, "Keputusan rapat").unwrap();
+        assert!(db.delete_waiver(waiver_id).is_err());
+
+        let log = db.get_payment_lock_overrides(10).unwrap();
+        assert_eq!((log[0].record_type.as_str(), log[0].action.as_str()), ("fee_waiver", "create"));
+        assert_eq!(log[0].fee_waiver.as_ref().unwrap().amount, 50000);
+        assert_eq!(log[1].payment.as_ref().unwrap().grave_id, unpaid_grave);
+    }
+
+    #[test]
+    fn test_deleting_grave_of_closed_year_needs_override() {
+        let (_dir, db, paid_grave, _, _) = db_with_closed_year();
+        db.override_create_waiver(
+            &CreateWaiverRequest {
+                grave_id: paid_grave,
+                year: 2023,
+                amount: 50000,
+                reason: None,
+                approved_by: None,
+            },
+            "Keputusan rapat",
+        )
+        .unwrap();
+
+        assert!(db.delete_grave(paid_grave).is_err());
+        assert!(db.override_delete_grave(paid_grave, " ").is_err());
+        assert!(db.get_grave_by_id(paid_grave).unwrap().is_some());
+        db.override_delete_grave(paid_grave, "Data ganda").unwrap();
+        assert!(db.get_grave_by_id(paid_grave).unwrap().is_none());
+
+        // Only the records of the closed year are logged
+        let log = db.get_payment_lock_overrides(10).unwrap();
+        let deleted: Vec<_> = log
+            .iter()
+            .filter(|o| o.action == "delete")
+            .map(|o| (o.record_type.as_str(), o.year))
+            .collect();
+        assert_eq!(deleted, [("fee_waiver", 2023), ("payment", 2023)]);
+    }
+}
@@ -0,0 +1,484 @@
+//! Grave listing (data makam page)
+//!
+//! Graves are sorted by block and number, name, date of death or burial
+//! date, and filtered on top of the name/number search. Pages are read with
+//! a keyset cursor: the sort key of the last row comes back with the page
+//! and the next page starts right after it. Each sort key has a matching
+//! index (migration 021), so a page seeks to the cursor and reads in index
+//! order instead of sorting the list; in block order the seek lands on the
+//! cursor's block, which is then read from its first grave. An offset is
+//! still accepted for jumping straight to a page.
+
+use chrono::{Datelike, NaiveDate};
+use rusqlite::types::Value;
+
+use super::dates::year_range;
+use super::{Database, GraveWithBlock, GRAVE_SEARCH_JOIN};
+
+/// First year a grave owes the annual fee (burial year, else year of death)
+const FIRST_BILLABLE_YEAR_SQL: &str = "CAST(substr(COALESCE(g.burial_date, g.date_of_death), 1, 4) AS INTEGER)";
+
+impl Database {
+    /// Get one page of graves, at most `limit`
+    pub fn get_graves(&self, query: &GraveListQuery, limit: i64) -> Result<GravePage, String> {
+        if limit < 1 {
+            return Err("Limit must be at least 1".to_string());
+        }
+        let order = GraveOrder::of(query);
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        let mut sql = format!(
+            "SELECT g.id, g.deceased_name, g.block_id, g.number, g.date_of_death, g.burial_date, g.notes, g.created_at, g.updated_at,
+                    b.code, b.annual_fee, {keys}
+             FROM graves g
+             JOIN blocks b ON g.block_id = b.id",
+            keys = order.keys.join(", "),
+        );
+        sql.push_str(&self.grave_list_filter_sql(query, &mut params)?);
+
+        if let Some(cursor) = &query.after {
+            let key = order.decode_cursor(cursor, query.descending)?;
+            // SQLite does not seek on a row value of expressions, so the
+            // leading key is bounded on its own as well
+            sql.push_str(&format!(
+                " AND {} {}= ? AND ({}) {} ({})",
+                order.keys[0],
+                if query.descending { "<" } else { ">" },
+                order.keys.join(", "),
+                if query.descending { "<" } else { ">" },
+                vec!["?"; key.len()].join(", "),
+            ));
+            params.push(Box::new(key[0].clone()));
+            params.extend(key.into_iter().map(|value| Box::new(value) as Box<dyn rusqlite::ToSql>));
+        }
+
+        let direction = if query.descending { " DESC" } else { "" };
+        let order_by: Vec<String> = order.keys.iter().map(|key| format!("{}{}", key, direction)).collect();
+        sql.push_str(&format!(" ORDER BY {} LIMIT ? OFFSET ?", order_by.join(", ")));
+        // One extra row tells whether there is a next page
+        params.push(Box::new(limit + 1));
+        params.push(Box::new(if query.after.is_some() { 0 } else { query.offset.max(0) }));
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut rows = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                let grave = GraveWithBlock {
+                    id: row.get(0)?,
+                    deceased_name: row.get(1)?,
+                    block_id: row.get(2)?,
+                    number: row.get(3)?,
+                    date_of_death: row.get(4)?,
+                    burial_date: row.get(5)?,
+                    notes: row.get(6)?,
+                    created_at: row.get(7)?,
+                    updated_at: row.get(8)?,
+                    code: row.get(9)?,
+                    annual_fee: row.get(10)?,
+                };
+                let key = (0..order.keys.len())
+                    .map(|i| row.get::<_, Value>(11 + i))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((grave, key))
+            })
+            .map_err(|e| format!("Failed to query graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|(_, key)| order.encode_cursor(key, query.descending))
+        } else {
+            None
+        };
+
+        Ok(GravePage {
+            graves: rows.into_iter().map(|(grave, _)| grave).collect(),
+            next_cursor,
+        })
+    }
+
+    /// Count graves matching the filters of `query` (sort and cursor are ignored)
+    pub fn count_graves(&self, query: &GraveListQuery) -> Result<i64, String> {
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        let mut sql = String::from("SELECT COUNT(*) FROM graves g JOIN blocks b ON g.block_id = b.id");
+        sql.push_str(&self.grave_list_filter_sql(query, &mut params)?);
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        self.conn
+            .query_row(&sql, param_refs.as_slice(), |row| row.get(0))
+            .map_err(|e| format!("Failed to count graves: {}", e))
+    }
+
+    /// Search join and WHERE clause of the listing filters
+    fn grave_list_filter_sql(
+        &self,
+        query: &GraveListQuery,
+        params: &mut Vec<Box<dyn rusqlite::ToSql>>,
+    ) -> Result<String, String> {
+        let mut sql = String::new();
+        if let Some(search) = &query.search {
+            sql.push_str(GRAVE_SEARCH_JOIN);
+            params.push(Box::new(self.grave_search_ids(search)?));
+        }
+        sql.push_str(" WHERE 1=1");
+
+        if let Some(block_id) = query.block_id {
+            sql.push_str(" AND g.block_id = ?");
+            params.push(Box::new(block_id));
+        }
+        if let Some(died_from) = query.died_from {
+            sql.push_str(" AND g.date_of_death >= ?");
+            params.push(Box::new(died_from));
+        }
+        if let Some(died_until) = query.died_until {
+            sql.push_str(" AND g.date_of_death <= ?");
+            params.push(Box::new(died_until));
+        }
+        if let Some(burial_year) = query.burial_year {
+            let (start, end) = year_range(burial_year);
+            sql.push_str(" AND g.burial_date >= ? AND g.burial_date < ?");
+            params.push(Box::new(start));
+            params.push(Box::new(end));
+        }
+        if query.without_heirs {
+            sql.push_str(" AND NOT EXISTS (SELECT 1 FROM heirs h WHERE h.grave_id = g.id)");
+        }
+        if query.without_phone {
+            sql.push_str(
+                " AND NOT EXISTS (SELECT 1 FROM heirs h WHERE h.grave_id = g.id AND TRIM(COALESCE(h.phone_number, '')) <> '')",
+            );
+        }
+        if let Some(min_arrears_years) = query.min_arrears_years {
+            // Billable years up to this year minus the settled ones; a year is
+            // settled when payment and waiver together cover the annual fee
+            let current_year = self.local_today()?.year();
+            sql.push_str(&format!(
+                " AND MAX(? - {first} + 1, 0) - (
+                     SELECT COUNT(*) FROM (
+                         SELECT year FROM payments WHERE grave_id = g.id
+                         UNION SELECT year FROM fee_waivers WHERE grave_id = g.id
+                     ) y
+                     WHERE y.year BETWEEN {first} AND ?
+                       AND COALESCE((SELECT SUM(amount) FROM payments WHERE grave_id = g.id AND year = y.year), 0)
+                         + COALESCE((SELECT SUM(amount) FROM fee_waivers WHERE grave_id = g.id AND year = y.year), 0)
+                         >= b.annual_fee
+                 ) >= ?",
+                first = FIRST_BILLABLE_YEAR_SQL,
+            ));
+            params.push(Box::new(current_year));
+            params.push(Box::new(current_year));
+            params.push(Box::new(min_arrears_years));
+        }
+        Ok(sql)
+    }
+}
+
+/// Sort key columns of a listing, ending with `g.id` so the key is unique
+struct GraveOrder {
+    name: &'static str,
+    keys: &'static [&'static str],
+}
+
+impl GraveOrder {
+    fn of(query: &GraveListQuery) -> Self {
+        let sort = match query.sort {
+            Some(sort) => sort,
+            // Search results come best match first
+            None if query.search.is_some() => {
+                return GraveOrder {
+                    name: "relevance",
+                    keys: &["m.key", "g.id"],
+                }
+            }
+            None => GraveSort::BlockNumber,
+        };
+        let keys: &'static [&'static str] = match sort {
+            // Natural number order, as `numbering::GRAVE_NUMBER_ORDER_SQL`
+            GraveSort::BlockNumber => &["b.code", "CAST(g.number AS INTEGER)", "length(g.number)", "g.number", "g.id"],
+            GraveSort::Name => &["g.deceased_name COLLATE NOCASE", "g.id"],
+            GraveSort::DateOfDeath => &["g.date_of_death", "g.id"],
+            // Graves without a burial date come last (first when descending)
+            GraveSort::BurialDate => &["COALESCE(g.burial_date, '9999-12-31')", "g.id"],
+        };
+        GraveOrder {
+            name: sort.as_str(),
+            keys,
+        }
+    }
+
+    fn encode_cursor(&self, key: &[Value], descending: bool) -> String {
+        let key: Vec<serde_json::Value> = key
+            .iter()
+            .map(|value| match value {
+                Value::Integer(n) => serde_json::Value::from(*n),
+                Value::Real(n) => serde_json::Value::from(*n),
+                Value::Text(s) => serde_json::Value::from(s.as_str()),
+                Value::Null | Value::Blob(_) => serde_json::Value::Null,
+            })
+            .collect();
+        serde_json::json!({ "sort": self.name, "descending": descending, "key": key }).to_string()
+    }
+
+    fn decode_cursor(&self, cursor: &str, descending: bool) -> Result<Vec<Value>, String> {
+        let cursor: serde_json::Value =
+            serde_json::from_str(cursor).map_err(|e| format!("Invalid cursor: {}", e))?;
+        if cursor["sort"] != self.name || cursor["descending"] != descending {
+            return Err("Cursor belongs to a different sort order".to_string());
+        }
+        let key = cursor["key"].as_array().ok_or("Invalid cursor: missing key")?;
+        if key.len() != self.keys.len() {
+            return Err("Invalid cursor: wrong key length".to_string());
+        }
+        key.iter()
+            .map(|value| match value {
+                serde_json::Value::Null => Ok(Value::Null),
+                serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(n) => Ok(Value::Integer(n)),
+                    None => n.as_f64().map(Value::Real).ok_or_else(|| "Invalid cursor: bad number".to_string()),
+                },
+                _ => Err("Invalid cursor: bad key value".to_string()),
+            })
+            .collect()
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraveSort {
+    /// Block code, then grave number in natural order
+    BlockNumber,
+    Name,
+    DateOfDeath,
+    BurialDate,
+}
+
+impl GraveSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GraveSort::BlockNumber => "block_number",
+            GraveSort::Name => "name",
+            GraveSort::DateOfDeath => "date_of_death",
+            GraveSort::BurialDate => "burial_date",
+        }
+    }
+}
+
+/// Sort, filters and page position of the grave listing
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GraveListQuery {
+    /// Name/number search
+    pub search: Option<String>,
+    pub block_id: Option<i64>,
+    /// Default: best match first when searching, else block and number
+    pub sort: Option<GraveSort>,
+    #[serde(default)]
+    pub descending: bool,
+    pub died_from: Option<NaiveDate>,
+    pub died_until: Option<NaiveDate>,
+    pub burial_year: Option<i32>,
+    #[serde(default)]
+    pub without_heirs: bool,
+    /// No heir with a phone number (includes graves without heirs)
+    #[serde(default)]
+    pub without_phone: bool,
+    /// Only graves with at least this many unsettled years up to this year
+    pub min_arrears_years: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub after: Option<String>,
+    /// Rows to skip when no cursor is given
+    #[serde(default)]
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GravePage {
+    pub graves: Vec<GraveWithBlock>,
+    /// Cursor for the next page, `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, heir_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreatePaymentRequest};
+
+    /// Zainab (no burial date), ahmad (buried two years ago, heir, paid
+    /// this year) and Budi (buried this year): `(dir, db, block_id)`
+    fn db_with_listing() -> (tempfile::TempDir, Database, i64) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let this_year = db.local_today().unwrap().year();
+        let graves = [
+            ("Zainab", "10", "2015-03-01", None),
+            ("ahmad", "2", "2020-07-15", Some(format!("{}-01-10", this_year - 2))),
+            ("Budi", "1", "2018-11-30", Some(format!("{}-02-03", this_year))),
+        ];
+        for (name, number, died, buried) in graves {
+            let grave_id = db
+                .create_grave(&CreateGraveRequest {
+                    deceased_name: name.to_string(),
+                    date_of_death: died.to_string(),
+                    burial_date: buried,
+                    ..grave_request(block_id, number)
+                })
+                .unwrap();
+            if name == "ahmad" {
+                db.create_heir(&heir_request(grave_id, "Siti")).unwrap();
+                db.create_payment(&CreatePaymentRequest {
+                    payment_date: format!("{}-01-05", this_year),
+                    ..payment_request(grave_id, this_year)
+                })
+                .unwrap();
+            }
+        }
+        (dir, db, block_id)
+    }
+
+    fn names(db: &Database, query: &GraveListQuery) -> Vec<String> {
+        db.get_graves(query, 10).unwrap().graves.into_iter().map(|g| g.deceased_name).collect()
+    }
+
+    #[test]
+    fn test_grave_listing_sort_orders() {
+        let (_dir, db, _) = db_with_listing();
+        assert_eq!(names(&db, &GraveListQuery::default()), ["Budi", "ahmad", "Zainab"]);
+        let by_name = GraveListQuery {
+            sort: Some(GraveSort::Name),
+            ..Default::default()
+        };
+        assert_eq!(names(&db, &by_name), ["ahmad", "Budi", "Zainab"]);
+        let by_burial = GraveListQuery {
+            sort: Some(GraveSort::BurialDate),
+            descending: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&db, &by_burial), ["Zainab", "Budi", "ahmad"]);
+    }
+
+    #[test]
+    fn test_grave_listing_cursor() {
+        let (_dir, db, _) = db_with_listing();
+
+        // Keyset pages cover the list without gaps or repeats
+        let by_death = GraveListQuery {
+            sort: Some(GraveSort::DateOfDeath),
+            ..Default::default()
+        };
+        let first = db.get_graves(&by_death, 2).unwrap();
+        assert_eq!(first.graves.len(), 2);
+        let next = GraveListQuery {
+            after: first.next_cursor.clone(),
+            ..by_death.clone()
+        };
+        let second = db.get_graves(&next, 2).unwrap();
+        assert_eq!(second.graves[0].deceased_name, "ahmad");
+        assert!(second.next_cursor.is_none());
+
+        let by_name = GraveListQuery {
+            sort: Some(GraveSort::Name),
+            after: first.next_cursor,
+            ..Default::default()
+        };
+        assert!(db.get_graves(&by_name, 2).is_err());
+    }
+
+    #[test]
+    fn test_grave_listing_filters() {
+        let (_dir, db, _) = db_with_listing();
+        let this_year = db.local_today().unwrap().year();
+        let filtered = |query: GraveListQuery| names(&db, &query);
+        assert_eq!(
+            filtered(GraveListQuery {
+                died_from: NaiveDate::from_ymd_opt(2016, 1, 1),
+                died_until: NaiveDate::from_ymd_opt(2019, 12, 31),
+                ..Default::default()
+            }),
+            ["Budi"]
+        );
+        assert_eq!(filtered(GraveListQuery { burial_year: Some(this_year - 2), ..Default::default() }), ["ahmad"]);
+        assert_eq!(filtered(GraveListQuery { without_heirs: true, ..Default::default() }), ["Budi", "Zainab"]);
+        assert_eq!(filtered(GraveListQuery { without_phone: true, ..Default::default() }).len(), 3);
+    }
+
+    #[test]
+    fn test_grave_listing_arrears_filter() {
+        let (_dir, db, block_id) = db_with_listing();
+        // ahmad: billable from burial, this year paid, two years open
+        let arrears = GraveListQuery {
+            min_arrears_years: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(names(&db, &arrears), ["Zainab"]);
+        assert_eq!(db.count_graves(&arrears).unwrap(), 1);
+        let two_years = GraveListQuery {
+            min_arrears_years: Some(2),
+            block_id: Some(block_id),
+            ..Default::default()
+        };
+        assert_eq!(names(&db, &two_years), ["ahmad", "Zainab"]);
+    }
+
+    #[test]
+    fn test_grave_listing_cursor_pages_match_full_list() {
+        let (_dir, db) = test_db();
+        for code in ["B", "A"] {
+            let block_id = create_block(&db, code);
+            for (name, number, buried) in [("Umar", "10", None), ("umar", "9", Some("2021-05-01")), ("Ali", "9A", None)] {
+                db.create_grave(&CreateGraveRequest {
+                    deceased_name: name.to_string(),
+                    burial_date: buried.map(str::to_string),
+                    ..grave_request(block_id, number)
+                })
+                .unwrap();
+            }
+        }
+
+        let sorts = [GraveSort::BlockNumber, GraveSort::Name, GraveSort::DateOfDeath, GraveSort::BurialDate];
+        for sort in sorts {
+            for descending in [false, true] {
+                let query = GraveListQuery {
+                    sort: Some(sort),
+                    descending,
+                    ..Default::default()
+                };
+                let all: Vec<i64> = db.get_graves(&query, 100).unwrap().graves.iter().map(|g| g.id).collect();
+                let mut paged = Vec::new();
+                let mut after = None;
+                loop {
+                    let page = db.get_graves(&GraveListQuery { after, ..query.clone() }, 1).unwrap();
+                    paged.extend(page.graves.iter().map(|g| g.id));
+                    match page.next_cursor {
+                        Some(cursor) => after = Some(cursor),
+                        None => break,
+                    }
+                }
+                assert_eq!(paged, all, "{} descending={}", sort.as_str(), descending);
+
+                // Read in index order, never sorted in a temp b-tree
+                let direction = if descending { " DESC" } else { "" };
+                let order = GraveOrder::of(&query);
+                let order_by: Vec<String> = order.keys.iter().map(|key| format!("{}{}", key, direction)).collect();
+                let plan: Vec<String> = db
+                    .conn
+                    .prepare(&format!(
+                        "EXPLAIN QUERY PLAN SELECT g.id FROM graves g JOIN blocks b ON g.block_id = b.id ORDER BY {}",
+                        order_by.join(", ")
+                    ))
+                    .unwrap()
+                    .query_map([], |row| row.get(3))
+                    .unwrap()
+                    .collect::<Result<_, _>>()
+                    .unwrap();
+                assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")), "{:?}", plan);
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Hijri dates and haul (death anniversary) calendar
+//!
+//! Dates are converted with the tabular Islamic calendar (30-year cycle,
+//! astronomical epoch). It can be a day or two off the dates announced
+//! after rukyat or hisab, which is fine for planning a haul; families
+//! confirm the exact day themselves.
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use super::Database;
+
+/// Julian day number of 1 Muharram 1 AH
+const HIJRI_EPOCH: i64 = 1948439;
+
+/// Julian day number minus `NaiveDate::num_days_from_ce`
+const CE_JULIAN_OFFSET: i64 = 1721425;
+
+const HIJRI_MONTHS: [&str; 12] = [
+    "Muharam",
+    "Safar",
+    "Rabiulawal",
+    "Rabiulakhir",
+    "Jumadilawal",
+    "Jumadilakhir",
+    "Rajab",
+    "Syakban",
+    "Ramadan",
+    "Syawal",
+    "Zulkaidah",
+    "Zulhijah",
+];
+
+/// Longest period `get_upcoming_hauls` looks ahead
+const MAX_HAUL_DAYS: i64 = 366;
+
+impl Database {
+    /// Haul anniversaries of everyone buried from `from` up to `days` later
+    ///
+    /// The haul falls on the Hijri day and month of death; a death on the
+    /// 30th is remembered on the 29th in 29-day months. Soonest first.
+    pub fn get_upcoming_hauls(&self, from: NaiveDate, days: i64) -> Result<Vec<HaulAnniversary>, String> {
+        if !(0..=MAX_HAUL_DAYS).contains(&days) {
+            return Err(format!("Days must be between 0 and {}", MAX_HAUL_DAYS));
+        }
+        let until = from + Duration::days(days);
+        let first_year = HijriDate::from_gregorian(from).year;
+        let last_year = HijriDate::from_gregorian(until).year;
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT i.id, i.grave_id, i.deceased_name, i.date_of_death, b.code, g.number
+                 FROM interments i
+                 JOIN graves g ON i.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let interments = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, NaiveDate>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })
+            .map_err(|e| format!("Failed to query interments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect interments: {}", e))?;
+
+        let mut hauls = Vec::new();
+        for (interment_id, grave_id, deceased_name, date_of_death, block_code, number) in interments {
+            let death = HijriDate::from_gregorian(date_of_death);
+            for year in first_year.max(death.year + 1)..=last_year {
+                let day = death.day.min(month_length(year, death.month));
+                let date = hijri_to_gregorian(year, death.month, day);
+                if date < from || date > until {
+                    continue;
+                }
+                hauls.push(HaulAnniversary {
+                    interment_id,
+                    grave_id,
+                    deceased_name: deceased_name.clone(),
+                    block_code: block_code.clone(),
+                    number: number.clone(),
+                    date_of_death,
+                    date_of_death_hijri: death.clone(),
+                    haul_date: date,
+                    haul_date_hijri: HijriDate::from_gregorian(date),
+                    haul_number: year - death.year,
+                    days_until: (date - from).num_days(),
+                    heirs: Vec::new(),
+                });
+            }
+        }
+
+        hauls.sort_by(|a, b| a.haul_date.cmp(&b.haul_date).then(a.deceased_name.cmp(&b.deceased_name)));
+        for haul in &mut hauls {
+            haul.heirs = self
+                .get_heirs_by_grave(haul.grave_id)?
+                .into_iter()
+                .map(|heir| HaulContact {
+                    full_name: heir.full_name,
+                    phone_number: heir.phone_number,
+                    relationship: heir.relationship,
+                    is_primary: heir.is_primary,
+                })
+                .collect();
+        }
+        Ok(hauls)
+    }
+}
+
+impl HijriDate {
+    pub fn from_gregorian(date: NaiveDate) -> Self {
+        let jdn = i64::from(date.num_days_from_ce()) + CE_JULIAN_OFFSET;
+
+        let mut year = ((30 * (jdn - HIJRI_EPOCH) + 10646).div_euclid(10631)) as i32;
+        while hijri_to_jdn(year + 1, 1, 1) <= jdn {
+            year += 1;
+        }
+        while hijri_to_jdn(year, 1, 1) > jdn {
+            year -= 1;
+        }
+        let mut month = 1;
+        while month < 12 && hijri_to_jdn(year, month + 1, 1) <= jdn {
+            month += 1;
+        }
+        let day = (jdn - hijri_to_jdn(year, month, 1) + 1) as u32;
+
+        let month_name = HIJRI_MONTHS[month as usize - 1].to_string();
+        HijriDate {
+            year,
+            month,
+            day,
+            formatted: format!("{} {} {} H", day, month_name, year),
+            month_name,
+        }
+    }
+}
+
+/// Julian day number of a Hijri date
+fn hijri_to_jdn(year: i32, month: u32, day: u32) -> i64 {
+    let (year, month, day) = (i64::from(year), i64::from(month), i64::from(day));
+    day + (59 * (month - 1) + 1) / 2 + (year - 1) * 354 + (3 + 11 * year).div_euclid(30) + HIJRI_EPOCH - 1
+}
+
+fn hijri_to_gregorian(year: i32, month: u32, day: u32) -> NaiveDate {
+    let days = hijri_to_jdn(year, month, day) - CE_JULIAN_OFFSET;
+    NaiveDate::from_num_days_from_ce_opt(days as i32).expect("Hijri date within chrono range")
+}
+
+/// Days in a Hijri month (29 or 30)
+fn month_length(year: i32, month: u32) -> u32 {
+    let next = if month == 12 {
+        hijri_to_jdn(year + 1, 1, 1)
+    } else {
+        hijri_to_jdn(year, month + 1, 1)
+    };
+    (next - hijri_to_jdn(year, month, 1)) as u32
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HijriDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub month_name: String,
+    /// e.g. "1 Muharam 1446 H"
+    pub formatted: String,
+}
+
+/// Haul of one interment
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HaulAnniversary {
+    pub interment_id: i64,
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub date_of_death: NaiveDate,
+    pub date_of_death_hijri: HijriDate,
+    pub haul_date: NaiveDate,
+    pub haul_date_hijri: HijriDate,
+    /// Hijri years since death (haul ke-N)
+    pub haul_number: i32,
+    pub days_until: i64,
+    pub heirs: Vec<HaulContact>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HaulContact {
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub relationship: Option<String>,
+    pub is_primary: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, heir_request, test_db};
+    use crate::db::{CreateGraveRequest, CreateHeirRequest};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_hijri_dates() {
+        let new_year = HijriDate::from_gregorian(date("2024-07-07"));
+        assert_eq!((new_year.year, new_year.month, new_year.day), (1446, 1, 1));
+        assert_eq!(new_year.formatted, "1 Muharam 1446 H");
+        assert_eq!(HijriDate::from_gregorian(date("2024-07-06")).formatted, "30 Zulhijah 1445 H");
+        assert_eq!(hijri_to_gregorian(1446, 1, 1), date("2024-07-07"));
+    }
+
+    #[test]
+    fn test_upcoming_hauls() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                date_of_death: "2019-05-06".to_string(),
+                ..grave_request(block_id, "12")
+            })
+            .unwrap();
+        db.create_heir(&CreateHeirRequest {
+            phone_number: Some("0812-3456-789".to_string()),
+            ..heir_request(grave_id, "Siti Rahayu")
+        })
+        .unwrap();
+
+        let death = HijriDate::from_gregorian(date("2019-05-06"));
+        let haul = hijri_to_gregorian(1446, death.month, death.day);
+        let from = haul - Duration::days(3);
+
+        let hauls = db.get_upcoming_hauls(from, 30).unwrap();
+        assert_eq!(hauls.len(), 1);
+        assert_eq!(hauls[0].grave_id, grave_id);
+        assert_eq!(hauls[0].haul_number, 1446 - death.year);
+        assert_eq!(hauls[0].days_until, 3);
+        assert_eq!((hauls[0].haul_date_hijri.month, hauls[0].haul_date_hijri.day), (death.month, death.day));
+        assert_eq!(hauls[0].heirs[0].phone_number.as_deref(), Some("0812-3456-789"));
+
+        assert!(db.get_upcoming_hauls(from, 2).unwrap().is_empty());
+        assert!(db.get_upcoming_hauls(from, 400).is_err());
+    }
+}
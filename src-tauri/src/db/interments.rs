@@ -0,0 +1,318 @@
+//! Multiple interments per grave (tumpang)
+//!
+//! Each grave has one or more interments in burial order. The first one
+//! mirrors the grave's `deceased_name`, `date_of_death` and `burial_date`
+//! (kept in step by database triggers), so fees, heirs and payments stay on
+//! the grave.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::OptionalExtension;
+
+use super::dates::{loose_date, loose_optional_date};
+use super::{validation, Database};
+
+const INTERMENT_COLUMNS: &str =
+    "id, grave_id, sequence, deceased_name, date_of_death, burial_date, notes, created_at, updated_at";
+
+impl Database {
+    /// Get interments of a grave in burial order
+    pub fn get_interments(&self, grave_id: i64) -> Result<Vec<Interment>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM interments WHERE grave_id = ?1 ORDER BY sequence",
+                INTERMENT_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let interments = stmt
+            .query_map([grave_id], map_interment)
+            .map_err(|e| format!("Failed to query interments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect interments: {}", e))?;
+
+        Ok(interments)
+    }
+
+    /// Get interment by ID
+    pub fn get_interment_by_id(&self, id: i64) -> Result<Option<Interment>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM interments WHERE id = ?1", INTERMENT_COLUMNS),
+                [id],
+                map_interment,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get interment: {}", e))
+    }
+
+    /// Record another burial in an existing grave
+    pub fn add_interment(&self, grave_id: i64, interment: &CreateIntermentRequest) -> Result<i64, String> {
+        validation::create_interment(interment, self.local_today()?)?;
+        if self.get_grave_by_id(grave_id)?.is_none() {
+            return Err(format!("Grave {} not found", grave_id));
+        }
+
+        self.conn
+            .execute(
+                "INSERT INTO interments (grave_id, sequence, deceased_name, date_of_death, burial_date, notes, created_by, updated_by)
+                 VALUES (?1, (SELECT COALESCE(MAX(sequence), 0) + 1 FROM interments WHERE grave_id = ?1), ?2, ?3, ?4, ?5, ?6, ?6)",
+                rusqlite::params![
+                    grave_id,
+                    interment.deceased_name.trim(),
+                    interment.date_of_death,
+                    validation::non_empty(interment.burial_date.as_deref()),
+                    validation::non_empty(interment.notes.as_deref()),
+                    self.operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to add interment: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Update an interment
+    ///
+    /// Changes to the first interment are written to the grave as well.
+    pub fn update_interment(&self, id: i64, interment: &UpdateIntermentRequest) -> Result<(), String> {
+        let existing = self
+            .get_interment_by_id(id)?
+            .ok_or_else(|| format!("Interment {} not found", id))?;
+        validation::update_interment(interment, existing.date_of_death, existing.burial_date, self.local_today()?)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        if self.first_interment_id(existing.grave_id)? == Some(id) {
+            self.conn
+                .execute(
+                    "UPDATE graves SET
+                        deceased_name = COALESCE(?1, deceased_name),
+                        date_of_death = COALESCE(?2, date_of_death),
+                        burial_date = COALESCE(?3, burial_date),
+                        updated_by = ?4
+                     WHERE id = ?5",
+                    rusqlite::params![
+                        interment.deceased_name,
+                        interment.date_of_death,
+                        validation::non_empty(interment.burial_date.as_deref()),
+                        self.operator,
+                        existing.grave_id
+                    ],
+                )
+                .map_err(|e| format!("Failed to update grave: {}", e))?;
+        }
+
+        self.conn
+            .execute(
+                "UPDATE interments SET
+                    deceased_name = COALESCE(?1, deceased_name),
+                    date_of_death = COALESCE(?2, date_of_death),
+                    burial_date = COALESCE(?3, burial_date),
+                    notes = COALESCE(?4, notes),
+                    updated_by = ?5
+                 WHERE id = ?6",
+                rusqlite::params![
+                    interment.deceased_name,
+                    interment.date_of_death,
+                    validation::non_empty(interment.burial_date.as_deref()),
+                    interment.notes,
+                    self.operator,
+                    id
+                ],
+            )
+            .map_err(|e| format!("Failed to update interment: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit interment update: {}", e))
+    }
+
+    /// Delete an interment
+    ///
+    /// The last remaining interment cannot be deleted (delete the grave
+    /// instead). Deleting the first one makes the next one first.
+    pub fn delete_interment(&self, id: i64) -> Result<(), String> {
+        let existing = self
+            .get_interment_by_id(id)?
+            .ok_or_else(|| format!("Interment {} not found", id))?;
+        let was_first = self.first_interment_id(existing.grave_id)? == Some(id);
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        self.conn
+            .execute("DELETE FROM interments WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete interment: {}", e))?;
+
+        if was_first {
+            let next_id = self
+                .first_interment_id(existing.grave_id)?
+                .ok_or_else(|| "A grave needs at least one interment; delete the grave instead".to_string())?;
+            self.conn
+                .execute(
+                    "UPDATE graves SET
+                        deceased_name = i.deceased_name,
+                        date_of_death = i.date_of_death,
+                        burial_date = i.burial_date,
+                        updated_by = ?1
+                     FROM (SELECT deceased_name, date_of_death, burial_date FROM interments WHERE id = ?2) AS i
+                     WHERE graves.id = ?3",
+                    rusqlite::params![self.operator, next_id, existing.grave_id],
+                )
+                .map_err(|e| format!("Failed to update grave: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit interment deletion: {}", e))
+    }
+
+    /// Read all interments from a schema (`main` or an attached database)
+    ///
+    /// Databases created before interments existed yield an empty list.
+    pub(super) fn read_interments(&self, schema: &str) -> Result<Vec<Interment>, String> {
+        let has_table: bool = self
+            .conn
+            .query_row(
+                &format!("SELECT COUNT(*) > 0 FROM {}.sqlite_master WHERE type = 'table' AND name = 'interments'", schema),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to check interments table: {}", e))?;
+        if !has_table {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM {}.interments ORDER BY grave_id, sequence",
+                INTERMENT_COLUMNS, schema
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let interments = stmt
+            .query_map([], map_interment)
+            .map_err(|e| format!("Failed to query interments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect interments: {}", e))?;
+
+        Ok(interments)
+    }
+
+    fn first_interment_id(&self, grave_id: i64) -> Result<Option<i64>, String> {
+        self.conn
+            .query_row(
+                "SELECT id FROM interments WHERE grave_id = ?1 ORDER BY sequence LIMIT 1",
+                [grave_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get first interment: {}", e))
+    }
+}
+
+fn map_interment(row: &rusqlite::Row) -> rusqlite::Result<Interment> {
+    Ok(Interment {
+        id: row.get(0)?,
+        grave_id: row.get(1)?,
+        sequence: row.get(2)?,
+        deceased_name: row.get(3)?,
+        date_of_death: loose_date(row, 4)?,
+        burial_date: loose_optional_date(row, 5)?,
+        notes: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Person buried in a grave
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Interment {
+    pub id: i64,
+    pub grave_id: i64,
+    /// Burial order within the grave (1 = first)
+    pub sequence: i64,
+    pub deceased_name: String,
+    pub date_of_death: NaiveDate,
+    pub burial_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreateIntermentRequest {
+    pub deceased_name: String,
+    pub date_of_death: String,
+    pub burial_date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdateIntermentRequest {
+    pub deceased_name: Option<String>,
+    pub date_of_death: Option<String>,
+    pub burial_date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::db_with_grave;
+    use crate::db::UpdateGraveRequest;
+
+    fn fatimah() -> CreateIntermentRequest {
+        CreateIntermentRequest {
+            deceased_name: "Fatimah".to_string(),
+            date_of_death: "2024-05-01".to_string(),
+            burial_date: Some("2024-05-02".to_string()),
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_first_interment_follows_grave() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        let first = db.get_interments(grave_id).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].deceased_name, "Almarhum 1");
+
+        db.update_grave(
+            grave_id,
+            &UpdateGraveRequest {
+                deceased_name: Some("Ahmad Dahlan".to_string()),
+                block_id: None,
+                number: None,
+                date_of_death: None,
+                burial_date: None,
+                notes: None,
+            },
+        )
+        .unwrap();
+        db.add_interment(grave_id, &fatimah()).unwrap();
+
+        let interments = db.get_interments(grave_id).unwrap();
+        assert_eq!(interments[0].deceased_name, "Ahmad Dahlan");
+        assert_eq!(interments[1].sequence, 2);
+    }
+
+    #[test]
+    fn test_deleting_interment_updates_grave() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        let second_id = db.add_interment(grave_id, &fatimah()).unwrap();
+
+        db.delete_interment(db.get_interments(grave_id).unwrap()[0].id).unwrap();
+        let grave = db.get_grave_by_id(grave_id).unwrap().unwrap();
+        assert_eq!(grave.deceased_name, "Fatimah");
+        assert_eq!(grave.date_of_death.to_string(), "2024-05-01");
+        // The last interment stays
+        assert!(db.delete_interment(second_id).is_err());
+    }
+}
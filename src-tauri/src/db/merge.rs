@@ -0,0 +1,429 @@
+//! Merge another Astana database into the current one
+//!
+//! The source file is attached only while its records are read, its
+//! blocks are mapped onto existing blocks (or created), and graves with
+//! their heirs, payments and fee waivers are copied in one transaction.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::OptionalExtension;
+
+use super::transfer::{ConflictStrategy, DataExport, ExportCounts, ExportManifest, TransferReport};
+use super::Database;
+
+/// Schema name used while the source database is attached
+const SOURCE_SCHEMA: &str = "merge_source";
+
+impl Database {
+    /// Merge another database file into this one
+    ///
+    /// Source blocks without an explicit mapping are mapped to the block with
+    /// the same code, or created when no such block exists. With `dry_run`
+    /// the transaction is rolled back, giving a preview of the merge. Any
+    /// error rolls back everything. Payments and fee waivers of closed years
+    /// are locked; see `override_merge_database`.
+    pub fn merge_database(
+        &self,
+        source_path: &Path,
+        options: &MergeOptions,
+        dry_run: bool,
+    ) -> Result<MergeReport, String> {
+        self.merge_source(source_path, options, dry_run, None)
+    }
+
+    /// Merge writing payments or fee waivers of closed years (admin
+    /// override, logged)
+    pub fn override_merge_database(
+        &self,
+        source_path: &Path,
+        options: &MergeOptions,
+        dry_run: bool,
+        reason: &str,
+    ) -> Result<MergeReport, String> {
+        self.merge_source(source_path, options, dry_run, Some(reason))
+    }
+
+    fn merge_source(
+        &self,
+        source_path: &Path,
+        options: &MergeOptions,
+        dry_run: bool,
+        override_reason: Option<&str>,
+    ) -> Result<MergeReport, String> {
+        let source = self.read_source_database(source_path)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start merge: {}", e))?;
+
+        let mut transfer = TransferReport {
+            dry_run,
+            strategy: options.strategy,
+            ..Default::default()
+        };
+
+        let mut plans = Vec::new();
+        let mut block_ids: HashMap<i64, i64> = HashMap::new();
+        for block in &source.blocks {
+            let action = match options
+                .block_mappings
+                .iter()
+                .find(|m| m.source_block_id == block.id)
+            {
+                Some(mapping) => mapping.action.clone(),
+                None => match self.find_block_id_by_code(&block.code)? {
+                    Some(target_block_id) => BlockMergeAction::Map { target_block_id },
+                    None => BlockMergeAction::Create { code: block.code.clone() },
+                },
+            };
+
+            let target_block_id = match &action {
+                BlockMergeAction::Map { target_block_id } => {
+                    if self.get_block_by_id(*target_block_id)?.is_none() {
+                        return Err(format!("Target block {} not found", target_block_id));
+                    }
+                    transfer.blocks.skipped += 1;
+                    *target_block_id
+                }
+                BlockMergeAction::Create { code } => {
+                    let code = code.trim();
+                    if code.is_empty() {
+                        return Err(format!("New code for block {} is empty", block.code));
+                    }
+                    if self.find_block_id_by_code(code)?.is_some() {
+                        return Err(format!("Block code {} already exists", code));
+                    }
+                    transfer.blocks.created += 1;
+                    self.insert_block_record(block, code)?
+                }
+            };
+            block_ids.insert(block.id, target_block_id);
+
+            plans.push(BlockMergePlan {
+                source_block_id: block.id,
+                source_code: block.code.clone(),
+                target_code: self.block_code(target_block_id)?,
+                graves: source.graves.iter().filter(|g| g.block_id == block.id).count() as i64,
+                action,
+            });
+        }
+
+        self.import_grave_records(&source, &block_ids, options.strategy, override_reason, &mut transfer)?;
+
+        if dry_run {
+            tx.rollback()
+                .map_err(|e| format!("Failed to roll back merge preview: {}", e))?;
+        } else {
+            tx.commit()
+                .map_err(|e| format!("Failed to commit merge: {}", e))?;
+        }
+
+        Ok(MergeReport {
+            source_name: source.manifest.foundation_name,
+            blocks: plans,
+            transfer,
+        })
+    }
+
+    /// Attach the source database, read all records and detach it again
+    fn read_source_database(&self, source_path: &Path) -> Result<DataExport, String> {
+        let source = source_path
+            .canonicalize()
+            .map_err(|e| format!("Source database not found: {}", e))?;
+        if let Some(current) = self.conn.path().and_then(|p| Path::new(p).canonicalize().ok()) {
+            if current == source {
+                return Err("Cannot merge a database into itself".to_string());
+            }
+        }
+
+        self.conn
+            .execute(
+                &format!("ATTACH DATABASE ?1 AS {}", SOURCE_SCHEMA),
+                [source.to_string_lossy()],
+            )
+            .map_err(|e| format!("Failed to attach source database: {}", e))?;
+
+        let data = self.read_attached_data();
+
+        self.conn
+            .execute(&format!("DETACH DATABASE {}", SOURCE_SCHEMA), [])
+            .map_err(|e| format!("Failed to detach source database: {}", e))?;
+
+        data
+    }
+
+    fn read_attached_data(&self) -> Result<DataExport, String> {
+        let tables: i64 = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {}.sqlite_master WHERE type = 'table' AND name IN ('blocks', 'graves', 'heirs', 'payments')",
+                    SOURCE_SCHEMA
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to read source database: {}", e))?;
+        if tables < 4 {
+            return Err("Source file is not an Astana database".to_string());
+        }
+
+        let foundation_name: Option<String> = self
+            .conn
+            .query_row(
+                &format!("SELECT foundation_name FROM {}.settings WHERE id = 1", SOURCE_SCHEMA),
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or(None);
+        let schema_version: i32 = self
+            .conn
+            .query_row(&format!("PRAGMA {}.user_version", SOURCE_SCHEMA), [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read source schema version: {}", e))?;
+
+        let blocks = self.read_blocks(SOURCE_SCHEMA)?;
+        let graves = self.read_graves(SOURCE_SCHEMA)?;
+        let heirs = self.read_heirs(SOURCE_SCHEMA)?;
+        let payments = self.read_payments(SOURCE_SCHEMA)?;
+        let fee_waivers = self.read_waivers(SOURCE_SCHEMA)?;
+        let interments = self.read_interments(SOURCE_SCHEMA)?;
+
+        Ok(DataExport {
+            manifest: ExportManifest {
+                format: super::transfer::EXPORT_FORMAT.to_string(),
+                format_version: super::transfer::EXPORT_FORMAT_VERSION,
+                schema_version,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                exported_at: chrono::Local::now().to_rfc3339(),
+                foundation_name: foundation_name.unwrap_or_default(),
+                counts: ExportCounts {
+                    blocks: blocks.len() as i64,
+                    graves: graves.len() as i64,
+                    heirs: heirs.len() as i64,
+                    payments: payments.len() as i64,
+                    fee_waivers: fee_waivers.len() as i64,
+                    interments: interments.len() as i64,
+                },
+            },
+            settings: None,
+            blocks,
+            graves,
+            heirs,
+            payments,
+            fee_waivers,
+            interments,
+        })
+    }
+
+    fn find_block_id_by_code(&self, code: &str) -> Result<Option<i64>, String> {
+        self.conn
+            .query_row(
+                "SELECT id FROM blocks WHERE code = ?1 COLLATE NOCASE",
+                [code.trim()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up block {}: {}", code, e))
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// What to do with a source block
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BlockMergeAction {
+    /// Put the graves into an existing block
+    Map { target_block_id: i64 },
+    /// Create a new block with the given code
+    Create { code: String },
+}
+
+/// Mapping chosen by the user for one source block
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockMapping {
+    pub source_block_id: i64,
+    #[serde(flatten)]
+    pub action: BlockMergeAction,
+}
+
+/// Merge options
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct MergeOptions {
+    #[serde(default)]
+    pub block_mappings: Vec<BlockMapping>,
+    /// How to handle grave numbers that already exist in the target block
+    #[serde(default)]
+    pub strategy: ConflictStrategy,
+}
+
+/// Resolved mapping for one source block
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockMergePlan {
+    pub source_block_id: i64,
+    pub source_code: String,
+    pub target_code: String,
+    pub graves: i64,
+    #[serde(flatten)]
+    pub action: BlockMergeAction,
+}
+
+/// Result (or preview) of a database merge
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MergeReport {
+    pub source_name: String,
+    pub blocks: Vec<BlockMergePlan>,
+    #[serde(flatten)]
+    pub transfer: TransferReport,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, grave_request, payment_request};
+    use crate::db::{CreateBlockRequest, CreateGraveRequest, CreateReservationRequest};
+
+    fn add_grave(db: &Database, block_code: &str, number: &str, name: &str) -> i64 {
+        let block_id = match db.find_block_id_by_code(block_code).unwrap() {
+            Some(id) => id,
+            None => db.create_block(&block_request(block_code)).unwrap(),
+        };
+        db.create_grave(&CreateGraveRequest {
+            deceased_name: name.to_string(),
+            ..grave_request(block_id, number)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_preview_and_block_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source.db");
+        let source = Database::init_with_path(source_path.clone()).unwrap();
+        let grave_id = add_grave(&source, "A", "1", "Ahmad");
+        add_grave(&source, "B", "1", "Umar");
+        source.create_payment(&payment_request(grave_id, 2024)).unwrap();
+        let source_block_b = source.find_block_id_by_code("B").unwrap().unwrap();
+        drop(source);
+
+        let target = Database::init_with_path(dir.path().join("astana.db")).unwrap();
+        add_grave(&target, "A", "1", "Siti");
+
+        let options = MergeOptions {
+            block_mappings: vec![BlockMapping {
+                source_block_id: source_block_b,
+                action: BlockMergeAction::Create { code: "B2".to_string() },
+            }],
+            strategy: ConflictStrategy::Rename,
+        };
+
+        let preview = target.merge_database(&source_path, &options, true).unwrap();
+        assert_eq!(preview.transfer.conflicts.len(), 1);
+        assert_eq!(preview.blocks[0].target_code, "A");
+        assert_eq!(preview.blocks[1].target_code, "B2");
+        assert_eq!(target.get_stats().unwrap().graves_count, 1);
+
+        let report = target.merge_database(&source_path, &options, false).unwrap();
+        assert_eq!(report.transfer.graves.created, 1);
+        assert_eq!(report.transfer.graves.renamed, 1);
+        assert_eq!(report.transfer.payments.created, 1);
+        assert_eq!(target.get_stats().unwrap().graves_count, 3);
+    }
+
+    #[test]
+    fn test_merge_rolls_back_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source.db");
+        let source = Database::init_with_path(source_path.clone()).unwrap();
+        add_grave(&source, "A", "1", "Ahmad");
+        add_grave(&source, "B", "1", "Umar");
+        let source_block_a = source.find_block_id_by_code("A").unwrap().unwrap();
+        let source_block_b = source.find_block_id_by_code("B").unwrap().unwrap();
+        drop(source);
+
+        let target = Database::init_with_path(dir.path().join("astana.db")).unwrap();
+        add_grave(&target, "A", "2", "Siti");
+
+        // Block "N" is created for A, then creating it again for B fails
+        let options = MergeOptions {
+            block_mappings: vec![
+                BlockMapping {
+                    source_block_id: source_block_a,
+                    action: BlockMergeAction::Create { code: "N".to_string() },
+                },
+                BlockMapping {
+                    source_block_id: source_block_b,
+                    action: BlockMergeAction::Create { code: "N".to_string() },
+                },
+            ],
+            strategy: ConflictStrategy::Skip,
+        };
+        assert!(target.merge_database(&source_path, &options, false).is_err());
+        assert_eq!(target.get_stats().unwrap().graves_count, 1);
+        assert_eq!(target.get_all_blocks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_checks_target_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source.db");
+        let source = Database::init_with_path(source_path.clone()).unwrap();
+        add_grave(&source, "A", "2", "Ahmad");
+        let source_block_a = source.find_block_id_by_code("A").unwrap().unwrap();
+        drop(source);
+
+        // Block "A" of two plots, one taken by a grave and one by a reservation of number 2
+        let target = Database::init_with_path(dir.path().join("astana.db")).unwrap();
+        let block_id = target
+            .create_block(&CreateBlockRequest {
+                total_capacity: 2,
+                ..block_request("A")
+            })
+            .unwrap();
+        target.create_grave(&grave_request(block_id, "1")).unwrap();
+        target
+            .create_reservation(&CreateReservationRequest {
+                block_id,
+                grave_number: Some("2".to_string()),
+                position: None,
+                reserved_for: None,
+                reserver_name: "Budi".to_string(),
+                phone_number: None,
+                address: None,
+                relationship: None,
+                reserved_until: None,
+                deposit_amount: 0,
+                notes: None,
+            })
+            .unwrap();
+
+        let options = MergeOptions::default();
+        let preview = target.merge_database(&source_path, &options, true).unwrap();
+        let violations: Vec<_> = preview
+            .transfer
+            .placement_violations
+            .iter()
+            .map(|v| (v.number.as_str(), v.violation.as_str()))
+            .collect();
+        assert_eq!(violations, [("2", "reserved"), ("2", "full")]);
+
+        assert!(target.merge_database(&source_path, &options, false).is_err());
+        // An override fills the block but does not take a reserved number
+        assert!(target.override_merge_database(&source_path, &options, false, "Pindahan").is_err());
+        assert_eq!(target.get_stats().unwrap().graves_count, 1);
+
+        let options = MergeOptions {
+            block_mappings: vec![BlockMapping {
+                source_block_id: source_block_a,
+                action: BlockMergeAction::Create { code: "A2".to_string() },
+            }],
+            ..Default::default()
+        };
+        let report = target.merge_database(&source_path, &options, false).unwrap();
+        assert!(report.transfer.placement_violations.is_empty());
+        assert_eq!(target.get_stats().unwrap().graves_count, 2);
+    }
+}
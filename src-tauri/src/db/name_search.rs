@@ -0,0 +1,323 @@
+//! Fuzzy name search tuned for Indonesian names
+//!
+//! Names are reduced to a phonetic key: titles (Alm., H., Hj., academic
+//! degrees) are dropped, abbreviations such as Moh./Moch./Muh. become
+//! Muhammad, and old and Arabic transliterations are folded together
+//! (Achmad = Ahmad, Djoko = Joko, Jusuf = Yusuf, Mohammed = Muhammad).
+//! Matches are ranked by the similarity of the keys, so a search finds
+//! spelling variants of both deceased and heir names.
+
+use std::collections::HashMap;
+
+use super::Database;
+
+/// Titles and particles left out of names
+const NAME_STOPWORDS: &[&str] = &[
+    "alm", "almh", "almarhum", "almarhumah", "h", "hj", "haji", "hajah", "hajjah", "kh", "ust", "ustadz", "ustaz",
+    "bpk", "bapak", "ibu", "ny", "nyonya", "tn", "tuan", "sdr", "sdri", "bin", "binti", "dr", "drs", "dra", "ir",
+    "prof", "s", "pd", "sh", "se", "skm", "spd", "ssos", "sag", "mpd",
+];
+
+/// Abbreviations written out before comparing
+const NAME_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("m", "muhammad"),
+    ("md", "muhammad"),
+    ("mh", "muhammad"),
+    ("mhd", "muhammad"),
+    ("moh", "muhammad"),
+    ("mohd", "muhammad"),
+    ("moch", "muhammad"),
+    ("mochd", "muhammad"),
+    ("muh", "muhammad"),
+    ("muhd", "muhammad"),
+    ("abd", "abdul"),
+];
+
+/// Spelling variants folded together, applied in order
+const SPELLING_VARIANTS: &[(&str, &str)] = &[
+    ("oe", "u"),
+    ("ae", "ai"),
+    ("dj", "j"),
+    ("tj", "c"),
+    ("sj", "sy"),
+    ("j", "y"),
+    ("ch", "h"),
+    ("kh", "h"),
+    ("sh", "sy"),
+    ("ph", "f"),
+    ("th", "t"),
+    ("dh", "d"),
+    ("dz", "z"),
+    ("q", "k"),
+    ("v", "f"),
+    ("x", "ks"),
+    ("o", "u"),
+    ("e", "a"),
+];
+
+/// Lowest similarity counted as a match
+const MIN_MATCH_SCORE: f64 = 0.8;
+
+/// Heir matches rank just below deceased matches of the same similarity
+const HEIR_MATCH_WEIGHT: f64 = 0.95;
+
+impl Database {
+    /// Search deceased and heir names, best match first
+    pub fn search_names(&self, query: &str, limit: usize) -> Result<Vec<NameMatch>, String> {
+        let query = NameQuery::new(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut matches = Vec::new();
+        for (kind, sql) in [
+            (NameKind::Deceased, "SELECT grave_id, deceased_name FROM interments"),
+            (NameKind::Heir, "SELECT grave_id, full_name FROM heirs"),
+        ] {
+            let mut stmt = self
+                .conn
+                .prepare(sql)
+                .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            let names = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+                .map_err(|e| format!("Failed to query names: {}", e))?;
+            for row in names {
+                let (grave_id, name) = row.map_err(|e| format!("Failed to collect names: {}", e))?;
+                let score = query.score(&name);
+                if score >= MIN_MATCH_SCORE {
+                    matches.push(NameMatch {
+                        grave_id,
+                        name,
+                        kind,
+                        score: (score * 100.0).round() / 100.0,
+                    });
+                }
+            }
+        }
+
+        matches.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then((a.kind == NameKind::Heir).cmp(&(b.kind == NameKind::Heir)))
+                .then(a.name.cmp(&b.name))
+        });
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    /// IDs of graves matching a search, best match first
+    ///
+    /// A grave matches on the name of anyone buried in it, the name of an
+    /// heir, or its grave number. Returned as a JSON array for
+    /// `GRAVE_SEARCH_JOIN`.
+    pub(super) fn grave_search_ids(&self, search: &str) -> Result<String, String> {
+        let mut best: HashMap<i64, f64> = HashMap::new();
+        for found in self.search_names(search, usize::MAX)? {
+            let score = match found.kind {
+                NameKind::Deceased => found.score,
+                NameKind::Heir => found.score * HEIR_MATCH_WEIGHT,
+            };
+            let entry = best.entry(found.grave_id).or_insert(score);
+            *entry = entry.max(score);
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM graves WHERE number LIKE ?1")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let numbered = stmt
+            .query_map([format!("%{}%", search.trim())], |row| row.get::<_, i64>(0))
+            .map_err(|e| format!("Failed to query grave numbers: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect grave numbers: {}", e))?;
+        for grave_id in numbered {
+            best.insert(grave_id, 1.0);
+        }
+
+        let mut ranked: Vec<(i64, f64)> = best.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        serde_json::to_string(&ranked.iter().map(|(id, _)| id).collect::<Vec<_>>())
+            .map_err(|e| format!("Failed to build search: {}", e))
+    }
+}
+
+/// A search query reduced to name keys
+struct NameQuery {
+    tokens: Vec<String>,
+    joined: String,
+}
+
+impl NameQuery {
+    fn new(query: &str) -> Self {
+        let key = name_key(query);
+        NameQuery {
+            tokens: key.split(' ').filter(|t| !t.is_empty()).map(String::from).collect(),
+            joined: key.replace(' ', ""),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Similarity of a name to the query (0-1)
+    ///
+    /// Every query word is matched to its closest word of the name; a word
+    /// the name word starts with counts almost as a full match, so partly
+    /// typed names are found. Names written as one word ("Abdurrahman" for
+    /// "Abdul Rahman") are compared as a whole as well.
+    fn score(&self, name: &str) -> f64 {
+        let key = name_key(name);
+        let words: Vec<&str> = key.split(' ').filter(|t| !t.is_empty()).collect();
+        if words.is_empty() {
+            return 0.0;
+        }
+        let by_word = self
+            .tokens
+            .iter()
+            .map(|token| {
+                words
+                    .iter()
+                    .map(|word| {
+                        if word == token {
+                            1.0
+                        } else if word.starts_with(token.as_str()) {
+                            0.9
+                        } else {
+                            ratio(token, word)
+                        }
+                    })
+                    .fold(0.0, f64::max)
+            })
+            .sum::<f64>()
+            / self.tokens.len() as f64;
+        by_word.max(ratio(&self.joined, &key.replace(' ', "")))
+    }
+}
+
+/// Phonetic key of a name: normalized words with spelling variants folded
+pub(super) fn name_key(name: &str) -> String {
+    normalize_name(name)
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .map(word_key)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lower-case a name, drop punctuation and titles, write out abbreviations
+fn normalize_name(name: &str) -> String {
+    name.to_lowercase()
+        .replace(['\'', '`', '’'], "")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !NAME_STOPWORDS.contains(word))
+        .map(|word| {
+            NAME_ABBREVIATIONS
+                .iter()
+                .find(|(short, _)| *short == word)
+                .map_or(word, |(_, long)| long)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn word_key(word: &str) -> String {
+    let mut key = word.to_string();
+    for (from, to) in SPELLING_VARIANTS {
+        key = key.replace(from, to);
+    }
+    // Final h is often left out (Fatimah / Fatima)
+    if key.len() > 3 && key.ends_with('h') {
+        key.pop();
+    }
+    let mut folded = String::with_capacity(key.len());
+    for c in key.chars() {
+        if !folded.ends_with(c) {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+/// 1 - edit distance / length of the longer string
+pub(super) fn ratio(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameKind {
+    Deceased,
+    Heir,
+}
+
+/// Name found by a search
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NameMatch {
+    pub grave_id: i64,
+    pub name: String,
+    pub kind: NameKind,
+    /// Similarity to the query (0-1)
+    pub score: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, heir_request, test_db};
+    use crate::db::{CreateGraveRequest, GraveListQuery};
+
+    #[test]
+    fn test_name_key_ignores_titles_and_spelling() {
+        assert_eq!(name_key("Alm. H. Moch. Achmad Djunaedi, S.Pd"), name_key("Muhammad Ahmad Junaidi"));
+        assert_eq!(name_key("Mohammed Jusuf"), name_key("Muhamad Yusuf"));
+        assert_eq!(name_key("Hj. Fatimah"), name_key("Fatima"));
+    }
+
+    #[test]
+    fn test_search_finds_spelling_variants() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave = |name: &str, number: &str| {
+            db.create_grave(&CreateGraveRequest {
+                deceased_name: name.to_string(),
+                ..grave_request(block_id, number)
+            })
+            .unwrap()
+        };
+        let muhammad = grave("H. Muhammad Ridwan", "1");
+        let moch = grave("Moch. Ridwan", "2");
+        let siti = grave("Siti Aminah", "3");
+        db.create_heir(&heir_request(siti, "Muhamad Rizal")).unwrap();
+
+        let found = db.search_names("Muhamad Ridwan", 10).unwrap();
+        let ids: Vec<i64> = found.iter().map(|m| m.grave_id).collect();
+        assert_eq!(&ids[..2], [muhammad, moch]);
+        assert!(found.iter().all(|m| m.kind == NameKind::Deceased));
+
+        let search = |s: &str| GraveListQuery {
+            search: Some(s.to_string()),
+            ..Default::default()
+        };
+        let graves: Vec<i64> = db.get_graves(&search("muhamad"), 10).unwrap().graves.iter().map(|g| g.id).collect();
+        assert_eq!(graves, [muhammad, moch, siti]);
+        assert_eq!(db.count_graves(&search("achmad")).unwrap(), 0);
+        assert_eq!(db.count_graves(&search("3")).unwrap(), 1);
+    }
+}
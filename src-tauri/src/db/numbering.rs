@@ -0,0 +1,313 @@
+//! Grave numbering scheme per block
+//!
+//! Each block numbers its graves one way: plain numbers, zero-padded numbers
+//! or padded numbers with a letter suffix (`05A`). The next free number and
+//! the gaps below the highest number are suggested to the grave form;
+//! numbers held by active reservations count as used.
+
+use std::collections::BTreeSet;
+
+use rusqlite::OptionalExtension;
+
+use super::reservations::ACTIVE_RESERVATION_SQL;
+use super::Database;
+
+/// Natural order of grave numbers (2 < 10 < 10A)
+pub(super) const GRAVE_NUMBER_ORDER_SQL: &str = "CAST(g.number AS INTEGER), length(g.number), g.number";
+
+/// Widest accepted zero padding
+const MAX_NUMBER_WIDTH: i64 = 6;
+
+/// Most gaps listed in a suggestion
+const MAX_LISTED_GAPS: usize = 100;
+
+impl Database {
+    /// Get the numbering scheme of a block
+    pub fn get_block_numbering(&self, block_id: i64) -> Result<GraveNumbering, String> {
+        let numbering: Option<(String, i64)> = self
+            .conn
+            .query_row(
+                "SELECT number_scheme, number_width FROM blocks WHERE id = ?1",
+                [block_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get block numbering: {}", e))?;
+
+        let (scheme, width) = numbering.ok_or_else(|| format!("Block {} not found", block_id))?;
+        Ok(GraveNumbering {
+            scheme: NumberScheme::parse(&scheme)?,
+            width,
+        })
+    }
+
+    /// Set the numbering scheme of a block
+    ///
+    /// Existing grave numbers are left as they are.
+    pub fn set_block_numbering(&self, block_id: i64, numbering: &GraveNumbering) -> Result<(), String> {
+        if !(0..=MAX_NUMBER_WIDTH).contains(&numbering.width) {
+            return Err(format!("Number width must be between 0 and {}", MAX_NUMBER_WIDTH));
+        }
+        if numbering.scheme == NumberScheme::Padded && numbering.width == 0 {
+            return Err("Padded numbers need a width".to_string());
+        }
+        let width = if numbering.scheme == NumberScheme::Numeric { 0 } else { numbering.width };
+
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE blocks SET number_scheme = ?1, number_width = ?2, updated_by = ?3 WHERE id = ?4",
+                rusqlite::params![numbering.scheme.as_str(), width, self.operator, block_id],
+            )
+            .map_err(|e| format!("Failed to update block numbering: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Block {} not found", block_id));
+        }
+        Ok(())
+    }
+
+    /// Suggest the next free grave number of a block and list the gaps
+    pub fn get_next_grave_number(&self, block_id: i64) -> Result<GraveNumberSuggestion, String> {
+        let numbering = self.get_block_numbering(block_id)?;
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT number FROM graves WHERE block_id = ?1
+                 UNION
+                 SELECT r.grave_number FROM reservations r
+                 WHERE r.block_id = ?1 AND r.grave_number IS NOT NULL AND {}",
+                ACTIVE_RESERVATION_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let used: BTreeSet<u64> = stmt
+            .query_map([block_id], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to query grave numbers: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect grave numbers: {}", e))?
+            .iter()
+            .filter_map(|number| parse_grave_number(number).map(|(base, _)| base))
+            .collect();
+
+        let highest = used.iter().next_back().copied().unwrap_or(0);
+        let gaps: Vec<u64> = (1..highest).filter(|base| !used.contains(base)).collect();
+
+        Ok(GraveNumberSuggestion {
+            next_number: numbering.format(highest + 1),
+            gap_count: gaps.len(),
+            gaps: gaps
+                .iter()
+                .take(MAX_LISTED_GAPS)
+                .map(|&base| numbering.format(base))
+                .collect(),
+            numbering,
+        })
+    }
+
+    /// Grave number written the way its block numbers graves
+    pub(super) fn normalized_grave_number(&self, block_id: i64, number: &str) -> Result<String, String> {
+        Ok(self.get_block_numbering(block_id)?.normalize(number))
+    }
+
+    /// Fail if another grave of the block has the same number in any
+    /// padding ("5" and "005"), leaving out `except_grave_id`
+    pub(super) fn check_number_free(
+        &self,
+        block_id: i64,
+        number: &str,
+        except_grave_id: Option<i64>,
+    ) -> Result<(), String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT number FROM graves WHERE block_id = ?1 AND id IS NOT ?2")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let numbers = stmt
+            .query_map(rusqlite::params![block_id, except_grave_id], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to query grave numbers: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect grave numbers: {}", e))?;
+
+        let key = grave_number_key(number);
+        match numbers.iter().find(|used| grave_number_key(used) == key) {
+            Some(used) => Err(format!("Grave number {} is already used", used)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Split a grave number into its numeric base and suffix (`05A` -> 5, "A")
+///
+/// Numbers that do not start with a digit have no base.
+pub fn parse_grave_number(number: &str) -> Option<(u64, String)> {
+    let number = number.trim();
+    let digits = number.len() - number.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let base = number[..digits].parse().ok()?;
+    Some((base, number[digits..].to_uppercase()))
+}
+
+/// Comparable form of a grave number: base without padding and suffix
+/// ("005a" -> "5A"); numbers without a base compare as written
+pub(super) fn grave_number_key(number: &str) -> String {
+    match parse_grave_number(number) {
+        Some((base, suffix)) => format!("{}{}", base, suffix),
+        None => number.trim().to_uppercase(),
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberScheme {
+    Numeric,
+    Padded,
+    Suffix,
+}
+
+impl NumberScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NumberScheme::Numeric => "numeric",
+            NumberScheme::Padded => "padded",
+            NumberScheme::Suffix => "suffix",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "numeric" => Ok(NumberScheme::Numeric),
+            "padded" => Ok(NumberScheme::Padded),
+            "suffix" => Ok(NumberScheme::Suffix),
+            other => Err(format!("Unknown numbering scheme: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GraveNumbering {
+    pub scheme: NumberScheme,
+    /// Digits of the numeric part (0 = no padding)
+    pub width: i64,
+}
+
+impl GraveNumbering {
+    /// Format a numeric base as a grave number of this scheme
+    pub fn format(&self, base: u64) -> String {
+        let width = self.width as usize;
+        match self.scheme {
+            NumberScheme::Numeric => base.to_string(),
+            NumberScheme::Padded => format!("{:0width$}", base, width = width),
+            NumberScheme::Suffix => format!("{:0width$}A", base, width = width),
+        }
+    }
+
+    /// Write an entered number in this scheme ("5" -> "005" with padding
+    /// 3); the suffix is kept and numbers without a base stay as entered
+    pub fn normalize(&self, number: &str) -> String {
+        let Some((base, suffix)) = parse_grave_number(number) else {
+            return number.trim().to_string();
+        };
+        let width = if self.scheme == NumberScheme::Numeric { 0 } else { self.width as usize };
+        format!("{:0width$}{}", base, suffix, width = width)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveNumberSuggestion {
+    pub numbering: GraveNumbering,
+    /// Number after the highest one in use
+    pub next_number: String,
+    /// Free numbers below the highest one (at most 100 listed)
+    pub gaps: Vec<String>,
+    pub gap_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, create_grave, grave_request, test_db};
+    use crate::db::{GraveListQuery, RelocateGraveRequest};
+
+    #[test]
+    fn test_parse_grave_number() {
+        assert_eq!(parse_grave_number("05b"), Some((5, "B".to_string())));
+        assert_eq!(parse_grave_number(" 12 "), Some((12, String::new())));
+        assert_eq!(parse_grave_number("Utara"), None);
+    }
+
+    #[test]
+    fn test_next_number_and_gaps_follow_scheme() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        for number in ["10", "2", "10A", "4"] {
+            create_grave(&db, block_id, number);
+        }
+
+        let numbers: Vec<String> = db
+            .get_graves(
+                &GraveListQuery {
+                    block_id: Some(block_id),
+                    ..Default::default()
+                },
+                10,
+            )
+            .unwrap()
+            .graves
+            .into_iter()
+            .map(|g| g.number)
+            .collect();
+        assert_eq!(numbers, ["2", "4", "10", "10A"]);
+
+        let suggestion = db.get_next_grave_number(block_id).unwrap();
+        assert_eq!(suggestion.next_number, "11");
+        assert_eq!(suggestion.gaps, ["1", "3", "5", "6", "7", "8", "9"]);
+
+        let suffix = GraveNumbering { scheme: NumberScheme::Suffix, width: 2 };
+        db.set_block_numbering(block_id, &suffix).unwrap();
+        let suggestion = db.get_next_grave_number(block_id).unwrap();
+        assert_eq!(suggestion.numbering, suffix);
+        assert_eq!(suggestion.next_number, "11A");
+        assert_eq!(suggestion.gaps[0], "01A");
+        assert!(db
+            .set_block_numbering(block_id, &GraveNumbering { scheme: NumberScheme::Padded, width: 0 })
+            .is_err());
+    }
+
+    #[test]
+    fn test_numbers_are_written_in_block_scheme() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        // Entered before the block was padded
+        db.conn
+            .execute(
+                "INSERT INTO graves (deceased_name, block_id, number, date_of_death) VALUES ('Lama', ?1, '7', '2010-01-01')",
+                [block_id],
+            )
+            .unwrap();
+        db.set_block_numbering(block_id, &GraveNumbering { scheme: NumberScheme::Padded, width: 3 })
+            .unwrap();
+
+        let grave_id = create_grave(&db, block_id, " 5 ");
+        assert_eq!(db.get_grave_by_id(grave_id).unwrap().unwrap().number, "005");
+        assert!(db.create_grave(&grave_request(block_id, "005")).is_err());
+        assert!(db.create_grave(&grave_request(block_id, "07")).is_err());
+
+        db.relocate_grave(
+            grave_id,
+            &RelocateGraveRequest {
+                to_block_id: block_id,
+                to_number: "8".to_string(),
+                to_position: None,
+                relocation_date: "2026-02-01".to_string(),
+                reason: "Penomoran ulang".to_string(),
+                authorized_by: "Ketua".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(db.get_grave_by_id(grave_id).unwrap().unwrap().number, "008");
+
+        let suffix = GraveNumbering { scheme: NumberScheme::Suffix, width: 2 };
+        assert_eq!(suffix.normalize("5b"), "05B");
+        assert_eq!(suffix.normalize("Utara"), "Utara");
+    }
+}
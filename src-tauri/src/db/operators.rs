@@ -0,0 +1,106 @@
+//! Payments received per operator
+//!
+//! Used at month end to reconcile the money each cashier received.
+
+use chrono::NaiveDate;
+
+use super::Database;
+
+impl Database {
+    /// Get payments received grouped by operator and payment date
+    ///
+    /// Dates are inclusive (YYYY-MM-DD). Payments recorded before operator
+    /// attribution existed are grouped under `operator_id = None`.
+    pub fn get_payments_by_operator(
+        &self,
+        start_date: &str,
+        end_date: &str,
+    ) -> Result<Vec<OperatorPaymentSummary>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT p.created_by, u.full_name, u.username, p.payment_date,
+                        COUNT(*), SUM(p.amount),
+                        SUM(CASE WHEN COALESCE(p.payment_method, 'cash') = 'cash' THEN p.amount ELSE 0 END)
+                 FROM payments p
+                 LEFT JOIN users u ON p.created_by = u.id
+                 WHERE p.payment_date BETWEEN ?1 AND ?2
+                 GROUP BY p.created_by, p.payment_date
+                 ORDER BY u.full_name IS NULL, u.full_name, p.payment_date",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let rows = stmt
+            .query_map([start_date, end_date], |row| {
+                let total_amount: i64 = row.get(5)?;
+                let cash_amount: i64 = row.get(6)?;
+                Ok(OperatorPaymentSummary {
+                    operator_id: row.get(0)?,
+                    operator_name: row.get(1)?,
+                    username: row.get(2)?,
+                    payment_date: row.get(3)?,
+                    payment_count: row.get(4)?,
+                    total_amount,
+                    cash_amount,
+                    non_cash_amount: total_amount - cash_amount,
+                })
+            })
+            .map_err(|e| format!("Failed to query payments by operator: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect payments by operator: {}", e))?;
+
+        Ok(rows)
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Payments received by one operator on one date
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OperatorPaymentSummary {
+    pub operator_id: Option<i64>,
+    pub operator_name: Option<String>,
+    pub username: Option<String>,
+    pub payment_date: NaiveDate,
+    pub payment_count: i64,
+    pub total_amount: i64,
+    pub cash_amount: i64,
+    pub non_cash_amount: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::{db_with_grave, payment_request};
+    use crate::db::{CreatePaymentRequest, CreateUserRequest, Role};
+
+    #[test]
+    fn test_payments_grouped_by_operator() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        let cashier_id = db
+            .create_user(&CreateUserRequest {
+                username: "kasir".to_string(),
+                full_name: "Kasir Satu".to_string(),
+                password: "rahasia123".to_string(),
+                role: Role::Cashier,
+            })
+            .unwrap();
+
+        let payment = |year: i32, method: &str| CreatePaymentRequest {
+            payment_date: "2026-01-09".to_string(),
+            payment_method: Some(method.to_string()),
+            ..payment_request(grave_id, year)
+        };
+        db.create_payment(&payment(2023, "cash")).unwrap();
+
+        let db = db.with_operator(cashier_id);
+        db.create_payment(&payment(2024, "cash")).unwrap();
+        db.create_payment(&payment(2025, "transfer")).unwrap();
+
+        let report = db.get_payments_by_operator("2026-01-01", "2026-01-31").unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].operator_name.as_deref(), Some("Kasir Satu"));
+        assert_eq!(report[0].payment_count, 2);
+        assert_eq!(report[0].cash_amount, 100000);
+        assert_eq!(report[0].non_cash_amount, 100000);
+        assert_eq!(report[1].operator_id, None);
+    }
+}
@@ -0,0 +1,387 @@
+//! Payment overview of many graves (payment page)
+//!
+//! One query computes, for every grave matching the filter, the fee due and
+//! paid for each year of the window ending at the selected year. Fee waivers
+//! lower the fee due; years before the burial (or death) year owe nothing.
+
+use super::numbering::GRAVE_NUMBER_ORDER_SQL;
+use super::{Database, Payment, GRAVE_SEARCH_JOIN};
+
+/// Years shown when the filter does not set a window
+const DEFAULT_YEAR_WINDOW: i32 = 5;
+
+/// Widest accepted year window
+const MAX_YEAR_WINDOW: i32 = 20;
+
+/// Status of one grave-year: nothing left to pay, some paid, nothing paid
+const YEAR_STATUS_SQL: &str = "CASE WHEN gy.paid >= gy.due THEN 'paid' WHEN gy.paid > 0 THEN 'partial' ELSE 'unpaid' END";
+
+impl Database {
+    /// Get graves with their payments per year of the window, `limit` at a time
+    pub fn get_graves_with_payment_summary(
+        &self,
+        filter: &PaymentOverviewFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<GravePaymentSummary>, String> {
+        let (mut query, mut params) = self.payment_overview_sql(filter)?;
+
+        // Search results come best match first
+        let order = if filter.search.is_some() {
+            "g.search_rank".to_string()
+        } else {
+            format!("g.code, {}", GRAVE_NUMBER_ORDER_SQL)
+        };
+        query.push_str(&format!(
+            ",
+             page AS (
+                 SELECT g.id, g.deceased_name, g.code, g.number, g.annual_fee, st.status, st.arrears_years,
+                        ROW_NUMBER() OVER (ORDER BY {order}) AS position
+                 FROM selected g
+                 JOIN summaries st ON st.grave_id = g.id
+                 {filter}
+                 ORDER BY {order}
+                 LIMIT ? OFFSET ?
+             )
+             SELECT page.id, page.deceased_name, page.code, page.number, page.annual_fee, page.status, page.arrears_years,
+                    gy.year, gy.due, {year_status},
+                    p.id, p.payment_date, p.amount, p.payment_method, p.payment_proof, p.paid_by, p.notes, p.created_at, p.updated_at
+             FROM page
+             JOIN grave_years gy ON gy.grave_id = page.id
+             LEFT JOIN payments p ON p.grave_id = page.id AND p.year = gy.year
+             ORDER BY page.position, gy.year",
+            order = order,
+            filter = summary_filter_sql(filter, &mut params),
+            year_status = YEAR_STATUS_SQL,
+        ));
+        params.push(Box::new(limit));
+        params.push(Box::new(offset));
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut stmt = self
+            .conn
+            .prepare(&query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut rows = stmt
+            .query(param_refs.as_slice())
+            .map_err(|e| format!("Failed to query payment summary: {}", e))?;
+
+        let mut summaries: Vec<GravePaymentSummary> = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| format!("Failed to read payment summary: {}", e))? {
+            let read = || -> rusqlite::Result<(i64, i32, YearPaymentStatus, Option<Payment>)> {
+                let grave_id: i64 = row.get(0)?;
+                let year: i32 = row.get(7)?;
+                let payment = match row.get::<_, Option<i64>>(10)? {
+                    Some(id) => Some(Payment {
+                        id,
+                        grave_id,
+                        year,
+                        payment_date: row.get(11)?,
+                        amount: row.get(12)?,
+                        payment_method: row.get(13)?,
+                        payment_proof: row.get(14)?,
+                        paid_by: row.get(15)?,
+                        notes: row.get(16)?,
+                        created_at: row.get(17)?,
+                        updated_at: row.get(18)?,
+                    }),
+                    None => None,
+                };
+                let status = YearPaymentStatus {
+                    year,
+                    is_paid: payment.is_some(),
+                    amount: payment.as_ref().map(|p| p.amount),
+                    fee_due: row.get(8)?,
+                    status: parse_status(&row.get::<_, String>(9)?),
+                };
+                Ok((grave_id, year, status, payment))
+            };
+            let (grave_id, year, year_status, payment) =
+                read().map_err(|e| format!("Failed to read payment summary: {}", e))?;
+
+            if summaries.last().is_none_or(|s| s.grave_id != grave_id) {
+                let read_grave = || -> rusqlite::Result<GravePaymentSummary> {
+                    Ok(GravePaymentSummary {
+                        grave_id,
+                        deceased_name: row.get(1)?,
+                        block_code: row.get(2)?,
+                        number: row.get(3)?,
+                        annual_fee: row.get(4)?,
+                        payment_status: parse_status(&row.get::<_, String>(5)?),
+                        arrears_years: row.get(6)?,
+                        current_year_payment: None,
+                        recent_payments: Vec::new(),
+                    })
+                };
+                summaries.push(read_grave().map_err(|e| format!("Failed to read payment summary: {}", e))?);
+            }
+            if let Some(summary) = summaries.last_mut() {
+                if year == filter.year {
+                    summary.current_year_payment = payment;
+                }
+                summary.recent_payments.push(year_status);
+            }
+        }
+
+        Ok(summaries)
+    }
+
+    /// Count graves matching a payment overview filter
+    pub fn count_graves_with_payment_summary(&self, filter: &PaymentOverviewFilter) -> Result<i64, String> {
+        let (mut query, mut params) = self.payment_overview_sql(filter)?;
+        query.push_str(&format!(
+            " SELECT COUNT(*) FROM selected g JOIN summaries st ON st.grave_id = g.id {}",
+            summary_filter_sql(filter, &mut params)
+        ));
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        self.conn
+            .query_row(&query, param_refs.as_slice(), |row| row.get(0))
+            .map_err(|e| format!("Failed to count graves: {}", e))
+    }
+
+    /// Common table expressions of the overview: `window_years`, the
+    /// `selected` graves, their `grave_years` and per-grave `summaries`
+    fn payment_overview_sql(
+        &self,
+        filter: &PaymentOverviewFilter,
+    ) -> Result<(String, Vec<Box<dyn rusqlite::ToSql>>), String> {
+        let window = filter.year_window.unwrap_or(DEFAULT_YEAR_WINDOW);
+        if !(1..=MAX_YEAR_WINDOW).contains(&window) {
+            return Err(format!("Year window must be between 1 and {}", MAX_YEAR_WINDOW));
+        }
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(filter.year - window + 1), Box::new(filter.year)];
+
+        let mut selected = String::from(
+            "SELECT g.id, g.deceased_name, g.number, g.burial_date, g.date_of_death, b.code, b.annual_fee",
+        );
+        match &filter.search {
+            Some(search) => {
+                selected.push_str(", m.key AS search_rank FROM graves g JOIN blocks b ON g.block_id = b.id");
+                selected.push_str(GRAVE_SEARCH_JOIN);
+                params.push(Box::new(self.grave_search_ids(search)?));
+            }
+            None => selected.push_str(" FROM graves g JOIN blocks b ON g.block_id = b.id"),
+        }
+        selected.push_str(" WHERE 1=1");
+        if let Some(block_id) = filter.block_id {
+            selected.push_str(" AND g.block_id = ?");
+            params.push(Box::new(block_id));
+        }
+
+        let query = format!(
+            "WITH RECURSIVE window_years(year) AS (
+                 SELECT ? UNION ALL SELECT year + 1 FROM window_years WHERE year < ?
+             ),
+             selected AS ({selected}),
+             grave_years AS (
+                 SELECT s.id AS grave_id, y.year, COALESCE(p.amount, 0) AS paid,
+                        CASE WHEN y.year < CAST(substr(COALESCE(s.burial_date, s.date_of_death), 1, 4) AS INTEGER) THEN 0
+                             ELSE MAX(s.annual_fee - COALESCE(w.amount, 0), 0) END AS due
+                 FROM selected s
+                 CROSS JOIN window_years y
+                 LEFT JOIN payments p ON p.grave_id = s.id AND p.year = y.year
+                 LEFT JOIN fee_waivers w ON w.grave_id = s.id AND w.year = y.year
+             ),
+             summaries AS (
+                 SELECT gy.grave_id,
+                        SUM(gy.paid < gy.due) AS arrears_years,
+                        MAX(CASE WHEN gy.year = (SELECT MAX(year) FROM window_years) THEN {year_status} END) AS status
+                 FROM grave_years gy
+                 GROUP BY gy.grave_id
+             )",
+            selected = selected,
+            year_status = YEAR_STATUS_SQL,
+        );
+        Ok((query, params))
+    }
+}
+
+/// WHERE clause on `summaries st` for the status and arrears filters
+fn summary_filter_sql(filter: &PaymentOverviewFilter, params: &mut Vec<Box<dyn rusqlite::ToSql>>) -> String {
+    let mut sql = String::from("WHERE 1=1");
+    if let Some(status) = filter.status {
+        sql.push_str(" AND st.status = ?");
+        params.push(Box::new(status.as_str()));
+    }
+    if let Some(min_arrears_years) = filter.min_arrears_years {
+        sql.push_str(" AND st.arrears_years >= ?");
+        params.push(Box::new(min_arrears_years));
+    }
+    sql
+}
+
+fn parse_status(status: &str) -> PaymentStatus {
+    match status {
+        "paid" => PaymentStatus::Paid,
+        "partial" => PaymentStatus::Partial,
+        _ => PaymentStatus::Unpaid,
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Fee status of a grave for one year
+///
+/// `Paid` means nothing is left to pay, which includes fully waived years
+/// and years before the burial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Paid,
+    Partial,
+    Unpaid,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Paid => "paid",
+            PaymentStatus::Partial => "partial",
+            PaymentStatus::Unpaid => "unpaid",
+        }
+    }
+}
+
+/// Filter of the payment overview
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PaymentOverviewFilter {
+    pub search: Option<String>,
+    pub block_id: Option<i64>,
+    /// Selected year; the window ends with it
+    pub year: i32,
+    /// Number of years shown (default 5)
+    #[serde(default)]
+    pub year_window: Option<i32>,
+    /// Status in the selected year
+    #[serde(default)]
+    pub status: Option<PaymentStatus>,
+    /// Only graves with at least this many unsettled years in the window
+    #[serde(default)]
+    pub min_arrears_years: Option<i64>,
+}
+
+/// Payment of one grave for one year
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct YearPaymentStatus {
+    pub year: i32,
+    /// A payment was recorded
+    pub is_paid: bool,
+    pub amount: Option<i64>,
+    /// Fee after waivers; 0 before the burial year
+    pub fee_due: i64,
+    pub status: PaymentStatus,
+}
+
+/// Grave with its payments for the payment page
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GravePaymentSummary {
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub annual_fee: i64,
+    /// Status in the selected year
+    pub payment_status: PaymentStatus,
+    /// Years of the window not fully paid
+    pub arrears_years: i64,
+    pub current_year_payment: Option<Payment>,
+    /// One entry per year of the window, oldest first
+    pub recent_payments: Vec<YearPaymentStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreatePaymentRequest, CreateWaiverRequest};
+
+    /// Paid (2020-2024 paid), partial (2023 waived, 2024 part paid) and
+    /// recent (died 2024, unpaid) graves: `(dir, db, block_id, [paid, partial, recent])`
+    fn db_with_payments() -> (tempfile::TempDir, Database, i64, [i64; 3]) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave = |name: &str, number: &str, date_of_death: &str| {
+            db.create_grave(&CreateGraveRequest {
+                deceased_name: name.to_string(),
+                date_of_death: date_of_death.to_string(),
+                ..grave_request(block_id, number)
+            })
+            .unwrap()
+        };
+        let pay = |grave_id: i64, year: i32, amount: i64| {
+            db.create_payment(&CreatePaymentRequest {
+                payment_date: format!("{}-02-01", year),
+                amount,
+                ..payment_request(grave_id, year)
+            })
+            .unwrap();
+        };
+        let paid = grave("Ahmad", "10", "2015-01-01");
+        let partial = grave("Budi", "2", "2015-01-01");
+        let recent = grave("Citra", "3", "2024-03-01");
+        for year in 2020..=2024 {
+            pay(paid, year, 100000);
+        }
+        pay(partial, 2024, 40000);
+        db.create_waiver(&CreateWaiverRequest {
+            grave_id: partial,
+            year: 2023,
+            amount: 100000,
+            reason: None,
+            approved_by: None,
+        })
+        .unwrap();
+        (dir, db, block_id, [paid, partial, recent])
+    }
+
+    fn filter(block_id: i64, status: Option<PaymentStatus>, min_arrears_years: Option<i64>) -> PaymentOverviewFilter {
+        PaymentOverviewFilter {
+            search: None,
+            block_id: Some(block_id),
+            year: 2024,
+            year_window: None,
+            status,
+            min_arrears_years,
+        }
+    }
+
+    #[test]
+    fn test_payment_summary_per_grave() {
+        let (_dir, db, block_id, [paid, partial, recent]) = db_with_payments();
+
+        let all = db.get_graves_with_payment_summary(&filter(block_id, None, None), 10, 0).unwrap();
+        let ids: Vec<i64> = all.iter().map(|s| s.grave_id).collect();
+        assert_eq!(ids, vec![partial, recent, paid]);
+        assert_eq!(all[0].payment_status, PaymentStatus::Partial);
+        assert_eq!(all[0].current_year_payment.as_ref().map(|p| p.amount), Some(40000));
+        // 2020-2022 unpaid, 2023 waived, 2024 partly paid
+        assert_eq!(all[0].arrears_years, 4);
+        assert_eq!(all[0].recent_payments.len(), 5);
+        assert_eq!(all[0].recent_payments[3].status, PaymentStatus::Paid);
+        // Buried in 2024: earlier years are not due
+        assert_eq!(all[1].recent_payments[0].fee_due, 0);
+        assert_eq!(all[1].arrears_years, 1);
+        assert_eq!(all[2].arrears_years, 0);
+    }
+
+    #[test]
+    fn test_payment_overview_filters() {
+        let (_dir, db, block_id, [_, _, recent]) = db_with_payments();
+
+        let unpaid = filter(block_id, Some(PaymentStatus::Unpaid), None);
+        let rows = db.get_graves_with_payment_summary(&unpaid, 10, 0).unwrap();
+        assert_eq!(rows.iter().map(|s| s.grave_id).collect::<Vec<_>>(), vec![recent]);
+        assert_eq!(db.count_graves_with_payment_summary(&filter(block_id, None, Some(2))).unwrap(), 1);
+        assert_eq!(db.count_graves_with_payment_summary(&filter(block_id, None, None)).unwrap(), 3);
+
+        let page = db.get_graves_with_payment_summary(&filter(block_id, None, None), 1, 1).unwrap();
+        assert_eq!(page[0].grave_id, recent);
+
+        let mut wide = filter(block_id, None, None);
+        wide.year_window = Some(10);
+        assert_eq!(db.get_graves_with_payment_summary(&wide, 10, 0).unwrap()[0].recent_payments.len(), 10);
+        wide.year_window = Some(0);
+        assert!(db.count_graves_with_payment_summary(&wide).is_err());
+    }
+}
@@ -0,0 +1,838 @@
+//! Persons shared across graves
+//!
+//! A person (heir or contact) can be responsible for several graves. Heir
+//! rows link a person to a grave with a relationship and order and keep a
+//! copy of the person's name, phone and address, which `update_person`
+//! keeps in step. Heirs are matched to a person by phone number and name
+//! together, since a family often shares one phone. Arrears of all graves
+//! of a person can be listed and paid in one go.
+
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+use super::numbering::GRAVE_NUMBER_ORDER_SQL;
+use super::phone::phone_search_digits;
+use super::{validation, CreatePaymentRequest, Database, Payment};
+
+const PERSON_COLUMNS: &str = "p.id, p.full_name, p.phone_number, p.address, p.notes,
+        (SELECT COUNT(DISTINCT h.grave_id) FROM heirs h WHERE h.person_id = p.id),
+        p.created_at, p.updated_at";
+
+impl Database {
+    /// Find persons by name or phone number
+    pub fn search_persons(&self, query: &str, limit: i64) -> Result<Vec<Person>, String> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let phone_pattern = phone_search_digits(query).map(|digits| format!("p:%{}%", digits));
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM persons p
+                 WHERE p.full_name LIKE ?1 OR p.match_key LIKE ?2
+                 ORDER BY p.full_name
+                 LIMIT ?3",
+                PERSON_COLUMNS
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let persons = stmt
+            .query_map(
+                rusqlite::params![format!("%{}%", query), phone_pattern, limit],
+                map_person,
+            )
+            .map_err(|e| format!("Failed to query persons: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect persons: {}", e))?;
+
+        Ok(persons)
+    }
+
+    /// Get person by ID
+    pub fn get_person_by_id(&self, id: i64) -> Result<Option<Person>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM persons p WHERE p.id = ?1", PERSON_COLUMNS),
+                [id],
+                map_person,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get person: {}", e))
+    }
+
+    /// Create a person not yet linked to any grave
+    pub fn create_person(&self, person: &CreatePersonRequest) -> Result<i64, String> {
+        let phone_e164 = validation::create_person(person)?;
+        let phone = person.phone_number.as_deref().map(str::trim).filter(|p| !p.is_empty());
+
+        self.conn
+            .execute(
+                "INSERT INTO persons (full_name, phone_number, phone_e164, address, notes, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                rusqlite::params![
+                    person.full_name.trim(),
+                    phone,
+                    phone_e164,
+                    person.address,
+                    person.notes,
+                    self.operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to create person: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Update a person and the copies on all heir rows linked to them
+    pub fn update_person(&self, id: i64, person: &UpdatePersonRequest) -> Result<(), String> {
+        let phone_e164 = validation::update_person(person)?;
+        let phone = person.phone_number.as_deref().map(str::trim);
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE persons SET
+                    full_name = COALESCE(?1, full_name),
+                    phone_number = COALESCE(NULLIF(?2, ''), CASE WHEN ?2 IS NULL THEN phone_number END),
+                    phone_e164 = CASE WHEN ?2 IS NULL THEN phone_e164 ELSE ?7 END,
+                    address = COALESCE(?3, address),
+                    notes = COALESCE(?4, notes),
+                    updated_by = ?5
+                 WHERE id = ?6",
+                rusqlite::params![
+                    person.full_name,
+                    phone,
+                    person.address,
+                    person.notes,
+                    self.operator,
+                    id,
+                    phone_e164,
+                ],
+            )
+            .map_err(|e| format!("Failed to update person: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Person {} not found", id));
+        }
+
+        self.conn
+            .execute(
+                "UPDATE heirs SET
+                    full_name = p.full_name,
+                    phone_number = p.phone_number,
+                    phone_e164 = p.phone_e164,
+                    address = p.address,
+                    updated_by = ?1
+                 FROM (SELECT full_name, phone_number, phone_e164, address FROM persons WHERE id = ?2) AS p
+                 WHERE heirs.person_id = ?2",
+                rusqlite::params![self.operator, id],
+            )
+            .map_err(|e| format!("Failed to update heirs of person: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit person update: {}", e))
+    }
+
+    /// Delete a person that is not heir of any grave
+    pub fn delete_person(&self, id: i64) -> Result<(), String> {
+        let person = self
+            .get_person_by_id(id)?
+            .ok_or_else(|| format!("Person {} not found", id))?;
+        if person.grave_count > 0 {
+            return Err(format!(
+                "{} is heir of {} grave(s); remove them as heir first",
+                person.full_name, person.grave_count
+            ));
+        }
+
+        self.conn
+            .execute("DELETE FROM persons WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete person: {}", e))?;
+        Ok(())
+    }
+
+    /// Get all graves a person is heir of
+    pub fn get_person_graves(&self, person_id: i64) -> Result<Vec<PersonGrave>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT h.id, g.id, g.deceased_name, b.code, g.number, h.relationship, h.order_number, h.is_primary
+                 FROM heirs h
+                 JOIN graves g ON h.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE h.person_id = ?1
+                 ORDER BY b.code, {}",
+                GRAVE_NUMBER_ORDER_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let graves = stmt
+            .query_map([person_id], |row| {
+                Ok(PersonGrave {
+                    heir_id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    deceased_name: row.get(2)?,
+                    block_code: row.get(3)?,
+                    number: row.get(4)?,
+                    relationship: row.get(5)?,
+                    order_number: row.get(6)?,
+                    is_primary: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query person graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect person graves: {}", e))?;
+
+        Ok(graves)
+    }
+
+    /// Make a person the next heir of a grave
+    ///
+    /// Returns the ID of the new heir row.
+    pub fn link_person_to_grave(&self, person_id: i64, link: &LinkPersonRequest) -> Result<i64, String> {
+        let person = self
+            .get_person_by_id(person_id)?
+            .ok_or_else(|| format!("Person {} not found", person_id))?;
+        if self.get_grave_by_id(link.grave_id)?.is_none() {
+            return Err(format!("Grave {} not found", link.grave_id));
+        }
+        let already: bool = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM heirs WHERE grave_id = ?1 AND person_id = ?2",
+                [link.grave_id, person_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to check heirs: {}", e))?;
+        if already {
+            return Err(format!("{} is already heir of this grave", person.full_name));
+        }
+
+        self.conn
+            .execute(
+                "INSERT INTO heirs (grave_id, person_id, order_number, full_name, phone_number, phone_e164, relationship, address, is_primary, created_by, updated_by)
+                 SELECT ?1, ?2, n, ?3, ?4, (SELECT phone_e164 FROM persons WHERE id = ?2), ?5, ?6, n = 1, ?7, ?7
+                 FROM (SELECT COALESCE(MAX(order_number), 0) + 1 AS n FROM heirs WHERE grave_id = ?1)",
+                rusqlite::params![
+                    link.grave_id,
+                    person_id,
+                    person.full_name,
+                    person.phone_number,
+                    link.relationship,
+                    person.address,
+                    self.operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to link person to grave: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Re-link a heir whose name, phone or address changed to the person
+    /// matching it, creating that person when there is none. A previous
+    /// person left without heirs or notes is removed.
+    pub(super) fn relink_heir_person(&self, heir_id: i64) -> Result<(), String> {
+        let previous: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT h.person_id FROM heirs h JOIN persons p ON p.id = h.person_id
+                 WHERE h.id = ?1 AND p.match_key <> h.person_key",
+                [heir_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to check heir person: {}", e))?;
+        let Some(previous) = previous else {
+            return Ok(());
+        };
+
+        self.conn
+            .execute(
+                "INSERT INTO persons (full_name, phone_number, phone_e164, address, created_by, updated_by)
+                 SELECT h.full_name, NULLIF(h.phone_number, ''), h.phone_e164, NULLIF(h.address, ''), ?2, ?2
+                 FROM heirs h
+                 WHERE h.id = ?1 AND NOT EXISTS (SELECT 1 FROM persons p WHERE p.match_key = h.person_key)",
+                rusqlite::params![heir_id, self.operator],
+            )
+            .map_err(|e| format!("Failed to create person: {}", e))?;
+        self.conn
+            .execute(
+                "UPDATE heirs SET person_id = (
+                    SELECT p.id FROM persons p WHERE p.match_key = heirs.person_key ORDER BY p.id LIMIT 1
+                 )
+                 WHERE id = ?1",
+                [heir_id],
+            )
+            .map_err(|e| format!("Failed to link heir to person: {}", e))?;
+        self.conn
+            .execute(
+                "DELETE FROM persons WHERE id = ?1 AND notes IS NULL
+                 AND NOT EXISTS (SELECT 1 FROM heirs WHERE person_id = ?1)",
+                [previous],
+            )
+            .map_err(|e| format!("Failed to remove unused person: {}", e))?;
+        Ok(())
+    }
+
+    /// Data migration 15: merge persons that share a match key
+    ///
+    /// Migration 014 grouped heirs by the phone number as typed, so the same
+    /// heir written as "0812..." and "+62 812..." became two persons until
+    /// their E.164 numbers were filled in. Their heirs move to the oldest
+    /// person of the key, which keeps the notes of the others.
+    pub(super) fn merge_persons_sharing_match_key(&self) -> Result<(), String> {
+        self.conn
+            .execute_batch(
+                "UPDATE persons SET notes = (
+                     SELECT group_concat(d.notes, char(10)) FROM persons d
+                     WHERE d.match_key = persons.match_key AND d.notes IS NOT NULL
+                 )
+                 WHERE id IN (SELECT MIN(id) FROM persons GROUP BY match_key HAVING COUNT(*) > 1);
+
+                 UPDATE heirs SET person_id = (
+                     SELECT MIN(k.id) FROM persons k
+                     WHERE k.match_key = (SELECT p.match_key FROM persons p WHERE p.id = heirs.person_id)
+                 )
+                 WHERE person_id NOT IN (SELECT MIN(id) FROM persons GROUP BY match_key);
+
+                 DELETE FROM persons WHERE id NOT IN (SELECT MIN(id) FROM persons GROUP BY match_key);",
+            )
+            .map_err(|e| format!("Failed to merge persons: {}", e))
+    }
+
+    /// Unpaid years and balance of every grave of a person
+    ///
+    /// A year is unpaid while its fee is not covered by payments and waivers
+    /// together; only the rest counts as arrears.
+    pub fn get_person_arrears(&self, person_id: i64) -> Result<PersonArrears, String> {
+        let person = self
+            .get_person_by_id(person_id)?
+            .ok_or_else(|| format!("Person {} not found", person_id))?;
+
+        let mut graves = Vec::new();
+        for link in self.get_person_graves(person_id)? {
+            let statement = self.get_grave_statement(link.grave_id)?;
+            let unpaid_years: Vec<UnpaidYear> = statement
+                .lines
+                .iter()
+                .map(|line| UnpaidYear {
+                    year: line.year,
+                    outstanding: line.fee_due - line.paid - line.waived,
+                })
+                .filter(|unpaid| unpaid.outstanding > 0)
+                .collect();
+            graves.push(GraveArrears {
+                grave_id: link.grave_id,
+                deceased_name: link.deceased_name,
+                block_code: link.block_code,
+                number: link.number,
+                annual_fee: statement.grave.annual_fee,
+                arrears: unpaid_years.iter().map(|unpaid| unpaid.outstanding).sum(),
+                unpaid_years,
+                balance: statement.balance,
+            });
+        }
+
+        Ok(PersonArrears {
+            total_arrears: graves.iter().map(|g| g.arrears).sum(),
+            total_balance: graves.iter().map(|g| g.balance).sum(),
+            person,
+            graves,
+        })
+    }
+
+    /// Pay all unpaid years of a person's graves in one transaction
+    ///
+    /// Pays every grave of the person unless `grave_ids` narrows it down.
+    /// Each year gets what is outstanding; a year already partly paid has
+    /// the rest added to its payment, which must use the same method.
+    /// Closed years are locked; see `override_pay_person_arrears`.
+    pub fn pay_person_arrears(
+        &self,
+        person_id: i64,
+        request: &CombinedPaymentRequest,
+    ) -> Result<CombinedPaymentResult, String> {
+        self.pay_arrears(person_id, request, None)
+    }
+
+    /// Pay a person's arrears including years already closed (admin
+    /// override; payments into closed years are logged)
+    pub fn override_pay_person_arrears(
+        &self,
+        person_id: i64,
+        request: &CombinedPaymentRequest,
+        reason: &str,
+    ) -> Result<CombinedPaymentResult, String> {
+        self.pay_arrears(person_id, request, Some(reason))
+    }
+
+    fn pay_arrears(
+        &self,
+        person_id: i64,
+        request: &CombinedPaymentRequest,
+        override_reason: Option<&str>,
+    ) -> Result<CombinedPaymentResult, String> {
+        validation::combined_payment(request)?;
+        let arrears = self.get_person_arrears(person_id)?;
+        if let Some(grave_ids) = &request.grave_ids {
+            if let Some(other) = grave_ids
+                .iter()
+                .find(|id| !arrears.graves.iter().any(|g| g.grave_id == **id))
+            {
+                return Err(format!("Grave {} is not one of {}'s graves", other, arrears.person.full_name));
+            }
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut result = CombinedPaymentResult {
+            payment_ids: Vec::new(),
+            graves_paid: 0,
+            total_amount: 0,
+        };
+        for grave in &arrears.graves {
+            let selected = request
+                .grave_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&grave.grave_id));
+            if !selected || grave.unpaid_years.is_empty() {
+                continue;
+            }
+            for unpaid in &grave.unpaid_years {
+                let year = unpaid.year;
+                if let Some(existing) = self.get_payment_by_grave_and_year(grave.grave_id, year)? {
+                    self.top_up_payment(&existing, unpaid.outstanding, request, override_reason)?;
+                    result.payment_ids.push(existing.id);
+                    result.total_amount += unpaid.outstanding;
+                    continue;
+                }
+                let payment = CreatePaymentRequest {
+                    grave_id: grave.grave_id,
+                    year,
+                    payment_date: request.payment_date.clone(),
+                    amount: unpaid.outstanding,
+                    payment_method: request.payment_method.clone(),
+                    payment_proof: request.payment_proof.clone(),
+                    paid_by: request
+                        .paid_by
+                        .clone()
+                        .or_else(|| Some(arrears.person.full_name.clone())),
+                    notes: request.notes.clone(),
+                };
+                let payment_id = match self.lock_override_for(year, override_reason)? {
+                    Some(reason) => self.insert_payment_override(&payment, reason)?,
+                    None => self.create_payment(&payment)?,
+                };
+                result.payment_ids.push(payment_id);
+                result.total_amount += unpaid.outstanding;
+            }
+            result.graves_paid += 1;
+        }
+        if result.payment_ids.is_empty() {
+            return Err("Nothing to pay".to_string());
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit combined payment: {}", e))?;
+
+        Ok(result)
+    }
+
+    /// Add the rest of a year's fee to the payment already made for it
+    fn top_up_payment(
+        &self,
+        payment: &Payment,
+        amount: i64,
+        request: &CombinedPaymentRequest,
+        override_reason: Option<&str>,
+    ) -> Result<(), String> {
+        // The payment keeps its method and cash session, so the money must
+        // come in the same way
+        let method = |method: Option<&str>| method.unwrap_or("cash").to_string();
+        if method(payment.payment_method.as_deref()) != method(request.payment_method.as_deref()) {
+            return Err(format!(
+                "Year {} is partly paid by {}; pay the rest the same way",
+                payment.year,
+                method(payment.payment_method.as_deref())
+            ));
+        }
+        self.check_cash_session_lock(payment.id)?;
+        if let Some(reason) = self.lock_override_for(payment.year, override_reason)? {
+            self.log_payment_override(payment.id, "update", reason)?;
+        }
+        self.conn
+            .execute(
+                "UPDATE payments SET amount = amount + ?1, updated_by = ?2 WHERE id = ?3",
+                rusqlite::params![amount, self.operator, payment.id],
+            )
+            .map_err(|e| format!("Failed to update payment: {}", e))?;
+        Ok(())
+    }
+}
+
+fn map_person(row: &rusqlite::Row) -> rusqlite::Result<Person> {
+    Ok(Person {
+        id: row.get(0)?,
+        full_name: row.get(1)?,
+        phone_number: row.get(2)?,
+        address: row.get(3)?,
+        notes: row.get(4)?,
+        grave_count: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Heir or contact, possibly responsible for several graves
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Person {
+    pub id: i64,
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub notes: Option<String>,
+    /// Number of graves the person is heir of
+    pub grave_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreatePersonRequest {
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdatePersonRequest {
+    pub full_name: Option<String>,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Grave a person is heir of
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PersonGrave {
+    pub heir_id: i64,
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub relationship: Option<String>,
+    pub order_number: i32,
+    pub is_primary: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LinkPersonRequest {
+    pub grave_id: i64,
+    pub relationship: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveArrears {
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub annual_fee: i64,
+    /// Billable years not covered by payments and waivers
+    pub unpaid_years: Vec<UnpaidYear>,
+    /// Outstanding amount of the unpaid years
+    pub arrears: i64,
+    /// Statement balance (overpaid years count against it)
+    pub balance: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct UnpaidYear {
+    pub year: i32,
+    /// Fee due less payments and waivers
+    pub outstanding: i64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PersonArrears {
+    pub person: Person,
+    pub graves: Vec<GraveArrears>,
+    pub total_arrears: i64,
+    pub total_balance: i64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CombinedPaymentRequest {
+    /// Graves to pay (None = all graves of the person)
+    pub grave_ids: Option<Vec<i64>>,
+    pub payment_date: String,
+    pub payment_method: Option<String>,
+    pub payment_proof: Option<String>,
+    /// Defaults to the person's name
+    pub paid_by: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CombinedPaymentResult {
+    pub payment_ids: Vec<i64>,
+    pub graves_paid: i64,
+    pub total_amount: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{
+        create_block, create_grave, db_with_grave, grave_request, heir_request, payment_request, test_db,
+    };
+    use crate::db::{CreateGraveRequest, CreateHeirRequest, CreateWaiverRequest, UpdateHeirRequest};
+
+    /// Heir with a phone number
+    fn heir(grave_id: i64, full_name: &str, phone: &str) -> CreateHeirRequest {
+        CreateHeirRequest {
+            phone_number: Some(phone.to_string()),
+            relationship: Some("Anak".to_string()),
+            ..heir_request(grave_id, full_name)
+        }
+    }
+
+    fn rename_person(full_name: &str) -> UpdatePersonRequest {
+        UpdatePersonRequest {
+            full_name: Some(full_name.to_string()),
+            phone_number: None,
+            address: None,
+            notes: None,
+        }
+    }
+
+    /// Budi is heir of father (died last year) and mother (died this year),
+    /// another Budi of `other`: `(dir, db, [father, mother, other])`
+    fn db_with_shared_heir() -> (tempfile::TempDir, Database, [i64; 3]) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let this_year = chrono::Datelike::year(&db.local_today().unwrap());
+        let grave = |number: &str, year: i32| {
+            db.create_grave(&CreateGraveRequest {
+                date_of_death: format!("{}-01-01", year),
+                ..grave_request(block_id, number)
+            })
+            .unwrap()
+        };
+        let (father, mother, other) = (grave("1", this_year - 1), grave("2", this_year), grave("3", this_year));
+        for (grave_id, phone) in [(father, "0812-3456-789"), (mother, "0812 3456 789"), (other, "0899-1111-222")] {
+            db.create_heir(&heir(grave_id, "Budi", phone)).unwrap();
+        }
+        (dir, db, [father, mother, other])
+    }
+
+    #[test]
+    fn test_person_shared_across_graves() {
+        let (_dir, db, [_, mother, _]) = db_with_shared_heir();
+
+        let found = db.search_persons("081234", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        let budi = &found[0];
+        assert_eq!(budi.grave_count, 2);
+        assert_eq!(db.get_person_graves(budi.id).unwrap().len(), 2);
+
+        db.update_person(budi.id, &rename_person("Budi Santoso")).unwrap();
+        assert_eq!(db.get_heirs_by_grave(mother).unwrap()[0].full_name, "Budi Santoso");
+    }
+
+    #[test]
+    fn test_person_pays_arrears_of_all_graves() {
+        let (_dir, db, [_, _, other]) = db_with_shared_heir();
+        let this_year = chrono::Datelike::year(&db.local_today().unwrap());
+        let budi = db.search_persons("081234", 10).unwrap().remove(0);
+
+        let arrears = db.get_person_arrears(budi.id).unwrap();
+        assert_eq!(arrears.total_arrears, 300000);
+
+        let paid = db
+            .pay_person_arrears(
+                budi.id,
+                &CombinedPaymentRequest {
+                    grave_ids: None,
+                    payment_date: format!("{}-02-01", this_year),
+                    payment_method: Some("transfer".to_string()),
+                    payment_proof: None,
+                    paid_by: None,
+                    notes: None,
+                },
+            )
+            .unwrap();
+        assert_eq!((paid.payment_ids.len(), paid.graves_paid, paid.total_amount), (3, 2, 300000));
+        assert_eq!(db.get_person_arrears(budi.id).unwrap().total_arrears, 0);
+        assert!(db.get_payments_by_grave(other).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_person_pays_rest_of_partly_covered_years() {
+        let (_dir, db, [father, mother, _]) = db_with_shared_heir();
+        let this_year = chrono::Datelike::year(&db.local_today().unwrap());
+        let budi = db.search_persons("081234", 10).unwrap().remove(0);
+        db.create_payment(&CreatePaymentRequest {
+            amount: 40000,
+            ..payment_request(father, this_year - 1)
+        })
+        .unwrap();
+        db.create_waiver(&CreateWaiverRequest {
+            grave_id: mother,
+            year: this_year,
+            amount: 30000,
+            reason: None,
+            approved_by: None,
+        })
+        .unwrap();
+
+        let arrears = db.get_person_arrears(budi.id).unwrap();
+        assert_eq!(arrears.total_arrears, 230000);
+        let father_arrears = arrears.graves.iter().find(|g| g.grave_id == father).unwrap();
+        assert_eq!(
+            father_arrears.unpaid_years,
+            [
+                UnpaidYear { year: this_year - 1, outstanding: 60000 },
+                UnpaidYear { year: this_year, outstanding: 100000 },
+            ]
+        );
+
+        let mut request = CombinedPaymentRequest {
+            grave_ids: None,
+            payment_date: " ".to_string(),
+            payment_method: Some("transfer".to_string()),
+            payment_proof: None,
+            paid_by: None,
+            notes: None,
+        };
+        let errors = validation::field_errors(db.pay_person_arrears(budi.id, &request));
+        assert_eq!((errors[0].field.as_str(), errors[0].code.as_str()), ("payment_date", "required"));
+
+        request.payment_date = format!("{}-02-01", this_year);
+        assert!(db.pay_person_arrears(budi.id, &request).unwrap_err().contains("partly paid by cash"));
+
+        request.payment_method = None;
+        let paid = db.pay_person_arrears(budi.id, &request).unwrap();
+        assert_eq!((paid.payment_ids.len(), paid.total_amount), (3, 230000));
+        assert_eq!(db.get_person_arrears(budi.id).unwrap().total_arrears, 0);
+        let topped_up = db.get_payment_by_grave_and_year(father, this_year - 1).unwrap().unwrap();
+        assert_eq!(topped_up.amount, 100000);
+    }
+
+    #[test]
+    fn test_family_sharing_a_phone_stays_separate_persons() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        for (order_number, name) in [(1, "Budi"), (2, "Siti")] {
+            db.create_heir(&CreateHeirRequest {
+                order_number,
+                is_primary: order_number == 1,
+                ..heir(grave_id, name, "0812-3456-789")
+            })
+            .unwrap();
+        }
+        assert_eq!(db.search_persons("081234", 10).unwrap().len(), 2);
+
+        let budi = db.search_persons("Budi", 10).unwrap().remove(0);
+        db.update_person(budi.id, &rename_person("Budi Santoso")).unwrap();
+        let names: Vec<String> = db.get_heirs_by_grave(grave_id).unwrap().into_iter().map(|h| h.full_name).collect();
+        assert_eq!(names, ["Budi Santoso", "Siti"]);
+
+        // Renaming a heir moves it to the person with that name and phone
+        let siti = db.get_heirs_by_grave(grave_id).unwrap()[1].id;
+        db.update_heir(
+            siti,
+            &UpdateHeirRequest {
+                full_name: Some("Budi Santoso".to_string()),
+                phone_number: None,
+                relationship: None,
+                address: None,
+                is_primary: None,
+            },
+        )
+        .unwrap();
+        let persons = db.search_persons("081234", 10).unwrap();
+        assert_eq!(persons.len(), 1);
+        assert_eq!(persons[0].id, budi.id);
+    }
+
+    #[test]
+    fn test_migration_groups_existing_heirs_by_phone_and_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("astana.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        for (version, sql) in crate::db::MIGRATIONS.iter().take_while(|(version, _)| *version < 14) {
+            conn.execute_batch(sql).unwrap();
+            conn.pragma_update(None, "user_version", version).unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO blocks (code) VALUES ('A');
+             INSERT INTO graves (deceased_name, block_id, number, date_of_death)
+             VALUES ('Ayah', 1, '1', '2020-01-01'), ('Ibu', 1, '2', '2021-01-01');
+             INSERT INTO heirs (grave_id, order_number, full_name, phone_number)
+             VALUES (1, 1, 'Budi', '0812-3456-789'), (2, 1, 'budi ', '+62 812 3456 789'), (2, 2, 'Siti', '0812-3456-789');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::init_with_path(path).unwrap();
+        let mut persons: Vec<(String, i64)> = db
+            .search_persons("081234", 10)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.full_name, p.grave_count))
+            .collect();
+        persons.sort();
+        assert_eq!(persons, [("Budi".to_string(), 2), ("Siti".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_persons_split_by_typed_phone_are_merged() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let heir_ids: Vec<i64> = ["1", "2"]
+            .iter()
+            .map(|number| {
+                let grave_id = create_grave(&db, block_id, number);
+                db.create_heir(&heir(grave_id, "Budi", "0812-3456-789")).unwrap()
+            })
+            .collect();
+        let person_of = |heir_id: i64| -> i64 {
+            db.conn
+                .query_row("SELECT person_id FROM heirs WHERE id = ?1", [heir_id], |row| row.get(0))
+                .unwrap()
+        };
+        let kept = person_of(heir_ids[0]);
+
+        // As migration 014 left it: the second heir on a person of its own
+        db.conn
+            .execute(
+                "INSERT INTO persons (full_name, phone_number, phone_e164, notes)
+                 VALUES ('Budi', '+62 812 3456 789', '+628123456789', 'Kontak lama')",
+                [],
+            )
+            .unwrap();
+        let duplicate = db.conn.last_insert_rowid();
+        db.conn
+            .execute("UPDATE heirs SET person_id = ?1 WHERE id = ?2", [duplicate, heir_ids[1]])
+            .unwrap();
+
+        db.merge_persons_sharing_match_key().unwrap();
+        for heir_id in heir_ids {
+            assert_eq!(person_of(heir_id), kept);
+        }
+        assert!(db.get_person_by_id(duplicate).unwrap().is_none());
+        let person = db.get_person_by_id(kept).unwrap().unwrap();
+        assert_eq!(person.grave_count, 2);
+        assert_eq!(person.notes.as_deref(), Some("Kontak lama"));
+    }
+}
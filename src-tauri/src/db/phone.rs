@@ -0,0 +1,198 @@
+//! Indonesian phone numbers
+//!
+//! Phone numbers keep the form they were typed in for display. Next to it
+//! the canonical E.164 form (`+628123456789`) is stored for search, person
+//! matching and reminders. Numbers written before validation existed were
+//! normalized once by migration 15; those it could not parse are listed in
+//! `phone_number_issues` until the heir's number is corrected.
+
+use chrono::{DateTime, Utc};
+
+use super::numbering::GRAVE_NUMBER_ORDER_SQL;
+use super::Database;
+
+impl Database {
+    /// Heirs whose stored phone number could not be normalized
+    pub fn get_phone_number_issues(&self) -> Result<Vec<PhoneNumberIssue>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT i.id, i.heir_id, h.grave_id, g.deceased_name, b.code, g.number, h.full_name, i.phone_number, i.error, i.created_at
+                 FROM phone_number_issues i
+                 JOIN heirs h ON i.heir_id = h.id
+                 JOIN graves g ON h.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 ORDER BY b.code, {}, h.order_number",
+                GRAVE_NUMBER_ORDER_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let issues = stmt
+            .query_map([], |row| {
+                Ok(PhoneNumberIssue {
+                    id: row.get(0)?,
+                    heir_id: row.get(1)?,
+                    grave_id: row.get(2)?,
+                    deceased_name: row.get(3)?,
+                    block_code: row.get(4)?,
+                    number: row.get(5)?,
+                    heir_name: row.get(6)?,
+                    phone_number: row.get(7)?,
+                    error: row.get(8)?,
+                    created_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query phone number issues: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect phone number issues: {}", e))?;
+
+        Ok(issues)
+    }
+
+    /// Data migration 15: fill `phone_e164` of existing heirs and persons
+    ///
+    /// Heir numbers that cannot be parsed are recorded as issues.
+    pub(super) fn normalize_stored_phone_numbers(&self) -> Result<(), String> {
+        for table in ["heirs", "persons"] {
+            let mut stmt = self
+                .conn
+                .prepare(&format!(
+                    "SELECT id, phone_number FROM {} WHERE trim(COALESCE(phone_number, '')) <> ''",
+                    table
+                ))
+                .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            let rows: Vec<(i64, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| format!("Failed to query phone numbers: {}", e))?
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to collect phone numbers: {}", e))?;
+
+            for (id, phone) in rows {
+                match normalize_phone(&phone) {
+                    Ok(e164) => {
+                        self.conn
+                            .execute(
+                                &format!("UPDATE {} SET phone_e164 = ?1 WHERE id = ?2", table),
+                                rusqlite::params![e164, id],
+                            )
+                            .map_err(|e| format!("Failed to store normalized phone number: {}", e))?;
+                    }
+                    Err(error) if table == "heirs" => {
+                        self.conn
+                            .execute(
+                                "INSERT INTO phone_number_issues (heir_id, phone_number, error) VALUES (?1, ?2, ?3)",
+                                rusqlite::params![id, phone, error],
+                            )
+                            .map_err(|e| format!("Failed to record phone number issue: {}", e))?;
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Normalize an Indonesian phone number to E.164 (`+62...`)
+///
+/// Accepts national (`0812...`) and international (`62812...`, `+62 812...`)
+/// forms with spaces, dashes, dots or parentheses. Mobile numbers start with
+/// 8 after the country code, landlines with an area code starting with 2-7
+/// or 9. Blank input yields `None`.
+pub fn normalize_phone(input: &str) -> Result<Option<String>, String> {
+    let compact: String = input
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')' | '/'))
+        .collect();
+    if compact.is_empty() {
+        return Ok(None);
+    }
+
+    let (plus, digits) = match compact.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, compact.as_str()),
+    };
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Phone number {} contains invalid characters", input.trim()));
+    }
+
+    let national = if let Some(rest) = digits.strip_prefix("62") {
+        rest
+    } else if plus {
+        return Err(format!("Phone number {} is not an Indonesian (+62) number", input.trim()));
+    } else if let Some(rest) = digits.strip_prefix("0062") {
+        rest
+    } else if let Some(rest) = digits.strip_prefix('0') {
+        rest
+    } else {
+        return Err(format!("Phone number {} must start with 0, 62 or +62", input.trim()));
+    };
+    // "+62 0812..." is a common way of writing it
+    let national = national.strip_prefix('0').unwrap_or(national);
+
+    let valid = match national.chars().next() {
+        Some('8') => (9..=12).contains(&national.len()),
+        Some('2'..='7' | '9') => (8..=11).contains(&national.len()),
+        _ => false,
+    };
+    if !valid {
+        return Err(format!("Phone number {} is not a valid Indonesian mobile or landline number", input.trim()));
+    }
+
+    Ok(Some(format!("+62{}", national)))
+}
+
+/// Digits to look for in a stored E.164 number when searching for `query`
+///
+/// Drops the country code or trunk prefix, so `0812` and `62812` both find
+/// `+62812...`.
+pub(super) fn phone_search_digits(query: &str) -> Option<String> {
+    let digits: String = query.chars().filter(|c| c.is_ascii_digit()).collect();
+    let national = digits
+        .strip_prefix("62")
+        .or_else(|| digits.strip_prefix('0'))
+        .unwrap_or(&digits);
+    if national.is_empty() {
+        None
+    } else {
+        Some(national.to_string())
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Heir phone number that could not be normalized
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PhoneNumberIssue {
+    pub id: i64,
+    pub heir_id: i64,
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub heir_name: String,
+    pub phone_number: String,
+    pub error: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_phone() {
+        for input in ["0812-3456-789", "+62 812 3456 789", "628123456789", "(0812) 3456.789", "+62 0812 3456 789"] {
+            assert_eq!(normalize_phone(input).unwrap().as_deref(), Some("+628123456789"), "{}", input);
+        }
+        assert_eq!(normalize_phone("(021) 555-1234").unwrap().as_deref(), Some("+62215551234"));
+        assert_eq!(normalize_phone("0274 512345").unwrap().as_deref(), Some("+62274512345"));
+        assert_eq!(normalize_phone("  ").unwrap(), None);
+
+        for input in ["0899", "+1 555 123 4567", "812345678", "0812-ABC", "0112345678"] {
+            assert!(normalize_phone(input).is_err(), "{}", input);
+        }
+        assert_eq!(phone_search_digits("0812 34").as_deref(), Some("81234"));
+    }
+}
@@ -0,0 +1,563 @@
+//! Plot layout and map per block
+//!
+//! A block can have a grid of rows x columns, some of which are unusable.
+//! Graves are placed in a cell; the map lists every cell with its state and
+//! the payment status of the grave in it for a given year.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rusqlite::OptionalExtension;
+
+use super::numbering::GRAVE_NUMBER_ORDER_SQL;
+use super::reservations::ACTIVE_RESERVATION_SQL;
+use super::Database;
+
+/// Largest accepted number of rows or columns
+const MAX_GRID_SIZE: i64 = 500;
+
+impl Database {
+    /// Get the layout of a block (None if the block has no grid)
+    pub fn get_block_layout(&self, block_id: i64) -> Result<Option<BlockLayout>, String> {
+        let size: Option<(Option<i64>, Option<i64>)> = self
+            .conn
+            .query_row(
+                "SELECT layout_rows, layout_columns FROM blocks WHERE id = ?1",
+                [block_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get block layout: {}", e))?;
+
+        let (rows, columns) = match size {
+            Some((Some(rows), Some(columns))) => (rows, columns),
+            Some(_) => return Ok(None),
+            None => return Err(format!("Block {} not found", block_id)),
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT plot_row, plot_column, notes FROM block_unusable_cells
+                 WHERE block_id = ?1 ORDER BY plot_row, plot_column",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let unusable_cells = stmt
+            .query_map([block_id], |row| {
+                Ok(UnusableCell {
+                    row: row.get(0)?,
+                    column: row.get(1)?,
+                    notes: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query unusable cells: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect unusable cells: {}", e))?;
+
+        Ok(Some(BlockLayout {
+            rows,
+            columns,
+            unusable_cells,
+        }))
+    }
+
+    /// Set the grid of a block
+    ///
+    /// Fails if a grave already placed would fall outside the grid or on an
+    /// unusable cell.
+    pub fn set_block_layout(&self, block_id: i64, layout: &BlockLayout) -> Result<(), String> {
+        if !(1..=MAX_GRID_SIZE).contains(&layout.rows) || !(1..=MAX_GRID_SIZE).contains(&layout.columns) {
+            return Err(format!(
+                "Rows and columns must be between 1 and {}",
+                MAX_GRID_SIZE
+            ));
+        }
+        for cell in &layout.unusable_cells {
+            if !layout.contains(cell.position()) {
+                return Err(format!(
+                    "Unusable cell {} is outside the {}x{} grid",
+                    cell.position(),
+                    layout.rows,
+                    layout.columns
+                ));
+            }
+        }
+
+        for (number, position) in self.placed_graves(block_id)? {
+            if !layout.contains(position) {
+                return Err(format!(
+                    "Grave {} at {} would be outside the {}x{} grid",
+                    number, position, layout.rows, layout.columns
+                ));
+            }
+            if layout.unusable_cells.iter().any(|c| c.position() == position) {
+                return Err(format!("Grave {} occupies cell {}", number, position));
+            }
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE blocks SET layout_rows = ?1, layout_columns = ?2, updated_by = ?3 WHERE id = ?4",
+                rusqlite::params![layout.rows, layout.columns, self.operator, block_id],
+            )
+            .map_err(|e| format!("Failed to update block layout: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Block {} not found", block_id));
+        }
+
+        self.conn
+            .execute("DELETE FROM block_unusable_cells WHERE block_id = ?1", [block_id])
+            .map_err(|e| format!("Failed to clear unusable cells: {}", e))?;
+        for cell in &layout.unusable_cells {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO block_unusable_cells (block_id, plot_row, plot_column, notes) VALUES (?1, ?2, ?3, ?4)",
+                    rusqlite::params![block_id, cell.row, cell.column, cell.notes],
+                )
+                .map_err(|e| format!("Failed to save unusable cell: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit block layout: {}", e))
+    }
+
+    /// Get the position of a grave (None if not placed)
+    pub fn get_grave_position(&self, grave_id: i64) -> Result<Option<PlotPosition>, String> {
+        let position: Option<(Option<i64>, Option<i64>)> = self
+            .conn
+            .query_row(
+                "SELECT plot_row, plot_column FROM graves WHERE id = ?1",
+                [grave_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get grave position: {}", e))?;
+
+        match position {
+            Some((Some(row), Some(column))) => Ok(Some(PlotPosition { row, column })),
+            Some(_) => Ok(None),
+            None => Err(format!("Grave {} not found", grave_id)),
+        }
+    }
+
+    /// Place a grave in a cell of its block, or remove it from the map (None)
+    pub fn set_grave_position(&self, grave_id: i64, position: Option<PlotPosition>) -> Result<(), String> {
+        let block_id: i64 = self
+            .conn
+            .query_row("SELECT block_id FROM graves WHERE id = ?1", [grave_id], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to get grave: {}", e))?
+            .ok_or_else(|| format!("Grave {} not found", grave_id))?;
+
+        if let Some(position) = position {
+            self.validate_grave_position(block_id, position, Some(grave_id))?;
+        }
+
+        self.conn
+            .execute(
+                "UPDATE graves SET plot_row = ?1, plot_column = ?2, updated_by = ?3 WHERE id = ?4",
+                rusqlite::params![
+                    position.map(|p| p.row),
+                    position.map(|p| p.column),
+                    self.operator,
+                    grave_id
+                ],
+            )
+            .map_err(|e| format!("Failed to set grave position: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Check that a cell exists, is usable and is not taken by another grave
+    ///
+    /// `grave_id` is the grave being placed, if it already exists.
+    pub fn validate_grave_position(
+        &self,
+        block_id: i64,
+        position: PlotPosition,
+        grave_id: Option<i64>,
+    ) -> Result<(), String> {
+        let layout = self
+            .get_block_layout(block_id)?
+            .ok_or_else(|| "Block has no plot layout".to_string())?;
+        if !layout.contains(position) {
+            return Err(format!(
+                "Cell {} is outside the {}x{} grid",
+                position, layout.rows, layout.columns
+            ));
+        }
+        if layout.unusable_cells.iter().any(|c| c.position() == position) {
+            return Err(format!("Cell {} is not usable", position));
+        }
+
+        let occupant: Option<(i64, String)> = self
+            .conn
+            .query_row(
+                "SELECT id, number FROM graves WHERE block_id = ?1 AND plot_row = ?2 AND plot_column = ?3",
+                rusqlite::params![block_id, position.row, position.column],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to check cell: {}", e))?;
+        if let Some((id, number)) = occupant {
+            if Some(id) != grave_id {
+                return Err(format!("Cell {} is already taken by grave {}", position, number));
+            }
+        }
+
+        if let Some(reservation) = self.reservation_at(block_id, position)? {
+            return Err(format!("Cell {} is reserved by {}", position, reservation.reserver_name));
+        }
+        Ok(())
+    }
+
+    /// Get the plot map of a block with payment status for `year`
+    pub fn get_plot_map(&self, block_id: i64, year: i32) -> Result<PlotMap, String> {
+        let block = self
+            .get_block_by_id(block_id)?
+            .ok_or_else(|| format!("Block {} not found", block_id))?;
+        let layout = self
+            .get_block_layout(block_id)?
+            .ok_or_else(|| format!("Block {} has no plot layout", block.code))?;
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT g.id, g.deceased_name, g.number, g.plot_row, g.plot_column,
+                        EXISTS (SELECT 1 FROM payments p WHERE p.grave_id = g.id AND p.year = ?2),
+                        EXISTS (SELECT 1 FROM fee_waivers w WHERE w.grave_id = g.id AND w.year = ?2),
+                        CAST(substr(COALESCE(NULLIF(g.burial_date, ''), g.date_of_death), 1, 4) AS INTEGER) > ?2
+                 FROM graves g
+                 WHERE g.block_id = ?1
+                 ORDER BY {}",
+                GRAVE_NUMBER_ORDER_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let graves = stmt
+            .query_map(rusqlite::params![block_id, year], |row| {
+                let (paid, waived, not_due): (bool, bool, bool) = (row.get(5)?, row.get(6)?, row.get(7)?);
+                let payment_status = if paid {
+                    PlotPaymentStatus::Paid
+                } else if waived {
+                    PlotPaymentStatus::Waived
+                } else if not_due {
+                    PlotPaymentStatus::NotDue
+                } else {
+                    PlotPaymentStatus::Unpaid
+                };
+                let position = match (row.get(3)?, row.get(4)?) {
+                    (Some(row), Some(column)) => Some(PlotPosition { row, column }),
+                    _ => None,
+                };
+                Ok((
+                    position,
+                    PlotGrave {
+                        grave_id: row.get(0)?,
+                        deceased_name: row.get(1)?,
+                        number: row.get(2)?,
+                        payment_status,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to query graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        let mut placed: HashMap<PlotPosition, PlotGrave> = HashMap::new();
+        let mut unplaced_graves = Vec::new();
+        for (position, grave) in graves {
+            match position {
+                Some(position) => {
+                    placed.insert(position, grave);
+                }
+                None => unplaced_graves.push(grave),
+            }
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT r.id, r.plot_row, r.plot_column, r.reserved_for, r.reserver_name, r.reserved_until
+                 FROM reservations r
+                 WHERE r.block_id = ?1 AND r.plot_row IS NOT NULL AND {}",
+                ACTIVE_RESERVATION_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut reserved: HashMap<PlotPosition, PlotReservation> = stmt
+            .query_map([block_id], |row| {
+                Ok((
+                    PlotPosition {
+                        row: row.get(1)?,
+                        column: row.get(2)?,
+                    },
+                    PlotReservation {
+                        reservation_id: row.get(0)?,
+                        reserved_for: row.get(3)?,
+                        reserver_name: row.get(4)?,
+                        reserved_until: row.get(5)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to query reservations: {}", e))?
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to collect reservations: {}", e))?;
+
+        let mut cells = Vec::with_capacity((layout.rows * layout.columns) as usize);
+        for row in 1..=layout.rows {
+            for column in 1..=layout.columns {
+                let position = PlotPosition { row, column };
+                let grave = placed.remove(&position);
+                let reservation = reserved.remove(&position);
+                let state = if grave.is_some() {
+                    PlotCellState::Occupied
+                } else if reservation.is_some() {
+                    PlotCellState::Reserved
+                } else if layout.unusable_cells.iter().any(|c| c.position() == position) {
+                    PlotCellState::Unusable
+                } else {
+                    PlotCellState::Free
+                };
+                cells.push(PlotCell {
+                    row,
+                    column,
+                    state,
+                    grave,
+                    reservation,
+                });
+            }
+        }
+
+        let count = |state: PlotCellState| cells.iter().filter(|c| c.state == state).count() as i64;
+        Ok(PlotMap {
+            block_id,
+            block_code: block.code,
+            year,
+            rows: layout.rows,
+            columns: layout.columns,
+            free_count: count(PlotCellState::Free),
+            occupied_count: count(PlotCellState::Occupied),
+            reserved_count: count(PlotCellState::Reserved),
+            unusable_count: count(PlotCellState::Unusable),
+            cells,
+            unplaced_graves,
+        })
+    }
+
+    /// Numbers and positions of the graves placed in a block
+    fn placed_graves(&self, block_id: i64) -> Result<Vec<(String, PlotPosition)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT number, plot_row, plot_column FROM graves
+                 WHERE block_id = ?1 AND plot_row IS NOT NULL AND plot_column IS NOT NULL",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let graves = stmt
+            .query_map([block_id], |row| {
+                Ok((
+                    row.get(0)?,
+                    PlotPosition {
+                        row: row.get(1)?,
+                        column: row.get(2)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to query placed graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect placed graves: {}", e))?;
+
+        Ok(graves)
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Cell position in a block grid (1-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct PlotPosition {
+    pub row: i64,
+    pub column: i64,
+}
+
+impl std::fmt::Display for PlotPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.row, self.column)
+    }
+}
+
+/// Grid cell that cannot hold a grave
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UnusableCell {
+    pub row: i64,
+    pub column: i64,
+    pub notes: Option<String>,
+}
+
+impl UnusableCell {
+    fn position(&self) -> PlotPosition {
+        PlotPosition {
+            row: self.row,
+            column: self.column,
+        }
+    }
+}
+
+/// Grid layout of a block
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockLayout {
+    pub rows: i64,
+    pub columns: i64,
+    #[serde(default)]
+    pub unusable_cells: Vec<UnusableCell>,
+}
+
+impl BlockLayout {
+    fn contains(&self, position: PlotPosition) -> bool {
+        (1..=self.rows).contains(&position.row) && (1..=self.columns).contains(&position.column)
+    }
+}
+
+/// State of a cell on the plot map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotCellState {
+    Free,
+    Occupied,
+    /// Held for a living person
+    Reserved,
+    Unusable,
+}
+
+/// Fee status of a grave for the map year
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlotPaymentStatus {
+    Paid,
+    Waived,
+    Unpaid,
+    /// Buried after the map year
+    NotDue,
+}
+
+/// Grave shown on the plot map
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlotGrave {
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub number: String,
+    pub payment_status: PlotPaymentStatus,
+}
+
+/// Reservation shown on the plot map
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlotReservation {
+    pub reservation_id: i64,
+    pub reserved_for: Option<String>,
+    pub reserver_name: String,
+    pub reserved_until: Option<NaiveDate>,
+}
+
+/// One cell of the plot map
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlotCell {
+    pub row: i64,
+    pub column: i64,
+    pub state: PlotCellState,
+    pub grave: Option<PlotGrave>,
+    pub reservation: Option<PlotReservation>,
+}
+
+/// Plot map of a block (cells in row-major order)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlotMap {
+    pub block_id: i64,
+    pub block_code: String,
+    pub year: i32,
+    pub rows: i64,
+    pub columns: i64,
+    pub free_count: i64,
+    pub occupied_count: i64,
+    pub reserved_count: i64,
+    pub unusable_count: i64,
+    pub cells: Vec<PlotCell>,
+    /// Graves of the block that have no position yet
+    pub unplaced_graves: Vec<PlotGrave>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, create_grave, payment_request, test_db};
+    use crate::db::CreateBlockRequest;
+
+    fn cell(row: i64, column: i64) -> PlotPosition {
+        PlotPosition { row, column }
+    }
+
+    /// Block "A" of 2 x 3 cells with an unusable cell (2, 3) and two graves:
+    /// `(dir, db, block_id, first, second)`
+    fn db_with_layout() -> (tempfile::TempDir, Database, i64, i64, i64) {
+        let (dir, db) = test_db();
+        let block_id = db
+            .create_block(&CreateBlockRequest {
+                total_capacity: 6,
+                ..block_request("A")
+            })
+            .unwrap();
+        let (first, second) = (create_grave(&db, block_id, "1"), create_grave(&db, block_id, "2"));
+        assert!(db.set_grave_position(first, Some(cell(1, 1))).is_err());
+        db.set_block_layout(
+            block_id,
+            &BlockLayout {
+                rows: 2,
+                columns: 3,
+                unusable_cells: vec![UnusableCell {
+                    row: 2,
+                    column: 3,
+                    notes: Some("Pohon".to_string()),
+                }],
+            },
+        )
+        .unwrap();
+        db.set_grave_position(first, Some(cell(1, 1))).unwrap();
+        (dir, db, block_id, first, second)
+    }
+
+    #[test]
+    fn test_grave_position_validation() {
+        let (_dir, db, block_id, _, second) = db_with_layout();
+        assert!(db.set_grave_position(second, Some(cell(1, 1))).is_err());
+        assert!(db.set_grave_position(second, Some(cell(2, 3))).is_err());
+        assert!(db.set_grave_position(second, Some(cell(3, 1))).is_err());
+
+        // Shrinking would leave the placed grave outside the layout
+        let shrunk = BlockLayout {
+            rows: 1,
+            columns: 1,
+            unusable_cells: vec![UnusableCell { row: 1, column: 1, notes: None }],
+        };
+        assert!(db.set_block_layout(block_id, &shrunk).is_err());
+    }
+
+    #[test]
+    fn test_plot_map_cells_and_payment_status() {
+        let (_dir, db, block_id, first, _) = db_with_layout();
+        db.create_payment(&payment_request(first, 2025)).unwrap();
+
+        let map = db.get_plot_map(block_id, 2025).unwrap();
+        assert_eq!(map.cells.len(), 6);
+        assert_eq!((map.free_count, map.occupied_count, map.unusable_count), (4, 1, 1));
+        let occupied = map.cells[0].grave.as_ref().unwrap();
+        assert_eq!(occupied.payment_status, PlotPaymentStatus::Paid);
+        assert_eq!(map.unplaced_graves.len(), 1);
+        assert_eq!(map.unplaced_graves[0].payment_status, PlotPaymentStatus::Unpaid);
+    }
+}
@@ -0,0 +1,271 @@
+//! Grave relocation and its history
+//!
+//! Moving a grave to another block, number or plot cell is recorded with the
+//! old and new location, so the history survives. Payments, heirs and
+//! interments stay attached to the grave.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::capacity::require_override_reason;
+use super::plot_map::PlotPosition;
+use super::{validation, Database};
+
+impl Database {
+    /// Move a grave to another block, number or plot cell
+    ///
+    /// A different block must be active and have a free plot. Returns the ID
+    /// of the relocation record.
+    pub fn relocate_grave(&self, grave_id: i64, request: &RelocateGraveRequest) -> Result<i64, String> {
+        self.move_grave(grave_id, request, None)
+    }
+
+    /// Move a grave into a full or inactive block (admin override, logged)
+    pub fn override_relocate_grave(
+        &self,
+        grave_id: i64,
+        request: &RelocateGraveRequest,
+        reason: &str,
+    ) -> Result<i64, String> {
+        let reason = require_override_reason(reason)?;
+        self.move_grave(grave_id, request, Some(reason))
+    }
+
+    fn move_grave(
+        &self,
+        grave_id: i64,
+        request: &RelocateGraveRequest,
+        override_reason: Option<&str>,
+    ) -> Result<i64, String> {
+        validation::relocate_grave(request)?;
+        let relocation_date = NaiveDate::parse_from_str(&request.relocation_date, "%Y-%m-%d")
+            .map_err(|_| "Relocation date must be a date (YYYY-MM-DD)".to_string())?;
+
+        let grave = self
+            .get_grave_by_id(grave_id)?
+            .ok_or_else(|| format!("Grave {} not found", grave_id))?;
+        self.check_grave_dates_confirmed(grave_id)?;
+        if self.get_block_by_id(request.to_block_id)?.is_none() {
+            return Err(format!("Block {} not found", request.to_block_id));
+        }
+        let to_number = self.normalized_grave_number(request.to_block_id, &request.to_number)?;
+        let from_position = self.get_grave_position(grave_id)?;
+
+        if request.to_block_id == grave.block_id
+            && to_number == grave.number
+            && request.to_position == from_position
+        {
+            return Err("The new location is the same as the current one".to_string());
+        }
+
+        self.check_number_free(request.to_block_id, &to_number, Some(grave_id))?;
+        self.check_number_not_reserved(request.to_block_id, &to_number)?;
+        if let Some(position) = request.to_position {
+            self.validate_grave_position(request.to_block_id, position, Some(grave_id))?;
+        }
+        let violation = if request.to_block_id == grave.block_id {
+            None
+        } else if override_reason.is_none() {
+            self.check_block_capacity(request.to_block_id)?;
+            None
+        } else {
+            self.block_capacity_violation(request.to_block_id)?
+        };
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        self.conn
+            .execute(
+                "INSERT INTO grave_relocations (grave_id, from_block_id, from_number, from_plot_row, from_plot_column,
+                    to_block_id, to_number, to_plot_row, to_plot_column, relocation_date, reason, authorized_by, recorded_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                rusqlite::params![
+                    grave_id,
+                    grave.block_id,
+                    grave.number,
+                    from_position.map(|p| p.row),
+                    from_position.map(|p| p.column),
+                    request.to_block_id,
+                    to_number,
+                    request.to_position.map(|p| p.row),
+                    request.to_position.map(|p| p.column),
+                    relocation_date,
+                    request.reason.trim(),
+                    request.authorized_by.trim(),
+                    self.operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to record relocation: {}", e))?;
+        let relocation_id = self.conn.last_insert_rowid();
+
+        self.conn
+            .execute(
+                "UPDATE graves SET block_id = ?1, number = ?2, plot_row = ?3, plot_column = ?4, updated_by = ?5 WHERE id = ?6",
+                rusqlite::params![
+                    request.to_block_id,
+                    to_number,
+                    request.to_position.map(|p| p.row),
+                    request.to_position.map(|p| p.column),
+                    self.operator,
+                    grave_id,
+                ],
+            )
+            .map_err(|e| format!("Failed to move grave: {}", e))?;
+        if let (Some(violation), Some(reason)) = (violation, override_reason) {
+            self.log_capacity_override(request.to_block_id, grave_id, "relocate", violation, reason)?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit relocation: {}", e))?;
+
+        Ok(relocation_id)
+    }
+
+    /// Get relocation history of a grave, oldest first
+    pub fn get_grave_relocations(&self, grave_id: i64) -> Result<Vec<GraveRelocation>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT r.id, r.grave_id, r.from_block_id, bf.code, r.from_number, r.from_plot_row, r.from_plot_column,
+                        r.to_block_id, bt.code, r.to_number, r.to_plot_row, r.to_plot_column,
+                        r.relocation_date, r.reason, r.authorized_by, r.recorded_by, u.full_name, r.created_at
+                 FROM grave_relocations r
+                 JOIN blocks bf ON r.from_block_id = bf.id
+                 JOIN blocks bt ON r.to_block_id = bt.id
+                 LEFT JOIN users u ON r.recorded_by = u.id
+                 WHERE r.grave_id = ?1
+                 ORDER BY r.relocation_date, r.id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let position = |row: Option<i64>, column: Option<i64>| match (row, column) {
+            (Some(row), Some(column)) => Some(PlotPosition { row, column }),
+            _ => None,
+        };
+        let relocations = stmt
+            .query_map([grave_id], |row| {
+                Ok(GraveRelocation {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    from_block_id: row.get(2)?,
+                    from_block_code: row.get(3)?,
+                    from_number: row.get(4)?,
+                    from_position: position(row.get(5)?, row.get(6)?),
+                    to_block_id: row.get(7)?,
+                    to_block_code: row.get(8)?,
+                    to_number: row.get(9)?,
+                    to_position: position(row.get(10)?, row.get(11)?),
+                    relocation_date: row.get(12)?,
+                    reason: row.get(13)?,
+                    authorized_by: row.get(14)?,
+                    recorded_by: row.get(15)?,
+                    recorded_by_name: row.get(16)?,
+                    created_at: row.get(17)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query relocations: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect relocations: {}", e))?;
+
+        Ok(relocations)
+    }
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// One move of a grave
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveRelocation {
+    pub id: i64,
+    pub grave_id: i64,
+    pub from_block_id: i64,
+    pub from_block_code: String,
+    pub from_number: String,
+    pub from_position: Option<PlotPosition>,
+    pub to_block_id: i64,
+    pub to_block_code: String,
+    pub to_number: String,
+    pub to_position: Option<PlotPosition>,
+    pub relocation_date: NaiveDate,
+    pub reason: String,
+    pub authorized_by: String,
+    pub recorded_by: Option<i64>,
+    pub recorded_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RelocateGraveRequest {
+    pub to_block_id: i64,
+    pub to_number: String,
+    /// Cell on the target block map (None = not placed)
+    pub to_position: Option<PlotPosition>,
+    pub relocation_date: String,
+    pub reason: String,
+    pub authorized_by: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, create_grave, db_with_grave, payment_request};
+    use crate::db::UpdateGraveRequest;
+
+    fn moved(to_block_id: i64, number: &str) -> RelocateGraveRequest {
+        RelocateGraveRequest {
+            to_block_id,
+            to_number: number.to_string(),
+            to_position: None,
+            relocation_date: "2026-02-01".to_string(),
+            reason: "Pelebaran jalan".to_string(),
+            authorized_by: "H. Usman".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_relocation_keeps_history_and_payments() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        let block_b = create_block(&db, "B");
+        create_grave(&db, block_b, "7");
+        db.create_payment(&payment_request(grave_id, 2024)).unwrap();
+
+        assert!(db.relocate_grave(grave_id, &moved(block_b, "7")).is_err());
+        db.relocate_grave(grave_id, &moved(block_b, "8")).unwrap();
+
+        let grave = db.get_grave_by_id(grave_id).unwrap().unwrap();
+        assert_eq!((grave.block_id, grave.number.as_str()), (block_b, "8"));
+        assert_eq!(db.get_payments_by_grave(grave_id).unwrap().len(), 1);
+
+        let history = db.get_grave_relocations(grave_id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from_block_code, "A");
+        assert_eq!(history[0].from_number, "1");
+        assert_eq!(history[0].to_block_code, "B");
+    }
+
+    #[test]
+    fn test_relocation_input_and_renumbering() {
+        let (_dir, db, block_id, grave_id) = db_with_grave();
+
+        let request = RelocateGraveRequest {
+            reason: " ".to_string(),
+            ..moved(block_id, "2")
+        };
+        let errors = validation::field_errors(db.relocate_grave(grave_id, &request));
+        assert_eq!(errors[0].field, "reason");
+        assert!(db.relocate_grave(grave_id, &moved(block_id, "1")).is_err());
+
+        // Renumbering goes through relocation only
+        let renumber = UpdateGraveRequest {
+            deceased_name: None,
+            block_id: None,
+            number: Some("9".to_string()),
+            date_of_death: None,
+            burial_date: None,
+            notes: None,
+        };
+        assert!(db.update_grave(grave_id, &renumber).is_err());
+    }
+}
@@ -0,0 +1,481 @@
+//! Plot reservations for living people
+//!
+//! A reservation holds a grave number and/or a cell of the block map. Active
+//! reservations count against block availability. When the burial happens the
+//! reservation is converted into a grave with the reserver as primary heir.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::OptionalExtension;
+
+use super::numbering::grave_number_key;
+use super::plot_map::PlotPosition;
+use super::{validation, CreateGraveRequest, CreateHeirRequest, Database};
+
+/// Reservation status with date expiry applied
+const STATUS_SQL: &str = "CASE WHEN r.status = 'active' AND r.reserved_until < (SELECT date(now) FROM local_clock)
+        THEN 'expired' ELSE r.status END";
+
+/// Reservations that still hold their plot
+pub(super) const ACTIVE_RESERVATION_SQL: &str = "r.status = 'active'
+        AND (r.reserved_until IS NULL OR r.reserved_until >= (SELECT date(now) FROM local_clock))";
+
+const RESERVATION_COLUMNS: &str = "r.id, r.block_id, b.code, r.grave_number, r.plot_row, r.plot_column,
+        r.reserved_for, r.reserver_name, r.phone_number, r.address, r.relationship, r.reserved_until,
+        r.deposit_amount, r.grave_id, r.notes, r.created_at, r.updated_at";
+
+impl Database {
+    /// Get reservations, optionally filtered by block and status
+    pub fn get_reservations(
+        &self,
+        block_id: Option<i64>,
+        status: Option<&str>,
+    ) -> Result<Vec<Reservation>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {}, {} FROM reservations r
+                 JOIN blocks b ON r.block_id = b.id
+                 WHERE (?1 IS NULL OR r.block_id = ?1) AND (?2 IS NULL OR {} = ?2)
+                 ORDER BY b.code, r.created_at DESC",
+                RESERVATION_COLUMNS, STATUS_SQL, STATUS_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let reservations = stmt
+            .query_map(rusqlite::params![block_id, status], map_reservation)
+            .map_err(|e| format!("Failed to query reservations: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect reservations: {}", e))?;
+
+        Ok(reservations)
+    }
+
+    /// Get reservation by ID
+    pub fn get_reservation_by_id(&self, id: i64) -> Result<Option<Reservation>, String> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {}, {} FROM reservations r JOIN blocks b ON r.block_id = b.id WHERE r.id = ?1",
+                    RESERVATION_COLUMNS, STATUS_SQL
+                ),
+                [id],
+                map_reservation,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get reservation: {}", e))
+    }
+
+    /// Create a reservation for a grave number and/or a plot cell
+    pub fn create_reservation(&self, reservation: &CreateReservationRequest) -> Result<i64, String> {
+        validation::create_reservation(reservation)?;
+        let number = match reservation.grave_number.as_deref().map(str::trim) {
+            Some(number) if !number.is_empty() => Some(self.normalized_grave_number(reservation.block_id, number)?),
+            _ => None,
+        };
+        if number.is_none() && reservation.position.is_none() {
+            return Err("A grave number or plot position is required".to_string());
+        }
+
+        self.expire_reservations()?;
+
+        if let Some(number) = &number {
+            self.check_number_free(reservation.block_id, number, None)?;
+            self.check_number_not_reserved(reservation.block_id, number)?;
+        }
+        if let Some(position) = reservation.position {
+            self.validate_grave_position(reservation.block_id, position, None)?;
+        }
+        self.conn
+            .execute(
+                "INSERT INTO reservations (block_id, grave_number, plot_row, plot_column, reserved_for, reserver_name,
+                    phone_number, address, relationship, reserved_until, deposit_amount, notes, created_by, updated_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?13)",
+                rusqlite::params![
+                    reservation.block_id,
+                    number,
+                    reservation.position.map(|p| p.row),
+                    reservation.position.map(|p| p.column),
+                    reservation.reserved_for,
+                    reservation.reserver_name.trim(),
+                    reservation.phone_number,
+                    reservation.address,
+                    reservation.relationship,
+                    validation::non_empty(reservation.reserved_until.as_deref()),
+                    reservation.deposit_amount,
+                    reservation.notes,
+                    self.operator,
+                ],
+            )
+            .map_err(|e| format!("Failed to create reservation: {}", e))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Update contact, expiry, deposit or notes of an active reservation
+    pub fn update_reservation(&self, id: i64, reservation: &UpdateReservationRequest) -> Result<(), String> {
+        self.require_active_reservation(id)?;
+        validation::update_reservation(reservation)?;
+
+        self.conn
+            .execute(
+                "UPDATE reservations SET
+                    reserved_for = COALESCE(?1, reserved_for),
+                    reserver_name = COALESCE(?2, reserver_name),
+                    phone_number = COALESCE(?3, phone_number),
+                    address = COALESCE(?4, address),
+                    relationship = COALESCE(?5, relationship),
+                    reserved_until = COALESCE(?6, reserved_until),
+                    deposit_amount = COALESCE(?7, deposit_amount),
+                    notes = COALESCE(?8, notes),
+                    updated_by = ?9
+                 WHERE id = ?10",
+                rusqlite::params![
+                    reservation.reserved_for,
+                    reservation.reserver_name,
+                    reservation.phone_number,
+                    reservation.address,
+                    reservation.relationship,
+                    validation::non_empty(reservation.reserved_until.as_deref()),
+                    reservation.deposit_amount,
+                    reservation.notes,
+                    self.operator,
+                    id,
+                ],
+            )
+            .map_err(|e| format!("Failed to update reservation: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Cancel an active reservation
+    pub fn cancel_reservation(&self, id: i64, notes: Option<&str>) -> Result<(), String> {
+        self.require_active_reservation(id)?;
+        self.conn
+            .execute(
+                "UPDATE reservations SET status = 'cancelled', notes = COALESCE(?1, notes), updated_by = ?2 WHERE id = ?3",
+                rusqlite::params![notes, self.operator, id],
+            )
+            .map_err(|e| format!("Failed to cancel reservation: {}", e))?;
+        Ok(())
+    }
+
+    /// Convert a reservation into a grave when the burial happens
+    ///
+    /// The grave takes the reserved number and cell; the reserver becomes the
+    /// primary heir. Returns the new grave ID.
+    pub fn convert_reservation(&self, id: i64, request: &ConvertReservationRequest) -> Result<i64, String> {
+        let reservation = self.require_active_reservation(id)?;
+
+        let deceased_name = request
+            .deceased_name
+            .clone()
+            .or_else(|| reservation.reserved_for.clone())
+            .unwrap_or_default();
+        let number = request
+            .number
+            .clone()
+            .or_else(|| reservation.grave_number.clone())
+            .unwrap_or_default();
+        validation::convert_reservation(&deceased_name, &number)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        // Release the reservation first so the grave may take its number and cell
+        self.conn
+            .execute(
+                "UPDATE reservations SET status = 'converted', updated_by = ?1 WHERE id = ?2",
+                rusqlite::params![self.operator, id],
+            )
+            .map_err(|e| format!("Failed to convert reservation: {}", e))?;
+
+        let grave_id = self.create_grave(&CreateGraveRequest {
+            deceased_name,
+            block_id: reservation.block_id,
+            number,
+            date_of_death: request.date_of_death.clone(),
+            burial_date: request.burial_date.clone(),
+            notes: request.notes.clone(),
+        })?;
+        if reservation.position.is_some() {
+            self.set_grave_position(grave_id, reservation.position)?;
+        }
+        self.create_heir(&CreateHeirRequest {
+            grave_id,
+            order_number: 1,
+            full_name: reservation.reserver_name.clone(),
+            phone_number: reservation.phone_number.clone(),
+            relationship: reservation.relationship.clone(),
+            address: reservation.address.clone(),
+            is_primary: true,
+        })?;
+
+        self.conn
+            .execute("UPDATE reservations SET grave_id = ?1 WHERE id = ?2", rusqlite::params![grave_id, id])
+            .map_err(|e| format!("Failed to link reservation to grave: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit reservation conversion: {}", e))?;
+
+        Ok(grave_id)
+    }
+
+    /// Number of active reservations in a block
+    pub(super) fn count_active_reservations(&self, block_id: i64) -> Result<i64, String> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM reservations r WHERE r.block_id = ?1 AND {}",
+                    ACTIVE_RESERVATION_SQL
+                ),
+                [block_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count reservations: {}", e))
+    }
+
+    /// Active reservation holding a cell, if any
+    pub(super) fn reservation_at(&self, block_id: i64, position: PlotPosition) -> Result<Option<Reservation>, String> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {}, {} FROM reservations r JOIN blocks b ON r.block_id = b.id
+                     WHERE r.block_id = ?1 AND r.plot_row = ?2 AND r.plot_column = ?3 AND {}",
+                    RESERVATION_COLUMNS, STATUS_SQL, ACTIVE_RESERVATION_SQL
+                ),
+                rusqlite::params![block_id, position.row, position.column],
+                map_reservation,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to check reservation: {}", e))
+    }
+
+    /// Fail if an active reservation holds a grave number (in any padding)
+    pub(super) fn check_number_not_reserved(&self, block_id: i64, number: &str) -> Result<(), String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT r.grave_number, r.reserver_name FROM reservations r
+                 WHERE r.block_id = ?1 AND r.grave_number IS NOT NULL AND {}",
+                ACTIVE_RESERVATION_SQL
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let reserved = stmt
+            .query_map([block_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to check reservation: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to check reservation: {}", e))?;
+
+        let key = grave_number_key(number);
+        match reserved.iter().find(|(reserved_number, _)| grave_number_key(reserved_number) == key) {
+            Some((_, name)) => Err(format!("Grave number {} is reserved by {}", number, name)),
+            None => Ok(()),
+        }
+    }
+
+    fn require_active_reservation(&self, id: i64) -> Result<Reservation, String> {
+        let reservation = self
+            .get_reservation_by_id(id)?
+            .ok_or_else(|| format!("Reservation {} not found", id))?;
+        if reservation.status != "active" {
+            return Err(format!("Reservation {} is {}", id, reservation.status));
+        }
+        Ok(reservation)
+    }
+
+    /// Mark reservations past their date as expired
+    fn expire_reservations(&self) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE reservations SET status = 'expired'
+                 WHERE status = 'active' AND reserved_until < (SELECT date(now) FROM local_clock)",
+                [],
+            )
+            .map_err(|e| format!("Failed to expire reservations: {}", e))?;
+        Ok(())
+    }
+}
+
+fn map_reservation(row: &rusqlite::Row) -> rusqlite::Result<Reservation> {
+    let position = match (row.get(4)?, row.get(5)?) {
+        (Some(row), Some(column)) => Some(PlotPosition { row, column }),
+        _ => None,
+    };
+    Ok(Reservation {
+        id: row.get(0)?,
+        block_id: row.get(1)?,
+        block_code: row.get(2)?,
+        grave_number: row.get(3)?,
+        position,
+        reserved_for: row.get(6)?,
+        reserver_name: row.get(7)?,
+        phone_number: row.get(8)?,
+        address: row.get(9)?,
+        relationship: row.get(10)?,
+        reserved_until: row.get(11)?,
+        deposit_amount: row.get(12)?,
+        grave_id: row.get(13)?,
+        notes: row.get(14)?,
+        created_at: row.get(15)?,
+        updated_at: row.get(16)?,
+        status: row.get(17)?,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Plot reservation
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Reservation {
+    pub id: i64,
+    pub block_id: i64,
+    pub block_code: String,
+    pub grave_number: Option<String>,
+    pub position: Option<PlotPosition>,
+    pub reserved_for: Option<String>,
+    pub reserver_name: String,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub relationship: Option<String>,
+    pub reserved_until: Option<NaiveDate>,
+    pub deposit_amount: i64,
+    /// active, converted, cancelled, expired
+    pub status: String,
+    /// Grave created from this reservation
+    pub grave_id: Option<i64>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreateReservationRequest {
+    pub block_id: i64,
+    pub grave_number: Option<String>,
+    pub position: Option<PlotPosition>,
+    pub reserved_for: Option<String>,
+    pub reserver_name: String,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub relationship: Option<String>,
+    pub reserved_until: Option<String>,
+    #[serde(default)]
+    pub deposit_amount: i64,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdateReservationRequest {
+    pub reserved_for: Option<String>,
+    pub reserver_name: Option<String>,
+    pub phone_number: Option<String>,
+    pub address: Option<String>,
+    pub relationship: Option<String>,
+    pub reserved_until: Option<String>,
+    pub deposit_amount: Option<i64>,
+    pub notes: Option<String>,
+}
+
+/// Burial data for converting a reservation into a grave
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConvertReservationRequest {
+    /// Defaults to the reservation's `reserved_for`
+    pub deceased_name: Option<String>,
+    /// Defaults to the reserved grave number
+    pub number: Option<String>,
+    pub date_of_death: String,
+    pub burial_date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, grave_request, test_db};
+    use crate::db::{BlockLayout, CreateBlockRequest, PlotCellState};
+
+    const CELL: PlotPosition = PlotPosition { row: 1, column: 2 };
+
+    /// Block "A" of 2 x 2 cells with number 2 and cell (1, 2) reserved for
+    /// Siti Aminah: `(dir, db, block_id, reservation_id)`
+    fn db_with_reservation() -> (tempfile::TempDir, Database, i64, i64) {
+        let (dir, db) = test_db();
+        let block_id = db
+            .create_block(&CreateBlockRequest {
+                total_capacity: 4,
+                ..block_request("A")
+            })
+            .unwrap();
+        db.set_block_layout(block_id, &BlockLayout { rows: 2, columns: 2, unusable_cells: vec![] })
+            .unwrap();
+        let reservation_id = db
+            .create_reservation(&CreateReservationRequest {
+                block_id,
+                grave_number: Some("2".to_string()),
+                position: Some(CELL),
+                reserved_for: Some("Siti Aminah".to_string()),
+                reserver_name: "Budi".to_string(),
+                phone_number: Some("08123456789".to_string()),
+                address: None,
+                relationship: Some("Anak".to_string()),
+                reserved_until: None,
+                deposit_amount: 500000,
+                notes: None,
+            })
+            .unwrap();
+        (dir, db, block_id, reservation_id)
+    }
+
+    fn convert() -> ConvertReservationRequest {
+        ConvertReservationRequest {
+            deceased_name: None,
+            number: None,
+            date_of_death: "2026-03-01".to_string(),
+            burial_date: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_reservation_holds_number_and_cell() {
+        let (_dir, db, block_id, _) = db_with_reservation();
+        assert_eq!(db.get_block_stats(block_id).unwrap().available, 3);
+        let map = db.get_plot_map(block_id, 2026).unwrap();
+        assert_eq!(map.cells[1].state, PlotCellState::Reserved);
+        assert!(db.validate_grave_position(block_id, CELL, None).is_err());
+        assert!(db.create_grave(&grave_request(block_id, "2")).is_err());
+        assert!(db.create_grave(&grave_request(block_id, "02")).is_err());
+    }
+
+    #[test]
+    fn test_reservation_converts_to_grave() {
+        let (_dir, db, block_id, reservation_id) = db_with_reservation();
+        let grave_id = db.convert_reservation(reservation_id, &convert()).unwrap();
+
+        let grave = db.get_grave_by_id(grave_id).unwrap().unwrap();
+        assert_eq!(grave.deceased_name, "Siti Aminah");
+        assert_eq!(db.get_grave_position(grave_id).unwrap(), Some(CELL));
+        let heirs = db.get_heirs_by_grave(grave_id).unwrap();
+        assert_eq!(heirs[0].full_name, "Budi");
+        assert!(heirs[0].is_primary);
+
+        let reservation = db.get_reservation_by_id(reservation_id).unwrap().unwrap();
+        assert_eq!(reservation.status, "converted");
+        assert_eq!(reservation.grave_id, Some(grave_id));
+        assert_eq!(db.get_block_stats(block_id).unwrap().available, 3);
+    }
+
+    #[test]
+    fn test_conversion_needs_deceased_name() {
+        let (_dir, db, _, reservation_id) = db_with_reservation();
+        let request = ConvertReservationRequest {
+            deceased_name: Some(" ".to_string()),
+            ..convert()
+        };
+        let errors = validation::field_errors(db.convert_reservation(reservation_id, &request));
+        assert_eq!(errors[0].field, "deceased_name");
+        assert_eq!(db.get_reservation_by_id(reservation_id).unwrap().unwrap().status, "active");
+    }
+}
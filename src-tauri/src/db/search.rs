@@ -0,0 +1,273 @@
+//! Full-text search across graves, heirs and payments
+//!
+//! Backed by the FTS5 indexes of migration 17, which triggers keep in step
+//! with the tables. Every word of the query is matched as a prefix, results
+//! are ranked by relevance per group and come with an HTML snippet in which
+//! the matched words are wrapped in `<mark>`.
+
+use chrono::NaiveDate;
+
+use super::Database;
+
+/// Markers put around matches by `snippet()`, replaced after escaping
+const MATCH_START: &str = "\u{2}";
+const MATCH_END: &str = "\u{3}";
+
+/// Words of context around a match in a snippet
+const SNIPPET_WORDS: i64 = 12;
+
+impl Database {
+    /// Search graves, heirs and payments, at most `limit` results per group
+    pub fn global_search(&self, query: &str, limit: i64) -> Result<GlobalSearchResults, String> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(GlobalSearchResults::default());
+        };
+        let snippet = |table: &str| {
+            format!(
+                "snippet({}, -1, '{}', '{}', '…', {})",
+                table, MATCH_START, MATCH_END, SNIPPET_WORDS
+            )
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT g.id, g.deceased_name, b.code, g.number, {}
+                 FROM graves_fts f
+                 JOIN graves g ON g.id = f.rowid
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE graves_fts MATCH ?1
+                 ORDER BY f.rank
+                 LIMIT ?2",
+                snippet("graves_fts")
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let graves = stmt
+            .query_map(rusqlite::params![fts_query, limit], |row| {
+                Ok(GraveSearchHit {
+                    grave_id: row.get(0)?,
+                    deceased_name: row.get(1)?,
+                    block_code: row.get(2)?,
+                    number: row.get(3)?,
+                    snippet: highlight(&row.get::<_, String>(4)?),
+                })
+            })
+            .map_err(|e| format!("Failed to search graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT h.id, h.grave_id, h.full_name, h.phone_number, g.deceased_name, b.code, g.number, {}
+                 FROM heirs_fts f
+                 JOIN heirs h ON h.id = f.rowid
+                 JOIN graves g ON h.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE heirs_fts MATCH ?1
+                 ORDER BY f.rank
+                 LIMIT ?2",
+                snippet("heirs_fts")
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let heirs = stmt
+            .query_map(rusqlite::params![fts_query, limit], |row| {
+                Ok(HeirSearchHit {
+                    heir_id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    full_name: row.get(2)?,
+                    phone_number: row.get(3)?,
+                    deceased_name: row.get(4)?,
+                    block_code: row.get(5)?,
+                    number: row.get(6)?,
+                    snippet: highlight(&row.get::<_, String>(7)?),
+                })
+            })
+            .map_err(|e| format!("Failed to search heirs: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect heirs: {}", e))?;
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT p.id, p.grave_id, p.year, p.payment_date, p.amount, g.deceased_name, b.code, g.number, {}
+                 FROM payments_fts f
+                 JOIN payments p ON p.id = f.rowid
+                 JOIN graves g ON p.grave_id = g.id
+                 JOIN blocks b ON g.block_id = b.id
+                 WHERE payments_fts MATCH ?1
+                 ORDER BY f.rank
+                 LIMIT ?2",
+                snippet("payments_fts")
+            ))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let payments = stmt
+            .query_map(rusqlite::params![fts_query, limit], |row| {
+                Ok(PaymentSearchHit {
+                    payment_id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    year: row.get(2)?,
+                    payment_date: row.get(3)?,
+                    amount: row.get(4)?,
+                    deceased_name: row.get(5)?,
+                    block_code: row.get(6)?,
+                    number: row.get(7)?,
+                    snippet: highlight(&row.get::<_, String>(8)?),
+                })
+            })
+            .map_err(|e| format!("Failed to search payments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect payments: {}", e))?;
+
+        Ok(GlobalSearchResults {
+            graves,
+            heirs,
+            payments,
+        })
+    }
+}
+
+/// Turn user input into an FTS5 query: every word as a quoted prefix
+///
+/// Punctuation is dropped, so FTS5 syntax in the input has no effect and
+/// "0812-3456" searches for the digits as one number.
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Escape a snippet for HTML and turn the match markers into `<mark>`
+fn highlight(snippet: &str) -> String {
+    snippet
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GlobalSearchResults {
+    pub graves: Vec<GraveSearchHit>,
+    pub heirs: Vec<HeirSearchHit>,
+    pub payments: Vec<PaymentSearchHit>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveSearchHit {
+    pub grave_id: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    /// HTML with matches in `<mark>`
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HeirSearchHit {
+    pub heir_id: i64,
+    pub grave_id: i64,
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    /// HTML with matches in `<mark>`
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PaymentSearchHit {
+    pub payment_id: i64,
+    pub grave_id: i64,
+    pub year: i32,
+    pub payment_date: NaiveDate,
+    pub amount: i64,
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    /// HTML with matches in `<mark>`
+    pub snippet: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, heir_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreateHeirRequest, CreatePaymentRequest, UpdateHeirRequest};
+
+    /// Grave and payment mentioning "kamboja", heir Siti Rahayu at Jl.
+    /// Kenanga 5: `(dir, db, grave_id, heir_id)`
+    fn db_with_records() -> (tempfile::TempDir, Database, i64, i64) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                deceased_name: "Ahmad Fauzi".to_string(),
+                notes: Some("Dekat pohon kamboja <besar>".to_string()),
+                ..grave_request(block_id, "12")
+            })
+            .unwrap();
+        let heir_id = db
+            .create_heir(&CreateHeirRequest {
+                phone_number: Some("0812-3456-789".to_string()),
+                address: Some("Jl. Kenanga 5".to_string()),
+                ..heir_request(grave_id, "Siti Rahayu")
+            })
+            .unwrap();
+        db.create_payment(&CreatePaymentRequest {
+            paid_by: Some("Budi".to_string()),
+            notes: Some("Titipan lewat kamboja".to_string()),
+            ..payment_request(grave_id, 2024)
+        })
+        .unwrap();
+        (dir, db, grave_id, heir_id)
+    }
+
+    #[test]
+    fn test_global_search_across_records() {
+        let (_dir, db, _, heir_id) = db_with_records();
+
+        let found = db.global_search("kamboja", 10).unwrap();
+        assert_eq!(found.graves.len(), 1);
+        assert_eq!(found.graves[0].snippet, "Dekat pohon <mark>kamboja</mark> &lt;besar&gt;");
+        assert_eq!(found.payments.len(), 1);
+
+        assert_eq!(db.global_search("08123456", 10).unwrap().heirs[0].heir_id, heir_id);
+        assert_eq!(db.global_search("kenang", 10).unwrap().heirs.len(), 1);
+    }
+
+    #[test]
+    fn test_global_search_follows_changes() {
+        let (_dir, db, grave_id, heir_id) = db_with_records();
+
+        db.update_heir(
+            heir_id,
+            &UpdateHeirRequest {
+                full_name: None,
+                phone_number: None,
+                relationship: None,
+                address: Some("Jl. Melati 2".to_string()),
+                is_primary: None,
+            },
+        )
+        .unwrap();
+        assert!(db.global_search("kenanga", 10).unwrap().heirs.is_empty());
+
+        db.delete_grave(grave_id).unwrap();
+        let found = db.global_search("kamboja \"OR", 10).unwrap();
+        assert!(found.graves.is_empty() && found.payments.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! Statement of account per grave
+//!
+//! Lists every year from the first billable year up to today with the fee
+//! due, payments, waivers and the running balance.
+
+use chrono::{Datelike, NaiveDate};
+
+use super::{Database, FeeWaiver, GraveExportData, GraveWithBlock, Heir, Payment};
+
+impl Database {
+    /// Get statement of account for a grave
+    pub fn get_grave_statement(&self, grave_id: i64) -> Result<GraveStatement, String> {
+        let grave = self
+            .get_grave_by_id(grave_id)?
+            .ok_or_else(|| format!("Grave {} not found", grave_id))?;
+        let date_unconfirmed = self.unconfirmed_date_of_death(grave_id)?.is_some();
+        let heirs = self.get_heirs_by_grave(grave_id)?;
+        let payments = self.get_payments_by_grave(grave_id)?;
+        let waivers = self.get_waivers_by_grave(grave_id)?;
+
+        let now = self.local_now()?;
+        let current_year = now.year();
+        let first_billable_year = first_billable_year(grave.date_of_death, grave.burial_date);
+
+        // Payments or waivers recorded outside the billable range still belong on the statement
+        let start_year = payments
+            .iter()
+            .map(|p| p.year)
+            .chain(waivers.iter().map(|w| w.year))
+            .chain(std::iter::once(first_billable_year))
+            .min()
+            .unwrap_or(first_billable_year);
+        let end_year = payments
+            .iter()
+            .map(|p| p.year)
+            .chain(waivers.iter().map(|w| w.year))
+            .chain(std::iter::once(current_year))
+            .max()
+            .unwrap_or(current_year);
+
+        let lines = build_statement_lines(
+            first_billable_year,
+            start_year,
+            end_year,
+            grave.annual_fee,
+            &payments,
+            &waivers,
+        );
+
+        let total_due: i64 = lines.iter().map(|l| l.fee_due).sum();
+        let total_paid: i64 = lines.iter().map(|l| l.paid).sum();
+        let total_waived: i64 = lines.iter().map(|l| l.waived).sum();
+
+        Ok(GraveStatement {
+            grave,
+            heirs,
+            first_billable_year,
+            start_year,
+            end_year,
+            generated_at: now.format("%Y-%m-%d %H:%M").to_string(),
+            date_unconfirmed,
+            total_due,
+            total_paid,
+            total_waived,
+            balance: total_due - total_paid - total_waived,
+            lines,
+        })
+    }
+}
+
+/// First year the annual fee is due: the burial year, or the year of death
+/// when the burial date is unknown
+fn first_billable_year(date_of_death: NaiveDate, burial_date: Option<NaiveDate>) -> i32 {
+    burial_date.unwrap_or(date_of_death).year()
+}
+
+impl GraveExportData {
+    /// Fee still open for a year by the statement rule: due from the first
+    /// billable year up to and including `current_year`, less payments and
+    /// waivers
+    pub fn open_fee(&self, year: i32, current_year: i32) -> i64 {
+        if year < first_billable_year(self.date_of_death, self.burial_date) || year > current_year {
+            return 0;
+        }
+        let paid: i64 = self.payments.iter().filter(|p| p.year == year).map(|p| p.amount).sum();
+        let waived: i64 = self.waivers.iter().filter(|w| w.year == year).map(|w| w.amount).sum();
+        (self.annual_fee - paid - waived).max(0)
+    }
+}
+
+/// Build one statement line per year with a running balance
+///
+/// Years before `first_billable_year` carry no fee; they only appear when a
+/// payment or waiver was recorded for them.
+fn build_statement_lines(
+    first_billable_year: i32,
+    start_year: i32,
+    end_year: i32,
+    annual_fee: i64,
+    payments: &[Payment],
+    waivers: &[FeeWaiver],
+) -> Vec<StatementLine> {
+    let mut balance = 0;
+    let mut lines = Vec::new();
+
+    for year in start_year..=end_year {
+        let fee_due = if year >= first_billable_year { annual_fee } else { 0 };
+
+        let year_payments: Vec<StatementPayment> = payments
+            .iter()
+            .filter(|p| p.year == year)
+            .map(|p| StatementPayment {
+                payment_id: p.id,
+                payment_date: p.payment_date,
+                amount: p.amount,
+                payment_method: p.payment_method.clone(),
+                receipt_number: receipt_number(p),
+            })
+            .collect();
+        let paid: i64 = year_payments.iter().map(|p| p.amount).sum();
+
+        let waiver = waivers.iter().find(|w| w.year == year);
+        let waived = waiver.map(|w| w.amount).unwrap_or(0);
+
+        balance += fee_due - paid - waived;
+
+        lines.push(StatementLine {
+            year,
+            fee_due,
+            payments: year_payments,
+            paid,
+            waived,
+            waiver_reason: waiver.and_then(|w| w.reason.clone()).filter(|r| !r.is_empty()),
+            balance,
+        });
+    }
+
+    lines
+}
+
+/// Receipt number printed on payment receipts (KW/<year>/<payment id>)
+pub fn receipt_number(payment: &Payment) -> String {
+    format!("KW/{}/{:06}", payment.year, payment.id)
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Statement of account for a single grave
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveStatement {
+    pub grave: GraveWithBlock,
+    pub heirs: Vec<Heir>,
+    pub first_billable_year: i32,
+    pub start_year: i32,
+    pub end_year: i32,
+    pub generated_at: String,
+    /// The date of death, and with it the first billable year, replaces an
+    /// unreadable date that is not confirmed yet
+    pub date_unconfirmed: bool,
+    pub total_due: i64,
+    pub total_paid: i64,
+    pub total_waived: i64,
+    /// Positive = arrears, negative = credit
+    pub balance: i64,
+    pub lines: Vec<StatementLine>,
+}
+
+/// Statement line for one year
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StatementLine {
+    pub year: i32,
+    pub fee_due: i64,
+    pub payments: Vec<StatementPayment>,
+    pub paid: i64,
+    pub waived: i64,
+    pub waiver_reason: Option<String>,
+    /// Running balance up to and including this year
+    pub balance: i64,
+}
+
+/// Payment as shown on the statement
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StatementPayment {
+    pub payment_id: i64,
+    pub payment_date: NaiveDate,
+    pub amount: i64,
+    pub payment_method: Option<String>,
+    pub receipt_number: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreatePaymentRequest, CreateWaiverRequest};
+
+    #[test]
+    fn test_grave_statement_running_balance() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                date_of_death: "2022-03-10".to_string(),
+                ..grave_request(block_id, "1")
+            })
+            .unwrap();
+        db.create_payment(&CreatePaymentRequest {
+            payment_date: "2022-04-01".to_string(),
+            ..payment_request(grave_id, 2022)
+        })
+        .unwrap();
+        db.create_waiver(&CreateWaiverRequest {
+            grave_id,
+            year: 2023,
+            amount: 100000,
+            reason: Some("Keluarga tidak mampu".to_string()),
+            approved_by: None,
+        })
+        .unwrap();
+
+        let statement = db.get_grave_statement(grave_id).unwrap();
+        let current_year = db.local_today().unwrap().year();
+
+        assert_eq!(statement.first_billable_year, 2022);
+        assert_eq!(statement.start_year, 2022);
+        assert_eq!(statement.end_year, current_year);
+        assert_eq!(statement.lines[0].balance, 0);
+        assert_eq!(statement.lines[0].payments[0].receipt_number, format!("KW/2022/{:06}", 1));
+        assert_eq!(statement.lines[1].waived, 100000);
+        assert_eq!(statement.lines[1].balance, 0);
+
+        let unpaid_years = (current_year - 2023) as i64;
+        assert_eq!(statement.balance, unpaid_years * 100000);
+        assert_eq!(statement.lines.last().unwrap().balance, statement.balance);
+    }
+}
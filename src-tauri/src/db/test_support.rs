@@ -0,0 +1,79 @@
+//! Fixtures shared by the database tests
+
+use tempfile::TempDir;
+
+use super::{CreateBlockRequest, CreateGraveRequest, CreateHeirRequest, CreatePaymentRequest, Database};
+
+/// Fresh database in a temporary directory; the directory must outlive
+/// the database
+pub(crate) fn test_db() -> (TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::init_with_path(dir.path().join("astana.db")).unwrap();
+    (dir, db)
+}
+
+/// Database with block "A" and grave "1" in it: `(dir, db, block_id, grave_id)`
+pub(crate) fn db_with_grave() -> (TempDir, Database, i64, i64) {
+    let (dir, db) = test_db();
+    let block_id = create_block(&db, "A");
+    let grave_id = create_grave(&db, block_id, "1");
+    (dir, db, block_id, grave_id)
+}
+
+/// Active block with room for 10 graves and an annual fee of Rp 100.000
+pub(crate) fn block_request(code: &str) -> CreateBlockRequest {
+    CreateBlockRequest {
+        code: code.to_string(),
+        description: None,
+        total_capacity: 10,
+        annual_fee: 100000,
+        status: "active".to_string(),
+    }
+}
+
+pub(crate) fn create_block(db: &Database, code: &str) -> i64 {
+    db.create_block(&block_request(code)).unwrap()
+}
+
+/// Grave "Almarhum <number>" who died on 1 January 2020
+pub(crate) fn grave_request(block_id: i64, number: &str) -> CreateGraveRequest {
+    CreateGraveRequest {
+        deceased_name: format!("Almarhum {}", number),
+        block_id,
+        number: number.to_string(),
+        date_of_death: "2020-01-01".to_string(),
+        burial_date: None,
+        notes: None,
+    }
+}
+
+pub(crate) fn create_grave(db: &Database, block_id: i64, number: &str) -> i64 {
+    db.create_grave(&grave_request(block_id, number)).unwrap()
+}
+
+/// Primary heir without contact details
+pub(crate) fn heir_request(grave_id: i64, full_name: &str) -> CreateHeirRequest {
+    CreateHeirRequest {
+        grave_id,
+        order_number: 1,
+        full_name: full_name.to_string(),
+        phone_number: None,
+        relationship: None,
+        address: None,
+        is_primary: true,
+    }
+}
+
+/// Rp 100.000 for `year`, paid on 10 January of that year
+pub(crate) fn payment_request(grave_id: i64, year: i32) -> CreatePaymentRequest {
+    CreatePaymentRequest {
+        grave_id,
+        year,
+        payment_date: format!("{}-01-10", year),
+        amount: 100000,
+        payment_method: None,
+        payment_proof: None,
+        paid_by: None,
+        notes: None,
+    }
+}
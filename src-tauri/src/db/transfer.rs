@@ -0,0 +1,1084 @@
+//! Full JSON data export/import for moving data between installations
+//!
+//! The export is a single versioned JSON document with a manifest, so it
+//! can be read by other tools and loaded into newer app versions. Records
+//! are matched by natural keys (block code, block + grave number, heir
+//! order, payment year) instead of database IDs.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::OptionalExtension;
+
+use super::dates::{loose_date, loose_optional_date, parse_loose_date, TIMEZONES};
+use super::phone::normalize_phone;
+use super::capacity::{require_override_reason, CapacityViolation};
+use super::{
+    validation, Block, CreateBlockRequest, CreateGraveRequest, CreateHeirRequest, CreateIntermentRequest,
+    CreatePaymentRequest, CreateWaiverRequest, Database, FeeWaiver, Grave, Heir, Interment, Payment, Settings,
+};
+
+/// Format identifier written to the manifest
+pub const EXPORT_FORMAT: &str = "astana-export";
+
+/// Current export format version; bump on incompatible changes
+///
+/// Version 2 writes timestamps as RFC 3339 UTC and requires ISO dates;
+/// version 1 documents are upgraded when read.
+pub const EXPORT_FORMAT_VERSION: i32 = 2;
+
+/// Record lists of the export document
+const RECORD_LISTS: &[&str] = &["blocks", "graves", "heirs", "payments", "fee_waivers", "interments"];
+
+/// Calendar date fields of exported records
+const DATE_FIELDS: &[&str] = &["date_of_death", "burial_date", "payment_date"];
+
+/// Timestamp fields of exported records and settings
+const TIMESTAMP_FIELDS: &[&str] = &["created_at", "updated_at", "last_backup"];
+
+impl Database {
+    /// Export all data as a versioned document
+    pub fn export_data(&self) -> Result<DataExport, String> {
+        let settings = self.get_settings()?;
+        let blocks = self.read_blocks("main")?;
+        let graves = self.read_graves("main")?;
+        let heirs = self.read_heirs("main")?;
+        let payments = self.read_payments("main")?;
+        let fee_waivers = self.read_waivers("main")?;
+        let interments = self.read_interments("main")?;
+
+        let schema_version: i32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+        Ok(DataExport {
+            manifest: ExportManifest {
+                format: EXPORT_FORMAT.to_string(),
+                format_version: EXPORT_FORMAT_VERSION,
+                schema_version,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                exported_at: chrono::Local::now().to_rfc3339(),
+                foundation_name: settings.foundation_name.clone(),
+                counts: ExportCounts {
+                    blocks: blocks.len() as i64,
+                    graves: graves.len() as i64,
+                    heirs: heirs.len() as i64,
+                    payments: payments.len() as i64,
+                    fee_waivers: fee_waivers.len() as i64,
+                    interments: interments.len() as i64,
+                },
+            },
+            settings: Some(settings),
+            blocks,
+            graves,
+            heirs,
+            payments,
+            fee_waivers,
+            interments,
+        })
+    }
+
+    /// Import an exported document
+    ///
+    /// Runs in one transaction. With `dry_run` the transaction is rolled back
+    /// after counting, so the report shows exactly what would happen.
+    /// Payments and fee waivers of closed years are locked; see
+    /// `override_import_data`.
+    pub fn import_data(
+        &self,
+        data: &DataExport,
+        strategy: ConflictStrategy,
+        dry_run: bool,
+    ) -> Result<TransferReport, String> {
+        self.import_document(data, strategy, dry_run, None)
+    }
+
+    /// Import writing payments or fee waivers of closed years (admin
+    /// override, logged)
+    pub fn override_import_data(
+        &self,
+        data: &DataExport,
+        strategy: ConflictStrategy,
+        dry_run: bool,
+        reason: &str,
+    ) -> Result<TransferReport, String> {
+        self.import_document(data, strategy, dry_run, Some(reason))
+    }
+
+    fn import_document(
+        &self,
+        data: &DataExport,
+        strategy: ConflictStrategy,
+        dry_run: bool,
+        override_reason: Option<&str>,
+    ) -> Result<TransferReport, String> {
+        check_manifest(&data.manifest)?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start import: {}", e))?;
+
+        let mut report = TransferReport {
+            dry_run,
+            strategy,
+            ..Default::default()
+        };
+
+        // Blocks are shared reference data: matching codes are reused,
+        // and only updated when overwriting.
+        let mut block_ids: HashMap<i64, i64> = HashMap::new();
+        for block in &data.blocks {
+            let request = CreateBlockRequest {
+                code: block.code.clone(),
+                description: block.description.clone(),
+                total_capacity: block.total_capacity,
+                annual_fee: block.annual_fee,
+                status: block.status.clone(),
+            };
+            check_record(&format!("Block {}", block.code), validation::create_block(&request))?;
+
+            let existing: Option<i64> = self
+                .conn
+                .query_row("SELECT id FROM blocks WHERE code = ?1", [&block.code], |row| row.get(0))
+                .optional()
+                .map_err(|e| format!("Failed to look up block {}: {}", block.code, e))?;
+
+            let new_id = match existing {
+                Some(id) if strategy == ConflictStrategy::Overwrite => {
+                    self.conn
+                        .execute(
+                            "UPDATE blocks SET description = ?1, total_capacity = ?2, annual_fee = ?3, status = ?4, updated_by = ?6 WHERE id = ?5",
+                            rusqlite::params![block.description, block.total_capacity, block.annual_fee, block.status, id, self.operator],
+                        )
+                        .map_err(|e| format!("Failed to update block {}: {}", block.code, e))?;
+                    report.blocks.updated += 1;
+                    id
+                }
+                Some(id) => {
+                    report.blocks.skipped += 1;
+                    id
+                }
+                None => {
+                    report.blocks.created += 1;
+                    self.insert_block_record(block, &block.code)?
+                }
+            };
+            block_ids.insert(block.id, new_id);
+        }
+
+        self.import_grave_records(data, &block_ids, strategy, override_reason, &mut report)?;
+
+        // Foundation profile is only replaced when overwriting; the active
+        // year never goes back to a closed year
+        if let (Some(settings), ConflictStrategy::Overwrite) = (&data.settings, strategy) {
+            self.conn
+                .execute(
+                    "UPDATE settings SET foundation_name = ?1, address = ?2, phone = ?3, email = ?4,
+                        active_year = CASE WHEN ?5 <= (SELECT COALESCE(MAX(year), 0) FROM closed_years) THEN active_year ELSE ?5 END,
+                        auto_backup = ?6
+                     WHERE id = 1",
+                    rusqlite::params![settings.foundation_name, settings.address, settings.phone, settings.email, settings.active_year, settings.auto_backup],
+                )
+                .map_err(|e| format!("Failed to import settings: {}", e))?;
+            report.settings_updated = true;
+        }
+
+        if dry_run {
+            tx.rollback()
+                .map_err(|e| format!("Failed to roll back dry run: {}", e))?;
+        } else {
+            tx.commit()
+                .map_err(|e| format!("Failed to commit import: {}", e))?;
+        }
+
+        Ok(report)
+    }
+
+    /// Insert a block record under the given code, returning the new ID
+    pub(super) fn insert_block_record(&self, block: &Block, code: &str) -> Result<i64, String> {
+        self.conn
+            .execute(
+                "INSERT INTO blocks (code, description, total_capacity, annual_fee, status, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                rusqlite::params![code, block.description, block.total_capacity, block.annual_fee, block.status, self.operator],
+            )
+            .map_err(|e| format!("Failed to create block {}: {}", code, e))?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Copy graves with their interments, heirs, payments and fee waivers
+    ///
+    /// `block_ids` maps block IDs in `data` to block IDs in this database.
+    /// Records get the checks of the matching create requests. Payments of
+    /// closed cash sessions are never overwritten; payments and fee waivers
+    /// of closed years need `override_reason`. Must be called inside a
+    /// transaction.
+    pub(super) fn import_grave_records(
+        &self,
+        data: &DataExport,
+        block_ids: &HashMap<i64, i64>,
+        strategy: ConflictStrategy,
+        override_reason: Option<&str>,
+        report: &mut TransferReport,
+    ) -> Result<(), String> {
+        self.check_grave_records(data)?;
+
+        // Graves: old id -> (new id, whether children should be written)
+        let mut grave_ids: HashMap<i64, (i64, bool)> = HashMap::new();
+        for grave in &data.graves {
+            let block_id = *block_ids
+                .get(&grave.block_id)
+                .ok_or_else(|| format!("Grave {} references unknown block {}", grave.id, grave.block_id))?;
+
+            let existing: Option<(i64, String)> = self
+                .conn
+                .query_row(
+                    "SELECT id, deceased_name FROM graves WHERE block_id = ?1 AND number = ?2",
+                    rusqlite::params![block_id, grave.number],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to look up grave {}: {}", grave.number, e))?;
+
+            let Some((existing_id, existing_name)) = existing else {
+                let violation = self.check_placement(grave, block_id, &grave.number, override_reason, report)?;
+                let new_id = self.insert_grave_record(grave, block_id, &grave.number)?;
+                self.log_placement_override(block_id, new_id, violation, override_reason)?;
+                report.graves.created += 1;
+                grave_ids.insert(grave.id, (new_id, true));
+                continue;
+            };
+
+            let mut conflict = GraveConflict {
+                block_code: self.block_code(block_id)?,
+                number: grave.number.clone(),
+                incoming_deceased_name: grave.deceased_name.clone(),
+                existing_deceased_name: existing_name,
+                new_number: None,
+            };
+
+            match strategy {
+                ConflictStrategy::Skip => {
+                    report.graves.skipped += 1;
+                    grave_ids.insert(grave.id, (existing_id, false));
+                }
+                ConflictStrategy::Overwrite => {
+                    self.conn
+                        .execute(
+                            "UPDATE graves SET deceased_name = ?1, date_of_death = ?2, burial_date = ?3, notes = ?4, updated_by = ?6 WHERE id = ?5",
+                            rusqlite::params![grave.deceased_name, grave.date_of_death, grave.burial_date, grave.notes, existing_id, self.operator],
+                        )
+                        .map_err(|e| format!("Failed to update grave {}: {}", grave.number, e))?;
+                    report.graves.updated += 1;
+                    grave_ids.insert(grave.id, (existing_id, true));
+                }
+                ConflictStrategy::Rename => {
+                    let number = self.free_grave_number(block_id, &grave.number)?;
+                    let violation = self.check_placement(grave, block_id, &number, override_reason, report)?;
+                    let new_id = self.insert_grave_record(grave, block_id, &number)?;
+                    self.log_placement_override(block_id, new_id, violation, override_reason)?;
+                    report.graves.renamed += 1;
+                    conflict.new_number = Some(number);
+                    grave_ids.insert(grave.id, (new_id, true));
+                }
+            }
+            report.conflicts.push(conflict);
+        }
+
+        // A preview lists every grave that cannot be placed; the real run
+        // writes none of them
+        if !report.dry_run && !report.placement_violations.is_empty() {
+            let graves: Vec<_> = report
+                .placement_violations
+                .iter()
+                .map(|v| format!("{} ({})", v.number, v.message))
+                .collect();
+            return Err(format!("Graves cannot be placed: {}", graves.join("; ")));
+        }
+
+        // First interments are created with the grave itself; all
+        // interments are then matched by burial order
+        for interment in &data.interments {
+            match grave_ids.get(&interment.grave_id) {
+                Some((grave_id, true)) => {
+                    self.conn
+                        .execute(
+                            "INSERT INTO interments (grave_id, sequence, deceased_name, date_of_death, burial_date, notes, created_by, updated_by)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+                             ON CONFLICT(grave_id, sequence) DO UPDATE SET
+                                deceased_name = excluded.deceased_name,
+                                date_of_death = excluded.date_of_death,
+                                burial_date = excluded.burial_date,
+                                notes = excluded.notes,
+                                updated_by = excluded.updated_by",
+                            rusqlite::params![grave_id, interment.sequence, interment.deceased_name, interment.date_of_death, interment.burial_date, interment.notes, self.operator],
+                        )
+                        .map_err(|e| format!("Failed to import interment {}: {}", interment.deceased_name, e))?;
+                    report.interments.created += 1;
+                }
+                _ => report.interments.skipped += 1,
+            }
+        }
+
+        for heir in &data.heirs {
+            match grave_ids.get(&heir.grave_id) {
+                Some((grave_id, true)) => {
+                    // Unparseable numbers from older exports are kept for display only
+                    let phone_e164 = normalize_phone(heir.phone_number.as_deref().unwrap_or("")).ok().flatten();
+                    self.conn
+                        .execute(
+                            "INSERT INTO heirs (grave_id, order_number, full_name, phone_number, relationship, address, is_primary, created_by, updated_by, phone_e164)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?9)
+                             ON CONFLICT(grave_id, order_number) DO UPDATE SET
+                                full_name = excluded.full_name,
+                                phone_number = excluded.phone_number,
+                                phone_e164 = excluded.phone_e164,
+                                relationship = excluded.relationship,
+                                address = excluded.address,
+                                is_primary = excluded.is_primary,
+                                updated_by = excluded.updated_by",
+                            rusqlite::params![grave_id, heir.order_number, heir.full_name, heir.phone_number, heir.relationship, heir.address, heir.is_primary, self.operator, phone_e164],
+                        )
+                        .map_err(|e| format!("Failed to import heir {}: {}", heir.full_name, e))?;
+                    report.heirs.created += 1;
+                }
+                _ => report.heirs.skipped += 1,
+            }
+        }
+
+        for payment in &data.payments {
+            match grave_ids.get(&payment.grave_id) {
+                Some((grave_id, true)) => {
+                    let existing = self.get_payment_by_grave_and_year(*grave_id, payment.year)?;
+                    if let Some(existing) = &existing {
+                        self.check_cash_session_lock(existing.id)?;
+                    }
+                    let lock_reason = self.lock_override_for(payment.year, override_reason)?;
+                    if let (Some(reason), Some(existing)) = (lock_reason, &existing) {
+                        self.log_payment_override(existing.id, "update", reason)?;
+                    }
+                    self.conn
+                        .execute(
+                            "INSERT INTO payments (grave_id, year, payment_date, amount, payment_method, payment_proof, paid_by, notes, created_by, updated_by)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)
+                             ON CONFLICT(grave_id, year) DO UPDATE SET
+                                payment_date = excluded.payment_date,
+                                amount = excluded.amount,
+                                payment_method = excluded.payment_method,
+                                payment_proof = excluded.payment_proof,
+                                paid_by = excluded.paid_by,
+                                notes = excluded.notes,
+                                updated_by = excluded.updated_by",
+                            rusqlite::params![grave_id, payment.year, payment.payment_date, payment.amount, payment.payment_method, payment.payment_proof, payment.paid_by, payment.notes, self.operator],
+                        )
+                        .map_err(|e| format!("Failed to import payment {}: {}", payment.year, e))?;
+                    if let (Some(reason), None) = (lock_reason, existing) {
+                        self.log_payment_override(self.conn.last_insert_rowid(), "create", reason)?;
+                    }
+                    report.payments.created += 1;
+                }
+                _ => report.payments.skipped += 1,
+            }
+        }
+
+        for waiver in &data.fee_waivers {
+            match grave_ids.get(&waiver.grave_id) {
+                Some((grave_id, true)) => {
+                    let lock_reason = self.lock_override_for(waiver.year, override_reason)?;
+                    let existing = match lock_reason {
+                        Some(_) => self.waiver_id_for_year(*grave_id, waiver.year)?,
+                        None => None,
+                    };
+                    if let (Some(reason), Some(existing)) = (lock_reason, existing) {
+                        self.log_waiver_override(existing, "update", reason)?;
+                    }
+                    self.conn
+                        .execute(
+                            "INSERT INTO fee_waivers (grave_id, year, amount, reason, approved_by)
+                             VALUES (?1, ?2, ?3, ?4, ?5)
+                             ON CONFLICT(grave_id, year) DO UPDATE SET
+                                amount = excluded.amount,
+                                reason = excluded.reason,
+                                approved_by = excluded.approved_by",
+                            rusqlite::params![grave_id, waiver.year, waiver.amount, waiver.reason, waiver.approved_by],
+                        )
+                        .map_err(|e| format!("Failed to import fee waiver {}: {}", waiver.year, e))?;
+                    if let (Some(reason), None) = (lock_reason, existing) {
+                        self.log_waiver_override(self.conn.last_insert_rowid(), "create", reason)?;
+                    }
+                    report.fee_waivers.created += 1;
+                }
+                _ => report.fee_waivers.skipped += 1,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record why a new grave cannot go into a block, as creating it in the
+    /// app would refuse
+    ///
+    /// Reserved numbers are always refused. With `override_reason` a full or
+    /// inactive block takes the grave; the violation is returned so it can
+    /// be logged once the grave exists.
+    fn check_placement(
+        &self,
+        grave: &Grave,
+        block_id: i64,
+        number: &str,
+        override_reason: Option<&str>,
+        report: &mut TransferReport,
+    ) -> Result<Option<CapacityViolation>, String> {
+        let block_code = self.block_code(block_id)?;
+        let mut violation = |kind: &str, message: String| {
+            report.placement_violations.push(PlacementViolation {
+                block_code: block_code.clone(),
+                number: number.to_string(),
+                deceased_name: grave.deceased_name.clone(),
+                violation: kind.to_string(),
+                message,
+            })
+        };
+        if let Err(message) = self.check_number_not_reserved(block_id, number) {
+            violation("reserved", message);
+        }
+        let capacity = self.block_capacity_violation(block_id)?;
+        match (capacity, override_reason) {
+            (Some(capacity), None) => {
+                let message = self.check_block_capacity(block_id).err().unwrap_or_default();
+                violation(capacity.as_str(), message);
+                Ok(None)
+            }
+            (capacity, _) => Ok(capacity),
+        }
+    }
+
+    fn log_placement_override(
+        &self,
+        block_id: i64,
+        grave_id: i64,
+        violation: Option<CapacityViolation>,
+        override_reason: Option<&str>,
+    ) -> Result<(), String> {
+        match (violation, override_reason) {
+            (Some(violation), Some(reason)) => {
+                let reason = require_override_reason(reason)?;
+                self.log_capacity_override(block_id, grave_id, "create", violation, reason)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check incoming graves and their records as the app checks new ones
+    fn check_grave_records(&self, data: &DataExport) -> Result<(), String> {
+        let today = self.local_today()?;
+        for grave in &data.graves {
+            let request = CreateGraveRequest {
+                deceased_name: grave.deceased_name.clone(),
+                block_id: grave.block_id,
+                number: grave.number.clone(),
+                date_of_death: grave.date_of_death.to_string(),
+                burial_date: grave.burial_date.map(|date| date.to_string()),
+                notes: grave.notes.clone(),
+            };
+            check_record(&format!("Grave {}", grave.number), validation::create_grave(&request, today))?;
+        }
+        for interment in &data.interments {
+            let request = CreateIntermentRequest {
+                deceased_name: interment.deceased_name.clone(),
+                date_of_death: interment.date_of_death.to_string(),
+                burial_date: interment.burial_date.map(|date| date.to_string()),
+                notes: interment.notes.clone(),
+            };
+            check_record(
+                &format!("Interment {}", interment.deceased_name),
+                validation::create_interment(&request, today),
+            )?;
+        }
+        for heir in &data.heirs {
+            // Phone numbers are left out: unparseable ones from older
+            // exports are kept for display only
+            let request = CreateHeirRequest {
+                grave_id: heir.grave_id,
+                order_number: heir.order_number,
+                full_name: heir.full_name.clone(),
+                phone_number: None,
+                relationship: heir.relationship.clone(),
+                address: heir.address.clone(),
+                is_primary: heir.is_primary,
+            };
+            check_record(&format!("Heir {}", heir.full_name), validation::create_heir(&request))?;
+        }
+        for payment in &data.payments {
+            let request = CreatePaymentRequest {
+                grave_id: payment.grave_id,
+                year: payment.year,
+                payment_date: payment.payment_date.to_string(),
+                amount: payment.amount,
+                payment_method: payment.payment_method.clone(),
+                payment_proof: payment.payment_proof.clone(),
+                paid_by: payment.paid_by.clone(),
+                notes: payment.notes.clone(),
+            };
+            check_record(&format!("Payment {}", payment.year), validation::payment(&request))?;
+        }
+        for waiver in &data.fee_waivers {
+            let request = CreateWaiverRequest {
+                grave_id: waiver.grave_id,
+                year: waiver.year,
+                amount: waiver.amount,
+                reason: waiver.reason.clone(),
+                approved_by: waiver.approved_by.clone(),
+            };
+            check_record(&format!("Fee waiver {}", waiver.year), validation::create_waiver(&request))?;
+        }
+        Ok(())
+    }
+
+    fn insert_grave_record(&self, grave: &Grave, block_id: i64, number: &str) -> Result<i64, String> {
+        self.conn
+            .execute(
+                "INSERT INTO graves (deceased_name, block_id, number, date_of_death, burial_date, notes, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                rusqlite::params![grave.deceased_name, block_id, number, grave.date_of_death, grave.burial_date, grave.notes, self.operator],
+            )
+            .map_err(|e| format!("Failed to create grave {}: {}", number, e))?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub(super) fn block_code(&self, block_id: i64) -> Result<String, String> {
+        self.conn
+            .query_row("SELECT code FROM blocks WHERE id = ?1", [block_id], |row| row.get(0))
+            .map_err(|e| format!("Failed to get block {}: {}", block_id, e))
+    }
+
+    /// Find a free grave number in a block by appending a suffix ("12" -> "12-2")
+    pub(crate) fn free_grave_number(&self, block_id: i64, number: &str) -> Result<String, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT number FROM graves WHERE block_id = ?1")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let taken: HashSet<String> = stmt
+            .query_map([block_id], |row| row.get(0))
+            .map_err(|e| format!("Failed to query grave numbers: {}", e))?
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to collect grave numbers: {}", e))?;
+
+        (2..)
+            .map(|n| format!("{}-{}", number, n))
+            .find(|candidate| !taken.contains(candidate))
+            .ok_or_else(|| format!("No free number for grave {}", number))
+    }
+
+    /// Read all blocks from a schema (`main` or an attached database)
+    pub(super) fn read_blocks(&self, schema: &str) -> Result<Vec<Block>, String> {
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT id, code, description, total_capacity, annual_fee, status, created_at, updated_at FROM {}.blocks ORDER BY code", schema))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let blocks = stmt
+            .query_map([], |row| {
+                Ok(Block {
+                    id: row.get(0)?,
+                    code: row.get(1)?,
+                    description: row.get(2)?,
+                    total_capacity: row.get(3)?,
+                    annual_fee: row.get(4)?,
+                    status: row.get(5)?,
+                    created_at: row.get(6)?,
+                    updated_at: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query blocks: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect blocks: {}", e))?;
+
+        Ok(blocks)
+    }
+
+    /// Read all graves from a schema (`main` or an attached database)
+    pub(super) fn read_graves(&self, schema: &str) -> Result<Vec<Grave>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT id, deceased_name, block_id, number, date_of_death, burial_date, notes, created_at, updated_at FROM {}.graves ORDER BY id", schema))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let graves = stmt
+            .query_map([], |row| {
+                Ok(Grave {
+                    id: row.get(0)?,
+                    deceased_name: row.get(1)?,
+                    block_id: row.get(2)?,
+                    number: row.get(3)?,
+                    date_of_death: loose_date(row, 4)?,
+                    burial_date: loose_optional_date(row, 5)?,
+                    notes: row.get(6)?,
+                    created_at: row.get(7)?,
+                    updated_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query graves: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect graves: {}", e))?;
+
+        Ok(graves)
+    }
+
+    /// Read all heirs from a schema (`main` or an attached database)
+    pub(super) fn read_heirs(&self, schema: &str) -> Result<Vec<Heir>, String> {
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT id, grave_id, order_number, full_name, phone_number, relationship, address, is_primary, created_at, updated_at FROM {}.heirs ORDER BY grave_id, order_number", schema))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let heirs = stmt
+            .query_map([], |row| {
+                Ok(Heir {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    order_number: row.get(2)?,
+                    full_name: row.get(3)?,
+                    phone_number: row.get(4)?,
+                    relationship: row.get(5)?,
+                    address: row.get(6)?,
+                    is_primary: row.get(7)?,
+                    created_at: row.get(8)?,
+                    updated_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query heirs: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect heirs: {}", e))?;
+
+        Ok(heirs)
+    }
+
+    /// Read all payments from a schema (`main` or an attached database)
+    pub(super) fn read_payments(&self, schema: &str) -> Result<Vec<Payment>, String> {
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT id, grave_id, year, payment_date, amount, payment_method, payment_proof, paid_by, notes, created_at, updated_at FROM {}.payments ORDER BY grave_id, year", schema))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let payments = stmt
+            .query_map([], |row| {
+                Ok(Payment {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    year: row.get(2)?,
+                    payment_date: loose_date(row, 3)?,
+                    amount: row.get(4)?,
+                    payment_method: row.get(5)?,
+                    payment_proof: row.get(6)?,
+                    paid_by: row.get(7)?,
+                    notes: row.get(8)?,
+                    created_at: row.get(9)?,
+                    updated_at: row.get(10)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query payments: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect payments: {}", e))?;
+
+        Ok(payments)
+    }
+
+    /// Read all fee waivers from a schema (`main` or an attached database)
+    ///
+    /// Databases created before fee waivers existed yield an empty list.
+    pub(super) fn read_waivers(&self, schema: &str) -> Result<Vec<FeeWaiver>, String> {
+        let has_table: bool = self
+            .conn
+            .query_row(
+                &format!("SELECT COUNT(*) > 0 FROM {}.sqlite_master WHERE type = 'table' AND name = 'fee_waivers'", schema),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to check fee waivers table: {}", e))?;
+        if !has_table {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT id, grave_id, year, amount, reason, approved_by, created_at, updated_at FROM {}.fee_waivers ORDER BY grave_id, year", schema))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let waivers = stmt
+            .query_map([], |row| {
+                Ok(FeeWaiver {
+                    id: row.get(0)?,
+                    grave_id: row.get(1)?,
+                    year: row.get(2)?,
+                    amount: row.get(3)?,
+                    reason: row.get(4)?,
+                    approved_by: row.get(5)?,
+                    created_at: row.get(6)?,
+                    updated_at: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query fee waivers: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect fee waivers: {}", e))?;
+
+        Ok(waivers)
+    }
+}
+
+impl DataExport {
+    /// Read an export document, upgrading older format versions
+    pub fn from_json(content: &str) -> Result<DataExport, String> {
+        let mut document: serde_json::Value =
+            serde_json::from_str(content).map_err(|e| format!("Invalid export file: {}", e))?;
+        let format_version = document
+            .pointer("/manifest/format_version")
+            .and_then(serde_json::Value::as_i64)
+            .unwrap_or(0);
+        if format_version == 1 {
+            upgrade_v1_document(&mut document);
+        }
+        serde_json::from_value(document).map_err(|e| format!("Invalid export file: {}", e))
+    }
+}
+
+/// Upgrade a version 1 document: dates in any notation and timestamps
+/// without offset (SQLite `CURRENT_TIMESTAMP`, UTC)
+fn upgrade_v1_document(document: &mut serde_json::Value) {
+    let mut records: Vec<&mut serde_json::Value> = Vec::new();
+    if let Some(object) = document.as_object_mut() {
+        for (key, value) in object.iter_mut() {
+            if key == "settings" {
+                records.push(value);
+            } else if RECORD_LISTS.contains(&key.as_str()) {
+                if let Some(list) = value.as_array_mut() {
+                    records.extend(list.iter_mut());
+                }
+            }
+        }
+    }
+
+    for record in records {
+        let Some(record) = record.as_object_mut() else {
+            continue;
+        };
+        for (key, value) in record.iter_mut() {
+            let Some(text) = value.as_str() else {
+                continue;
+            };
+            if DATE_FIELDS.contains(&key.as_str()) {
+                *value = match parse_loose_date(text) {
+                    Some(date) => serde_json::Value::String(date.to_string()),
+                    None if text.trim().is_empty() => serde_json::Value::Null,
+                    // Left as is; the import reports the record as invalid
+                    None => continue,
+                };
+            } else if TIMESTAMP_FIELDS.contains(&key.as_str()) {
+                if let Ok(timestamp) = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+                    *value = serde_json::Value::String(timestamp.and_utc().to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+                }
+            }
+        }
+        if record.contains_key("active_year") && !record.contains_key("timezone") {
+            record.insert("timezone".to_string(), TIMEZONES[0].name.into());
+        }
+    }
+}
+
+/// Reject files that are not Astana exports or come from a newer format
+fn check_manifest(manifest: &ExportManifest) -> Result<(), String> {
+    if manifest.format != EXPORT_FORMAT {
+        return Err(format!("Not an Astana export file (format '{}')", manifest.format));
+    }
+    if manifest.format_version > EXPORT_FORMAT_VERSION {
+        return Err(format!(
+            "Export format version {} is newer than supported version {}; please update the application",
+            manifest.format_version, EXPORT_FORMAT_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Name the record in the messages of a failed request check
+fn check_record(record: &str, result: Result<impl Sized, String>) -> Result<(), String> {
+    result.map(|_| ()).map_err(|e| match validation::parse_validation_errors(&e) {
+        Some(errors) => {
+            let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+            format!("{}: {}", record, messages.join("; "))
+        }
+        None => e,
+    })
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Full data export document
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DataExport {
+    pub manifest: ExportManifest,
+    #[serde(default)]
+    pub settings: Option<Settings>,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+    #[serde(default)]
+    pub graves: Vec<Grave>,
+    #[serde(default)]
+    pub heirs: Vec<Heir>,
+    #[serde(default)]
+    pub payments: Vec<Payment>,
+    #[serde(default)]
+    pub fee_waivers: Vec<FeeWaiver>,
+    #[serde(default)]
+    pub interments: Vec<Interment>,
+}
+
+/// Export manifest
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportManifest {
+    pub format: String,
+    pub format_version: i32,
+    /// Database schema version (`PRAGMA user_version`) of the source
+    pub schema_version: i32,
+    pub app_version: String,
+    pub exported_at: String,
+    pub foundation_name: String,
+    pub counts: ExportCounts,
+}
+
+/// Number of records per entity in the export
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ExportCounts {
+    pub blocks: i64,
+    pub graves: i64,
+    pub heirs: i64,
+    pub payments: i64,
+    pub fee_waivers: i64,
+    #[serde(default)]
+    pub interments: i64,
+}
+
+/// How to handle records that already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Keep existing records, ignore incoming ones
+    #[default]
+    Skip,
+    /// Replace existing records with incoming ones
+    Overwrite,
+    /// Import conflicting graves under a new number
+    Rename,
+}
+
+/// Import counts per entity
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TransferCounts {
+    pub created: i64,
+    pub updated: i64,
+    pub skipped: i64,
+    pub renamed: i64,
+}
+
+/// Incoming grave whose block and number already exist
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GraveConflict {
+    pub block_code: String,
+    pub number: String,
+    pub incoming_deceased_name: String,
+    pub existing_deceased_name: String,
+    /// Number the grave was imported under (rename strategy only)
+    pub new_number: Option<String>,
+}
+
+/// Incoming grave refused by its target block
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlacementViolation {
+    pub block_code: String,
+    pub number: String,
+    pub deceased_name: String,
+    /// `full`, `inactive` or `reserved`
+    pub violation: String,
+    pub message: String,
+}
+
+/// Result of a data import
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TransferReport {
+    pub dry_run: bool,
+    pub strategy: ConflictStrategy,
+    pub blocks: TransferCounts,
+    pub graves: TransferCounts,
+    pub heirs: TransferCounts,
+    pub payments: TransferCounts,
+    pub fee_waivers: TransferCounts,
+    pub interments: TransferCounts,
+    pub conflicts: Vec<GraveConflict>,
+    /// New graves a block cannot take; only a dry run gets past them
+    pub placement_violations: Vec<PlacementViolation>,
+    pub settings_updated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, create_block, grave_request, heir_request, payment_request, test_db};
+    use crate::db::{CreateUserRequest, GraveListQuery, Role};
+
+    /// Grave A-1 with one heir and one payment
+    fn seed(db: &Database, name: &str) {
+        let block_id = create_block(db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                deceased_name: name.to_string(),
+                ..grave_request(block_id, "1")
+            })
+            .unwrap();
+        db.create_heir(&heir_request(grave_id, "Budi")).unwrap();
+        db.create_payment(&payment_request(grave_id, 2024)).unwrap();
+    }
+
+    /// Export of a database holding Ahmad at A-1, through JSON, and a
+    /// target database holding Siti at A-1
+    fn conflicting_target() -> (tempfile::TempDir, DataExport, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Database::init_with_path(dir.path().join("source.db")).unwrap();
+        seed(&source, "Ahmad");
+        let json = serde_json::to_string(&source.export_data().unwrap()).unwrap();
+        let target = Database::init_with_path(dir.path().join("target.db")).unwrap();
+        seed(&target, "Siti");
+        (dir, serde_json::from_str(&json).unwrap(), target)
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let (_dir, source) = test_db();
+        seed(&source, "Ahmad");
+        let export = source.export_data().unwrap();
+        assert_eq!(export.manifest.counts.graves, 1);
+
+        // JSON roundtrip keeps the document readable by other tools
+        let json = serde_json::to_string(&export).unwrap();
+        let export: DataExport = serde_json::from_str(&json).unwrap();
+
+        let (_dir, empty) = test_db();
+        let report = empty.import_data(&export, ConflictStrategy::Skip, false).unwrap();
+        assert_eq!(report.graves.created, 1);
+        assert_eq!(report.payments.created, 1);
+    }
+
+    #[test]
+    fn test_import_skips_conflicts() {
+        let (_dir, export, target) = conflicting_target();
+        let report = target.import_data(&export, ConflictStrategy::Skip, false).unwrap();
+        assert_eq!(report.graves.skipped, 1);
+        assert_eq!(report.heirs.skipped, 1);
+    }
+
+    #[test]
+    fn test_import_dry_run_renames_conflicts() {
+        let (_dir, export, target) = conflicting_target();
+        let report = target.import_data(&export, ConflictStrategy::Rename, true).unwrap();
+        assert_eq!(report.conflicts[0].existing_deceased_name, "Siti");
+        assert_eq!(report.conflicts[0].new_number.as_deref(), Some("1-2"));
+        assert_eq!(target.get_stats().unwrap().graves_count, 1);
+    }
+
+    #[test]
+    fn test_import_overwrites_conflicts() {
+        let (_dir, export, target) = conflicting_target();
+        let report = target.import_data(&export, ConflictStrategy::Overwrite, false).unwrap();
+        assert_eq!(report.graves.updated, 1);
+        let graves = target.get_graves(&GraveListQuery::default(), 10).unwrap().graves;
+        assert_eq!(graves[0].deceased_name, "Ahmad");
+    }
+
+    #[test]
+    fn test_reads_version_1_export() {
+        let (_dir, db) = test_db();
+        seed(&db, "Ahmad");
+        let mut document = serde_json::to_value(db.export_data().unwrap()).unwrap();
+        document["manifest"]["format_version"] = 1.into();
+        document["graves"][0]["date_of_death"] = "1/1/2020".into();
+        document["graves"][0]["burial_date"] = "".into();
+        document["graves"][0]["created_at"] = "2020-01-02 03:04:05".into();
+        document["settings"].as_object_mut().unwrap().remove("timezone");
+
+        let export = DataExport::from_json(&document.to_string()).unwrap();
+        assert_eq!(export.graves[0].date_of_death.to_string(), "2020-01-01");
+        assert_eq!(export.graves[0].burial_date, None);
+        assert_eq!(export.graves[0].created_at.to_string(), "2020-01-02 03:04:05 UTC");
+        assert_eq!(export.settings.unwrap().timezone, "Asia/Jakarta");
+    }
+
+    #[test]
+    fn test_rejects_newer_format() {
+        let (_dir, db) = test_db();
+        let mut export = db.export_data().unwrap();
+        export.manifest.format_version = EXPORT_FORMAT_VERSION + 1;
+        assert!(db.import_data(&export, ConflictStrategy::Skip, true).is_err());
+    }
+
+    #[test]
+    fn test_import_keeps_closed_session_payments() {
+        let (_dir, source) = test_db();
+        seed(&source, "Ahmad");
+        let export = source.export_data().unwrap();
+
+        let (_dir, target) = test_db();
+        let cashier_id = target
+            .create_user(&CreateUserRequest {
+                username: "kasir".to_string(),
+                full_name: "Kasir".to_string(),
+                password: "rahasia123".to_string(),
+                role: Role::Cashier,
+            })
+            .unwrap();
+        let target = target.with_operator(cashier_id);
+        let session_id = target.open_cash_session(0).unwrap();
+        seed(&target, "Siti");
+        target.close_cash_session(session_id, 100000, None).unwrap();
+
+        let error = target.import_data(&export, ConflictStrategy::Overwrite, false).unwrap_err();
+        assert!(error.contains("closed cash session"));
+        let graves = target.get_graves(&GraveListQuery::default(), 10).unwrap().graves;
+        assert_eq!(graves[0].deceased_name, "Siti");
+    }
+
+    #[test]
+    fn test_import_checks_records() {
+        let (_dir, source) = test_db();
+        seed(&source, "Ahmad");
+        let (_dir, target) = test_db();
+
+        let mut export = source.export_data().unwrap();
+        export.payments[0].amount = -100000;
+        let error = target.import_data(&export, ConflictStrategy::Skip, true).unwrap_err();
+        assert_eq!(error, "Payment 2024: Amount cannot be negative");
+
+        let mut export = source.export_data().unwrap();
+        export.heirs[0].order_number = 4;
+        let error = target.import_data(&export, ConflictStrategy::Skip, true).unwrap_err();
+        assert_eq!(error, "Heir Budi: Heir order must be between 1 and 3");
+        assert_eq!(target.get_stats().unwrap().graves_count, 0);
+    }
+
+    #[test]
+    fn test_import_into_full_block_needs_override() {
+        let (_dir, source) = test_db();
+        seed(&source, "Ahmad");
+        let export = source.export_data().unwrap();
+
+        let (_dir, target) = test_db();
+        let block_id = target
+            .create_block(&CreateBlockRequest {
+                total_capacity: 1,
+                ..block_request("A")
+            })
+            .unwrap();
+        target.create_grave(&grave_request(block_id, "5")).unwrap();
+
+        let preview = target.import_data(&export, ConflictStrategy::Skip, true).unwrap();
+        assert_eq!(preview.placement_violations[0].violation, "full");
+        let error = target.import_data(&export, ConflictStrategy::Skip, false).unwrap_err();
+        assert!(error.starts_with("Graves cannot be placed: 1 (Block A is full"));
+
+        let report = target.override_import_data(&export, ConflictStrategy::Skip, false, "Pindahan").unwrap();
+        assert!(report.placement_violations.is_empty());
+        assert_eq!(target.get_capacity_overrides(10).unwrap()[0].block_code, "A");
+    }
+}
@@ -0,0 +1,345 @@
+//! Local user accounts
+//!
+//! Passwords are stored as Argon2 PHC strings, which carry their own random
+//! salt and parameters.
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+use super::{validation, Database};
+
+/// Minimum password length for new passwords
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+impl Database {
+    /// Check whether any user account exists (first run sets up an admin)
+    pub fn has_users(&self) -> Result<bool, String> {
+        self.conn
+            .query_row("SELECT COUNT(*) > 0 FROM users", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to count users: {}", e))
+    }
+
+    /// Get all users
+    pub fn get_users(&self) -> Result<Vec<User>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT id, username, full_name, role, is_active, last_login, created_at, updated_at FROM users ORDER BY username")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let users = stmt
+            .query_map([], map_user)
+            .map_err(|e| format!("Failed to query users: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect users: {}", e))?;
+
+        Ok(users)
+    }
+
+    /// Get user by ID
+    pub fn get_user_by_id(&self, id: i64) -> Result<Option<User>, String> {
+        self.conn
+            .query_row(
+                "SELECT id, username, full_name, role, is_active, last_login, created_at, updated_at FROM users WHERE id = ?1",
+                [id],
+                map_user,
+            )
+            .optional()
+            .map_err(|e| format!("Failed to get user: {}", e))
+    }
+
+    /// Create new user
+    pub fn create_user(&self, user: &CreateUserRequest) -> Result<i64, String> {
+        validation::create_user(user, MIN_PASSWORD_LENGTH)?;
+        let username = user.username.trim();
+        let password_hash = hash_password(&user.password)?;
+
+        self.conn
+            .execute(
+                "INSERT INTO users (username, full_name, password_hash, role) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![username, user.full_name.trim(), password_hash, user.role.as_str()],
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::SqliteFailure(err, _)
+                    if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    format!("Username {} is already taken", username)
+                }
+                e => format!("Failed to create user: {}", e),
+            })?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Update user name, role or active status
+    pub fn update_user(&self, id: i64, user: &UpdateUserRequest) -> Result<(), String> {
+        let current = self
+            .get_user_by_id(id)?
+            .ok_or_else(|| format!("User {} not found", id))?;
+        validation::update_user(user)?;
+
+        // Never leave the installation without an active admin
+        let loses_admin = current.role == Role::Admin
+            && current.is_active
+            && (user.role.is_some_and(|r| r != Role::Admin) || user.is_active == Some(false));
+        if loses_admin && self.count_active_admins()? <= 1 {
+            return Err("Cannot remove the last active admin".to_string());
+        }
+
+        self.conn
+            .execute(
+                "UPDATE users SET full_name = COALESCE(?1, full_name), role = COALESCE(?2, role), is_active = COALESCE(?3, is_active) WHERE id = ?4",
+                rusqlite::params![
+                    user.full_name.as_deref().map(str::trim),
+                    user.role.map(|r| r.as_str()),
+                    user.is_active,
+                    id
+                ],
+            )
+            .map_err(|e| format!("Failed to update user: {}", e))?;
+        Ok(())
+    }
+
+    /// Set a new password for a user
+    pub fn set_user_password(&self, id: i64, password: &str) -> Result<(), String> {
+        let password_hash = hash_password(password)?;
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+                rusqlite::params![password_hash, id],
+            )
+            .map_err(|e| format!("Failed to update password: {}", e))?;
+        if updated == 0 {
+            return Err(format!("User {} not found", id));
+        }
+        Ok(())
+    }
+
+    /// Delete user
+    pub fn delete_user(&self, id: i64) -> Result<(), String> {
+        let user = self
+            .get_user_by_id(id)?
+            .ok_or_else(|| format!("User {} not found", id))?;
+        if user.role == Role::Admin && user.is_active && self.count_active_admins()? <= 1 {
+            return Err("Cannot remove the last active admin".to_string());
+        }
+
+        self.conn
+            .execute("DELETE FROM users WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to delete user: {}", e))?;
+        Ok(())
+    }
+
+    /// Verify username and password
+    ///
+    /// Returns the user on success and records the login time. The error
+    /// message does not reveal whether the username exists.
+    pub fn authenticate(&self, username: &str, password: &str) -> Result<User, String> {
+        const INVALID: &str = "Invalid username or password";
+
+        let found: Option<(i64, String)> = self
+            .conn
+            .query_row(
+                "SELECT id, password_hash FROM users WHERE username = ?1 AND is_active = 1",
+                [username.trim()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up user: {}", e))?;
+        let (id, password_hash) = found.ok_or_else(|| INVALID.to_string())?;
+
+        if !verify_password(password, &password_hash) {
+            return Err(INVALID.to_string());
+        }
+
+        self.conn
+            .execute("UPDATE users SET last_login = CURRENT_TIMESTAMP WHERE id = ?1", [id])
+            .map_err(|e| format!("Failed to record login: {}", e))?;
+
+        self.get_user_by_id(id)?
+            .ok_or_else(|| INVALID.to_string())
+    }
+
+    /// Verify the current password of a user (for changing own password)
+    pub fn verify_user_password(&self, id: i64, password: &str) -> Result<bool, String> {
+        let password_hash: String = self
+            .conn
+            .query_row("SELECT password_hash FROM users WHERE id = ?1", [id], |row| row.get(0))
+            .map_err(|e| format!("Failed to look up user: {}", e))?;
+        Ok(verify_password(password, &password_hash))
+    }
+
+    fn count_active_admins(&self) -> Result<i64, String> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM users WHERE role = 'admin' AND is_active = 1",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count admins: {}", e))
+    }
+}
+
+fn map_user(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get(3)?;
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        full_name: row.get(2)?,
+        role: Role::parse(&role).unwrap_or(Role::Viewer),
+        is_active: row.get(4)?,
+        last_login: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+/// Hash a password with a fresh random salt
+fn hash_password(password: &str) -> Result<String, String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// User role
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Full access, including users, settings and deleting blocks
+    Admin,
+    /// Treasurer/cashier: records payments and fee waivers
+    Cashier,
+    /// Data clerk: maintains blocks, graves and heirs
+    Clerk,
+    /// Read only
+    Viewer,
+}
+
+impl Role {
+    /// Value stored in the `users.role` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Cashier => "cashier",
+            Role::Clerk => "clerk",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "admin" => Some(Role::Admin),
+            "cashier" => Some(Role::Cashier),
+            "clerk" => Some(Role::Clerk),
+            "viewer" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+}
+
+/// User account (without password hash)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub full_name: String,
+    pub role: Role,
+    pub is_active: bool,
+    pub last_login: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub full_name: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpdateUserRequest {
+    pub full_name: Option<String>,
+    pub role: Option<Role>,
+    pub is_active: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::test_db;
+
+    fn user(username: &str, role: Role) -> CreateUserRequest {
+        CreateUserRequest {
+            username: username.to_string(),
+            full_name: "Pengurus".to_string(),
+            password: "rahasia123".to_string(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let (_dir, db) = test_db();
+        assert!(!db.has_users().unwrap());
+        let admin_id = db.create_user(&user("admin", Role::Admin)).unwrap();
+
+        let user = db.authenticate("ADMIN", "rahasia123").unwrap();
+        assert_eq!(user.id, admin_id);
+        assert_eq!(user.role, Role::Admin);
+        assert!(user.last_login.is_some());
+        assert!(db.authenticate("admin", "salah-sandi").is_err());
+    }
+
+    #[test]
+    fn test_password_hashes_are_salted() {
+        let (_dir, db) = test_db();
+        db.create_user(&user("admin", Role::Admin)).unwrap();
+        db.create_user(&user("kasir", Role::Cashier)).unwrap();
+
+        // Same password, different hash
+        let distinct: i64 = db
+            .conn
+            .query_row("SELECT COUNT(DISTINCT password_hash) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(distinct, 2);
+    }
+
+    #[test]
+    fn test_last_admin_stays() {
+        let (_dir, db) = test_db();
+        let admin_id = db.create_user(&user("admin", Role::Admin)).unwrap();
+        db.create_user(&user("kasir", Role::Cashier)).unwrap();
+
+        assert!(db.delete_user(admin_id).is_err());
+        let demote = UpdateUserRequest {
+            full_name: None,
+            role: Some(Role::Viewer),
+            is_active: None,
+        };
+        assert!(db.update_user(admin_id, &demote).is_err());
+    }
+}
@@ -0,0 +1,570 @@
+//! Validation of create and update requests
+//!
+//! Every create/update method of `Database` checks its request here before
+//! writing anything. Problems are collected per field and returned together
+//! as the usual `String` error, holding JSON the frontend can show next to
+//! the inputs:
+//!
+//! `{"validation":[{"field":"amount","code":"negative","message":"Amount cannot be negative"}]}`
+//!
+//! `field` is the request field name and `code` a stable identifier the
+//! frontend can translate; `message` is the English fallback.
+
+use chrono::NaiveDate;
+
+use super::{
+    normalize_phone, CombinedPaymentRequest, CreateBlockRequest, CreateGraveRequest, CreateHeirRequest,
+    CreateIntermentRequest, CreatePaymentRequest, CreatePersonRequest, CreateReservationRequest, CreateUserRequest,
+    CreateWaiverRequest, GraveWithBlock, RelocateGraveRequest, UpdateBlockRequest, UpdateGraveRequest, UpdateHeirRequest,
+    UpdateIntermentRequest, UpdatePersonRequest, UpdateReservationRequest, UpdateSettingsRequest, UpdateUserRequest,
+    TIMEZONES,
+};
+
+/// Allowed block statuses
+pub const BLOCK_STATUSES: &[&str] = &["active", "inactive"];
+
+/// Allowed payment methods
+pub const PAYMENT_METHODS: &[&str] = &["cash", "transfer", "qris"];
+
+/// Heirs per grave, ordered 1 up to this
+pub const MAX_HEIR_ORDER: i64 = 3;
+
+/// Range accepted for fee and fiscal years
+const MIN_YEAR: i32 = 1900;
+const MAX_YEAR: i32 = 2100;
+
+/// Stored date format
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Collects field errors of one request
+#[derive(Debug, Default)]
+struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn add(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        // One error per field is enough to show next to an input
+        if !self.errors.iter().any(|e| e.field == field) {
+            self.errors.push(FieldError {
+                field: field.to_string(),
+                code: code.to_string(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn required(&mut self, field: &str, value: &str, label: &str) {
+        if value.trim().is_empty() {
+            self.add(field, "required", format!("{} is required", label));
+        }
+    }
+
+    /// Text that may be left out but not blanked
+    fn not_blank(&mut self, field: &str, value: Option<&str>, label: &str) {
+        if let Some(value) = value {
+            self.required(field, value, label);
+        }
+    }
+
+    /// Required `YYYY-MM-DD` date
+    fn date(&mut self, field: &str, value: &str, label: &str) -> Option<NaiveDate> {
+        if value.trim().is_empty() {
+            self.add(field, "required", format!("{} is required", label));
+            return None;
+        }
+        self.optional_date(field, Some(value), label)
+    }
+
+    /// Optional `YYYY-MM-DD` date; empty counts as not given
+    ///
+    /// The value is stored as given, so it must be written exactly in the
+    /// stored form (zero-padded, no surrounding spaces).
+    fn optional_date(&mut self, field: &str, value: Option<&str>, label: &str) -> Option<NaiveDate> {
+        let value = value.filter(|v| !v.trim().is_empty())?;
+        match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+            Ok(date) if date.format(DATE_FORMAT).to_string() == value => Some(date),
+            _ => {
+                self.add(field, "invalid_date", format!("{} must be a date (YYYY-MM-DD)", label));
+                None
+            }
+        }
+    }
+
+    /// `today` is the local date of the configured timezone
+    fn not_in_future(&mut self, field: &str, date: Option<NaiveDate>, today: NaiveDate, label: &str) {
+        if date.is_some_and(|date| date > today) {
+            self.add(field, "future_date", format!("{} cannot be in the future", label));
+        }
+    }
+
+    /// Burial cannot precede death
+    fn burial_after_death(&mut self, burial: Option<NaiveDate>, death: Option<NaiveDate>) {
+        if let (Some(burial), Some(death)) = (burial, death) {
+            if burial < death {
+                self.add(
+                    "burial_date",
+                    "before_date_of_death",
+                    "Burial date cannot be before the date of death",
+                );
+            }
+        }
+    }
+
+    fn not_negative(&mut self, field: &str, value: i64, label: &str) {
+        if value < 0 {
+            self.add(field, "negative", format!("{} cannot be negative", label));
+        }
+    }
+
+    fn between(&mut self, field: &str, value: i64, min: i64, max: i64, label: &str) {
+        if !(min..=max).contains(&value) {
+            self.add(field, "out_of_range", format!("{} must be between {} and {}", label, min, max));
+        }
+    }
+
+    fn year(&mut self, field: &str, year: i32, label: &str) {
+        if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+            self.add(
+                field,
+                "out_of_range",
+                format!("{} must be between {} and {}", label, MIN_YEAR, MAX_YEAR),
+            );
+        }
+    }
+
+    fn one_of(&mut self, field: &str, value: &str, allowed: &[&str], label: &str) {
+        if !allowed.contains(&value) {
+            self.add(
+                field,
+                "invalid_choice",
+                format!("{} must be one of: {}", label, allowed.join(", ")),
+            );
+        }
+    }
+
+    /// Indonesian phone number; returns the E.164 form when valid
+    fn phone(&mut self, field: &str, value: Option<&str>) -> Option<String> {
+        match normalize_phone(value.unwrap_or("")) {
+            Ok(e164) => e164,
+            Err(message) => {
+                self.add(field, "invalid_phone", message);
+                None
+            }
+        }
+    }
+
+    fn email(&mut self, field: &str, value: Option<&str>) {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            return;
+        };
+        let valid = value
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !domain.contains('@'));
+        if !valid || value.contains(char::is_whitespace) {
+            self.add(field, "invalid_email", "Email address is not valid");
+        }
+    }
+
+    /// `Ok` when no errors were found, else the JSON error
+    fn finish(self) -> Result<(), String> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let errors = ValidationErrors { validation: self.errors };
+        let json =
+            serde_json::to_string(&errors).map_err(|e| format!("Failed to report validation errors: {}", e))?;
+        Err(json)
+    }
+}
+
+/// Optional text with blank values stored as NULL
+pub(super) fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Field errors of a failed request, `None` for any other error
+pub fn parse_validation_errors(error: &str) -> Option<Vec<FieldError>> {
+    serde_json::from_str::<ValidationErrors>(error)
+        .ok()
+        .map(|errors| errors.validation)
+}
+
+/// Field errors of a request check, empty when it passed
+pub(super) fn field_errors<T>(result: Result<T, String>) -> Vec<FieldError> {
+    result
+        .err()
+        .and_then(|e| parse_validation_errors(&e))
+        .unwrap_or_default()
+}
+
+// ==================== REQUEST CHECKS ====================
+
+pub(super) fn create_block(block: &CreateBlockRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("code", &block.code, "Block code");
+    v.not_negative("total_capacity", block.total_capacity, "Capacity");
+    v.not_negative("annual_fee", block.annual_fee, "Annual fee");
+    v.one_of("status", &block.status, BLOCK_STATUSES, "Status");
+    v.finish()
+}
+
+pub(super) fn update_block(block: &UpdateBlockRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("code", block.code.as_deref(), "Block code");
+    if let Some(capacity) = block.total_capacity {
+        v.not_negative("total_capacity", capacity, "Capacity");
+    }
+    if let Some(fee) = block.annual_fee {
+        v.not_negative("annual_fee", fee, "Annual fee");
+    }
+    if let Some(status) = &block.status {
+        v.one_of("status", status, BLOCK_STATUSES, "Status");
+    }
+    v.finish()
+}
+
+pub(super) fn create_grave(grave: &CreateGraveRequest, today: NaiveDate) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("deceased_name", &grave.deceased_name, "Deceased name");
+    v.required("number", &grave.number, "Grave number");
+    burial_dates(&mut v, today, &grave.date_of_death, grave.burial_date.as_deref());
+    v.finish()
+}
+
+/// Checks an update against the grave as it is now
+pub(super) fn update_grave(grave: &UpdateGraveRequest, current: &GraveWithBlock, today: NaiveDate) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("deceased_name", grave.deceased_name.as_deref(), "Deceased name");
+    v.not_blank("number", grave.number.as_deref(), "Grave number");
+    let current_death = current.date_of_death.to_string();
+    let current_burial = current.burial_date.map(|date| date.to_string());
+    burial_dates(
+        &mut v,
+        today,
+        grave.date_of_death.as_deref().unwrap_or(&current_death),
+        grave.burial_date.as_deref().or(current_burial.as_deref()),
+    );
+    v.finish()
+}
+
+pub(super) fn create_interment(interment: &CreateIntermentRequest, today: NaiveDate) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("deceased_name", &interment.deceased_name, "Deceased name");
+    burial_dates(&mut v, today, &interment.date_of_death, interment.burial_date.as_deref());
+    v.finish()
+}
+
+pub(super) fn update_interment(
+    interment: &UpdateIntermentRequest,
+    date_of_death: NaiveDate,
+    burial_date: Option<NaiveDate>,
+    today: NaiveDate,
+) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("deceased_name", interment.deceased_name.as_deref(), "Deceased name");
+    let current_death = date_of_death.to_string();
+    let current_burial = burial_date.map(|date| date.to_string());
+    burial_dates(
+        &mut v,
+        today,
+        interment.date_of_death.as_deref().unwrap_or(&current_death),
+        interment.burial_date.as_deref().or(current_burial.as_deref()),
+    );
+    v.finish()
+}
+
+fn burial_dates(v: &mut Validator, today: NaiveDate, date_of_death: &str, burial_date: Option<&str>) {
+    let death = v.date("date_of_death", date_of_death, "Date of death");
+    v.not_in_future("date_of_death", death, today, "Date of death");
+    let burial = v.optional_date("burial_date", burial_date, "Burial date");
+    v.not_in_future("burial_date", burial, today, "Burial date");
+    v.burial_after_death(burial, death);
+}
+
+/// Date entered for an unreadable stored date; `latest` is today for dates
+/// that cannot lie in the future
+pub(super) fn resolve_date_issue(date: Option<&str>, latest: Option<NaiveDate>) -> Result<(), String> {
+    let mut v = Validator::default();
+    let date = v.optional_date("date", date, "Date");
+    if let Some(today) = latest {
+        v.not_in_future("date", date, today, "Date");
+    }
+    v.finish()
+}
+
+/// Returns the E.164 phone number
+pub(super) fn create_heir(heir: &CreateHeirRequest) -> Result<Option<String>, String> {
+    let mut v = Validator::default();
+    v.required("full_name", &heir.full_name, "Full name");
+    v.between("order_number", heir.order_number, 1, MAX_HEIR_ORDER, "Heir order");
+    let phone_e164 = v.phone("phone_number", heir.phone_number.as_deref().map(str::trim));
+    v.finish().map(|_| phone_e164)
+}
+
+/// Returns the E.164 phone number when a phone number is given
+pub(super) fn update_heir(heir: &UpdateHeirRequest) -> Result<Option<String>, String> {
+    let mut v = Validator::default();
+    v.not_blank("full_name", heir.full_name.as_deref(), "Full name");
+    let phone = heir.phone_number.as_deref().map(str::trim);
+    let phone_e164 = phone.and_then(|phone| v.phone("phone_number", Some(phone)));
+    v.finish().map(|_| phone_e164)
+}
+
+/// Check a new grave and its heirs before anything is written
+///
+/// Heir fields are reported as `heirs[0].phone_number` (index in `heirs`).
+/// `today` is `Database::local_today`.
+pub fn validate_grave_with_heirs(
+    grave: &CreateGraveRequest,
+    heirs: &[CreateHeirRequest],
+    today: NaiveDate,
+) -> Result<(), String> {
+    let mut v = Validator::default();
+    for e in field_errors(create_grave(grave, today)) {
+        v.add(&e.field, &e.code, e.message);
+    }
+    add_heir_errors(&mut v, heirs);
+    v.finish()
+}
+
+/// Check heirs replacing those of a grave, reported as in `validate_grave_with_heirs`
+pub fn validate_heirs(heirs: &[CreateHeirRequest]) -> Result<(), String> {
+    let mut v = Validator::default();
+    add_heir_errors(&mut v, heirs);
+    v.finish()
+}
+
+fn add_heir_errors(v: &mut Validator, heirs: &[CreateHeirRequest]) {
+    for (index, heir) in heirs.iter().enumerate() {
+        for e in field_errors(create_heir(heir)) {
+            v.add(&format!("heirs[{}].{}", index, e.field), &e.code, e.message);
+        }
+    }
+}
+
+pub(super) fn payment(payment: &CreatePaymentRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.year("year", payment.year, "Year");
+    v.date("payment_date", &payment.payment_date, "Payment date");
+    v.not_negative("amount", payment.amount, "Amount");
+    if let Some(method) = &payment.payment_method {
+        v.one_of("payment_method", method, PAYMENT_METHODS, "Payment method");
+    }
+    v.finish()
+}
+
+pub(super) fn combined_payment(payment: &CombinedPaymentRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.date("payment_date", &payment.payment_date, "Payment date");
+    if let Some(method) = &payment.payment_method {
+        v.one_of("payment_method", method, PAYMENT_METHODS, "Payment method");
+    }
+    v.finish()
+}
+
+pub(super) fn create_waiver(waiver: &CreateWaiverRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.year("year", waiver.year, "Year");
+    v.not_negative("amount", waiver.amount, "Amount");
+    v.finish()
+}
+
+pub(super) fn relocate_grave(request: &RelocateGraveRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("to_number", &request.to_number, "Grave number");
+    v.date("relocation_date", &request.relocation_date, "Relocation date");
+    v.required("reason", &request.reason, "Relocation reason");
+    v.required("authorized_by", &request.authorized_by, "Authorizing person");
+    v.finish()
+}
+
+pub(super) fn open_cash_session(opening_float: i64) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_negative("opening_float", opening_float, "Opening float");
+    v.finish()
+}
+
+pub(super) fn close_cash_session(counted_amount: i64) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_negative("counted_amount", counted_amount, "Counted amount");
+    v.finish()
+}
+
+/// Name and number after falling back to the reservation's own
+pub(super) fn convert_reservation(deceased_name: &str, number: &str) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("deceased_name", deceased_name, "Deceased name");
+    v.required("number", number, "Grave number");
+    v.finish()
+}
+
+/// `last_closed_year`: the active year cannot go back to a closed year
+pub(super) fn update_settings(settings: &UpdateSettingsRequest, last_closed_year: Option<i32>) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("foundation_name", settings.foundation_name.as_deref(), "Foundation name");
+    v.email("email", settings.email.as_deref());
+    if let Some(year) = settings.active_year {
+        v.year("active_year", year, "Active year");
+        if let Some(closed) = last_closed_year.filter(|closed| year <= *closed) {
+            v.add(
+                "active_year",
+                "closed_year",
+                format!("Year {} is closed; the active year must be after {}", year, closed),
+            );
+        }
+    }
+    if let Some(timezone) = settings.timezone.as_deref() {
+        let names: Vec<&str> = TIMEZONES.iter().map(|t| t.name).collect();
+        v.one_of("timezone", timezone, &names, "Timezone");
+    }
+    v.finish()
+}
+
+pub(super) fn create_reservation(reservation: &CreateReservationRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("reserver_name", &reservation.reserver_name, "Reserver name");
+    v.not_negative("deposit_amount", reservation.deposit_amount, "Deposit");
+    v.optional_date("reserved_until", reservation.reserved_until.as_deref(), "Reserved until");
+    // Becomes the heir's number on conversion
+    v.phone("phone_number", reservation.phone_number.as_deref());
+    v.finish()
+}
+
+pub(super) fn update_reservation(reservation: &UpdateReservationRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("reserver_name", reservation.reserver_name.as_deref(), "Reserver name");
+    if let Some(deposit) = reservation.deposit_amount {
+        v.not_negative("deposit_amount", deposit, "Deposit");
+    }
+    v.optional_date("reserved_until", reservation.reserved_until.as_deref(), "Reserved until");
+    v.phone("phone_number", reservation.phone_number.as_deref());
+    v.finish()
+}
+
+/// Returns the E.164 phone number
+pub(super) fn create_person(person: &CreatePersonRequest) -> Result<Option<String>, String> {
+    let mut v = Validator::default();
+    v.required("full_name", &person.full_name, "Full name");
+    let phone_e164 = v.phone("phone_number", person.phone_number.as_deref().map(str::trim));
+    v.finish().map(|_| phone_e164)
+}
+
+/// Returns the E.164 phone number when a phone number is given
+pub(super) fn update_person(person: &UpdatePersonRequest) -> Result<Option<String>, String> {
+    let mut v = Validator::default();
+    v.not_blank("full_name", person.full_name.as_deref(), "Full name");
+    let phone = person.phone_number.as_deref().map(str::trim);
+    let phone_e164 = phone.and_then(|phone| v.phone("phone_number", Some(phone)));
+    v.finish().map(|_| phone_e164)
+}
+
+pub(super) fn create_user(user: &CreateUserRequest, min_password_length: usize) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.required("username", &user.username, "Username");
+    if user.username.trim().contains(char::is_whitespace) {
+        v.add("username", "invalid_username", "Username cannot contain spaces");
+    }
+    v.required("full_name", &user.full_name, "Full name");
+    if user.password.chars().count() < min_password_length {
+        v.add(
+            "password",
+            "too_short",
+            format!("Password must be at least {} characters", min_password_length),
+        );
+    }
+    v.finish()
+}
+
+pub(super) fn update_user(user: &UpdateUserRequest) -> Result<(), String> {
+    let mut v = Validator::default();
+    v.not_blank("full_name", user.full_name.as_deref(), "Full name");
+    v.finish()
+}
+
+// ==================== DATA STRUCTURES ====================
+
+/// Problem with one field of a request
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FieldError {
+    /// Request field name, e.g. `date_of_death`
+    pub field: String,
+    /// e.g. `required`, `invalid_date`, `negative`
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct ValidationErrors {
+    validation: Vec<FieldError>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{block_request, create_block, db_with_grave, grave_request, payment_request, test_db};
+
+    fn fields(error: &str) -> Vec<String> {
+        parse_validation_errors(error).unwrap().into_iter().map(|e| e.field).collect()
+    }
+
+    #[test]
+    fn test_block_request_errors_per_field() {
+        let (_dir, db) = test_db();
+        let error = db
+            .create_block(&CreateBlockRequest {
+                code: " ".to_string(),
+                annual_fee: -5,
+                status: "penuh".to_string(),
+                ..block_request("A")
+            })
+            .unwrap_err();
+        assert_eq!(fields(&error), ["code", "annual_fee", "status"]);
+    }
+
+    #[test]
+    fn test_grave_dates_are_checked() {
+        let (_dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave = |date_of_death: &str, burial_date: Option<&str>| CreateGraveRequest {
+            date_of_death: date_of_death.to_string(),
+            burial_date: burial_date.map(String::from),
+            ..grave_request(block_id, "1")
+        };
+
+        let errors = parse_validation_errors(&db.create_grave(&grave("kemarin", None)).unwrap_err()).unwrap();
+        assert_eq!(errors[0].code, "invalid_date");
+        let errors =
+            parse_validation_errors(&db.create_grave(&grave("2020-05-02", Some("2020-05-01"))).unwrap_err()).unwrap();
+        assert_eq!((errors[0].field.as_str(), errors[0].code.as_str()), ("burial_date", "before_date_of_death"));
+
+        // Missing burial date and notes are stored as NULL
+        let grave_id = db.create_grave(&grave("2020-05-02", Some(""))).unwrap();
+        let stored = db.get_grave_by_id(grave_id).unwrap().unwrap();
+        assert_eq!((stored.burial_date, stored.notes), (None, None));
+
+        let update = UpdateGraveRequest {
+            deceased_name: None,
+            block_id: None,
+            number: None,
+            date_of_death: None,
+            burial_date: Some("2020-04-30".to_string()),
+            notes: None,
+        };
+        assert!(parse_validation_errors(&db.update_grave(grave_id, &update).unwrap_err()).is_some());
+    }
+
+    #[test]
+    fn test_payment_request_errors_per_field() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        let payment = CreatePaymentRequest {
+            amount: -1,
+            payment_method: Some("cek".to_string()),
+            ..payment_request(grave_id, 2024)
+        };
+        assert_eq!(fields(&db.create_payment(&payment).unwrap_err()), ["amount", "payment_method"]);
+
+        // Other errors are not validation errors
+        assert_eq!(parse_validation_errors("Grave 7 not found"), None);
+    }
+}
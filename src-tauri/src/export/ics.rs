@@ -0,0 +1,112 @@
+//! iCalendar (ICS) generation
+//!
+//! Haul anniversaries as all-day events that can be imported into Google
+//! Calendar, Outlook or a phone calendar.
+
+use crate::db::{HaulAnniversary, Settings};
+
+/// Longest content line in octets before folding (RFC 5545)
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Build an ICS calendar of haul anniversaries
+pub fn haul_calendar(hauls: &[HaulAnniversary], settings: &Settings) -> Vec<u8> {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Astana//Kalender Haul//ID".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(&format!("Haul - {}", settings.foundation_name))),
+    ];
+
+    for haul in hauls {
+        let start = haul.haul_date.format("%Y%m%d");
+        let end = (haul.haul_date + chrono::Duration::days(1)).format("%Y%m%d");
+
+        let mut description = format!(
+            "Wafat: {} ({})\nHaul: {}\nMakam: Blok {} No. {}",
+            haul.date_of_death, haul.date_of_death_hijri.formatted, haul.haul_date_hijri.formatted, haul.block_code, haul.number
+        );
+        if !haul.heirs.is_empty() {
+            description.push_str("\nAhli waris:");
+            for heir in &haul.heirs {
+                description.push_str(&format!("\n- {}", heir.full_name));
+                if let Some(relationship) = &heir.relationship {
+                    description.push_str(&format!(" ({})", relationship));
+                }
+                if let Some(phone) = &heir.phone_number {
+                    description.push_str(&format!(", {}", phone));
+                }
+            }
+        }
+
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:haul-{}-{}@astana", haul.interment_id, haul.haul_date_hijri.year),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", start),
+            format!("DTEND;VALUE=DATE:{}", end),
+            format!(
+                "SUMMARY:{}",
+                escape_text(&format!("Haul ke-{} {}", haul.haul_number, haul.deceased_name))
+            ),
+            format!(
+                "LOCATION:{}",
+                escape_text(&format!("Blok {} No. {}, {}", haul.block_code, haul.number, settings.foundation_name))
+            ),
+            format!("DESCRIPTION:{}", escape_text(&description)),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// Escape a TEXT value
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line at 75 octets and end it with CRLF
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_and_escape() {
+        assert_eq!(escape_text("Blok A; No. 1, Jl. Kenanga\nRT 2"), "Blok A\\; No. 1\\, Jl. Kenanga\\nRT 2");
+
+        let line = format!("SUMMARY:{}", "é".repeat(50));
+        let folded = fold_line(&line);
+        assert!(folded.ends_with("\r\n"));
+        assert!(folded.split("\r\n").all(|part| part.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), line);
+    }
+}
@@ -0,0 +1,94 @@
+//! Export module for Astana
+//!
+//! Generates printable/downloadable files (PDF, XLSX and ICS) on the Rust side.
+//! Writers return the file bytes; saving is done by the Tauri commands.
+
+pub mod ics;
+pub mod pdf;
+pub mod xlsx;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::db::{self, GraveStatement, Settings};
+
+/// Supported export file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Pdf,
+    Xlsx,
+}
+
+impl ExportFormat {
+    /// File extension without dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+
+    /// Filter name shown in the save dialog
+    pub fn filter_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "PDF Files",
+            ExportFormat::Xlsx => "Excel Files",
+        }
+    }
+}
+
+/// Format amount as Rupiah with thousand separators (e.g. "Rp 1.250.000")
+pub fn format_rupiah(amount: i64) -> String {
+    let digits = amount.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push('.');
+        }
+        grouped.push(c);
+    }
+
+    if amount < 0 {
+        format!("-Rp {}", grouped)
+    } else {
+        format!("Rp {}", grouped)
+    }
+}
+
+/// Format a date the way it is printed on documents (e.g. "31/12/2023")
+pub fn format_date(date: NaiveDate) -> String {
+    date.format("%d/%m/%Y").to_string()
+}
+
+/// Date of death on a statement, flagged while it is not confirmed
+pub fn statement_date_of_death(statement: &GraveStatement) -> String {
+    let date = format_date(statement.grave.date_of_death);
+    if statement.date_unconfirmed {
+        format!("{} (belum dikonfirmasi)", date)
+    } else {
+        date
+    }
+}
+
+/// Format a stored UTC timestamp in the configured timezone (e.g. "31/12/2023 14:05 WIB")
+pub fn format_timestamp(timestamp: DateTime<Utc>, settings: &Settings) -> String {
+    let timezone = db::timezone(&settings.timezone).unwrap_or(&db::TIMEZONES[0]);
+    format!(
+        "{} {}",
+        timestamp.with_timezone(&timezone.offset()).format("%d/%m/%Y %H:%M"),
+        timezone.abbreviation
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rupiah() {
+        assert_eq!(format_rupiah(0), "Rp 0");
+        assert_eq!(format_rupiah(150000), "Rp 150.000");
+        assert_eq!(format_rupiah(1250000), "Rp 1.250.000");
+        assert_eq!(format_rupiah(-50000), "-Rp 50.000");
+    }
+}
@@ -0,0 +1,369 @@
+//! PDF writers
+//!
+//! Uses the built-in Helvetica fonts, so documents render without bundling
+//! font files. Layout is a simple A4 page with a header and a table.
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+
+use super::{format_date, format_rupiah, format_timestamp, statement_date_of_death};
+use crate::db::{CashClosingReport, GraveStatement, Settings};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 6.0;
+
+/// Simple page-flowing document writer
+pub(crate) struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    /// Current vertical position (from bottom of page, in mm)
+    y: f32,
+}
+
+impl PdfWriter {
+    pub fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let font = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| format!("Failed to load PDF font: {}", e))?;
+        let bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| format!("Failed to load PDF font: {}", e))?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(Self {
+            doc,
+            layer,
+            font,
+            bold,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    /// Start a new page when less than `needed` mm is left
+    fn ensure_space(&mut self, needed: f32) {
+        if self.y - needed < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    /// Write a line of text and move down
+    pub fn text(&mut self, text: &str, size: f32, bold: bool) {
+        let height = size * 0.5;
+        self.ensure_space(height);
+        self.y -= height;
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer.use_text(text, size, Mm(MARGIN), Mm(self.y), font);
+    }
+
+    /// Write a label/value pair on one line
+    pub fn field(&mut self, label: &str, value: &str) {
+        self.ensure_space(ROW_HEIGHT);
+        self.y -= ROW_HEIGHT;
+        self.layer.use_text(label, 10.0, Mm(MARGIN), Mm(self.y), &self.bold);
+        self.layer
+            .use_text(value, 10.0, Mm(MARGIN + 40.0), Mm(self.y), &self.font);
+    }
+
+    /// Add vertical space
+    pub fn space(&mut self, mm: f32) {
+        self.y -= mm;
+    }
+
+    /// Draw a horizontal rule across the page
+    pub fn rule(&mut self) {
+        self.y -= 1.5;
+        let line = Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        };
+        self.layer.add_line(line);
+    }
+
+    /// Write a table; the header is repeated on every page
+    ///
+    /// `columns` holds header text and column width in mm.
+    pub fn table(&mut self, columns: &[(&str, f32)], rows: &[Vec<String>], bold_last_row: bool) {
+        self.table_header(columns);
+        for (i, row) in rows.iter().enumerate() {
+            if self.y - ROW_HEIGHT < MARGIN {
+                self.ensure_space(ROW_HEIGHT);
+                self.table_header(columns);
+            }
+            self.y -= ROW_HEIGHT;
+            let font = if bold_last_row && i == rows.len() - 1 {
+                &self.bold
+            } else {
+                &self.font
+            };
+            let mut x = MARGIN;
+            for ((_, width), cell) in columns.iter().zip(row) {
+                self.layer.use_text(cell, 8.0, Mm(x + 1.0), Mm(self.y), font);
+                x += width;
+            }
+        }
+    }
+
+    fn table_header(&mut self, columns: &[(&str, f32)]) {
+        self.ensure_space(ROW_HEIGHT * 2.0);
+        self.y -= ROW_HEIGHT;
+        let mut x = MARGIN;
+        for (title, width) in columns {
+            self.layer.use_text(*title, 8.0, Mm(x + 1.0), Mm(self.y), &self.bold);
+            x += width;
+        }
+        self.rule();
+    }
+
+    /// Write the foundation letterhead
+    pub fn letterhead(&mut self, settings: &Settings, title: &str) {
+        self.text(&settings.foundation_name, 14.0, true);
+        let contact: Vec<&str> = [settings.address.as_deref(), settings.phone.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !contact.is_empty() {
+            self.text(&contact.join(" - "), 9.0, false);
+        }
+        self.rule();
+        self.space(4.0);
+        self.text(title, 12.0, true);
+        self.space(2.0);
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        self.doc
+            .save_to_bytes()
+            .map_err(|e| format!("Failed to write PDF file: {}", e))
+    }
+}
+
+/// Statement of account for a single grave
+pub fn grave_statement(statement: &GraveStatement, settings: &Settings) -> Result<Vec<u8>, String> {
+    let grave = &statement.grave;
+    let mut pdf = PdfWriter::new("Rekening Iuran Makam")?;
+    pdf.letterhead(settings, "REKENING IURAN MAKAM");
+
+    let primary_heir = statement
+        .heirs
+        .iter()
+        .find(|h| h.is_primary)
+        .or_else(|| statement.heirs.first());
+    pdf.field("Nama Almarhum/ah", &grave.deceased_name);
+    pdf.field("Lokasi", &format!("Blok {} No. {}", grave.code, grave.number));
+    pdf.field("Tanggal Wafat", &statement_date_of_death(statement));
+    if let Some(heir) = primary_heir {
+        pdf.field("Ahli Waris", &heir.full_name);
+    }
+    pdf.field("Iuran per Tahun", &format_rupiah(grave.annual_fee));
+    pdf.field("Dicetak", &statement.generated_at);
+    pdf.space(4.0);
+
+    let columns = [
+        ("Tahun", 14.0),
+        ("Iuran", 24.0),
+        ("Tgl Bayar", 22.0),
+        ("Jumlah", 24.0),
+        ("Metode", 18.0),
+        ("No. Kwitansi", 28.0),
+        ("Dispensasi", 24.0),
+        ("Saldo", 26.0),
+    ];
+
+    let mut rows = Vec::new();
+    for line in &statement.lines {
+        let row_count = line.payments.len().max(1);
+        for i in 0..row_count {
+            let payment = line.payments.get(i);
+            let first = i == 0;
+            let last = i == row_count - 1;
+            rows.push(vec![
+                if first { line.year.to_string() } else { String::new() },
+                if first { format_rupiah(line.fee_due) } else { String::new() },
+                payment.map(|p| format_date(p.payment_date)).unwrap_or_else(|| "-".to_string()),
+                payment.map(|p| format_rupiah(p.amount)).unwrap_or_default(),
+                payment
+                    .and_then(|p| p.payment_method.clone())
+                    .unwrap_or_default(),
+                payment.map(|p| p.receipt_number.clone()).unwrap_or_default(),
+                if first && line.waived > 0 {
+                    format_rupiah(line.waived)
+                } else {
+                    String::new()
+                },
+                if last { format_rupiah(line.balance) } else { String::new() },
+            ]);
+        }
+    }
+    rows.push(vec![
+        "Total".to_string(),
+        format_rupiah(statement.total_due),
+        String::new(),
+        format_rupiah(statement.total_paid),
+        String::new(),
+        String::new(),
+        format_rupiah(statement.total_waived),
+        format_rupiah(statement.balance),
+    ]);
+    pdf.table(&columns, &rows, true);
+
+    pdf.space(6.0);
+    let summary = if statement.balance > 0 {
+        format!("Tunggakan: {}", format_rupiah(statement.balance))
+    } else if statement.balance < 0 {
+        format!("Kelebihan bayar: {}", format_rupiah(-statement.balance))
+    } else {
+        "Lunas".to_string()
+    };
+    pdf.text(&summary, 11.0, true);
+
+    // Waiver reasons as footnotes
+    let waived_lines: Vec<_> = statement
+        .lines
+        .iter()
+        .filter(|l| l.waived > 0)
+        .collect();
+    if !waived_lines.is_empty() {
+        pdf.space(4.0);
+        pdf.text("Keterangan dispensasi:", 9.0, true);
+        for line in waived_lines {
+            pdf.text(
+                &format!(
+                    "{}: {}",
+                    line.year,
+                    line.waiver_reason.as_deref().unwrap_or("-")
+                ),
+                9.0,
+                false,
+            );
+        }
+    }
+
+    pdf.finish()
+}
+
+/// Cash closing report (daily hand-over of collected cash)
+pub fn cash_closing(report: &CashClosingReport, settings: &Settings) -> Result<Vec<u8>, String> {
+    let session = &report.session;
+    let mut pdf = PdfWriter::new("Laporan Tutup Kas")?;
+    pdf.letterhead(settings, "LAPORAN TUTUP KAS");
+
+    pdf.field("No. Sesi", &session.id.to_string());
+    pdf.field("Kasir", session.opened_by_name.as_deref().unwrap_or("-"));
+    pdf.field("Dibuka", &format_timestamp(session.opened_at, settings));
+    pdf.field(
+        "Ditutup",
+        &session
+            .closed_at
+            .map(|closed_at| format_timestamp(closed_at, settings))
+            .unwrap_or_else(|| "Belum ditutup".to_string()),
+    );
+    pdf.space(4.0);
+
+    let columns = [
+        ("No. Kwitansi", 28.0),
+        ("Almarhum/ah", 50.0),
+        ("Lokasi", 24.0),
+        ("Tahun", 14.0),
+        ("Dibayar oleh", 32.0),
+        ("Jumlah", 32.0),
+    ];
+    let mut rows: Vec<Vec<String>> = report
+        .payments
+        .iter()
+        .map(|p| {
+            vec![
+                p.receipt_number.clone(),
+                p.deceased_name.clone(),
+                format!("{} / {}", p.block_code, p.grave_number),
+                p.year.to_string(),
+                p.paid_by.clone().unwrap_or_default(),
+                format_rupiah(p.amount),
+            ]
+        })
+        .collect();
+    rows.push(vec![
+        format!("{} transaksi", report.payments.len()),
+        String::new(),
+        String::new(),
+        String::new(),
+        "Total tunai".to_string(),
+        format_rupiah(session.cash_total),
+    ]);
+    pdf.table(&columns, &rows, true);
+
+    pdf.space(6.0);
+    let expected = session
+        .expected_amount
+        .unwrap_or(session.opening_float + session.cash_total);
+    pdf.field("Modal Awal", &format_rupiah(session.opening_float));
+    pdf.field("Seharusnya", &format_rupiah(expected));
+    if let Some(counted) = session.counted_amount {
+        pdf.field("Uang Dihitung", &format_rupiah(counted));
+    }
+    if let Some(difference) = session.difference {
+        let label = match difference {
+            d if d < 0 => format!("Kurang {}", format_rupiah(-d)),
+            d if d > 0 => format!("Lebih {}", format_rupiah(d)),
+            _ => "Sesuai".to_string(),
+        };
+        pdf.field("Selisih", &label);
+    }
+    if let Some(notes) = session.notes.as_deref().filter(|n| !n.is_empty()) {
+        pdf.field("Catatan", notes);
+    }
+
+    pdf.space(16.0);
+    pdf.field("Diserahkan", session.opened_by_name.as_deref().unwrap_or("-"));
+    pdf.field("Diterima", session.closed_by_name.as_deref().unwrap_or("-"));
+
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{db_with_grave, payment_request};
+    use crate::db::CreateWaiverRequest;
+
+    /// Whether the document draws `text` (builtin fonts write it as a hex string)
+    fn shows(pdf: &[u8], text: &str) -> bool {
+        let hex: String = text.bytes().map(|b| format!("{:02X}", b)).collect();
+        String::from_utf8_lossy(pdf).contains(&format!("<{}> Tj", hex))
+    }
+
+    #[test]
+    fn test_grave_statement_pdf() {
+        let (_dir, db, _, grave_id) = db_with_grave();
+        db.create_payment(&payment_request(grave_id, 2020)).unwrap();
+        db.create_waiver(&CreateWaiverRequest {
+            grave_id,
+            year: 2021,
+            amount: 100000,
+            reason: Some("Keluarga tidak mampu".to_string()),
+            approved_by: None,
+        })
+        .unwrap();
+        let statement = db.get_grave_statement(grave_id).unwrap();
+
+        let pdf = grave_statement(&statement, &db.get_settings().unwrap()).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(shows(&pdf, "Almarhum 1"));
+        assert!(shows(&pdf, "Blok A No. 1"));
+        assert!(shows(&pdf, "KW/2020/000001"));
+        assert!(shows(&pdf, "2021: Keluarga tidak mampu"));
+        assert!(shows(&pdf, &format!("Tunggakan: {}", format_rupiah(statement.balance))));
+    }
+}
@@ -0,0 +1,701 @@
+//! XLSX writers
+
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{
+    ExcelDateTime, Format, FormatAlign, FormatBorder, Workbook, Worksheet, XlsxError,
+};
+
+use super::statement_date_of_death;
+use crate::db::{GraveExportData, GraveStatement, Settings, YearlyReport};
+
+/// Number format for Rupiah amounts
+const RUPIAH_FORMAT: &str = "\"Rp\" #,##0;[Red]-\"Rp\" #,##0";
+
+/// Number format for percentages already scaled to 0-100
+const PERCENT_FORMAT: &str = "0.0\"%\"";
+
+/// Shared cell formats so every workbook looks the same
+pub(crate) struct Styles {
+    pub title: Format,
+    pub subtitle: Format,
+    pub header: Format,
+    pub text: Format,
+    pub number: Format,
+    pub currency: Format,
+    pub date: Format,
+    pub percent: Format,
+    pub paid: Format,
+    pub unpaid: Format,
+    pub total_label: Format,
+    pub total_currency: Format,
+    pub total_percent: Format,
+}
+
+impl Styles {
+    pub fn new() -> Self {
+        let header = Format::new()
+            .set_bold()
+            .set_align(FormatAlign::Center)
+            .set_align(FormatAlign::VerticalCenter)
+            .set_text_wrap()
+            .set_background_color(0xD9EAD3)
+            .set_border(FormatBorder::Thin);
+
+        Self {
+            title: Format::new().set_bold().set_font_size(14),
+            subtitle: Format::new().set_italic(),
+            header,
+            text: Format::new().set_border(FormatBorder::Thin),
+            number: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_align(FormatAlign::Center),
+            currency: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_num_format(RUPIAH_FORMAT),
+            date: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_align(FormatAlign::Center)
+                .set_num_format("dd/mm/yyyy"),
+            percent: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_num_format(PERCENT_FORMAT),
+            paid: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_font_color(0x38761D),
+            unpaid: Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_font_color(0xCC0000),
+            total_label: Format::new()
+                .set_bold()
+                .set_border(FormatBorder::Thin)
+                .set_background_color(0xF3F3F3),
+            total_currency: Format::new()
+                .set_bold()
+                .set_border(FormatBorder::Thin)
+                .set_background_color(0xF3F3F3)
+                .set_num_format(RUPIAH_FORMAT),
+            total_percent: Format::new()
+                .set_bold()
+                .set_border(FormatBorder::Thin)
+                .set_background_color(0xF3F3F3)
+                .set_num_format(PERCENT_FORMAT),
+        }
+    }
+}
+
+/// Map XLSX writer error to the String errors used by commands
+pub(crate) fn xlsx_err(e: XlsxError) -> String {
+    format!("Failed to write Excel file: {}", e)
+}
+
+/// Write foundation name and document title on top of a sheet.
+/// Returns the next free row.
+pub(crate) fn write_sheet_header(
+    sheet: &mut Worksheet,
+    styles: &Styles,
+    settings: &Settings,
+    title: &str,
+) -> Result<u32, XlsxError> {
+    sheet.write_string_with_format(0, 0, &settings.foundation_name, &styles.title)?;
+    sheet.write_string_with_format(1, 0, title, &styles.subtitle)?;
+    Ok(3)
+}
+
+/// Write a date as a real Excel date, or "-" when empty
+fn write_date(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    date: Option<NaiveDate>,
+    styles: &Styles,
+) -> Result<(), XlsxError> {
+    match date {
+        Some(date) => {
+            let excel_date = ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)?;
+            sheet.write_datetime_with_format(row, col, &excel_date, &styles.date)?;
+        }
+        None => {
+            sheet.write_string_with_format(row, col, "-", &styles.number)?;
+        }
+    }
+    Ok(())
+}
+
+/// Human readable heir relationship
+fn format_relationship(relationship: Option<&str>) -> String {
+    match relationship.unwrap_or("") {
+        "" => "-".to_string(),
+        "anak" => "Anak".to_string(),
+        "istri" => "Istri".to_string(),
+        "suami" => "Suami".to_string(),
+        "cucu" => "Cucu".to_string(),
+        "saudara" => "Saudara".to_string(),
+        "lainnya" => "Lainnya".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Statement of account for a single grave
+pub fn grave_statement(statement: &GraveStatement, settings: &Settings) -> Result<Vec<u8>, String> {
+    let styles = Styles::new();
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Rekening").map_err(xlsx_err)?;
+
+    write_statement_sheet(sheet, &styles, statement, settings).map_err(xlsx_err)?;
+
+    workbook.save_to_buffer().map_err(xlsx_err)
+}
+
+fn write_statement_sheet(
+    sheet: &mut Worksheet,
+    styles: &Styles,
+    statement: &GraveStatement,
+    settings: &Settings,
+) -> Result<(), XlsxError> {
+    let grave = &statement.grave;
+    let mut row = write_sheet_header(sheet, styles, settings, "Rekening Iuran Makam")?;
+
+    // Grave identity
+    let primary_heir = statement
+        .heirs
+        .iter()
+        .find(|h| h.is_primary)
+        .or_else(|| statement.heirs.first());
+    let identity = [
+        ("Nama Almarhum/ah", grave.deceased_name.clone()),
+        ("Lokasi", format!("Blok {} No. {}", grave.code, grave.number)),
+        ("Tanggal Wafat", statement_date_of_death(statement)),
+        ("Ahli Waris", primary_heir.map(|h| h.full_name.clone()).unwrap_or_default()),
+        ("Dicetak", statement.generated_at.clone()),
+    ];
+    for (label, value) in identity {
+        sheet.write_string(row, 0, label)?;
+        sheet.write_string(row, 1, value)?;
+        row += 1;
+    }
+    row += 1;
+
+    // Table header
+    let headers = [
+        "Tahun",
+        "Iuran",
+        "Tanggal Bayar",
+        "Jumlah Bayar",
+        "Metode",
+        "No. Kwitansi",
+        "Dispensasi",
+        "Saldo",
+    ];
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(row, col as u16, *header, &styles.header)?;
+    }
+    sheet.set_freeze_panes(row + 1, 0)?;
+    row += 1;
+
+    for line in &statement.lines {
+        // One row per payment; years without payment still get one row
+        let row_count = line.payments.len().max(1);
+        for i in 0..row_count {
+            let payment = line.payments.get(i);
+            if i == 0 {
+                sheet.write_number_with_format(row, 0, line.year, &styles.number)?;
+                sheet.write_number_with_format(row, 1, line.fee_due as f64, &styles.currency)?;
+            } else {
+                sheet.write_blank(row, 0, &styles.number)?;
+                sheet.write_blank(row, 1, &styles.currency)?;
+            }
+
+            match payment {
+                Some(p) => {
+                    write_date(sheet, row, 2, Some(p.payment_date), styles)?;
+                    sheet.write_number_with_format(row, 3, p.amount as f64, &styles.currency)?;
+                    sheet.write_string_with_format(
+                        row,
+                        4,
+                        p.payment_method.as_deref().unwrap_or(""),
+                        &styles.text,
+                    )?;
+                    sheet.write_string_with_format(row, 5, &p.receipt_number, &styles.text)?;
+                }
+                None => {
+                    sheet.write_blank(row, 2, &styles.text)?;
+                    sheet.write_blank(row, 3, &styles.currency)?;
+                    sheet.write_blank(row, 4, &styles.text)?;
+                    sheet.write_blank(row, 5, &styles.text)?;
+                }
+            }
+
+            if i == 0 && line.waived > 0 {
+                sheet.write_number_with_format(row, 6, line.waived as f64, &styles.currency)?;
+            } else {
+                sheet.write_blank(row, 6, &styles.currency)?;
+            }
+
+            if i == row_count - 1 {
+                sheet.write_number_with_format(row, 7, line.balance as f64, &styles.currency)?;
+            } else {
+                sheet.write_blank(row, 7, &styles.currency)?;
+            }
+            row += 1;
+        }
+    }
+
+    // Totals
+    sheet.write_string_with_format(row, 0, "Total", &styles.total_label)?;
+    sheet.write_number_with_format(row, 1, statement.total_due as f64, &styles.total_currency)?;
+    sheet.write_blank(row, 2, &styles.total_label)?;
+    sheet.write_number_with_format(row, 3, statement.total_paid as f64, &styles.total_currency)?;
+    sheet.write_blank(row, 4, &styles.total_label)?;
+    sheet.write_blank(row, 5, &styles.total_label)?;
+    sheet.write_number_with_format(row, 6, statement.total_waived as f64, &styles.total_currency)?;
+    sheet.write_number_with_format(row, 7, statement.balance as f64, &styles.total_currency)?;
+
+    // First column also holds the identity labels
+    let widths = [18, 14, 14, 14, 12, 16, 14, 14];
+    for (col, width) in widths.iter().enumerate() {
+        sheet.set_column_width(col as u16, *width)?;
+    }
+
+    Ok(())
+}
+
+// ==================== EXCEL EXPORT WORKBOOK ====================
+
+/// Sheets that can be included in an Excel export workbook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExcelSheet {
+    /// Grave data with heirs and per-year payments
+    Graves,
+    /// Payment overview per grave and year
+    Payments,
+    /// Yearly report per block
+    YearlyReport,
+}
+
+impl ExcelSheet {
+    fn sheet_name(&self) -> &'static str {
+        match self {
+            ExcelSheet::Graves => "Data Makam",
+            ExcelSheet::Payments => "Data Pembayaran",
+            ExcelSheet::YearlyReport => "Laporan Tahunan",
+        }
+    }
+}
+
+/// Data used to fill an Excel export workbook
+pub struct WorkbookData<'a> {
+    pub settings: &'a Settings,
+    pub graves: &'a [GraveExportData],
+    pub start_year: i32,
+    pub end_year: i32,
+    /// Unpaid years up to and including this are arrears (as on the
+    /// statement of account), later years are not yet due
+    pub current_year: i32,
+    pub yearly_report: Option<&'a YearlyReport>,
+}
+
+/// Build a workbook with one worksheet per requested sheet
+pub fn workbook(sheets: &[ExcelSheet], data: &WorkbookData) -> Result<Vec<u8>, String> {
+    if sheets.is_empty() {
+        return Err("No sheet selected for export".to_string());
+    }
+
+    let styles = Styles::new();
+    let mut workbook = Workbook::new();
+
+    for sheet_kind in sheets {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(sheet_kind.sheet_name()).map_err(xlsx_err)?;
+
+        match sheet_kind {
+            ExcelSheet::Graves => write_graves_sheet(sheet, &styles, data),
+            ExcelSheet::Payments => write_payments_sheet(sheet, &styles, data),
+            ExcelSheet::YearlyReport => match data.yearly_report {
+                Some(report) => write_yearly_report_sheet(sheet, &styles, data.settings, report),
+                None => return Err("Yearly report data is missing".to_string()),
+            },
+        }
+        .map_err(xlsx_err)?;
+    }
+
+    workbook.save_to_buffer().map_err(xlsx_err)
+}
+
+/// Grave data with heirs (up to 3) and per-year payment columns
+fn write_graves_sheet(
+    sheet: &mut Worksheet,
+    styles: &Styles,
+    data: &WorkbookData,
+) -> Result<(), XlsxError> {
+    let title = format!("Data Makam {} - {}", data.start_year, data.end_year);
+    let header_row = write_sheet_header(sheet, styles, data.settings, &title)?;
+    let sub_row = header_row + 1;
+
+    let mut columns: Vec<(&str, f64)> = vec![
+        ("No", 5.0),
+        ("Nama Almarhum", 25.0),
+        ("Blok", 8.0),
+        ("Nomor Makam", 12.0),
+        ("Tanggal Wafat", 14.0),
+        ("Tanggal Pemakaman", 14.0),
+        ("Iuran Tahunan", 15.0),
+        ("Catatan", 20.0),
+    ];
+    for _ in 0..3 {
+        columns.extend([
+            ("Ahli Waris", 20.0),
+            ("No. HP", 15.0),
+            ("Hubungan", 12.0),
+            ("Alamat", 25.0),
+        ]);
+    }
+
+    // Fixed columns span both header rows
+    for (col, (title, width)) in columns.iter().enumerate() {
+        let col = col as u16;
+        let title = if (8..20).contains(&col) {
+            format!("{} {}", title, (col - 8) / 4 + 1)
+        } else {
+            title.to_string()
+        };
+        sheet.merge_range(header_row, col, sub_row, col, &title, &styles.header)?;
+        sheet.set_column_width(col, *width)?;
+    }
+
+    // Year columns: merged year header over status / payment date / amount
+    let year_col_start = columns.len() as u16;
+    for (i, year) in (data.start_year..=data.end_year).enumerate() {
+        let col = year_col_start + i as u16 * 3;
+        sheet.merge_range(header_row, col, header_row, col + 2, &year.to_string(), &styles.header)?;
+        sheet.write_string_with_format(sub_row, col, "Status", &styles.header)?;
+        sheet.write_string_with_format(sub_row, col + 1, "Tgl Bayar", &styles.header)?;
+        sheet.write_string_with_format(sub_row, col + 2, "Jumlah", &styles.header)?;
+        sheet.set_column_width(col, 18)?;
+        sheet.set_column_width(col + 1, 12)?;
+        sheet.set_column_width(col + 2, 14)?;
+    }
+
+    let year_count = (data.end_year - data.start_year + 1).max(0) as u16;
+    let summary_col = year_col_start + year_count * 3;
+    sheet.merge_range(header_row, summary_col, sub_row, summary_col, "Total Dibayar", &styles.header)?;
+    sheet.merge_range(
+        header_row,
+        summary_col + 1,
+        sub_row,
+        summary_col + 1,
+        "Jumlah Tahun Lunas",
+        &styles.header,
+    )?;
+    sheet.set_column_width(summary_col, 15)?;
+    sheet.set_column_width(summary_col + 1, 10)?;
+    sheet.set_freeze_panes(sub_row + 1, 2)?;
+
+    for (index, grave) in data.graves.iter().enumerate() {
+        let row = sub_row + 1 + index as u32;
+        sheet.write_number_with_format(row, 0, (index + 1) as f64, &styles.number)?;
+        sheet.write_string_with_format(row, 1, &grave.deceased_name, &styles.text)?;
+        sheet.write_string_with_format(row, 2, &grave.block_code, &styles.number)?;
+        sheet.write_string_with_format(row, 3, &grave.number, &styles.number)?;
+        write_date(sheet, row, 4, Some(grave.date_of_death), styles)?;
+        write_date(sheet, row, 5, grave.burial_date, styles)?;
+        sheet.write_number_with_format(row, 6, grave.annual_fee as f64, &styles.currency)?;
+        sheet.write_string_with_format(
+            row,
+            7,
+            grave.notes.as_deref().filter(|n| !n.is_empty()).unwrap_or("-"),
+            &styles.text,
+        )?;
+
+        for i in 0..3u16 {
+            let col = 8 + i * 4;
+            match grave.heirs.get(i as usize) {
+                Some(heir) => {
+                    sheet.write_string_with_format(row, col, &heir.full_name, &styles.text)?;
+                    sheet.write_string_with_format(
+                        row,
+                        col + 1,
+                        heir.phone_number.as_deref().filter(|p| !p.is_empty()).unwrap_or("-"),
+                        &styles.text,
+                    )?;
+                    sheet.write_string_with_format(
+                        row,
+                        col + 2,
+                        format_relationship(heir.relationship.as_deref()),
+                        &styles.text,
+                    )?;
+                    sheet.write_string_with_format(
+                        row,
+                        col + 3,
+                        heir.address.as_deref().filter(|a| !a.is_empty()).unwrap_or("-"),
+                        &styles.text,
+                    )?;
+                }
+                None => {
+                    for offset in 0..4 {
+                        sheet.write_string_with_format(row, col + offset, "-", &styles.text)?;
+                    }
+                }
+            }
+        }
+
+        let mut total_paid = 0;
+        let mut years_paid = 0;
+        for (i, year) in (data.start_year..=data.end_year).enumerate() {
+            let col = year_col_start + i as u16 * 3;
+            match grave.payments.iter().find(|p| p.year == year) {
+                Some(payment) => {
+                    sheet.write_string_with_format(row, col, "Lunas", &styles.paid)?;
+                    write_date(sheet, row, col + 1, Some(payment.payment_date), styles)?;
+                    sheet.write_number_with_format(row, col + 2, payment.amount as f64, &styles.currency)?;
+                    total_paid += payment.amount;
+                    years_paid += 1;
+                }
+                None => {
+                    if grave.open_fee(year, data.current_year) > 0 {
+                        sheet.write_string_with_format(row, col, "Tunggakan", &styles.unpaid)?;
+                    } else if year > data.current_year {
+                        sheet.write_string_with_format(row, col, "Belum Jatuh Tempo", &styles.text)?;
+                    } else if grave.waivers.iter().any(|w| w.year == year) {
+                        sheet.write_string_with_format(row, col, "Dibebaskan", &styles.text)?;
+                    } else {
+                        sheet.write_string_with_format(row, col, "-", &styles.text)?;
+                    }
+                    sheet.write_string_with_format(row, col + 1, "-", &styles.number)?;
+                    sheet.write_blank(row, col + 2, &styles.currency)?;
+                }
+            }
+        }
+
+        sheet.write_number_with_format(row, summary_col, total_paid as f64, &styles.currency)?;
+        sheet.write_number_with_format(row, summary_col + 1, years_paid as f64, &styles.number)?;
+    }
+
+    Ok(())
+}
+
+/// Payment overview: one row per grave, one column per year
+fn write_payments_sheet(
+    sheet: &mut Worksheet,
+    styles: &Styles,
+    data: &WorkbookData,
+) -> Result<(), XlsxError> {
+    let title = format!("Data Pembayaran {} - {}", data.start_year, data.end_year);
+    let header_row = write_sheet_header(sheet, styles, data.settings, &title)?;
+
+    let fixed = [
+        ("No", 5.0),
+        ("Nama Almarhum", 25.0),
+        ("Blok", 8.0),
+        ("Nomor Makam", 12.0),
+        ("Iuran Tahunan", 15.0),
+    ];
+    for (col, (title, width)) in fixed.iter().enumerate() {
+        sheet.write_string_with_format(header_row, col as u16, *title, &styles.header)?;
+        sheet.set_column_width(col as u16, *width)?;
+    }
+
+    let year_col_start = fixed.len() as u16;
+    for (i, year) in (data.start_year..=data.end_year).enumerate() {
+        let col = year_col_start + i as u16;
+        sheet.write_string_with_format(header_row, col, year.to_string(), &styles.header)?;
+        sheet.set_column_width(col, 15)?;
+    }
+    let year_count = (data.end_year - data.start_year + 1).max(0) as u16;
+    let summary_col = year_col_start + year_count;
+    sheet.write_string_with_format(header_row, summary_col, "Total Dibayar", &styles.header)?;
+    sheet.write_string_with_format(header_row, summary_col + 1, "Jumlah Tahun Lunas", &styles.header)?;
+    sheet.write_string_with_format(header_row, summary_col + 2, "Tunggakan", &styles.header)?;
+    sheet.set_column_width(summary_col, 15)?;
+    sheet.set_column_width(summary_col + 1, 10)?;
+    sheet.set_column_width(summary_col + 2, 15)?;
+    sheet.set_freeze_panes(header_row + 1, 2)?;
+
+    let mut row = header_row + 1;
+    let first_data_row = row;
+    for (index, grave) in data.graves.iter().enumerate() {
+        sheet.write_number_with_format(row, 0, (index + 1) as f64, &styles.number)?;
+        sheet.write_string_with_format(row, 1, &grave.deceased_name, &styles.text)?;
+        sheet.write_string_with_format(row, 2, &grave.block_code, &styles.number)?;
+        sheet.write_string_with_format(row, 3, &grave.number, &styles.number)?;
+        sheet.write_number_with_format(row, 4, grave.annual_fee as f64, &styles.currency)?;
+
+        let mut total_paid = 0;
+        let mut years_paid = 0;
+        let mut arrears = 0;
+        for (i, year) in (data.start_year..=data.end_year).enumerate() {
+            let col = year_col_start + i as u16;
+            match grave.payments.iter().find(|p| p.year == year) {
+                Some(payment) => {
+                    sheet.write_number_with_format(row, col, payment.amount as f64, &styles.currency)?;
+                    total_paid += payment.amount;
+                    years_paid += 1;
+                }
+                None if grave.open_fee(year, data.current_year) > 0 => {
+                    sheet.write_string_with_format(row, col, "Belum Bayar", &styles.unpaid)?;
+                }
+                None if grave.waivers.iter().any(|w| w.year == year) => {
+                    sheet.write_string_with_format(row, col, "Dibebaskan", &styles.text)?;
+                }
+                None => {
+                    sheet.write_string_with_format(row, col, "-", &styles.text)?;
+                }
+            }
+            arrears += grave.open_fee(year, data.current_year);
+        }
+
+        sheet.write_number_with_format(row, summary_col, total_paid as f64, &styles.currency)?;
+        sheet.write_number_with_format(row, summary_col + 1, years_paid as f64, &styles.number)?;
+        sheet.write_number_with_format(row, summary_col + 2, arrears as f64, &styles.currency)?;
+        row += 1;
+    }
+
+    // Totals row with formulas so edits in Excel stay consistent
+    if row > first_data_row {
+        sheet.merge_range(row, 0, row, 3, "Total", &styles.total_label)?;
+        for col in 4..summary_col + 3 {
+            if col == summary_col + 1 {
+                sheet.write_blank(row, col, &styles.total_label)?;
+                continue;
+            }
+            let letter = rust_xlsxwriter::utility::column_number_to_name(col);
+            let formula = format!("=SUM({0}{1}:{0}{2})", letter, first_data_row + 1, row);
+            sheet.write_formula_with_format(row, col, formula.as_str(), &styles.total_currency)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Yearly report per block
+fn write_yearly_report_sheet(
+    sheet: &mut Worksheet,
+    styles: &Styles,
+    settings: &Settings,
+    report: &YearlyReport,
+) -> Result<(), XlsxError> {
+    let title = format!("Laporan Tahunan {}", report.year);
+    let mut row = write_sheet_header(sheet, styles, settings, &title)?;
+
+    let columns = [
+        ("Blok", 10.0),
+        ("Jumlah Makam", 12.0),
+        ("Makam Baru", 12.0),
+        ("Lunas", 10.0),
+        ("Belum Bayar", 12.0),
+        ("Iuran Tahunan", 15.0),
+        ("Pendapatan", 16.0),
+        ("Target Pendapatan", 18.0),
+        ("Tingkat Penagihan", 14.0),
+    ];
+    for (col, (title, width)) in columns.iter().enumerate() {
+        sheet.write_string_with_format(row, col as u16, *title, &styles.header)?;
+        sheet.set_column_width(col as u16, *width)?;
+    }
+    row += 1;
+
+    for block in &report.block_reports {
+        let new_graves = report
+            .new_graves_per_block
+            .get(&block.block_id)
+            .copied()
+            .unwrap_or(0);
+        sheet.write_string_with_format(row, 0, &block.block_code, &styles.number)?;
+        sheet.write_number_with_format(row, 1, block.total_graves as f64, &styles.number)?;
+        sheet.write_number_with_format(row, 2, new_graves as f64, &styles.number)?;
+        sheet.write_number_with_format(row, 3, block.paid_count as f64, &styles.number)?;
+        sheet.write_number_with_format(row, 4, block.unpaid_count as f64, &styles.number)?;
+        sheet.write_number_with_format(row, 5, block.annual_fee as f64, &styles.currency)?;
+        sheet.write_number_with_format(row, 6, block.total_revenue as f64, &styles.currency)?;
+        sheet.write_number_with_format(row, 7, block.expected_revenue as f64, &styles.currency)?;
+        sheet.write_number_with_format(row, 8, block.collection_rate, &styles.percent)?;
+        row += 1;
+    }
+
+    sheet.write_string_with_format(row, 0, "Total", &styles.total_label)?;
+    sheet.write_number_with_format(row, 1, report.total_graves as f64, &styles.total_label)?;
+    sheet.write_number_with_format(row, 2, report.new_graves_count as f64, &styles.total_label)?;
+    sheet.write_number_with_format(row, 3, report.total_paid as f64, &styles.total_label)?;
+    sheet.write_number_with_format(row, 4, report.total_unpaid as f64, &styles.total_label)?;
+    sheet.write_blank(row, 5, &styles.total_label)?;
+    sheet.write_number_with_format(row, 6, report.total_revenue as f64, &styles.total_currency)?;
+    sheet.write_number_with_format(row, 7, report.total_expected_revenue as f64, &styles.total_currency)?;
+    sheet.write_number_with_format(
+        row,
+        8,
+        report.overall_collection_rate,
+        &styles.total_percent,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{create_block, grave_request, payment_request, test_db};
+    use crate::db::{CreateGraveRequest, CreatePaymentRequest, CreateWaiverRequest, Database};
+
+    /// Siti Aminah at A-12, died 2023, paid 2024 and waived 2025
+    fn db_with_ledger() -> (tempfile::TempDir, Database) {
+        let (dir, db) = test_db();
+        let block_id = create_block(&db, "A");
+        let grave_id = db
+            .create_grave(&CreateGraveRequest {
+                deceased_name: "Siti Aminah".to_string(),
+                date_of_death: "2023-01-05".to_string(),
+                ..grave_request(block_id, "12")
+            })
+            .unwrap();
+        db.create_payment(&CreatePaymentRequest {
+            payment_date: "2024-02-01".to_string(),
+            ..payment_request(grave_id, 2024)
+        })
+        .unwrap();
+        db.create_waiver(&CreateWaiverRequest {
+            grave_id,
+            year: 2025,
+            amount: 100000,
+            reason: Some("Tidak mampu".to_string()),
+            approved_by: None,
+        })
+        .unwrap();
+        (dir, db)
+    }
+
+    #[test]
+    fn test_open_fee_per_year() {
+        let (_dir, db) = db_with_ledger();
+        let graves = db.get_all_graves_with_heirs(None, None).unwrap();
+        // Due from the year of death through the current year, less waivers
+        let open: Vec<i64> = (2022..=2026).map(|year| graves[0].open_fee(year, 2025)).collect();
+        assert_eq!(open, [0, 100000, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_multi_sheet_workbook() {
+        let (_dir, db) = db_with_ledger();
+        let settings = db.get_settings().unwrap();
+        let graves = db.get_all_graves_with_heirs(None, None).unwrap();
+        let report = db.get_yearly_report(2024).unwrap();
+        let data = WorkbookData {
+            settings: &settings,
+            graves: &graves,
+            start_year: 2023,
+            end_year: 2025,
+            current_year: 2025,
+            yearly_report: Some(&report),
+        };
+
+        let bytes = workbook(
+            &[ExcelSheet::Graves, ExcelSheet::Payments, ExcelSheet::YearlyReport],
+            &data,
+        )
+        .unwrap();
+        // XLSX files are zip archives
+        assert_eq!(&bytes[0..2], b"PK");
+
+        assert!(workbook(&[], &data).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Excel/CSV reader for bulk import
+//!
+//! Reads the first worksheet of an XLSX/XLS/ODS file or a CSV file into a
+//! table of strings, then maps columns to grave fields using a
+//! `ColumnMapping`. Validation against the database happens in
+//! `db::Database::import_graves`.
+
+use std::path::Path;
+
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+
+use crate::db::{parse_loose_date, ImportHeir, ImportPayment, ImportRow, ImportRowError};
+
+/// Number of rows returned by a preview
+const PREVIEW_ROWS: usize = 10;
+
+/// Raw table read from the source file
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Preview of the source file for building a column mapping
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImportPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub total_rows: i64,
+}
+
+/// Which source column (by header name) feeds which field
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ColumnMapping {
+    pub deceased_name: String,
+    pub block_code: String,
+    pub number: String,
+    pub date_of_death: String,
+    pub burial_date: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub heirs: Vec<HeirColumnMapping>,
+    #[serde(default)]
+    pub payments: Vec<PaymentColumnMapping>,
+}
+
+/// Columns for one heir (order 1-3)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HeirColumnMapping {
+    pub order_number: i64,
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub relationship: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Columns for a historical payment of one year
+///
+/// Ledger spreadsheets usually have one amount column per year; an empty
+/// amount cell means no payment for that year.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PaymentColumnMapping {
+    pub year: i32,
+    pub amount: String,
+    pub payment_date: Option<String>,
+    pub payment_method: Option<String>,
+}
+
+/// Read the source file into a table of strings
+pub fn read_table(path: &Path) -> Result<ImportTable, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let mut rows = match extension.as_str() {
+        "csv" | "txt" => read_csv(path)?,
+        "xlsx" | "xlsm" | "xls" | "ods" => read_spreadsheet(path)?,
+        other => return Err(format!("Unsupported import file type: .{}", other)),
+    };
+
+    if rows.is_empty() {
+        return Err("Import file is empty".to_string());
+    }
+
+    let headers = rows.remove(0).into_iter().map(|h| h.trim().to_string()).collect();
+    Ok(ImportTable { headers, rows })
+}
+
+/// Preview the first rows of the source file
+pub fn preview(path: &Path) -> Result<ImportPreview, String> {
+    let table = read_table(path)?;
+    Ok(ImportPreview {
+        total_rows: table.rows.len() as i64,
+        rows: table.rows.into_iter().take(PREVIEW_ROWS).collect(),
+        headers: table.headers,
+    })
+}
+
+fn read_csv(path: &Path) -> Result<Vec<Vec<String>>, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read import file: {}", e))?;
+
+    // Excel with Indonesian locale saves CSV with ';'
+    let first_line = content.lines().next().unwrap_or("");
+    let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(|c| c.to_string()).collect())
+                .map_err(|e| format!("Failed to read CSV row: {}", e))
+        })
+        .collect()
+}
+
+fn read_spreadsheet(path: &Path) -> Result<Vec<Vec<String>>, String> {
+    let mut workbook =
+        open_workbook_auto(path).map_err(|e| format!("Failed to open import file: {}", e))?;
+
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("Import file has no worksheet")?
+        .map_err(|e| format!("Failed to read worksheet: {}", e))?;
+
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(cell_to_string).collect())
+        .collect())
+}
+
+/// Convert a spreadsheet cell to text; dates become ISO dates
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) if f.fract() == 0.0 => format!("{}", *f as i64),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(_) | Data::DateTimeIso(_) => cell
+            .as_date()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        Data::DurationIso(s) => s.clone(),
+        Data::Error(_) => String::new(),
+    }
+}
+
+/// Map table rows to import rows using the column mapping
+///
+/// Fails only when a mapped column does not exist in the file; cell level
+/// problems are recorded per row.
+pub fn map_rows(table: &ImportTable, mapping: &ColumnMapping) -> Result<Vec<ImportRow>, String> {
+    let columns = ColumnIndex::new(&table.headers);
+
+    let deceased_name = columns.required(&mapping.deceased_name)?;
+    let block_code = columns.required(&mapping.block_code)?;
+    let number = columns.required(&mapping.number)?;
+    let date_of_death = columns.required(&mapping.date_of_death)?;
+    let burial_date = columns.optional(mapping.burial_date.as_deref())?;
+    let notes = columns.optional(mapping.notes.as_deref())?;
+
+    let mut heir_columns = Vec::new();
+    for heir in &mapping.heirs {
+        heir_columns.push((
+            heir.order_number,
+            columns.required(&heir.full_name)?,
+            columns.optional(heir.phone_number.as_deref())?,
+            columns.optional(heir.relationship.as_deref())?,
+            columns.optional(heir.address.as_deref())?,
+        ));
+    }
+
+    let mut payment_columns = Vec::new();
+    for payment in &mapping.payments {
+        payment_columns.push((
+            payment.year,
+            columns.required(&payment.amount)?,
+            columns.optional(payment.payment_date.as_deref())?,
+            columns.optional(payment.payment_method.as_deref())?,
+        ));
+    }
+
+    let mut result = Vec::new();
+    for (index, cells) in table.rows.iter().enumerate() {
+        // Skip completely empty lines (common at the end of spreadsheets)
+        if cells.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+
+        let row_number = index as i64 + 2;
+        let cell = |col: usize| cells.get(col).map(|c| c.trim()).unwrap_or("");
+        let optional_cell =
+            |col: Option<usize>| col.map(cell).filter(|c| !c.is_empty()).map(str::to_string);
+        let mut errors = Vec::new();
+        let mut error = |field: &str, message: String| {
+            errors.push(ImportRowError {
+                row_number,
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        let date_of_death_value = match cell(date_of_death) {
+            "" => String::new(),
+            raw => parse_loose_date(raw).map(|date| date.to_string()).unwrap_or_else(|| {
+                error("date_of_death", format!("Invalid date of death '{}'", raw));
+                String::new()
+            }),
+        };
+
+        let burial_date_value = match optional_cell(burial_date) {
+            None => None,
+            Some(raw) => match parse_loose_date(&raw).map(|date| date.to_string()) {
+                Some(date) => Some(date),
+                None => {
+                    error("burial_date", format!("Invalid burial date '{}'", raw));
+                    None
+                }
+            },
+        };
+
+        let heirs = heir_columns
+            .iter()
+            .filter(|(_, name, ..)| !cell(*name).is_empty())
+            .map(|(order, name, phone, relationship, address)| ImportHeir {
+                order_number: *order,
+                full_name: cell(*name).to_string(),
+                phone_number: optional_cell(*phone),
+                relationship: optional_cell(*relationship).map(|r| r.to_lowercase()),
+                address: optional_cell(*address),
+            })
+            .collect();
+
+        let mut payments = Vec::new();
+        for (year, amount_col, date_col, method_col) in &payment_columns {
+            let raw_amount = cell(*amount_col);
+            if raw_amount.is_empty() || raw_amount == "-" {
+                continue;
+            }
+            let Some(amount) = parse_amount(raw_amount) else {
+                error("payments", format!("Invalid amount '{}' for {}", raw_amount, year));
+                continue;
+            };
+
+            // Without a payment date, assume the payment was made at the end of that year
+            let payment_date = match optional_cell(*date_col) {
+                None => format!("{}-12-31", year),
+                Some(raw) => match parse_loose_date(&raw).map(|date| date.to_string()) {
+                    Some(date) => date,
+                    None => {
+                        error("payments", format!("Invalid payment date '{}' for {}", raw, year));
+                        continue;
+                    }
+                },
+            };
+
+            let payment_method = match optional_cell(*method_col) {
+                None => None,
+                Some(raw) => match parse_payment_method(&raw) {
+                    Some(method) => Some(method.to_string()),
+                    None => {
+                        error("payments", format!("Invalid payment method '{}' for {}", raw, year));
+                        continue;
+                    }
+                },
+            };
+
+            payments.push(ImportPayment {
+                year: *year,
+                payment_date,
+                amount,
+                payment_method,
+            });
+        }
+
+        result.push(ImportRow {
+            row_number,
+            deceased_name: cell(deceased_name).to_string(),
+            block_code: cell(block_code).to_string(),
+            number: cell(number).to_string(),
+            date_of_death: date_of_death_value,
+            burial_date: burial_date_value,
+            notes: optional_cell(notes),
+            heirs,
+            payments,
+            errors,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Case-insensitive header lookup
+struct ColumnIndex<'a> {
+    headers: &'a [String],
+}
+
+impl<'a> ColumnIndex<'a> {
+    fn new(headers: &'a [String]) -> Self {
+        Self { headers }
+    }
+
+    fn required(&self, name: &str) -> Result<usize, String> {
+        self.headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| format!("Column '{}' not found in import file", name))
+    }
+
+    fn optional(&self, name: Option<&str>) -> Result<Option<usize>, String> {
+        match name.filter(|n| !n.trim().is_empty()) {
+            Some(name) => self.required(name).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parse a Rupiah amount such as "Rp 100.000", "100,000" or "100000"
+///
+/// Negative amounts ("-50.000", "(50.000)") are refunds or corrections and
+/// not valid payments.
+pub fn parse_amount(raw: &str) -> Option<i64> {
+    if raw.contains(['-', '(', ')']) {
+        return None;
+    }
+    let mut value = raw.trim().trim_start_matches("Rp").trim_start_matches("rp").trim();
+    // Drop decimal cents (",00" in Indonesian notation)
+    for suffix in [",00", ",0", ".00"] {
+        if let Some(stripped) = value.strip_suffix(suffix) {
+            value = stripped;
+            break;
+        }
+    }
+
+    let digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || value.chars().any(|c| c.is_alphabetic()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Map a payment method as written in the ledger ("Tunai", "Transfer
+/// Bank", "QRIS") to its stored name in `PAYMENT_METHODS`
+pub fn parse_payment_method(raw: &str) -> Option<&'static str> {
+    match raw.trim().to_lowercase().as_str() {
+        "tunai" | "cash" | "kas" => Some("cash"),
+        "transfer" | "transfer bank" | "tf" | "bank" => Some("transfer"),
+        "qris" => Some("qris"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("Rp 100.000"), Some(100000));
+        assert_eq!(parse_amount("150,000"), Some(150000));
+        assert_eq!(parse_amount("Rp 75.000,00"), Some(75000));
+        assert_eq!(parse_amount("lunas"), None);
+        assert_eq!(parse_amount("-50.000"), None);
+        assert_eq!(parse_amount("Rp -50.000"), None);
+        assert_eq!(parse_amount("(50.000)"), None);
+    }
+
+    #[test]
+    fn test_parse_payment_method() {
+        assert_eq!(parse_payment_method("Tunai"), Some("cash"));
+        assert_eq!(parse_payment_method(" Transfer Bank "), Some("transfer"));
+        assert_eq!(parse_payment_method("QRIS"), Some("qris"));
+        assert_eq!(parse_payment_method("cek"), None);
+    }
+
+    #[test]
+    fn test_map_rows_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buku_induk.csv");
+        std::fs::write(
+            &path,
+            "Nama;Blok;No;Wafat;Waris 1;HP 1;2023\n\
+             Ahmad;A;1;05/03/2020;Budi;0812;Rp 100.000\n\
+             ;;;;;;\n\
+             Siti;B;2;kemarin;;;\n",
+        )
+        .unwrap();
+
+        let table = read_table(&path).unwrap();
+        let mapping = ColumnMapping {
+            deceased_name: "nama".to_string(),
+            block_code: "Blok".to_string(),
+            number: "No".to_string(),
+            date_of_death: "Wafat".to_string(),
+            burial_date: None,
+            notes: None,
+            heirs: vec![HeirColumnMapping {
+                order_number: 1,
+                full_name: "Waris 1".to_string(),
+                phone_number: Some("HP 1".to_string()),
+                relationship: None,
+                address: None,
+            }],
+            payments: vec![PaymentColumnMapping {
+                year: 2023,
+                amount: "2023".to_string(),
+                payment_date: None,
+                payment_method: None,
+            }],
+        };
+
+        let rows = map_rows(&table, &mapping).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date_of_death, "2020-03-05");
+        assert_eq!(rows[0].heirs[0].full_name, "Budi");
+        assert_eq!(rows[0].payments[0].amount, 100000);
+        assert_eq!(rows[0].payments[0].payment_date, "2023-12-31");
+        assert_eq!(rows[1].row_number, 4);
+        assert_eq!(rows[1].errors[0].field, "date_of_death");
+
+        let mut bad_mapping = mapping.clone();
+        bad_mapping.number = "Nomor".to_string();
+        assert!(map_rows(&table, &bad_mapping).is_err());
+    }
+}
@@ -3,9 +3,18 @@
 
 use tauri::Manager;
 
+// Modul login & hak akses
+pub mod auth;
+
 // Modul database
 pub mod db;
 
+// Modul export file (PDF/XLSX)
+pub mod export;
+
+// Modul import file (Excel/CSV)
+pub mod import;
+
 /// Command untuk greeting (contoh)
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -26,10 +35,161 @@ async fn get_database_stats(app_handle: tauri::AppHandle) -> Result<db::Database
 
 /// Command untuk backup database
 #[tauri::command]
-async fn backup_database(app_handle: tauri::AppHandle, backup_path: String) -> Result<(), String> {
+async fn backup_database(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    backup_path: String,
+) -> Result<(), String> {
+    session.require(auth::Permission::ManageSettings)?;
     db::backup_database_command(app_handle, backup_path)
 }
 
+// ==================== AUTH COMMANDS ====================
+
+/// Check whether any user account exists (false = first run, show admin setup)
+#[tauri::command]
+async fn has_users(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.has_users()
+}
+
+/// Create the first admin account and log in (only when no users exist)
+#[tauri::command]
+async fn setup_admin(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    user: db::CreateUserRequest,
+) -> Result<auth::Session, String> {
+    let db = db::Database::init(&app_handle)?;
+    if db.has_users()? {
+        return Err("Admin account already exists".to_string());
+    }
+
+    let id = db.create_user(&db::CreateUserRequest {
+        role: db::Role::Admin,
+        ..user
+    })?;
+    let user = db
+        .get_user_by_id(id)?
+        .ok_or_else(|| format!("User {} not found", id))?;
+
+    let new_session = auth::Session::new(&user);
+    session.set(Some(new_session.clone()));
+    Ok(new_session)
+}
+
+/// Log in with username and password
+#[tauri::command]
+async fn login(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    username: String,
+    password: String,
+) -> Result<auth::Session, String> {
+    let db = db::Database::init(&app_handle)?;
+    let user = db.authenticate(&username, &password)?;
+
+    let new_session = auth::Session::new(&user);
+    session.set(Some(new_session.clone()));
+    log::info!("User {} logged in as {}", user.username, user.role.as_str());
+    Ok(new_session)
+}
+
+/// Log out
+#[tauri::command]
+async fn logout(session: tauri::State<'_, auth::SessionState>) -> Result<(), String> {
+    session.set(None);
+    Ok(())
+}
+
+/// Get current session (None if not logged in)
+#[tauri::command]
+async fn get_current_session(
+    session: tauri::State<'_, auth::SessionState>,
+) -> Result<Option<auth::Session>, String> {
+    Ok(session.current())
+}
+
+/// Change own password
+#[tauri::command]
+async fn change_password(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    let current = session.current().ok_or_else(|| "Not logged in".to_string())?;
+    let db = db::Database::init(&app_handle)?;
+    if !db.verify_user_password(current.user_id, &old_password)? {
+        return Err("Current password is incorrect".to_string());
+    }
+    db.set_user_password(current.user_id, &new_password)
+}
+
+/// Get all users
+#[tauri::command]
+async fn get_users(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+) -> Result<Vec<db::User>, String> {
+    session.require(auth::Permission::ManageUsers)?;
+    let db = db::Database::init(&app_handle)?;
+    db.get_users()
+}
+
+/// Create user
+#[tauri::command]
+async fn create_user(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    user: db::CreateUserRequest,
+) -> Result<i64, String> {
+    session.require(auth::Permission::ManageUsers)?;
+    let db = db::Database::init(&app_handle)?;
+    db.create_user(&user)
+}
+
+/// Update user name, role or active status
+#[tauri::command]
+async fn update_user(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    user: db::UpdateUserRequest,
+) -> Result<(), String> {
+    session.require(auth::Permission::ManageUsers)?;
+    let db = db::Database::init(&app_handle)?;
+    db.update_user(id, &user)
+}
+
+/// Reset password of another user
+#[tauri::command]
+async fn reset_user_password(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    password: String,
+) -> Result<(), String> {
+    session.require(auth::Permission::ManageUsers)?;
+    let db = db::Database::init(&app_handle)?;
+    db.set_user_password(id, &password)
+}
+
+/// Delete user
+#[tauri::command]
+async fn delete_user(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    let current = session.require(auth::Permission::ManageUsers)?;
+    if current.user_id == id {
+        return Err("Cannot delete the account you are logged in with".to_string());
+    }
+    let db = db::Database::init(&app_handle)?;
+    db.delete_user(id)
+}
+
 // ==================== BLOCKS COMMANDS ====================
 
 /// Get all blocks
@@ -48,21 +208,37 @@ async fn get_block_by_id(app_handle: tauri::AppHandle, id: i64) -> Result<Option
 
 /// Create new block
 #[tauri::command]
-async fn create_block(app_handle: tauri::AppHandle, block: db::CreateBlockRequest) -> Result<i64, String> {
-    let db = db::Database::init(&app_handle)?;
+async fn create_block(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    block: db::CreateBlockRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageBlocks)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
     db.create_block(&block)
 }
 
 /// Update block
 #[tauri::command]
-async fn update_block(app_handle: tauri::AppHandle, id: i64, block: db::UpdateBlockRequest) -> Result<(), String> {
-    let db = db::Database::init(&app_handle)?;
+async fn update_block(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    block: db::UpdateBlockRequest,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageBlocks)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
     db.update_block(id, &block)
 }
 
 /// Delete block
 #[tauri::command]
-async fn delete_block(app_handle: tauri::AppHandle, id: i64) -> Result<(), String> {
+async fn delete_block(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(auth::Permission::DeleteBlock)?;
     let db = db::Database::init(&app_handle)?;
     db.delete_block(id)
 }
@@ -74,6 +250,99 @@ async fn get_block_stats(app_handle: tauri::AppHandle, block_id: i64) -> Result<
     db.get_block_stats(block_id)
 }
 
+/// Project when each block will be full from the burial rate of recent years
+#[tauri::command]
+async fn get_capacity_forecast(
+    app_handle: tauri::AppHandle,
+    years: Option<i32>,
+) -> Result<db::CapacityForecast, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_capacity_forecast(years.unwrap_or(5))
+}
+
+/// Get audit log of graves placed into full or inactive blocks
+#[tauri::command]
+async fn get_capacity_overrides(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    limit: Option<i64>,
+) -> Result<Vec<db::CapacityOverride>, String> {
+    session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?;
+    db.get_capacity_overrides(limit.unwrap_or(100))
+}
+
+/// Get block grid layout (None if not set)
+#[tauri::command]
+async fn get_block_layout(
+    app_handle: tauri::AppHandle,
+    block_id: i64,
+) -> Result<Option<db::BlockLayout>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_block_layout(block_id)
+}
+
+/// Set block grid layout (rows, columns, unusable cells)
+#[tauri::command]
+async fn set_block_layout(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    block_id: i64,
+    layout: db::BlockLayout,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageBlocks)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.set_block_layout(block_id, &layout)
+}
+
+/// Get grave numbering scheme of a block
+#[tauri::command]
+async fn get_block_numbering(
+    app_handle: tauri::AppHandle,
+    block_id: i64,
+) -> Result<db::GraveNumbering, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_block_numbering(block_id)
+}
+
+/// Set grave numbering scheme of a block (numeric, padded, suffix)
+#[tauri::command]
+async fn set_block_numbering(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    block_id: i64,
+    numbering: db::GraveNumbering,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageBlocks)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.set_block_numbering(block_id, &numbering)
+}
+
+/// Suggest the next free grave number of a block and list the gaps
+#[tauri::command]
+async fn get_next_grave_number(
+    app_handle: tauri::AppHandle,
+    block_id: i64,
+) -> Result<db::GraveNumberSuggestion, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_next_grave_number(block_id)
+}
+
+/// Get plot map of a block (payment status for `year`, default active year)
+#[tauri::command]
+async fn get_plot_map(
+    app_handle: tauri::AppHandle,
+    block_id: i64,
+    year: Option<i32>,
+) -> Result<db::PlotMap, String> {
+    let db = db::Database::init(&app_handle)?;
+    let year = match year {
+        Some(year) => year,
+        None => db.get_settings()?.active_year,
+    };
+    db.get_plot_map(block_id, year)
+}
+
 // ==================== GRAVES COMMANDS ====================
 
 /// Request untuk membuat grave dengan heirs
@@ -81,30 +350,49 @@ async fn get_block_stats(app_handle: tauri::AppHandle, block_id: i64) -> Result<
 pub struct CreateGraveWithHeirsRequest {
     pub grave: db::CreateGraveRequest,
     pub heirs: Vec<db::CreateHeirRequest>,
+    /// Plot cell picked on the block map
+    #[serde(default)]
+    pub position: Option<db::PlotPosition>,
 }
 
-/// Get graves with pagination and search
+/// Get one page of graves, sorted and filtered
 #[tauri::command]
 async fn get_graves(
     app_handle: tauri::AppHandle,
-    search: Option<String>,
-    block_id: Option<i64>,
+    query: db::GraveListQuery,
     limit: i64,
-    offset: i64,
-) -> Result<Vec<db::GraveWithBlock>, String> {
+) -> Result<db::GravePage, String> {
     let db = db::Database::init(&app_handle)?;
-    db.get_graves(search, block_id, limit, offset)
+    db.get_graves(&query, limit)
 }
 
-/// Count graves for pagination
+/// Count graves matching the listing filters
 #[tauri::command]
-async fn count_graves(
+async fn count_graves(app_handle: tauri::AppHandle, query: db::GraveListQuery) -> Result<i64, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.count_graves(&query)
+}
+
+/// Search deceased and heir names, tolerant of spelling variants
+#[tauri::command]
+async fn search_names(
     app_handle: tauri::AppHandle,
-    search: Option<String>,
-    block_id: Option<i64>,
-) -> Result<i64, String> {
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<db::NameMatch>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.search_names(&query, limit.unwrap_or(50))
+}
+
+/// Full-text search over graves, heirs and payments
+#[tauri::command]
+async fn global_search(
+    app_handle: tauri::AppHandle,
+    query: String,
+    limit: Option<i64>,
+) -> Result<db::GlobalSearchResults, String> {
     let db = db::Database::init(&app_handle)?;
-    db.count_graves(search, block_id)
+    db.global_search(&query, limit.unwrap_or(10))
 }
 
 /// Get grave by ID
@@ -130,28 +418,7 @@ async fn export_graves(
     
     // Get all graves with heirs and payments
     let graves = db.get_all_graves_with_heirs(search, block_id)?;
-    
-    // Determine year range from data if "all" is selected
-    let (actual_start_year, actual_end_year) = if start_year.is_none() || end_year.is_none() {
-        // Find min and max year from all payments
-        let mut years: Vec<i32> = Vec::new();
-        for grave in &graves {
-            for payment in &grave.payments {
-                years.push(payment.year);
-            }
-        }
-        
-        if years.is_empty() {
-            // No payments at all, use a reasonable default range
-            (2022, 2026)
-        } else {
-            years.sort_unstable();
-            years.dedup();
-            (*years.first().unwrap(), *years.last().unwrap())
-        }
-    } else {
-        (start_year.unwrap(), end_year.unwrap())
-    };
+    let (actual_start_year, actual_end_year) = export_year_range(&graves, start_year, end_year);
     
     Ok(ExportGravesResult {
         graves,
@@ -160,6 +427,28 @@ async fn export_graves(
     })
 }
 
+/// Determine export year range; derived from payment data if "all" is selected
+fn export_year_range(
+    graves: &[db::GraveExportData],
+    start_year: Option<i32>,
+    end_year: Option<i32>,
+) -> (i32, i32) {
+    match (start_year, end_year) {
+        (Some(start), Some(end)) => (start, end),
+        _ => {
+            // Find min and max year from all payments
+            let years = graves.iter().flat_map(|g| g.payments.iter().map(|p| p.year));
+            let min = years.clone().min();
+            let max = years.max();
+            match (min, max) {
+                (Some(min), Some(max)) => (min, max),
+                // No payments at all, use a reasonable default range
+                _ => (2022, 2026),
+            }
+        }
+    }
+}
+
 /// Export result with year range info
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ExportGravesResult {
@@ -168,6 +457,79 @@ pub struct ExportGravesResult {
     pub end_year: i32,
 }
 
+/// Request for Excel export generated on the Rust side
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExcelExportRequest {
+    /// Worksheets to include, in order (multi-sheet workbook when more than one)
+    pub sheets: Vec<export::xlsx::ExcelSheet>,
+    pub search: Option<String>,
+    pub block_id: Option<i64>,
+    pub start_year: Option<i32>,
+    pub end_year: Option<i32>,
+    /// Year for the yearly report sheet (defaults to active year)
+    pub report_year: Option<i32>,
+}
+
+/// Export graves, payment overview and/or yearly report to Excel (with save dialog)
+#[tauri::command]
+async fn export_excel(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    request: ExcelExportRequest,
+) -> Result<Option<String>, String> {
+    use chrono::Datelike;
+    use export::xlsx::ExcelSheet;
+
+    let db = db::Database::init(&app_handle)?;
+    let settings = db.get_settings()?;
+
+    let needs_graves = request
+        .sheets
+        .iter()
+        .any(|s| matches!(s, ExcelSheet::Graves | ExcelSheet::Payments));
+    let graves = if needs_graves {
+        db.get_all_graves_with_heirs(request.search.clone(), request.block_id)?
+    } else {
+        Vec::new()
+    };
+    let (start_year, end_year) = export_year_range(&graves, request.start_year, request.end_year);
+
+    let yearly_report = if request.sheets.contains(&ExcelSheet::YearlyReport) {
+        Some(db.get_yearly_report(request.report_year.unwrap_or(settings.active_year))?)
+    } else {
+        None
+    };
+
+    let file_data = export::xlsx::workbook(
+        &request.sheets,
+        &export::xlsx::WorkbookData {
+            settings: &settings,
+            graves: &graves,
+            start_year,
+            end_year,
+            current_year: db.local_today()?.year(),
+            yearly_report: yearly_report.as_ref(),
+        },
+    )?;
+
+    let prefix = match request.sheets.as_slice() {
+        [ExcelSheet::Graves] => "Data_Makam".to_string(),
+        [ExcelSheet::Payments] => "Data_Pembayaran".to_string(),
+        [ExcelSheet::YearlyReport] => format!(
+            "Laporan_Tahunan_{}",
+            yearly_report.as_ref().map(|r| r.year).unwrap_or(settings.active_year)
+        ),
+        _ => "Astana_Export".to_string(),
+    };
+    let default_name = format!(
+        "{}_{}.xlsx",
+        prefix,
+        chrono::Local::now().format("%Y%m%d_%H%M")
+    );
+
+    save_file_with_dialog(&app_handle, &window, file_data, &default_name, "Excel Files", &["xlsx"])
+}
+
 /// Save Excel file with dialog - auto open in Downloads or Documents
 #[tauri::command]
 async fn save_excel_file(
@@ -175,6 +537,20 @@ async fn save_excel_file(
     window: tauri::Window,
     file_data: Vec<u8>,
     default_name: String,
+) -> Result<Option<String>, String> {
+    save_file_with_dialog(&app_handle, &window, file_data, &default_name, "Excel Files", &["xlsx"])
+}
+
+/// Show save dialog (Downloads > Documents > Home) and write the file
+///
+/// Returns `None` if the user cancelled the dialog.
+fn save_file_with_dialog(
+    app_handle: &tauri::AppHandle,
+    window: &tauri::Window,
+    file_data: Vec<u8>,
+    default_name: &str,
+    filter_name: &str,
+    extensions: &[&str],
 ) -> Result<Option<String>, String> {
     use tauri_plugin_dialog::DialogExt;
     use tauri::Manager;
@@ -204,17 +580,17 @@ async fn save_excel_file(
     let file_path = if let Some(dir) = default_dir {
         app_handle.dialog()
             .file()
-            .set_parent(&window)
-            .set_file_name(&default_name)
-            .add_filter("Excel Files", &["xlsx"])
+            .set_parent(window)
+            .set_file_name(default_name)
+            .add_filter(filter_name, extensions)
             .set_directory(dir)
             .blocking_save_file()
     } else {
         app_handle.dialog()
             .file()
-            .set_parent(&window)
-            .set_file_name(&default_name)
-            .add_filter("Excel Files", &["xlsx"])
+            .set_parent(window)
+            .set_file_name(default_name)
+            .add_filter(filter_name, extensions)
             .blocking_save_file()
     };
     
@@ -231,45 +607,567 @@ async fn save_excel_file(
     }
 }
 
-/// Create new grave with heirs
+/// Create new grave with heirs
+#[tauri::command]
+async fn create_grave_with_heirs(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    request: CreateGraveWithHeirsRequest,
+    override_reason: Option<String>,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db::validate_grave_with_heirs(&request.grave, &request.heirs, db.local_today()?)?;
+    
+    if let Some(position) = request.position {
+        db.validate_grave_position(request.grave.block_id, position, None)?;
+    }
+
+    // Create grave (a full or inactive block needs an admin override)
+    let grave_id = match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_create_grave(&request.grave, &reason)?
+        }
+        None => db.create_grave(&request.grave)?,
+    };
+    if request.position.is_some() {
+        db.set_grave_position(grave_id, request.position)?;
+    }
+    
+    // Create heirs
+    for mut heir in request.heirs {
+        heir.grave_id = grave_id;
+        db.create_heir(&heir)?;
+    }
+    
+    Ok(grave_id)
+}
+
+/// Update grave
+#[tauri::command]
+async fn update_grave(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    grave: db::UpdateGraveRequest,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.update_grave(id, &grave)
+}
+
+/// Place grave on the block plot map (None removes it from the map)
+#[tauri::command]
+async fn set_grave_position(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    position: Option<db::PlotPosition>,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.set_grave_position(id, position)
+}
+
+/// Delete grave (will cascade delete heirs and payments)
+#[tauri::command]
+async fn delete_grave(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    override_reason: Option<String>,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_delete_grave(id, &reason)
+        }
+        None => db.delete_grave(id),
+    }
+}
+
+// ==================== INTERMENT COMMANDS ====================
+
+/// Get interments of a grave in burial order
+#[tauri::command]
+async fn get_interments(
+    app_handle: tauri::AppHandle,
+    grave_id: i64,
+) -> Result<Vec<db::Interment>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_interments(grave_id)
+}
+
+/// Record another burial in an existing grave (tumpang)
+#[tauri::command]
+async fn add_interment(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    grave_id: i64,
+    interment: db::CreateIntermentRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.add_interment(grave_id, &interment)
+}
+
+/// Update interment
+#[tauri::command]
+async fn update_interment(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    interment: db::UpdateIntermentRequest,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.update_interment(id, &interment)
+}
+
+/// Delete interment (a grave keeps at least one)
+#[tauri::command]
+async fn delete_interment(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.delete_interment(id)
+}
+
+// ==================== PERSON COMMANDS ====================
+
+/// Find heirs/contacts by name or phone number
+#[tauri::command]
+async fn search_persons(
+    app_handle: tauri::AppHandle,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<db::Person>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.search_persons(&query, limit.unwrap_or(20))
+}
+
+/// Get person by ID
+#[tauri::command]
+async fn get_person_by_id(
+    app_handle: tauri::AppHandle,
+    id: i64,
+) -> Result<Option<db::Person>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_person_by_id(id)
+}
+
+/// Create person
+#[tauri::command]
+async fn create_person(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    person: db::CreatePersonRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.create_person(&person)
+}
+
+/// Update person (also updates the heir data of all their graves)
+#[tauri::command]
+async fn update_person(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    person: db::UpdatePersonRequest,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.update_person(id, &person)
+}
+
+/// Delete person that is not heir of any grave
+#[tauri::command]
+async fn delete_person(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.delete_person(id)
+}
+
+/// Get all graves a person is heir of
+#[tauri::command]
+async fn get_person_graves(
+    app_handle: tauri::AppHandle,
+    person_id: i64,
+) -> Result<Vec<db::PersonGrave>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_person_graves(person_id)
+}
+
+/// Make a person the next heir of a grave
+#[tauri::command]
+async fn link_person_to_grave(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    person_id: i64,
+    link: db::LinkPersonRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.link_person_to_grave(person_id, &link)
+}
+
+/// Get unpaid years of all graves of a person
+#[tauri::command]
+async fn get_person_arrears(
+    app_handle: tauri::AppHandle,
+    person_id: i64,
+) -> Result<db::PersonArrears, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_person_arrears(person_id)
+}
+
+/// Pay all unpaid years of a person's graves at once
+#[tauri::command]
+async fn pay_person_arrears(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    person_id: i64,
+    payment: db::CombinedPaymentRequest,
+    override_reason: Option<String>,
+) -> Result<db::CombinedPaymentResult, String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    require_cash_session(&db, payment.payment_method.as_deref())?;
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_pay_person_arrears(person_id, &payment, &reason)
+        }
+        None => db.pay_person_arrears(person_id, &payment),
+    }
+}
+
+// ==================== DUPLICATE COMMANDS ====================
+
+/// Find likely duplicate graves for review
+#[tauri::command]
+async fn find_duplicate_graves(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    min_score: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Vec<db::DuplicateCandidate>, String> {
+    session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?;
+    db.find_duplicate_graves(min_score.unwrap_or(70), limit.unwrap_or(100))
+}
+
+/// Mark two graves as not being duplicates
+#[tauri::command]
+async fn dismiss_duplicate(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    grave_id: i64,
+    other_grave_id: i64,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.dismiss_duplicate(grave_id, other_grave_id)
+}
+
+/// Merge a duplicate grave into the grave that is kept
+#[tauri::command]
+async fn merge_graves(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    keep_id: i64,
+    remove_id: i64,
+    override_reason: Option<String>,
+) -> Result<db::GraveMergeReport, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_merge_graves(keep_id, remove_id, &reason)
+        }
+        None => db.merge_graves(keep_id, remove_id),
+    }
+}
+
+// ==================== RELOCATION COMMANDS ====================
+
+/// Move grave to another block, number or plot cell, keeping the history
+#[tauri::command]
+async fn relocate_grave(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    grave_id: i64,
+    relocation: db::RelocateGraveRequest,
+    override_reason: Option<String>,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_relocate_grave(grave_id, &relocation, &reason)
+        }
+        None => db.relocate_grave(grave_id, &relocation),
+    }
+}
+
+/// Get relocation history of a grave
+#[tauri::command]
+async fn get_grave_relocations(
+    app_handle: tauri::AppHandle,
+    grave_id: i64,
+) -> Result<Vec<db::GraveRelocation>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_grave_relocations(grave_id)
+}
+
+// ==================== RESERVATION COMMANDS ====================
+
+/// Get reservations (filter by block and status: active, converted, cancelled, expired)
+#[tauri::command]
+async fn get_reservations(
+    app_handle: tauri::AppHandle,
+    block_id: Option<i64>,
+    status: Option<String>,
+) -> Result<Vec<db::Reservation>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_reservations(block_id, status.as_deref())
+}
+
+/// Get reservation by ID
+#[tauri::command]
+async fn get_reservation_by_id(
+    app_handle: tauri::AppHandle,
+    id: i64,
+) -> Result<Option<db::Reservation>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_reservation_by_id(id)
+}
+
+/// Create plot reservation
+#[tauri::command]
+async fn create_reservation(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    reservation: db::CreateReservationRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.create_reservation(&reservation)
+}
+
+/// Update active reservation
+#[tauri::command]
+async fn update_reservation(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    reservation: db::UpdateReservationRequest,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.update_reservation(id, &reservation)
+}
+
+/// Cancel active reservation
+#[tauri::command]
+async fn cancel_reservation(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    notes: Option<String>,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.cancel_reservation(id, notes.as_deref())
+}
+
+/// Convert reservation into a grave (reserver becomes primary heir)
+#[tauri::command]
+async fn convert_reservation(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    request: db::ConvertReservationRequest,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.convert_reservation(id, &request)
+}
+
+// ==================== IMPORT COMMANDS ====================
+
+/// Preview import file (headers and first rows) for column mapping
+#[tauri::command]
+async fn preview_import_file(file_path: String) -> Result<import::ImportPreview, String> {
+    import::preview(std::path::Path::new(&file_path))
+}
+
+/// Import graves, heirs and historical payments from Excel/CSV
+///
+/// With `dry_run` nothing is written; the report lists row-level errors.
+/// Mapping payment columns also needs the payment permission.
+#[tauri::command]
+async fn import_graves(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    file_path: String,
+    mapping: import::ColumnMapping,
+    dry_run: bool,
+) -> Result<db::ImportReport, String> {
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    // Payment columns record money, which is the cashier's job
+    if !mapping.payments.is_empty() {
+        session.require(auth::Permission::ManagePayments)?;
+    }
+    let table = import::read_table(std::path::Path::new(&file_path))?;
+    let rows = import::map_rows(&table, &mapping)?;
+
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.import_graves(&rows, dry_run)
+}
+
+/// Get saved import mapping profiles
 #[tauri::command]
-async fn create_grave_with_heirs(
+async fn get_import_profiles(app_handle: tauri::AppHandle) -> Result<Vec<db::ImportProfile>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_import_profiles()
+}
+
+/// Save import mapping profile
+#[tauri::command]
+async fn save_import_profile(
     app_handle: tauri::AppHandle,
-    request: CreateGraveWithHeirsRequest,
+    session: tauri::State<'_, auth::SessionState>,
+    name: String,
+    mapping: import::ColumnMapping,
 ) -> Result<i64, String> {
+    session.require(auth::Permission::ManageGraves)?;
     let db = db::Database::init(&app_handle)?;
-    
-    // Create grave
-    let grave_id = db.create_grave(&request.grave)?;
-    
-    // Create heirs
-    for mut heir in request.heirs {
-        heir.grave_id = grave_id;
-        db.create_heir(&heir)?;
-    }
-    
-    Ok(grave_id)
+    let mapping = serde_json::to_value(&mapping)
+        .map_err(|e| format!("Failed to serialize mapping: {}", e))?;
+    db.save_import_profile(&name, &mapping)
 }
 
-/// Update grave
+/// Delete import mapping profile
 #[tauri::command]
-async fn update_grave(
+async fn delete_import_profile(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     id: i64,
-    grave: db::UpdateGraveRequest,
 ) -> Result<(), String> {
+    session.require(auth::Permission::ManageGraves)?;
     let db = db::Database::init(&app_handle)?;
-    db.update_grave(id, &grave)
+    db.delete_import_profile(id)
 }
 
-/// Delete grave (will cascade delete heirs and payments)
+// ==================== DATA TRANSFER COMMANDS ====================
+
+/// Export all data as versioned JSON (with save dialog)
 #[tauri::command]
-async fn delete_grave(
+async fn export_data_json(
     app_handle: tauri::AppHandle,
-    id: i64,
-) -> Result<(), String> {
+    window: tauri::Window,
+) -> Result<Option<String>, String> {
     let db = db::Database::init(&app_handle)?;
-    db.delete_grave(id)
+    let data = db.export_data()?;
+    let file_data = serde_json::to_vec_pretty(&data)
+        .map_err(|e| format!("Failed to serialize export: {}", e))?;
+
+    let default_name = format!("Astana_Data_{}.json", chrono::Local::now().format("%Y%m%d"));
+    save_file_with_dialog(&app_handle, &window, file_data, &default_name, "JSON Files", &["json"])
+}
+
+/// Read manifest of a JSON data export (shown before importing)
+#[tauri::command]
+async fn preview_data_file(file_path: String) -> Result<db::ExportManifest, String> {
+    Ok(read_data_file(&file_path)?.manifest)
+}
+
+/// Import JSON data export into the current database
+///
+/// With `dry_run` nothing is written; the report shows what would change.
+#[tauri::command]
+async fn import_data_json(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    file_path: String,
+    strategy: db::ConflictStrategy,
+    dry_run: bool,
+    override_reason: Option<String>,
+) -> Result<db::TransferReport, String> {
+    let operator = session.require(auth::Permission::ManageSettings)?;
+    let data = read_data_file(&file_path)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_import_data(&data, strategy, dry_run, &reason)
+        }
+        None => db.import_data(&data, strategy, dry_run),
+    }
+}
+
+/// Preview merging another astana.db into the current database
+///
+/// Nothing is written; the report shows the block mapping and conflicts.
+#[tauri::command]
+async fn preview_merge_database(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    file_path: String,
+    options: db::MergeOptions,
+    override_reason: Option<String>,
+) -> Result<db::MergeReport, String> {
+    let operator = session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    let source = std::path::Path::new(&file_path);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_merge_database(source, &options, true, &reason)
+        }
+        None => db.merge_database(source, &options, true),
+    }
+}
+
+/// Merge another astana.db into the current database (rolled back on failure)
+#[tauri::command]
+async fn merge_database(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    file_path: String,
+    options: db::MergeOptions,
+    override_reason: Option<String>,
+) -> Result<db::MergeReport, String> {
+    let operator = session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    let source = std::path::Path::new(&file_path);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_merge_database(source, &options, false, &reason)
+        }
+        None => db.merge_database(source, &options, false),
+    }
+}
+
+fn read_data_file(file_path: &str) -> Result<db::DataExport, String> {
+    let content = std::fs::read_to_string(file_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    db::DataExport::from_json(&content)
 }
 
 // ==================== HEIRS COMMANDS ====================
@@ -288,9 +1186,11 @@ async fn get_heirs_by_grave(
 #[tauri::command]
 async fn create_heir(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     heir: db::CreateHeirRequest,
 ) -> Result<i64, String> {
-    let db = db::Database::init(&app_handle)?;
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
     db.create_heir(&heir)
 }
 
@@ -298,10 +1198,12 @@ async fn create_heir(
 #[tauri::command]
 async fn update_heir(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     id: i64,
     heir: db::UpdateHeirRequest,
 ) -> Result<(), String> {
-    let db = db::Database::init(&app_handle)?;
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
     db.update_heir(id, &heir)
 }
 
@@ -309,8 +1211,10 @@ async fn update_heir(
 #[tauri::command]
 async fn delete_heir(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     id: i64,
 ) -> Result<(), String> {
+    session.require(auth::Permission::ManageGraves)?;
     let db = db::Database::init(&app_handle)?;
     db.delete_heir(id)
 }
@@ -319,10 +1223,15 @@ async fn delete_heir(
 #[tauri::command]
 async fn update_grave_heirs(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     grave_id: i64,
     heirs: Vec<db::CreateHeirRequest>,
 ) -> Result<(), String> {
-    let db = db::Database::init(&app_handle)?;
+    let operator = session.require(auth::Permission::ManageGraves)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+
+    // Reject invalid heirs before anything is deleted
+    db::validate_heirs(&heirs)?;
     
     // Delete existing heirs
     db.delete_heirs_by_grave(grave_id)?;
@@ -336,6 +1245,17 @@ async fn update_grave_heirs(
     Ok(())
 }
 
+/// Get heirs whose stored phone number could not be normalized
+#[tauri::command]
+async fn get_phone_number_issues(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+) -> Result<Vec<db::PhoneNumberIssue>, String> {
+    session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?;
+    db.get_phone_number_issues()
+}
+
 /// Get grave detail with heirs
 #[tauri::command]
 async fn get_grave_detail(
@@ -349,9 +1269,19 @@ async fn get_grave_detail(
     match grave {
         Some(g) => {
             let heirs = db.get_heirs_by_grave(id)?;
+            let position = db.get_grave_position(id)?;
+            let interments = db.get_interments(id)?;
+            let relocations = db.get_grave_relocations(id)?;
+            let date_of_death_hijri = db::HijriDate::from_gregorian(g.date_of_death);
+            let burial_date_hijri = g.burial_date.map(db::HijriDate::from_gregorian);
             Ok(Some(GraveDetail {
+                date_of_death_hijri,
+                burial_date_hijri,
                 grave: g,
                 heirs,
+                position,
+                interments,
+                relocations,
             }))
         }
         None => Ok(None),
@@ -362,7 +1292,15 @@ async fn get_grave_detail(
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GraveDetail {
     pub grave: db::GraveWithBlock,
+    /// Hijri dates
+    pub date_of_death_hijri: db::HijriDate,
+    pub burial_date_hijri: Option<db::HijriDate>,
     pub heirs: Vec<db::Heir>,
+    pub position: Option<db::PlotPosition>,
+    /// Everyone buried in the grave, first interment first
+    pub interments: Vec<db::Interment>,
+    /// Earlier locations, oldest move first
+    pub relocations: Vec<db::GraveRelocation>,
 }
 
 // ==================== PAYMENTS COMMANDS ====================
@@ -392,102 +1330,300 @@ async fn get_payment_by_grave_and_year(
 #[tauri::command]
 async fn create_payment(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     payment: db::CreatePaymentRequest,
+    override_reason: Option<String>,
 ) -> Result<i64, String> {
-    let db = db::Database::init(&app_handle)?;
-    db.create_payment(&payment)
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    require_cash_session(&db, payment.payment_method.as_deref())?;
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_create_payment(&payment, &reason)
+        }
+        None => db.create_payment(&payment),
+    }
 }
 
 /// Update payment
 #[tauri::command]
 async fn update_payment(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     id: i64,
     payment: db::CreatePaymentRequest,
+    override_reason: Option<String>,
 ) -> Result<(), String> {
-    let db = db::Database::init(&app_handle)?;
-    // Use create payment request as update (simplified)
-    db.create_payment(&payment)?;
-    Ok(())
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_update_payment(id, &payment, &reason)
+        }
+        None => db.update_payment(id, &payment),
+    }
 }
 
 /// Delete payment
 #[tauri::command]
 async fn delete_payment(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    override_reason: Option<String>,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_delete_payment(id, &reason)
+        }
+        None => db.delete_payment(id),
+    }
+}
+
+/// Cash (the default method) is only taken into an open cash session
+fn require_cash_session(db: &db::Database, payment_method: Option<&str>) -> Result<(), String> {
+    let is_cash = payment_method.unwrap_or("cash") == "cash";
+    if is_cash && db.get_open_cash_session()?.is_none() {
+        return Err("Open a cash session before recording cash payments".to_string());
+    }
+    Ok(())
+}
+
+/// Overriding closed years and block capacity is reserved for admins
+fn require_admin(operator: &auth::Session) -> Result<(), String> {
+    if operator.role != db::Role::Admin {
+        return Err("Only an admin can override this restriction".to_string());
+    }
+    Ok(())
+}
+
+/// Get fee waivers by grave ID
+#[tauri::command]
+async fn get_waivers_by_grave(
+    app_handle: tauri::AppHandle,
+    grave_id: i64,
+) -> Result<Vec<db::FeeWaiver>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_waivers_by_grave(grave_id)
+}
+
+/// Create fee waiver
+#[tauri::command]
+async fn create_waiver(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    waiver: db::CreateWaiverRequest,
+    override_reason: Option<String>,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_create_waiver(&waiver, &reason)
+        }
+        None => db.create_waiver(&waiver),
+    }
+}
+
+/// Delete fee waiver
+#[tauri::command]
+async fn delete_waiver(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     id: i64,
+    override_reason: Option<String>,
 ) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    match override_reason {
+        Some(reason) => {
+            require_admin(&operator)?;
+            db.override_delete_waiver(id, &reason)
+        }
+        None => db.delete_waiver(id),
+    }
+}
+
+/// Get statement of account for a grave
+#[tauri::command]
+async fn get_grave_statement(
+    app_handle: tauri::AppHandle,
+    grave_id: i64,
+) -> Result<db::GraveStatement, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_grave_statement(grave_id)
+}
+
+/// Export statement of account as PDF or XLSX (with save dialog)
+#[tauri::command]
+async fn export_grave_statement(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    grave_id: i64,
+    format: export::ExportFormat,
+) -> Result<Option<String>, String> {
     let db = db::Database::init(&app_handle)?;
-    db.delete_payment(id)
+    let statement = db.get_grave_statement(grave_id)?;
+    let settings = db.get_settings()?;
+
+    let file_data = match format {
+        export::ExportFormat::Pdf => export::pdf::grave_statement(&statement, &settings)?,
+        export::ExportFormat::Xlsx => export::xlsx::grave_statement(&statement, &settings)?,
+    };
+
+    let default_name = format!(
+        "Rekening_{}_{}.{}",
+        statement.grave.code,
+        statement.grave.number,
+        format.extension()
+    );
+    save_file_with_dialog(
+        &app_handle,
+        &window,
+        file_data,
+        &default_name,
+        format.filter_name(),
+        &[format.extension()],
+    )
 }
 
 /// Get graves with payment summary for payment page
 #[tauri::command]
 async fn get_graves_with_payment_summary(
     app_handle: tauri::AppHandle,
-    search: Option<String>,
-    block_id: Option<i64>,
-    year: i32,
+    filter: db::PaymentOverviewFilter,
     limit: i64,
     offset: i64,
-) -> Result<Vec<GravePaymentSummary>, String> {
+) -> Result<Vec<db::GravePaymentSummary>, String> {
     let db = db::Database::init(&app_handle)?;
-    
-    // Get graves
-    let graves = db.get_graves(search.clone(), block_id, limit, offset)?;
-    
-    let mut result = Vec::new();
-    for grave in graves {
-        // Get payments for this grave
-        let payments = db.get_payments_by_grave(grave.id)?;
-        
-        // Check if paid for requested year
-        let payment_for_year = payments.iter().find(|p| p.year == year).cloned();
-        
-        // Get last 5 years payment status
-        let current_year = year;
-        let mut recent_payments = Vec::new();
-        for y in (current_year - 4)..=current_year {
-            let p = payments.iter().find(|p| p.year == y);
-            recent_payments.push(YearPaymentStatus {
-                year: y,
-                is_paid: p.is_some(),
-                amount: p.map(|pay| pay.amount),
-            });
-        }
-        
-        result.push(GravePaymentSummary {
-            grave_id: grave.id,
-            deceased_name: grave.deceased_name,
-            block_code: grave.code,
-            number: grave.number,
-            annual_fee: grave.annual_fee,
-            current_year_payment: payment_for_year,
-            recent_payments,
-        });
+    db.get_graves_with_payment_summary(&filter, limit, offset)
+}
+
+/// Count graves matching a payment summary filter
+#[tauri::command]
+async fn count_graves_with_payment_summary(
+    app_handle: tauri::AppHandle,
+    filter: db::PaymentOverviewFilter,
+) -> Result<i64, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.count_graves_with_payment_summary(&filter)
+}
+
+// ==================== CASH SESSION COMMANDS ====================
+
+/// Open cash session for the logged-in cashier
+#[tauri::command]
+async fn open_cash_session(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    opening_float: i64,
+) -> Result<i64, String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.open_cash_session(opening_float)
+}
+
+/// Get open cash session of the logged-in user
+#[tauri::command]
+async fn get_open_cash_session(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+) -> Result<Option<db::CashSession>, String> {
+    let current = session.current().ok_or_else(|| "Not logged in".to_string())?;
+    let db = db::Database::init(&app_handle)?.with_operator(current.user_id);
+    db.get_open_cash_session()
+}
+
+/// Get recent cash sessions
+#[tauri::command]
+async fn get_cash_sessions(
+    app_handle: tauri::AppHandle,
+    limit: Option<i64>,
+) -> Result<Vec<db::CashSession>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_cash_sessions(limit.unwrap_or(50))
+}
+
+/// Close cash session with the counted amount (own session, or any as admin)
+#[tauri::command]
+async fn close_cash_session(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    counted_amount: i64,
+    notes: Option<String>,
+) -> Result<db::CashClosingReport, String> {
+    let operator = session.require(auth::Permission::ManagePayments)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+
+    let cash_session = db
+        .get_cash_session_by_id(id)?
+        .ok_or_else(|| format!("Cash session {} not found", id))?;
+    if cash_session.opened_by != operator.user_id && operator.role != db::Role::Admin {
+        return Err("Only the cashier who opened the session or an admin can close it".to_string());
     }
-    
-    Ok(result)
+    db.close_cash_session(id, counted_amount, notes.as_deref())
 }
 
-/// Year payment status
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct YearPaymentStatus {
-    pub year: i32,
-    pub is_paid: bool,
-    pub amount: Option<i64>,
+/// Get cash closing report
+#[tauri::command]
+async fn get_cash_closing_report(
+    app_handle: tauri::AppHandle,
+    id: i64,
+) -> Result<db::CashClosingReport, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_cash_closing_report(id)
 }
 
-/// Grave payment summary for payment page
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct GravePaymentSummary {
-    pub grave_id: i64,
-    pub deceased_name: String,
-    pub block_code: String,
-    pub number: String,
-    pub annual_fee: i64,
-    pub current_year_payment: Option<db::Payment>,
-    pub recent_payments: Vec<YearPaymentStatus>,
+/// Export cash closing report as PDF (with save dialog)
+#[tauri::command]
+async fn export_cash_closing(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    id: i64,
+) -> Result<Option<String>, String> {
+    let db = db::Database::init(&app_handle)?;
+    let report = db.get_cash_closing_report(id)?;
+    let settings = db.get_settings()?;
+    let file_data = export::pdf::cash_closing(&report, &settings)?;
+
+    let default_name = format!("Tutup_Kas_{:04}.pdf", id);
+    save_file_with_dialog(&app_handle, &window, file_data, &default_name, "PDF Files", &["pdf"])
+}
+
+// ==================== HAUL COMMANDS ====================
+
+/// List haul anniversaries in the next `days` days (default 30)
+#[tauri::command]
+async fn get_upcoming_hauls(
+    app_handle: tauri::AppHandle,
+    days: Option<i64>,
+) -> Result<Vec<db::HaulAnniversary>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_upcoming_hauls(db.local_today()?, days.unwrap_or(30))
+}
+
+/// Export haul anniversaries in the next `days` days as ICS (with save dialog)
+#[tauri::command]
+async fn export_haul_calendar(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    days: Option<i64>,
+) -> Result<Option<String>, String> {
+    let db = db::Database::init(&app_handle)?;
+    let today = db.local_today()?;
+    let hauls = db.get_upcoming_hauls(today, days.unwrap_or(30))?;
+    let settings = db.get_settings()?;
+    let file_data = export::ics::haul_calendar(&hauls, &settings);
+
+    let default_name = format!("Haul_{}.ics", today.format("%Y%m%d"));
+    save_file_with_dialog(&app_handle, &window, file_data, &default_name, "Calendar Files", &["ics"])
 }
 
 // ==================== DASHBOARD COMMANDS ====================
@@ -561,6 +1697,73 @@ async fn get_available_years(
     db.get_available_years()
 }
 
+/// Get payments received per operator and date (cash reconciliation)
+#[tauri::command]
+async fn get_payments_by_operator(
+    app_handle: tauri::AppHandle,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<db::OperatorPaymentSummary>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_payments_by_operator(&start_date, &end_date)
+}
+
+// ==================== FISCAL YEAR COMMANDS ====================
+
+/// Close the active fiscal year and carry arrears into the next year
+#[tauri::command]
+async fn close_fiscal_year(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    year: i32,
+    notes: Option<String>,
+) -> Result<db::ClosedYear, String> {
+    let operator = session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.close_fiscal_year(year, notes.as_deref())
+}
+
+/// Get closed fiscal years
+#[tauri::command]
+async fn get_closed_years(app_handle: tauri::AppHandle) -> Result<Vec<db::ClosedYear>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_closed_years()
+}
+
+/// Get yearly report snapshot stored when the year was closed
+#[tauri::command]
+async fn get_closed_year_report(
+    app_handle: tauri::AppHandle,
+    year: i32,
+) -> Result<db::YearlyReport, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_closed_year(year)?
+        .map(|closed| closed.report)
+        .ok_or_else(|| format!("Year {} is not closed", year))
+}
+
+/// Get arrears carried into a year
+#[tauri::command]
+async fn get_carried_arrears(
+    app_handle: tauri::AppHandle,
+    year: i32,
+) -> Result<Vec<db::CarriedArrears>, String> {
+    let db = db::Database::init(&app_handle)?;
+    db.get_carried_arrears(year)
+}
+
+/// Get audit log of changes to locked payments
+#[tauri::command]
+async fn get_payment_lock_overrides(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    limit: Option<i64>,
+) -> Result<Vec<db::PaymentLockOverride>, String> {
+    session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?;
+    db.get_payment_lock_overrides(limit.unwrap_or(100))
+}
+
 // ==================== SETTINGS COMMANDS ====================
 
 /// Get settings
@@ -576,17 +1779,51 @@ async fn get_settings(
 #[tauri::command]
 async fn update_settings(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     settings: db::UpdateSettingsRequest,
 ) -> Result<(), String> {
+    session.require(auth::Permission::ManageSettings)?;
     let db = db::Database::init(&app_handle)?;
     db.update_settings(&settings)
 }
 
+/// List the timezones that can be configured
+#[tauri::command]
+async fn get_timezones() -> Result<Vec<db::Timezone>, String> {
+    Ok(db::TIMEZONES.to_vec())
+}
+
+/// Get stored dates that could not be read during the date migration
+#[tauri::command]
+async fn get_date_issues(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+) -> Result<Vec<db::DateIssue>, String> {
+    session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?;
+    db.get_date_issues()
+}
+
+/// Confirm a date that could not be read, optionally with the right date
+#[tauri::command]
+async fn resolve_date_issue(
+    app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
+    id: i64,
+    date: Option<String>,
+) -> Result<(), String> {
+    let operator = session.require(auth::Permission::ManageSettings)?;
+    let db = db::Database::init(&app_handle)?.with_operator(operator.user_id);
+    db.resolve_date_issue(id, date.as_deref())
+}
+
 /// Update last backup time
 #[tauri::command]
 async fn update_last_backup(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
 ) -> Result<(), String> {
+    session.require(auth::Permission::ManageSettings)?;
     let db = db::Database::init(&app_handle)?;
     db.update_last_backup()
 }
@@ -595,9 +1832,11 @@ async fn update_last_backup(
 #[tauri::command]
 async fn upload_logo(
     app_handle: tauri::AppHandle,
+    session: tauri::State<'_, auth::SessionState>,
     file_data: Vec<u8>,
     file_name: String,
 ) -> Result<String, String> {
+    session.require(auth::Permission::ManageSettings)?;
     // Get app data directory
     let app_data_dir = app_handle
         .path()
@@ -716,12 +1955,25 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_os::init())
+        .manage(auth::SessionState::default())
         .setup(setup_handler)
         .invoke_handler(tauri::generate_handler![
             greet,
             get_database_path,
             get_database_stats,
             backup_database,
+            // Auth
+            has_users,
+            setup_admin,
+            login,
+            logout,
+            get_current_session,
+            change_password,
+            get_users,
+            create_user,
+            update_user,
+            reset_user_password,
+            delete_user,
             // Blocks
             get_blocks,
             get_block_by_id,
@@ -729,22 +1981,76 @@ pub fn run() {
             update_block,
             delete_block,
             get_block_stats,
+            get_capacity_forecast,
+            get_capacity_overrides,
+            get_block_layout,
+            set_block_layout,
+            get_plot_map,
+            get_block_numbering,
+            set_block_numbering,
+            get_next_grave_number,
             // Graves
             get_graves,
             count_graves,
+            search_names,
+            global_search,
             get_grave_by_id,
             create_grave_with_heirs,
             update_grave,
             delete_grave,
+            set_grave_position,
             get_grave_detail,
             export_graves,
+            export_excel,
             save_excel_file,
+            // Interments
+            get_interments,
+            add_interment,
+            update_interment,
+            delete_interment,
+            // Persons
+            search_persons,
+            get_person_by_id,
+            create_person,
+            update_person,
+            delete_person,
+            get_person_graves,
+            link_person_to_grave,
+            get_person_arrears,
+            pay_person_arrears,
+            // Duplicates
+            find_duplicate_graves,
+            dismiss_duplicate,
+            merge_graves,
+            // Relocations
+            relocate_grave,
+            get_grave_relocations,
+            // Reservations
+            get_reservations,
+            get_reservation_by_id,
+            create_reservation,
+            update_reservation,
+            cancel_reservation,
+            convert_reservation,
+            // Import
+            preview_import_file,
+            import_graves,
+            get_import_profiles,
+            save_import_profile,
+            delete_import_profile,
+            // Data transfer
+            export_data_json,
+            preview_data_file,
+            import_data_json,
+            preview_merge_database,
+            merge_database,
             // Heirs
             get_heirs_by_grave,
             create_heir,
             update_heir,
             delete_heir,
             update_grave_heirs,
+            get_phone_number_issues,
             // Payments
             get_payments_by_grave,
             get_payment_by_grave_and_year,
@@ -752,6 +2058,22 @@ pub fn run() {
             update_payment,
             delete_payment,
             get_graves_with_payment_summary,
+            count_graves_with_payment_summary,
+            get_waivers_by_grave,
+            create_waiver,
+            delete_waiver,
+            get_grave_statement,
+            export_grave_statement,
+            // Cash sessions
+            open_cash_session,
+            get_open_cash_session,
+            get_cash_sessions,
+            close_cash_session,
+            get_cash_closing_report,
+            export_cash_closing,
+            // Haul
+            get_upcoming_hauls,
+            export_haul_calendar,
             // Dashboard
             get_dashboard_stats,
             get_recent_payments,
@@ -761,9 +2083,19 @@ pub fn run() {
             // Reports
             get_yearly_report,
             get_available_years,
+            get_payments_by_operator,
+            // Fiscal year
+            close_fiscal_year,
+            get_closed_years,
+            get_closed_year_report,
+            get_carried_arrears,
+            get_payment_lock_overrides,
             // Settings
             get_settings,
             update_settings,
+            get_timezones,
+            get_date_issues,
+            resolve_date_issue,
             update_last_backup,
             upload_logo,
             get_logo_data,
@@ -40,6 +40,23 @@ async function getBlockStats(blockId) {
     }
 }
 
+/** Capacity forecast per block ID (empty if unavailable) */
+async function getCapacityForecasts() {
+    try {
+        const forecast = await invoke('get_capacity_forecast', { years: 5 });
+        return Object.fromEntries(forecast.blocks.map(b => [b.block_id, b]));
+    } catch (error) {
+        console.error('Failed to load capacity forecast:', error);
+        return {};
+    }
+}
+
+function formatFullYear(forecast) {
+    if (!forecast || forecast.projected_full_year == null) return '-';
+    if (forecast.available === 0) return 'Penuh';
+    return `± ${forecast.projected_full_year}`;
+}
+
 // ==================== RENDER CARDS ====================
 
 function renderBlockCards() {
@@ -81,6 +98,11 @@ function renderBlockCards() {
                             ${isActive ? 'Aktif' : 'Nonaktif'}
                         </span>
                         <div class="flex gap-2">
+                            <button onclick="openPlotMapModal(${block.id})" class="p-2 text-emerald-600 hover:bg-emerald-50 rounded-lg" title="Denah">
+                                <svg class="w-4 h-4" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M4 5a1 1 0 011-1h4a1 1 0 011 1v4a1 1 0 01-1 1H5a1 1 0 01-1-1V5zm10 0a1 1 0 011-1h4a1 1 0 011 1v4a1 1 0 01-1 1h-4a1 1 0 01-1-1V5zM4 15a1 1 0 011-1h4a1 1 0 011 1v4a1 1 0 01-1 1H5a1 1 0 01-1-1v-4zm10 0a1 1 0 011-1h4a1 1 0 011 1v4a1 1 0 01-1 1h-4a1 1 0 01-1-1v-4z"></path>
+                                </svg>
+                            </button>
                             <button onclick="openEditModal(${block.id})" class="p-2 text-blue-600 hover:bg-blue-50 rounded-lg" title="Edit">
                                 <svg class="w-4 h-4" fill="none" stroke="currentColor" viewBox="0 0 24 24">
                                     <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15.232 5.232l3.536 3.536m-2.036-5.036a2.5 2.5 0 113.536 3.536L6.5 21.036H3v-3.572L16.732 3.732z"></path>
@@ -130,7 +152,9 @@ async function loadOccupiedCount(blockId, card) {
     const stats = await getBlockStats(blockId);
     const occupiedEl = card.querySelector('.block-occupied');
     if (occupiedEl) {
-        occupiedEl.textContent = `${stats.occupied} makam`;
+        occupiedEl.textContent = stats.reserved > 0
+            ? `${stats.occupied} makam · ${stats.reserved} dipesan`
+            : `${stats.occupied} makam`;
     }
 }
 
@@ -145,7 +169,7 @@ async function renderBlockTable() {
     if (currentBlocks.length === 0) {
         tbody.innerHTML = `
             <tr>
-                <td colspan="8" class="px-4 py-8 text-center text-gray-500">
+                <td colspan="9" class="px-4 py-8 text-center text-gray-500">
                     Tidak ada data blok
                 </td>
             </tr>
@@ -161,6 +185,7 @@ async function renderBlockTable() {
     const blockStats = await Promise.all(
         currentBlocks.map(block => getBlockStats(block.id))
     );
+    const forecasts = await getCapacityForecasts();
     
     currentBlocks.forEach((block, index) => {
         const isActive = block.status === 'active';
@@ -174,6 +199,7 @@ async function renderBlockTable() {
             <td class="px-4 py-3 text-sm text-center text-gray-600">${block.total_capacity}</td>
             <td class="px-4 py-3 text-sm text-center text-emerald-600 font-medium">${stats.occupied}</td>
             <td class="px-4 py-3 text-sm text-center text-gray-600">${stats.available}</td>
+            <td class="px-4 py-3 text-sm text-center text-gray-600">${formatFullYear(forecasts[block.id])}</td>
             <td class="px-4 py-3 text-sm text-right font-semibold text-emerald-600">Rp ${formatNumber(block.annual_fee)}</td>
             <td class="px-4 py-3 text-center">
                 <span class="inline-flex px-2 py-1 ${isActive ? 'bg-emerald-100 text-emerald-700' : 'bg-gray-100 text-gray-600'} text-xs font-medium rounded-full">
@@ -286,7 +312,13 @@ async function simpanBlock() {
         await loadBlocks();
     } catch (error) {
         console.error('Failed to create block:', error);
-        showToast('Gagal menambahkan blok: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, {
+            code: 'tambahKode',
+            total_capacity: 'tambahKapasitas',
+            annual_fee: 'tambahIuran',
+            status: 'tambahStatus'
+        });
+        showToast('Gagal menambahkan blok: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
@@ -314,6 +346,10 @@ async function openEditModal(blockId) {
         document.getElementById('editIuran').value = block.annual_fee;
         document.getElementById('editStatus').value = block.status;
         
+        const numbering = await invoke('get_block_numbering', { blockId });
+        document.getElementById('editNumberScheme').value = numbering.scheme;
+        document.getElementById('editNumberWidth').value = numbering.width;
+        
         const modal = document.getElementById('modalEdit');
         const panel = document.getElementById('panelEdit');
         modal.classList.remove('hidden');
@@ -342,6 +378,10 @@ async function updateBlockData() {
         const total_capacity = parseInt(document.getElementById('editKapasitas').value);
         const annual_fee = parseInt(document.getElementById('editIuran').value);
         const status = document.getElementById('editStatus').value;
+        const numbering = {
+            scheme: document.getElementById('editNumberScheme').value,
+            width: parseInt(document.getElementById('editNumberWidth').value) || 0
+        };
         
         // Validation
         if (isNaN(total_capacity) || total_capacity <= 0) {
@@ -352,6 +392,10 @@ async function updateBlockData() {
             showToast('Harga iuran wajib diisi', 'error');
             return;
         }
+        if (numbering.scheme === 'padded' && numbering.width === 0) {
+            showToast('Jumlah digit wajib diisi untuk nomor dengan nol', 'error');
+            return;
+        }
         
         showLoading(true);
         
@@ -365,13 +409,19 @@ async function updateBlockData() {
                 status
             }
         });
+        await invoke('set_block_numbering', { blockId: currentEditingId, numbering });
         
         closeEditModal();
         showToast('Blok berhasil diperbarui', 'success');
         await loadBlocks();
     } catch (error) {
         console.error('Failed to update block:', error);
-        showToast('Gagal memperbarui blok: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, {
+            total_capacity: 'editKapasitas',
+            annual_fee: 'editIuran',
+            status: 'editStatus'
+        });
+        showToast('Gagal memperbarui blok: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
@@ -444,6 +494,166 @@ async function confirmDelete() {
     }
 }
 
+// ==================== PLOT MAP MODAL ====================
+
+let currentMapBlockId = null;
+let currentLayout = null;
+
+const cellStyles = {
+    free: 'bg-white border-gray-300 hover:bg-emerald-50',
+    reserved: 'bg-amber-100 border-amber-300',
+    unusable: 'bg-gray-300 border-gray-400',
+};
+
+const paymentStyles = {
+    paid: 'bg-emerald-500 border-emerald-600 text-white',
+    waived: 'bg-blue-400 border-blue-500 text-white',
+    unpaid: 'bg-red-500 border-red-600 text-white',
+    not_due: 'bg-gray-500 border-gray-600 text-white',
+};
+
+async function openPlotMapModal(blockId) {
+    currentMapBlockId = blockId;
+
+    let mapModal = document.getElementById('modalDenah');
+    if (!mapModal) {
+        mapModal = document.createElement('div');
+        mapModal.id = 'modalDenah';
+        mapModal.className = 'fixed inset-0 z-50 hidden';
+        mapModal.innerHTML = `
+            <div class="absolute inset-0 bg-black/50" onclick="closePlotMapModal()"></div>
+            <div class="absolute inset-0 flex items-center justify-center p-4">
+                <div class="bg-white rounded-2xl shadow-2xl w-full max-w-4xl max-h-[90vh] flex flex-col">
+                    <div class="px-6 py-4 border-b border-gray-200 flex items-center justify-between">
+                        <h3 class="text-lg font-semibold text-gray-800" id="denahJudul">Denah Blok</h3>
+                        <button onclick="closePlotMapModal()" class="p-2 text-gray-400 hover:text-gray-600 rounded-lg">&times;</button>
+                    </div>
+                    <div class="px-6 py-3 border-b border-gray-200 flex flex-wrap items-end gap-3 text-sm">
+                        <label class="flex flex-col">Baris
+                            <input id="denahBaris" type="number" min="1" class="w-20 px-2 py-1 border border-gray-300 rounded-lg">
+                        </label>
+                        <label class="flex flex-col">Kolom
+                            <input id="denahKolom" type="number" min="1" class="w-20 px-2 py-1 border border-gray-300 rounded-lg">
+                        </label>
+                        <button onclick="saveLayoutSize()" class="px-3 py-1.5 bg-emerald-600 text-white rounded-lg hover:bg-emerald-700">Simpan Ukuran</button>
+                        <span class="text-gray-500">Klik petak kosong untuk menandai tidak dapat dipakai</span>
+                    </div>
+                    <div class="p-6 overflow-auto flex-1">
+                        <div id="denahGrid"></div>
+                        <p id="denahBelumDitempatkan" class="mt-4 text-sm text-gray-500"></p>
+                    </div>
+                    <div class="px-6 py-3 border-t border-gray-200 flex flex-wrap gap-4 text-xs text-gray-600">
+                        <span><span class="inline-block w-3 h-3 rounded bg-white border border-gray-300"></span> Kosong</span>
+                        <span><span class="inline-block w-3 h-3 rounded bg-amber-100 border border-amber-300"></span> Dipesan</span>
+                        <span><span class="inline-block w-3 h-3 rounded bg-gray-300"></span> Tidak dapat dipakai</span>
+                        <span><span class="inline-block w-3 h-3 rounded bg-emerald-500"></span> Lunas</span>
+                        <span><span class="inline-block w-3 h-3 rounded bg-blue-400"></span> Dispensasi</span>
+                        <span><span class="inline-block w-3 h-3 rounded bg-red-500"></span> Belum bayar</span>
+                        <span id="denahRingkasan" class="ml-auto"></span>
+                    </div>
+                </div>
+            </div>
+        `;
+        document.body.appendChild(mapModal);
+    }
+
+    mapModal.classList.remove('hidden');
+    await loadPlotMap();
+}
+
+function closePlotMapModal() {
+    const mapModal = document.getElementById('modalDenah');
+    if (mapModal) {
+        mapModal.classList.add('hidden');
+    }
+    currentMapBlockId = null;
+    currentLayout = null;
+}
+
+async function loadPlotMap() {
+    const block = currentBlocks.find(b => b.id === currentMapBlockId);
+    document.getElementById('denahJudul').textContent = `Denah Blok ${block ? block.code : ''}`;
+    const grid = document.getElementById('denahGrid');
+
+    try {
+        currentLayout = await invoke('get_block_layout', { blockId: currentMapBlockId });
+        document.getElementById('denahBaris').value = currentLayout ? currentLayout.rows : '';
+        document.getElementById('denahKolom').value = currentLayout ? currentLayout.columns : '';
+
+        if (!currentLayout) {
+            grid.innerHTML = '<p class="text-sm text-gray-500">Blok ini belum memiliki denah. Isi jumlah baris dan kolom lalu simpan.</p>';
+            document.getElementById('denahBelumDitempatkan').textContent = '';
+            document.getElementById('denahRingkasan').textContent = '';
+            return;
+        }
+
+        const map = await invoke('get_plot_map', { blockId: currentMapBlockId });
+        grid.className = 'grid gap-1';
+        grid.style.gridTemplateColumns = `repeat(${map.columns}, minmax(2.5rem, 1fr))`;
+        grid.innerHTML = map.cells.map(cell => {
+            const style = cell.grave ? paymentStyles[cell.grave.payment_status] : cellStyles[cell.state];
+            const title = cell.grave
+                ? `${cell.grave.number} - ${cell.grave.deceased_name}`
+                : cell.reservation
+                    ? `Dipesan oleh ${cell.reservation.reserver_name}${cell.reservation.reserved_until ? ' s/d ' + cell.reservation.reserved_until : ''}`
+                    : `Baris ${cell.row}, Kolom ${cell.column}`;
+            const clickable = cell.state === 'free' || cell.state === 'unusable';
+            return `
+                <div class="h-10 rounded border text-[10px] flex items-center justify-center truncate ${style} ${clickable ? 'cursor-pointer' : ''}"
+                     title="${escapeHtml(title)}"
+                     ${clickable ? `onclick="toggleUnusableCell(${cell.row}, ${cell.column})"` : ''}>
+                    ${cell.grave ? escapeHtml(cell.grave.number) : ''}
+                </div>
+            `;
+        }).join('');
+
+        document.getElementById('denahRingkasan').textContent =
+            `Kosong ${map.free_count} · Terisi ${map.occupied_count} · Dipesan ${map.reserved_count} (tahun ${map.year})`;
+        document.getElementById('denahBelumDitempatkan').textContent = map.unplaced_graves.length
+            ? `Belum ditempatkan di denah: ${map.unplaced_graves.map(g => g.number).join(', ')}`
+            : '';
+    } catch (error) {
+        console.error('Failed to load plot map:', error);
+        showToast('Gagal memuat denah: ' + error, 'error');
+    }
+}
+
+async function saveLayoutSize() {
+    const rows = parseInt(document.getElementById('denahBaris').value);
+    const columns = parseInt(document.getElementById('denahKolom').value);
+    if (isNaN(rows) || rows <= 0 || isNaN(columns) || columns <= 0) {
+        showToast('Jumlah baris dan kolom wajib diisi', 'error');
+        return;
+    }
+
+    const unusable_cells = (currentLayout ? currentLayout.unusable_cells : [])
+        .filter(c => c.row <= rows && c.column <= columns);
+    await saveLayout({ rows, columns, unusable_cells });
+}
+
+async function toggleUnusableCell(row, column) {
+    if (!currentLayout) return;
+
+    const isUnusable = currentLayout.unusable_cells.some(c => c.row === row && c.column === column);
+    const unusable_cells = isUnusable
+        ? currentLayout.unusable_cells.filter(c => !(c.row === row && c.column === column))
+        : [...currentLayout.unusable_cells, { row, column, notes: null }];
+    await saveLayout({ rows: currentLayout.rows, columns: currentLayout.columns, unusable_cells });
+}
+
+async function saveLayout(layout) {
+    try {
+        showLoading(true);
+        await invoke('set_block_layout', { blockId: currentMapBlockId, layout });
+        await loadPlotMap();
+    } catch (error) {
+        console.error('Failed to save layout:', error);
+        showToast('Gagal menyimpan denah: ' + error, 'error');
+    } finally {
+        showLoading(false);
+    }
+}
+
 // ==================== UTILITIES ====================
 
 function formatNumber(num) {
@@ -492,3 +702,7 @@ window.updateBlockData = updateBlockData;
 window.openDeleteModal = openDeleteModal;
 window.closeDeleteModal = closeDeleteModal;
 window.confirmDelete = confirmDelete;
+window.openPlotMapModal = openPlotMapModal;
+window.closePlotMapModal = closePlotMapModal;
+window.saveLayoutSize = saveLayoutSize;
+window.toggleUnusableCell = toggleUnusableCell;
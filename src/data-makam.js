@@ -9,7 +9,10 @@ let currentBlocks = [];
 let currentPage = 1;
 let totalPages = 1;
 const itemsPerPage = 10;
+// Keyset cursor where each page starts, filled while paging forward
+let pageCursors = {};
 let currentEditingId = null;
+let currentEditingGrave = null;
 let currentDeletingId = null;
 let currentDeletingName = '';
 
@@ -20,6 +23,9 @@ const maxAhliWaris = 3;
 // Variables for Edit Modal
 let jumlahAhliWarisEdit = 1;
 
+// Heirs of the last save, to place backend validation errors
+let lastSavedHeirs = [];
+
 // ==================== INITIALIZATION ====================
 
 document.addEventListener('DOMContentLoaded', async () => {
@@ -34,18 +40,26 @@ function setupEventListeners() {
     if (searchInput) {
         searchInput.addEventListener('input', debounce(async (e) => {
             currentPage = 1;
+            pageCursors = {};
             await loadGraves(e.target.value);
         }, 300));
     }
 
-    // Block filter
+    // Block, sort and advanced filters
     const blockSelect = document.querySelector('aside + main select');
-    if (blockSelect) {
-        blockSelect.addEventListener('change', async () => {
-            currentPage = 1;
-            await loadGraves();
-        });
-    }
+    const filterControls = [blockSelect].concat(
+        ['sortSelect', 'diedFromFilter', 'diedUntilFilter', 'burialYearFilter', 'arrearsFilter', 'withoutHeirsFilter', 'withoutPhoneFilter']
+            .map(id => document.getElementById(id))
+    );
+    filterControls.forEach(control => {
+        if (control) {
+            control.addEventListener('change', async () => {
+                currentPage = 1;
+                pageCursors = {};
+                await loadGraves(searchInput ? searchInput.value : '');
+            });
+        }
+    });
     
     // Export modal year selectors
     const startYearSelect = document.getElementById('exportStartYear');
@@ -120,6 +134,72 @@ function populateTambahBlockSelect() {
         option.textContent = `Blok ${block.code}`;
         blockSelect.appendChild(option);
     });
+    blockSelect.onchange = () => {
+        loadPlotPicker(parseInt(blockSelect.value));
+        suggestGraveNumber(parseInt(blockSelect.value));
+    };
+}
+
+/** Isi nomor makam berikutnya yang kosong dan tampilkan nomor yang terlewat */
+async function suggestGraveNumber(blockId) {
+    const input = document.getElementById('tambahNomor');
+    const hint = document.getElementById('tambahNomorSaran');
+    hint.textContent = '';
+    if (!blockId) return;
+
+    try {
+        const suggestion = await invoke('get_next_grave_number', { blockId });
+        if (!input.value.trim()) input.value = suggestion.next_number;
+        let text = `Nomor berikutnya: ${suggestion.next_number}`;
+        if (suggestion.gap_count > 0) {
+            const more = suggestion.gap_count > 10 ? ', ...' : '';
+            text += ` · Terlewat: ${suggestion.gaps.slice(0, 10).join(', ')}${more}`;
+        }
+        hint.textContent = text;
+    } catch (error) {
+        console.error('Failed to suggest grave number:', error);
+    }
+}
+
+// ==================== PLOT PICKER ====================
+
+let selectedPosition = null;
+
+async function loadPlotPicker(blockId) {
+    const container = document.getElementById('tambahPetakContainer');
+    const grid = document.getElementById('tambahPetakGrid');
+    selectedPosition = null;
+    document.getElementById('tambahPetakTerpilih').textContent = '';
+    container.classList.add('hidden');
+    if (!blockId) return;
+
+    try {
+        const layout = await invoke('get_block_layout', { blockId });
+        if (!layout) return;
+
+        const map = await invoke('get_plot_map', { blockId });
+        grid.style.gridTemplateColumns = `repeat(${map.columns}, minmax(1.75rem, 1fr))`;
+        grid.innerHTML = map.cells.map(cell => {
+            const free = cell.state === 'free';
+            const style = free ? 'bg-white border-gray-300 hover:bg-emerald-100 cursor-pointer' : 'bg-gray-200 border-gray-300';
+            return `<div class="plot-cell h-7 rounded border ${style}" data-row="${cell.row}" data-column="${cell.column}"
+                         title="Baris ${cell.row}, Kolom ${cell.column}${cell.grave ? ' - ' + escapeHtml(cell.grave.number) : ''}"
+                         ${free ? `onclick="selectPlotCell(${cell.row}, ${cell.column})"` : ''}></div>`;
+        }).join('');
+        container.classList.remove('hidden');
+    } catch (error) {
+        console.error('Failed to load plot map:', error);
+    }
+}
+
+function selectPlotCell(row, column) {
+    selectedPosition = { row, column };
+    document.querySelectorAll('#tambahPetakGrid .plot-cell').forEach(el => {
+        const isSelected = parseInt(el.dataset.row) === row && parseInt(el.dataset.column) === column;
+        el.classList.toggle('bg-emerald-500', isSelected);
+        el.classList.toggle('bg-white', !isSelected);
+    });
+    document.getElementById('tambahPetakTerpilih').textContent = `(Baris ${row}, Kolom ${column})`;
 }
 
 function populateEditBlockSelect(selectedBlockId = null) {
@@ -138,26 +218,56 @@ function populateEditBlockSelect(selectedBlockId = null) {
     });
 }
 
+// Sort and filters of the grave list, as taken by get_graves and count_graves
+function buildGraveQuery(search) {
+    const blockSelect = document.querySelector('aside + main select');
+    const sortSelect = document.getElementById('sortSelect');
+    const [sort, direction] = (sortSelect ? sortSelect.value : '').split(':');
+    const value = (id) => {
+        const element = document.getElementById(id);
+        return element && element.value ? element.value : null;
+    };
+    const checked = (id) => {
+        const element = document.getElementById(id);
+        return element ? element.checked : false;
+    };
+
+    return {
+        search: search || null,
+        block_id: blockSelect && blockSelect.value ? parseInt(blockSelect.value) : null,
+        sort: sort || null,
+        descending: direction === 'desc',
+        died_from: value('diedFromFilter'),
+        died_until: value('diedUntilFilter'),
+        burial_year: value('burialYearFilter') ? parseInt(value('burialYearFilter')) : null,
+        without_heirs: checked('withoutHeirsFilter'),
+        without_phone: checked('withoutPhoneFilter'),
+        min_arrears_years: value('arrearsFilter') ? parseInt(value('arrearsFilter')) : null
+    };
+}
+
 async function loadGraves(search = '') {
     try {
         showLoading(true);
         
-        const blockSelect = document.querySelector('aside + main select');
-        const blockId = blockSelect && blockSelect.value ? parseInt(blockSelect.value) : null;
-        
-        const offset = (currentPage - 1) * itemsPerPage;
+        const query = buildGraveQuery(search);
         
-        const graves = await invoke('get_graves', {
-            search: search || null,
-            blockId: blockId,
-            limit: itemsPerPage,
-            offset: offset
+        // Continue from the previous page's cursor when known, else skip rows
+        const cursor = pageCursors[currentPage];
+        const page = await invoke('get_graves', {
+            query: {
+                ...query,
+                after: cursor || null,
+                offset: cursor ? 0 : (currentPage - 1) * itemsPerPage
+            },
+            limit: itemsPerPage
         });
+        if (page.next_cursor) {
+            pageCursors[currentPage + 1] = page.next_cursor;
+        }
+        const graves = page.graves;
         
-        const totalCount = await invoke('count_graves', {
-            search: search || null,
-            blockId: blockId
-        });
+        const totalCount = await invoke('count_graves', { query });
         
         currentGraves = graves;
         totalPages = Math.ceil(totalCount / itemsPerPage) || 1;
@@ -334,6 +444,8 @@ function openModal() {
     document.getElementById('tambahBlockSelect').value = '';
     document.getElementById('tambahNomor').value = '';
     document.getElementById('tambahTanggal').value = '';
+    loadPlotPicker(null);
+    document.getElementById('tambahNomorSaran').textContent = '';
     resetAhliWaris();
 }
 
@@ -489,6 +601,8 @@ async function simpanData() {
             return;
         }
         
+        lastSavedHeirs = heirs;
+
         // Create request
         const request = {
             grave: {
@@ -499,23 +613,55 @@ async function simpanData() {
                 burial_date: null,
                 notes: null
             },
-            heirs: heirs
+            heirs: heirs,
+            position: selectedPosition
         };
         
         showLoading(true);
-        await invoke('create_grave_with_heirs', { request });
+        try {
+            await invoke('create_grave_with_heirs', { request });
+        } catch (error) {
+            const reason = askCapacityOverride(error);
+            if (!reason) return;
+            await invoke('create_grave_with_heirs', { request, overrideReason: reason });
+        }
         
         closeModal();
         showToast('Data makam berhasil disimpan', 'success');
         await loadGraves();
     } catch (error) {
         console.error('Failed to save grave:', error);
-        showToast('Gagal menyimpan data: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, (field) => graveFieldInput(field, 'tambah', 'ahliWarisContainer', lastSavedHeirs));
+        showToast('Gagal menyimpan data: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
 }
 
+/**
+ * Blok penuh/nonaktif: admin dapat tetap menempatkan makam dengan alasan.
+ * Melempar ulang error lain; mengembalikan null jika dibatalkan.
+ */
+// Input for a backend validation field of the add ('tambah') or edit form;
+// heir fields are `heirs[i].field`, `i` being the index in `heirs` sent
+function graveFieldInput(field, prefix, heirContainerId, heirs) {
+    const inputs = { deceased_name: 'Nama', number: 'Nomor', date_of_death: 'Tanggal', burial_date: 'Tanggal' };
+    if (inputs[field]) return document.getElementById(prefix + inputs[field]);
+
+    const match = /^heirs\[(\d+)\]\.(\w+)$/.exec(field);
+    const heir = match && heirs[Number(match[1])];
+    if (!heir) return null;
+    const row = document.querySelectorAll(`#${heirContainerId} > div`)[heir.order_number - 1];
+    const classes = { full_name: '.heir-nama', phone_number: '.heir-telp', address: '.heir-alamat' };
+    return row && classes[match[2]] ? row.querySelector(classes[match[2]]) : null;
+}
+
+function askCapacityOverride(error) {
+    if (!/is (full|inactive)/.test(String(error))) throw error;
+    const reason = prompt('Blok penuh atau nonaktif. Alasan tetap menempatkan makam (khusus admin):');
+    return reason && reason.trim() ? reason.trim() : null;
+}
+
 // ==================== EDIT MODAL ====================
 
 async function openEditModal(graveId) {
@@ -530,13 +676,21 @@ async function openEditModal(graveId) {
             return;
         }
         
+        currentEditingGrave = detail.grave;
+        
         // Populate form
         document.getElementById('editNama').value = detail.grave.deceased_name;
         document.getElementById('editTanggal').value = detail.grave.date_of_death;
+        const hijri = [];
+        if (detail.date_of_death_hijri) hijri.push(`Wafat ${detail.date_of_death_hijri.formatted}`);
+        if (detail.burial_date_hijri) hijri.push(`dimakamkan ${detail.burial_date_hijri.formatted}`);
+        document.getElementById('editTanggalHijri').textContent = hijri.join(', ');
         document.getElementById('editNomor').value = detail.grave.number;
         
         // Populate block select
         populateEditBlockSelect(detail.grave.block_id);
+
+        renderTumpangList(detail.interments);
         
         // Populate heirs
         const container = document.getElementById('editAhliWarisContainer');
@@ -565,6 +719,75 @@ async function openEditModal(graveId) {
     }
 }
 
+// Interments after the first one (the first is edited through the grave form)
+function renderTumpangList(interments) {
+    const list = document.getElementById('editTumpangList');
+    const others = interments.slice(1);
+    list.innerHTML = others.length === 0
+        ? '<p class="text-gray-500">Belum ada jenazah tumpang</p>'
+        : others.map(i => `
+            <div class="flex items-center justify-between p-2 bg-gray-50 rounded-lg">
+                <div>
+                    <p class="font-medium text-gray-800">${escapeHtml(i.deceased_name)}</p>
+                    <p class="text-xs text-gray-500">Wafat ${escapeHtml(i.date_of_death)}${i.burial_date ? ' · Dimakamkan ' + escapeHtml(i.burial_date) : ''}</p>
+                </div>
+                <button type="button" onclick="hapusTumpang(${i.id})" class="px-2 py-1 text-xs text-red-600 hover:bg-red-50 rounded">Hapus</button>
+            </div>
+        `).join('');
+}
+
+async function reloadTumpang() {
+    renderTumpangList(await invoke('get_interments', { graveId: currentEditingId }));
+}
+
+async function tambahTumpang() {
+    if (!currentEditingId) return;
+
+    const deceased_name = document.getElementById('tumpangNama').value.trim();
+    const date_of_death = document.getElementById('tumpangWafat').value;
+    if (!deceased_name || !date_of_death) {
+        showToast('Nama dan tanggal wafat jenazah tumpang wajib diisi', 'error');
+        return;
+    }
+
+    try {
+        await invoke('add_interment', {
+            graveId: currentEditingId,
+            interment: {
+                deceased_name,
+                date_of_death,
+                burial_date: document.getElementById('tumpangMakam').value || null,
+                notes: null
+            }
+        });
+        document.getElementById('tumpangNama').value = '';
+        document.getElementById('tumpangWafat').value = '';
+        document.getElementById('tumpangMakam').value = '';
+        await reloadTumpang();
+        showToast('Jenazah tumpang berhasil ditambahkan', 'success');
+    } catch (error) {
+        console.error('Failed to add interment:', error);
+        window.astanaApp.showFieldErrors(error, {
+            deceased_name: 'tumpangNama',
+            date_of_death: 'tumpangWafat',
+            burial_date: 'tumpangMakam'
+        });
+        showToast('Gagal menambahkan jenazah tumpang: ' + window.astanaApp.errorText(error), 'error');
+    }
+}
+
+async function hapusTumpang(id) {
+    if (!confirm('Hapus data jenazah tumpang ini?')) return;
+
+    try {
+        await invoke('delete_interment', { id });
+        await reloadTumpang();
+    } catch (error) {
+        console.error('Failed to delete interment:', error);
+        showToast('Gagal menghapus jenazah tumpang: ' + error, 'error');
+    }
+}
+
 function closeEditModal() {
     const modal = document.getElementById('editModal');
     const panel = document.getElementById('editModalPanel');
@@ -671,16 +894,53 @@ async function simpanEdit() {
             showToast('Minimal 1 ahli waris wajib diisi', 'error');
             return;
         }
+        lastSavedHeirs = heirs;
+        
+        // Moving or renumbering goes through relocation so the history is kept
+        const pindah = currentEditingGrave &&
+            (blockId !== currentEditingGrave.block_id || nomor !== currentEditingGrave.number);
+        let relocation = null;
+        if (pindah) {
+            const reason = prompt('Blok/nomor makam berubah. Alasan pemindahan:');
+            if (!reason || !reason.trim()) return;
+            const authorizedBy = prompt('Disetujui oleh (nama pengurus):');
+            if (!authorizedBy || !authorizedBy.trim()) return;
+            relocation = {
+                to_block_id: blockId,
+                to_number: nomor,
+                to_position: null,
+                relocation_date: window.astanaApp.tanggalLokal(),
+                reason: reason.trim(),
+                authorized_by: authorizedBy.trim()
+            };
+        }
         
         showLoading(true);
         
+        if (relocation) {
+            try {
+                await invoke('relocate_grave', {
+                    graveId: currentEditingId,
+                    relocation: relocation
+                });
+            } catch (error) {
+                const reason = askCapacityOverride(error);
+                if (!reason) return;
+                await invoke('relocate_grave', {
+                    graveId: currentEditingId,
+                    relocation: relocation,
+                    overrideReason: reason
+                });
+            }
+        }
+        
         // Update grave
         await invoke('update_grave', {
             id: currentEditingId,
             grave: {
                 deceased_name: nama,
-                block_id: blockId,
-                number: nomor,
+                block_id: null,
+                number: null,
                 date_of_death: tanggalWafat,
                 burial_date: null,
                 notes: null
@@ -698,7 +958,8 @@ async function simpanEdit() {
         await loadGraves();
     } catch (error) {
         console.error('Failed to update grave:', error);
-        showToast('Gagal memperbarui data: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, (field) => graveFieldInput(field, 'edit', 'editAhliWarisContainer', lastSavedHeirs));
+        showToast('Gagal memperbarui data: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
@@ -724,7 +985,9 @@ async function confirmDelete() {
     
     try {
         showLoading(true);
-        await invoke('delete_grave', { id: currentDeletingId });
+        // Tahun buku sudah ditutup: admin dapat menghapus dengan alasan
+        const deleted = await window.astanaApp.invokeLocked('delete_grave', { id: currentDeletingId });
+        if (deleted === undefined) return;
         
         closeDeleteModal();
         showToast('Data makam berhasil dihapus', 'success');
@@ -737,6 +1000,94 @@ async function confirmDelete() {
     }
 }
 
+// ==================== DUPLICATES ====================
+
+let duplicateCandidates = [];
+
+async function openDuplicateModal() {
+    document.getElementById('duplicateModal').classList.remove('hidden');
+    await loadDuplicates();
+}
+
+function closeDuplicateModal() {
+    document.getElementById('duplicateModal').classList.add('hidden');
+}
+
+async function loadDuplicates() {
+    const list = document.getElementById('duplicateList');
+    try {
+        duplicateCandidates = await invoke('find_duplicate_graves', { minScore: 70 });
+    } catch (error) {
+        console.error('Failed to find duplicates:', error);
+        list.innerHTML = `<p class="text-sm text-red-600 text-center">Gagal mencari data ganda: ${escapeHtml(String(error))}</p>`;
+        return;
+    }
+    if (duplicateCandidates.length === 0) {
+        list.innerHTML = '<p class="text-sm text-gray-500 text-center">Tidak ada data makam yang terindikasi ganda</p>';
+        return;
+    }
+
+    const graveCard = (g) => `
+        <div class="flex-1 bg-gray-50 rounded-lg p-3 text-sm">
+            <div class="font-medium text-gray-800">${escapeHtml(g.deceased_name)}</div>
+            <div class="text-gray-500">Blok ${escapeHtml(g.block_code)} No. ${escapeHtml(g.number)} &middot; wafat ${formatDate(g.date_of_death)}</div>
+            <div class="text-gray-500">Ahli waris: ${escapeHtml(g.heir_names.join(', ') || '-')}</div>
+            <div class="text-gray-500">${g.payment_count} pembayaran</div>
+        </div>`;
+
+    list.innerHTML = duplicateCandidates.map((c, i) => {
+        const reasons = [`nama ${Math.round(c.name_similarity * 100)}% mirip`];
+        if (c.same_date_of_death) reasons.push('tanggal wafat sama');
+        if (c.same_block) reasons.push('blok sama');
+        if (c.shared_phones.length) reasons.push('telepon ahli waris sama');
+        return `
+            <div class="border border-gray-200 rounded-xl p-4">
+                <div class="flex items-center justify-between mb-3">
+                    <span class="text-sm font-semibold ${c.score >= 85 ? 'text-red-600' : 'text-amber-600'}">Skor ${c.score}</span>
+                    <span class="text-xs text-gray-500">${reasons.join(' &middot; ')}</span>
+                </div>
+                <div class="flex gap-3 mb-3">${graveCard(c.first)}${graveCard(c.second)}</div>
+                <div class="flex flex-wrap gap-2 justify-end">
+                    <button onclick="dismissDuplicate(${i})" class="px-3 py-1.5 text-sm border border-gray-300 rounded-lg hover:bg-gray-50">Bukan Duplikat</button>
+                    <button onclick="mergeDuplicate(${i}, false)" class="px-3 py-1.5 text-sm bg-blue-600 text-white rounded-lg hover:bg-blue-700">Simpan Kiri, Gabung Kanan</button>
+                    <button onclick="mergeDuplicate(${i}, true)" class="px-3 py-1.5 text-sm bg-blue-600 text-white rounded-lg hover:bg-blue-700">Simpan Kanan, Gabung Kiri</button>
+                </div>
+            </div>`;
+    }).join('');
+}
+
+async function dismissDuplicate(index) {
+    const c = duplicateCandidates[index];
+    try {
+        await invoke('dismiss_duplicate', { graveId: c.first.id, otherGraveId: c.second.id });
+        await loadDuplicates();
+    } catch (error) {
+        console.error('Failed to dismiss duplicate:', error);
+        showToast('Gagal menyimpan: ' + error, 'error');
+    }
+}
+
+async function mergeDuplicate(index, keepSecond) {
+    const c = duplicateCandidates[index];
+    const [keep, remove] = keepSecond ? [c.second, c.first] : [c.first, c.second];
+    if (!confirm(`Gabungkan data ${remove.deceased_name} (Blok ${remove.block_code} No. ${remove.number}) ke ${keep.deceased_name} (Blok ${keep.block_code} No. ${keep.number})?\n\nAhli waris dan pembayaran dipindahkan, lalu data ${remove.deceased_name} dihapus.`)) {
+        return;
+    }
+    try {
+        showLoading(true);
+        const report = await window.astanaApp.invokeLocked('merge_graves', { keepId: keep.id, removeId: remove.id });
+        if (report === undefined) return;
+        showToast(`Data digabung: ${report.payments_moved} pembayaran dan ${report.heirs_moved} ahli waris dipindahkan`, 'success');
+        await loadDuplicates();
+        await loadGraves();
+    } catch (error) {
+        console.error('Failed to merge graves:', error);
+        showToast('Gagal menggabungkan data: ' + error, 'error');
+    } finally {
+        showLoading(false);
+    }
+}
+
 // ==================== UTILITIES ====================
 
 function formatDate(dateString) {
@@ -900,8 +1251,7 @@ async function updateExportDataCount() {
         const blockId = blockSelect && blockSelect.value ? parseInt(blockSelect.value) : null;
         
         const count = await invoke('count_graves', {
-            search: search || null,
-            blockId: blockId
+            query: { search: search || null, block_id: blockId }
         });
         
         document.getElementById('exportDataCount').textContent = `${count} data makam`;
@@ -915,6 +1265,12 @@ async function confirmExportExcel() {
     await exportToExcel(exportStartYear, exportEndYear);
 }
 
+// Sheets ticked in the export modal, in workbook order
+function selectedExportSheets() {
+    return Array.from(document.querySelectorAll('input[name="exportSheet"]:checked'))
+        .map(input => input.value);
+}
+
 async function exportToExcel(startYear, endYear) {
     try {
         showLoading(true);
@@ -930,169 +1286,37 @@ async function exportToExcel(startYear, endYear) {
         const allBtn = document.querySelector('button[data-range="all"]');
         const isAll = allBtn && allBtn.classList.contains('active');
         
-        // Fetch all graves with heirs for export
-        const result = await invoke('export_graves', {
-            search: search || null,
-            blockId: blockId,
-            startYear: isAll ? null : startYear,
-            endYear: isAll ? null : endYear
-        });
-        
-        const exportData = result.graves;
-        const actualStartYear = result.start_year;
-        const actualEndYear = result.end_year;
-        
-        if (exportData.length === 0) {
-            showToast('Tidak ada data untuk diexport', 'error');
-            showLoading(false);
+        const sheets = selectedExportSheets();
+        if (sheets.length === 0) {
+            showToast('Pilih minimal satu sheet untuk diexport', 'error');
             return;
         }
         
-        // Determine years to show - use actual years from database if "all"
-        const yearsToShow = [];
-        const displayStartYear = isAll ? actualStartYear : startYear;
-        const displayEndYear = isAll ? actualEndYear : endYear;
-        
-        for (let year = displayStartYear; year <= displayEndYear; year++) {
-            yearsToShow.push(year);
+        const count = await invoke('count_graves', {
+            query: { search: search || null, block_id: blockId }
+        });
+        if (count === 0) {
+            showToast('Tidak ada data untuk diexport', 'error');
+            return;
         }
         
-        // Prepare data for Excel
-        const excelData = exportData.map((item, index) => {
-            const row = {
-                'No': index + 1,
-                'Nama Almarhum': item.deceased_name,
-                'Blok': item.block_code,
-                'Nomor Makam': item.number,
-                'Tanggal Wafat': formatDateForExport(item.date_of_death),
-                'Tanggal Pemakaman': item.burial_date ? formatDateForExport(item.burial_date) : '-',
-                'Iuran Tahunan': item.annual_fee ? formatRupiah(item.annual_fee) : '-',
-                'Catatan': item.notes || '-',
-            };
-            
-            // Add heir data (up to 3)
-            for (let i = 0; i < 3; i++) {
-                const heir = item.heirs[i];
-                const num = i + 1;
-                if (heir) {
-                    row[`Ahli Waris ${num}`] = heir.full_name;
-                    row[`No. HP ${num}`] = heir.phone_number || '-';
-                    row[`Hubungan ${num}`] = formatRelationship(heir.relationship);
-                    row[`Alamat ${num}`] = heir.address || '-';
-                } else {
-                    row[`Ahli Waris ${num}`] = '-';
-                    row[`No. HP ${num}`] = '-';
-                    row[`Hubungan ${num}`] = '-';
-                    row[`Alamat ${num}`] = '-';
-                }
+        // Workbook is generated in Rust and saved with the save dialog
+        const savedPath = await invoke('export_excel', {
+            request: {
+                sheets,
+                search: search || null,
+                block_id: blockId,
+                start_year: isAll ? null : startYear,
+                end_year: isAll ? null : endYear,
+                report_year: null
             }
-            
-            // Add payment status for each year
-            let totalPaid = 0;
-            let yearsPaid = 0;
-            
-            yearsToShow.forEach(year => {
-                const payment = item.payments.find(p => p.year === year);
-                if (payment) {
-                    row[`Status ${year}`] = `Lunas (${formatRupiah(payment.amount)})`;
-                    row[`Tgl Bayar ${year}`] = formatDateForExport(payment.payment_date);
-                    totalPaid += payment.amount;
-                    yearsPaid++;
-                } else {
-                    const isPast = year < currentYear;
-                    row[`Status ${year}`] = isPast ? 'Tunggakan' : 'Belum Jatuh Tempo';
-                    row[`Tgl Bayar ${year}`] = '-';
-                }
-            });
-            
-            // Summary columns
-            row['Total Dibayar'] = formatRupiah(totalPaid);
-            row['Jumlah Tahun Lunas'] = yearsPaid;
-            
-            return row;
         });
         
-        // Create workbook
-        const wb = XLSX.utils.book_new();
-        const ws = XLSX.utils.json_to_sheet(excelData);
-        
-        // Set column widths
-        const colWidths = [
-            { wch: 5 },   // No
-            { wch: 25 },  // Nama Almarhum
-            { wch: 8 },   // Blok
-            { wch: 12 },  // Nomor Makam
-            { wch: 15 },  // Tanggal Wafat
-            { wch: 15 },  // Tanggal Pemakaman
-            { wch: 15 },  // Iuran Tahunan
-            { wch: 20 },  // Catatan
-            { wch: 20 },  // Ahli Waris 1
-            { wch: 15 },  // No. HP 1
-            { wch: 12 },  // Hubungan 1
-            { wch: 25 },  // Alamat 1
-            { wch: 20 },  // Ahli Waris 2
-            { wch: 15 },  // No. HP 2
-            { wch: 12 },  // Hubungan 2
-            { wch: 25 },  // Alamat 2
-            { wch: 20 },  // Ahli Waris 3
-            { wch: 15 },  // No. HP 3
-            { wch: 12 },  // Hubungan 3
-            { wch: 25 },  // Alamat 3
-            { wch: 20 },  // Status tahun
-            { wch: 15 },  // Tgl Bayar tahun
-            { wch: 20 },  // Status tahun
-            { wch: 15 },  // Tgl Bayar tahun
-            { wch: 20 },  // Status tahun
-            { wch: 15 },  // Tgl Bayar tahun
-            { wch: 20 },  // Status tahun
-            { wch: 15 },  // Tgl Bayar tahun
-            { wch: 15 },  // Total Dibayar
-            { wch: 10 },  // Jumlah Tahun Lunas
-        ];
-        ws['!cols'] = colWidths;
-        
-        // Add worksheet to workbook
-        XLSX.utils.book_append_sheet(wb, ws, 'Data Makam');
-        
-        // Generate filename with timestamp
-        const now = new Date();
-        const timestamp = `${now.getFullYear()}${String(now.getMonth() + 1).padStart(2, '0')}${String(now.getDate()).padStart(2, '0')}_${String(now.getHours()).padStart(2, '0')}${String(now.getMinutes()).padStart(2, '0')}`;
-        const defaultFilename = `Data_Makam_${timestamp}.xlsx`;
-        
-        // Write to array buffer
-        const excelBuffer = XLSX.write(wb, { bookType: 'xlsx', type: 'array' });
-        const blob = new Blob([excelBuffer], { type: 'application/vnd.openxmlformats-officedocument.spreadsheetml.sheet' });
-        
-        // Check if running in Tauri
-        if (window.__TAURI__) {
-            try {
-                // Convert blob to array
-                const arrayBuffer = await blob.arrayBuffer();
-                const uint8Array = new Uint8Array(arrayBuffer);
-                
-                // Convert to regular array for Tauri
-                const fileData = Array.from(uint8Array);
-                
-                // Use Tauri command to save with dialog
-                const savedPath = await invoke('save_excel_file', {
-                    fileData: fileData,
-                    defaultName: defaultFilename
-                });
-                
-                if (savedPath) {
-                    showToast(`Berhasil export ${exportData.length} data ke:\n${savedPath}`, 'success');
-                } else {
-                    // User cancelled
-                    showToast('Export dibatalkan', 'info');
-                }
-            } catch (tauriError) {
-                console.error('Tauri save failed:', tauriError);
-                // Fallback to browser download
-                fallbackDownload(blob, defaultFilename, exportData.length);
-            }
+        if (savedPath) {
+            showToast(`Berhasil export data ke:\n${savedPath}`, 'success');
         } else {
-            // Browser mode - fallback download
-            fallbackDownload(blob, defaultFilename, exportData.length);
+            // User cancelled
+            showToast('Export dibatalkan', 'info');
         }
         
     } catch (error) {
@@ -1103,76 +1327,27 @@ async function exportToExcel(startYear, endYear) {
     }
 }
 
-function fallbackDownload(blob, filename, dataCount) {
-    // Create download link
-    const url = URL.createObjectURL(blob);
-    const a = document.createElement('a');
-    a.href = url;
-    a.download = filename;
-    document.body.appendChild(a);
-    a.click();
-    document.body.removeChild(a);
-    URL.revokeObjectURL(url);
-    
-    // Get OS info for message
-    const osInfo = getOSInfo();
-    showToast(`Berhasil export ${dataCount} data ke folder Downloads (${osInfo})`, 'success');
-}
-
-function getOSInfo() {
-    const userAgent = navigator.userAgent;
-    let os = 'Unknown OS';
-    
-    if (userAgent.indexOf('Win') !== -1) os = 'Windows';
-    else if (userAgent.indexOf('Mac') !== -1) os = 'macOS';
-    else if (userAgent.indexOf('Linux') !== -1) os = 'Linux';
-    else if (userAgent.indexOf('X11') !== -1) os = 'UNIX';
-    
-    return os;
-}
-
-function formatDateForExport(dateString) {
-    if (!dateString) return '-';
-    const date = new Date(dateString);
-    return date.toLocaleDateString('id-ID', { 
-        day: '2-digit', 
-        month: '2-digit', 
-        year: 'numeric' 
-    });
-}
-
-function formatRelationship(relationship) {
-    if (!relationship) return '-';
-    const map = {
-        'anak': 'Anak',
-        'istri': 'Istri',
-        'suami': 'Suami',
-        'cucu': 'Cucu',
-        'saudara': 'Saudara',
-        'lainnya': 'Lainnya'
-    };
-    return map[relationship] || relationship;
-}
-
-function formatRupiah(amount) {
-    if (!amount || amount === 0) return 'Rp 0';
-    return 'Rp ' + amount.toString().replace(/\B(?=(\d{3})+(?!\d))/g, '.');
-}
-
 // Expose functions to global scope for onclick handlers
 window.openModal = openModal;
 window.closeModal = closeModal;
 window.tambahAhliWaris = tambahAhliWaris;
 window.hapusAhliWarisTerakhir = hapusAhliWarisTerakhir;
 window.simpanData = simpanData;
+window.selectPlotCell = selectPlotCell;
 window.openEditModal = openEditModal;
 window.closeEditModal = closeEditModal;
 window.tambahAhliWarisEdit = tambahAhliWarisEdit;
 window.hapusAhliWarisTerakhirEdit = hapusAhliWarisTerakhirEdit;
 window.simpanEdit = simpanEdit;
+window.tambahTumpang = tambahTumpang;
+window.hapusTumpang = hapusTumpang;
 window.openDeleteModal = openDeleteModal;
 window.closeDeleteModal = closeDeleteModal;
 window.confirmDelete = confirmDelete;
+window.openDuplicateModal = openDuplicateModal;
+window.closeDuplicateModal = closeDuplicateModal;
+window.dismissDuplicate = dismissDuplicate;
+window.mergeDuplicate = mergeDuplicate;
 window.goToPage = goToPage;
 window.exportToExcel = exportToExcel;
 window.openExportExcelModal = openExportExcelModal;
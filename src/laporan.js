@@ -342,9 +342,33 @@ function exportToPDF() {
     alert('Fitur export PDF akan segera tersedia!');
 }
 
-// Export to Excel (placeholder)
-function exportToExcel() {
-    alert('Fitur export Excel akan segera tersedia!');
+// Export yearly report to Excel (generated in Rust)
+async function exportToExcel() {
+    if (!invoke) {
+        alert('Export Excel hanya tersedia di aplikasi desktop');
+        return;
+    }
+    
+    try {
+        const tahun = parseInt(document.getElementById('tahunSelect').value);
+        const savedPath = await invoke('export_excel', {
+            request: {
+                sheets: ['yearly_report'],
+                search: null,
+                block_id: null,
+                start_year: null,
+                end_year: null,
+                report_year: tahun
+            }
+        });
+        
+        if (savedPath) {
+            alert('Laporan berhasil disimpan ke:\n' + savedPath);
+        }
+    } catch (error) {
+        console.error('Error exporting report:', error);
+        alert('Gagal export laporan: ' + error);
+    }
 }
 
 // Make functions available globally for onclick handlers
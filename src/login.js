@@ -0,0 +1,53 @@
+// Login page - Astana
+// Pada penggunaan pertama (belum ada user) halaman ini membuat akun admin
+
+const { invoke } = window.__TAURI__?.core || {};
+
+let setupMode = false;
+
+document.addEventListener('DOMContentLoaded', async () => {
+  if (!invoke) return;
+
+  try {
+    setupMode = !(await invoke('has_users'));
+  } catch (error) {
+    console.error('Error checking users:', error);
+  }
+
+  if (setupMode) {
+    document.getElementById('formSubtitle').textContent = 'Buat akun admin pertama';
+    document.getElementById('fullNameField').classList.remove('hidden');
+    document.getElementById('fullName').required = true;
+    document.getElementById('submitButton').textContent = 'Buat Akun Admin';
+  }
+
+  document.getElementById('loginForm').addEventListener('submit', handleSubmit);
+});
+
+async function handleSubmit(event) {
+  event.preventDefault();
+
+  const username = document.getElementById('username').value.trim();
+  const password = document.getElementById('password').value;
+  const errorEl = document.getElementById('loginError');
+  errorEl.classList.add('hidden');
+
+  try {
+    if (setupMode) {
+      await invoke('setup_admin', {
+        user: {
+          username,
+          full_name: document.getElementById('fullName').value.trim(),
+          password,
+          role: 'admin',
+        },
+      });
+    } else {
+      await invoke('login', { username, password });
+    }
+    window.location.href = 'index.html';
+  } catch (error) {
+    errorEl.textContent = error;
+    errorEl.classList.remove('hidden');
+  }
+}
@@ -4,6 +4,22 @@
 // Initialize Tauri API
 const { invoke } = window.__TAURI__?.core || {};
 
+// Pesan validasi backend per kode error (lihat src-tauri/src/db/validation.rs)
+const VALIDATION_MESSAGES = {
+  required: 'Wajib diisi',
+  invalid_date: 'Tanggal tidak valid',
+  future_date: 'Tanggal tidak boleh melewati hari ini',
+  before_date_of_death: 'Tanggal pemakaman tidak boleh sebelum tanggal wafat',
+  negative: 'Tidak boleh negatif',
+  too_short: 'Terlalu pendek',
+  out_of_range: 'Di luar rentang yang diizinkan',
+  invalid_choice: 'Pilihan tidak valid',
+  invalid_phone: 'Nomor telepon tidak valid',
+  invalid_email: 'Alamat email tidak valid',
+  invalid_username: 'Username tidak boleh berisi spasi',
+  closed_year: 'Tahun buku sudah ditutup'
+};
+
 // Fungsi utilitas yang bisa digunakan di seluruh aplikasi
 window.astanaApp = {
   // Fungsi untuk navigasi ke halaman lain
@@ -30,6 +46,73 @@ window.astanaApp = {
   // Format rupiah
   formatRupiah: (angka) => {
     return 'Rp ' + angka.toLocaleString('id-ID');
+  },
+
+  // Tanggal hari ini (YYYY-MM-DD) menurut jam lokal; toISOString memakai UTC
+  tanggalLokal: (date = new Date()) => {
+    const pad = (n) => String(n).padStart(2, '0');
+    return `${date.getFullYear()}-${pad(date.getMonth() + 1)}-${pad(date.getDate())}`;
+  },
+
+  // Error validasi per field dari backend, atau null untuk error lain
+  validationErrors: (error) => {
+    try {
+      const parsed = JSON.parse(String(error));
+      return Array.isArray(parsed?.validation) ? parsed.validation : null;
+    } catch {
+      return null;
+    }
+  },
+
+  // Teks error yang bisa ditampilkan di toast
+  errorText: (error) => {
+    const errors = window.astanaApp.validationErrors(error);
+    if (!errors) return String(error);
+    return errors.map((e) => VALIDATION_MESSAGES[e.code] || e.message).join(', ');
+  },
+
+  // Tampilkan error validasi di bawah input. `fields` memetakan nama field
+  // ke ID input, atau fungsi (field) => elemen. Mengembalikan true bila
+  // error adalah error validasi.
+  showFieldErrors: (error, fields) => {
+    window.astanaApp.clearFieldErrors();
+    const errors = window.astanaApp.validationErrors(error);
+    if (!errors) return false;
+    errors.forEach((e) => {
+      const input = typeof fields === 'function'
+        ? fields(e.field)
+        : document.getElementById(fields[e.field]);
+      if (!input) return;
+      input.classList.add('border-red-500');
+      input.dataset.fieldError = 'true';
+      const message = document.createElement('p');
+      message.className = 'field-error text-xs text-red-600 mt-1';
+      message.textContent = VALIDATION_MESSAGES[e.code] || e.message;
+      input.insertAdjacentElement('afterend', message);
+    });
+    return true;
+  },
+
+  // Panggil command yang ditolak bila menyentuh tahun buku yang sudah
+  // ditutup; admin dapat melanjutkan dengan alasan. Mengembalikan undefined
+  // bila dibatalkan (command tanpa hasil mengembalikan null).
+  invokeLocked: async (command, args) => {
+    try {
+      return await invoke(command, args);
+    } catch (error) {
+      if (!String(error).includes('is closed')) throw error;
+      const reason = prompt('Tahun buku sudah ditutup. Alasan perubahan (khusus admin):');
+      if (!reason) return undefined;
+      return await invoke(command, { ...args, overrideReason: reason });
+    }
+  },
+
+  clearFieldErrors: () => {
+    document.querySelectorAll('.field-error').forEach((el) => el.remove());
+    document.querySelectorAll('[data-field-error]').forEach((el) => {
+      el.classList.remove('border-red-500');
+      delete el.dataset.fieldError;
+    });
   }
 };
 
@@ -100,6 +183,53 @@ function updateSidebar(name, subtitle, logoDataUrl) {
   });
 }
 
+// Redirect to login page when there is no active session
+async function checkSession() {
+  if (!invoke) return null;
+
+  try {
+    const session = await invoke('get_current_session');
+    if (!session) {
+      window.location.href = 'login.html';
+      return null;
+    }
+    showSessionUser(session);
+    return session;
+  } catch (error) {
+    console.error('Error checking session:', error);
+    return null;
+  }
+}
+
+// Show logged-in user and logout button at the bottom of the sidebar
+function showSessionUser(session) {
+  const roleLabels = {
+    admin: 'Admin',
+    cashier: 'Bendahara/Kasir',
+    clerk: 'Petugas Data',
+    viewer: 'Peninjau',
+  };
+
+  const aside = document.querySelector('aside');
+  if (!aside || aside.querySelector('#sessionUser')) return;
+
+  const container = document.createElement('div');
+  container.id = 'sessionUser';
+  container.className = 'p-4 border-t border-emerald-700 text-sm';
+  container.innerHTML = `
+    <p class="font-medium truncate"></p>
+    <p class="text-xs text-emerald-200 mb-2"></p>
+    <button type="button" class="text-xs text-emerald-200 hover:text-white underline">Keluar</button>
+  `;
+  container.querySelector('p.font-medium').textContent = session.full_name;
+  container.querySelector('p.text-xs').textContent = roleLabels[session.role] || session.role;
+  container.querySelector('button').addEventListener('click', async () => {
+    await invoke('logout');
+    window.location.href = 'login.html';
+  });
+  aside.appendChild(container);
+}
+
 // Listen for settings updates from other pages
 window.addEventListener('storage', (e) => {
   if (e.key === 'settingsUpdated') {
@@ -110,12 +240,14 @@ window.addEventListener('storage', (e) => {
 
 // Initialize when DOM is ready
 document.addEventListener('DOMContentLoaded', () => {
+  checkSession();
   loadSidebarInfo();
 });
 
 // Expose functions globally for cross-page communication
 window.loadSidebarInfo = loadSidebarInfo;
 window.updateSidebar = updateSidebar;
+window.checkSession = checkSession;
 
 // Log aplikasi sudah siap
 console.log('🕌 Astana - Sistem Wakaf Makam berhasil dimuat');
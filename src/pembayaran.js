@@ -11,6 +11,7 @@ let currentYear = new Date().getFullYear();
 let currentPage = 1;
 let totalPages = 1;
 const itemsPerPage = 10;
+const yearWindow = 5;
 let currentPaymentData = null;
 
 // ==================== INITIALIZATION ====================
@@ -41,8 +42,19 @@ function setupEventListeners() {
         });
     }
     
+    // Status and arrears filters
+    ['statusFilter', 'arrearsFilter'].forEach(id => {
+        const select = document.getElementById(id);
+        if (select) {
+            select.addEventListener('change', async () => {
+                currentPage = 1;
+                await loadPayments();
+            });
+        }
+    });
+    
     // Year filter
-    const yearSelect = document.querySelectorAll('select')[2];
+    const yearSelect = document.getElementById('yearFilter');
     if (yearSelect) {
         yearSelect.addEventListener('change', async (e) => {
             currentYear = parseInt(e.target.value);
@@ -127,20 +139,27 @@ async function loadPayments() {
         const blockSelect = document.querySelector('select');
         const blockId = blockSelect && blockSelect.value ? parseInt(blockSelect.value) : null;
         
-        const offset = (currentPage - 1) * itemsPerPage;
+        const statusSelect = document.getElementById('statusFilter');
+        const arrearsSelect = document.getElementById('arrearsFilter');
         
-        const payments = await invoke('get_graves_with_payment_summary', {
+        const filter = {
             search: search || null,
-            blockId: blockId,
+            block_id: blockId,
             year: currentYear,
+            year_window: yearWindow,
+            status: statusSelect && statusSelect.value ? statusSelect.value : null,
+            min_arrears_years: arrearsSelect && arrearsSelect.value ? parseInt(arrearsSelect.value) : null
+        };
+        
+        const offset = (currentPage - 1) * itemsPerPage;
+        
+        const payments = await invoke('get_graves_with_payment_summary', {
+            filter,
             limit: itemsPerPage,
             offset: offset
         });
         
-        const totalCount = await invoke('count_graves', {
-            search: search || null,
-            blockId: blockId
-        });
+        const totalCount = await invoke('count_graves_with_payment_summary', { filter });
         
         currentPayments = payments;
         totalPages = Math.ceil(totalCount / itemsPerPage) || 1;
@@ -157,7 +176,23 @@ async function loadPayments() {
 
 // ==================== RENDER TABLE ====================
 
+function renderYearHeaders() {
+    const headerRow = document.getElementById('paymentTableHeader');
+    if (!headerRow) return;
+    
+    headerRow.querySelectorAll('th[data-year], th.text-emerald-600').forEach(th => th.remove());
+    for (let year = currentYear - yearWindow + 1; year <= currentYear; year++) {
+        const th = document.createElement('th');
+        th.dataset.year = year;
+        th.className = 'px-3 py-3 text-center text-xs font-semibold text-emerald-600 uppercase tracking-wider border-r w-32';
+        th.textContent = year;
+        headerRow.appendChild(th);
+    }
+}
+
 function renderPaymentsTable() {
+    renderYearHeaders();
+    
     const tbody = document.querySelector('tbody');
     if (!tbody) return;
     
@@ -166,7 +201,7 @@ function renderPaymentsTable() {
     if (currentPayments.length === 0) {
         tbody.innerHTML = `
             <tr>
-                <td colspan="9" class="px-3 py-8 text-center text-gray-500">
+                <td colspan="${4 + yearWindow}" class="px-3 py-8 text-center text-gray-500">
                     Tidak ada data pembayaran
                 </td>
             </tr>
@@ -178,15 +213,17 @@ function renderPaymentsTable() {
         const row = document.createElement('tr');
         row.className = 'hover:bg-gray-50';
         
-        // Generate year columns (5 years)
+        // Generate year columns (one per year of the window)
         let yearCells = '';
         item.recent_payments.forEach(payment => {
             const isPaid = payment.is_paid;
             const amount = payment.amount || item.annual_fee;
-            const btnClass = isPaid 
-                ? 'bg-emerald-100 hover:bg-emerald-200 text-emerald-800' 
-                : 'bg-red-100 hover:bg-red-200 text-red-700';
-            const btnText = isPaid ? formatRupiahShort(amount) : 'Bayar';
+            const btnClass = payment.status === 'paid'
+                ? 'bg-emerald-100 hover:bg-emerald-200 text-emerald-800'
+                : payment.status === 'partial'
+                    ? 'bg-amber-100 hover:bg-amber-200 text-amber-800'
+                    : 'bg-red-100 hover:bg-red-200 text-red-700';
+            const btnText = isPaid ? formatRupiahShort(amount) : (payment.fee_due === 0 ? '-' : 'Bayar');
             
             yearCells += `
                 <td class="px-2 py-2 text-center border-r">
@@ -344,7 +381,7 @@ function renderPaymentModal(isPaid) {
             <div class="space-y-4">
                 <div>
                     <label class="block text-sm font-medium text-gray-700 mb-1.5">Tanggal Pembayaran</label>
-                    <input type="date" id="inputTanggal" class="w-full px-3 py-2.5 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-emerald-500" value="${new Date().toISOString().split('T')[0]}">
+                    <input type="date" id="inputTanggal" class="w-full px-3 py-2.5 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-emerald-500" value="${window.astanaApp.tanggalLokal()}">
                 </div>
                 <div>
                     <label class="block text-sm font-medium text-gray-700 mb-1.5">Jumlah Bayar</label>
@@ -404,6 +441,21 @@ function closeDetailModal() {
     currentPaymentData = null;
 }
 
+// Pembayaran tunai harus masuk ke sesi kas kasir yang sedang terbuka
+async function ensureCashSession() {
+    const cashSession = await invoke('get_open_cash_session');
+    if (cashSession) return true;
+    
+    if (!confirm('Belum ada sesi kas yang terbuka. Buka sesi kas sekarang?')) {
+        return false;
+    }
+    
+    const modalAwal = parseInt(prompt('Modal awal (uang kembalian) dalam Rupiah:', '0') || '0');
+    await invoke('open_cash_session', { openingFloat: isNaN(modalAwal) ? 0 : modalAwal });
+    showToast('Sesi kas dibuka', 'success');
+    return true;
+}
+
 async function processPayment() {
     if (!currentPaymentData) return;
     
@@ -424,9 +476,13 @@ async function processPayment() {
             return;
         }
         
+        if (metode === 'cash' && !(await ensureCashSession())) {
+            return;
+        }
+        
         showLoading(true);
         
-        await invoke('create_payment', {
+        const paymentId = await window.astanaApp.invokeLocked('create_payment', {
             payment: {
                 grave_id: currentPaymentData.graveId,
                 year: currentPaymentData.year,
@@ -438,13 +494,19 @@ async function processPayment() {
                 notes: keterangan || null
             }
         });
+        if (paymentId === undefined) return;
         
         closeDetailModal();
         showToast('Pembayaran berhasil dicatat', 'success');
         await loadPayments();
     } catch (error) {
         console.error('Failed to process payment:', error);
-        showToast('Gagal mencatat pembayaran: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, {
+            payment_date: 'inputTanggal',
+            amount: 'inputJumlah',
+            payment_method: 'inputMetode'
+        });
+        showToast('Gagal mencatat pembayaran: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
@@ -458,9 +520,10 @@ async function deleteCurrentPayment() {
     try {
         showLoading(true);
         
-        await invoke('delete_payment', {
-            id: currentPaymentData.existingPayment.id
-        });
+        const id = currentPaymentData.existingPayment.id;
+        // Tahun buku sudah ditutup: admin dapat menghapus dengan alasan
+        const deleted = await window.astanaApp.invokeLocked('delete_payment', { id });
+        if (deleted === undefined) return;
         
         closeDetailModal();
         showToast('Pembayaran berhasil dihapus', 'success');
@@ -598,8 +661,7 @@ async function updateExportDataCount() {
         const blockId = blockSelect && blockSelect.value ? parseInt(blockSelect.value) : null;
         
         const count = await invoke('count_graves', {
-            search: search || null,
-            blockId: blockId
+            query: { search: search || null, block_id: blockId }
         });
         
         document.getElementById('exportDataCount').textContent = `${count} data makam`;
@@ -613,6 +675,12 @@ async function confirmExportExcel() {
     await exportToExcel(exportStartYear, exportEndYear);
 }
 
+// Sheets ticked in the export modal, in workbook order
+function selectedExportSheets() {
+    return Array.from(document.querySelectorAll('input[name="exportSheet"]:checked'))
+        .map(input => input.value);
+}
+
 async function exportToExcel(startYear, endYear) {
     try {
         showLoading(true);
@@ -628,130 +696,37 @@ async function exportToExcel(startYear, endYear) {
         const allBtn = document.querySelector('button[data-range="all"]');
         const isAll = allBtn && allBtn.classList.contains('active');
         
-        // Fetch all graves with payment data for export
-        const result = await invoke('export_graves', {
-            search: search || null,
-            blockId: blockId,
-            startYear: isAll ? null : startYear,
-            endYear: isAll ? null : endYear
-        });
-        
-        const exportData = result.graves;
-        const actualStartYear = result.start_year;
-        const actualEndYear = result.end_year;
-        
-        if (exportData.length === 0) {
-            showToast('Tidak ada data untuk diexport', 'error');
-            showLoading(false);
+        const sheets = selectedExportSheets();
+        if (sheets.length === 0) {
+            showToast('Pilih minimal satu sheet untuk diexport', 'error');
             return;
         }
         
-        // Determine years to show - use actual years from database if "all"
-        const yearsToShow = [];
-        const displayStartYear = isAll ? actualStartYear : startYear;
-        const displayEndYear = isAll ? actualEndYear : endYear;
-        
-        for (let year = displayStartYear; year <= displayEndYear; year++) {
-            yearsToShow.push(year);
-        }
-        
-        // Prepare data for Excel
-        const excelData = exportData.map((item, index) => {
-            const row = {
-                'No': index + 1,
-                'Nama Almarhum': item.deceased_name,
-                'Blok': item.block_code,
-                'Nomor Makam': item.number,
-                'Iuran Tahunan': item.annual_fee ? formatRupiah(item.annual_fee) : '-',
-            };
-            
-            // Add payment status for each year
-            let totalPaid = 0;
-            let yearsPaid = 0;
-            
-            yearsToShow.forEach(year => {
-                const payment = item.payments.find(p => p.year === year);
-                if (payment) {
-                    row[`Status ${year}`] = `Lunas (${formatRupiah(payment.amount)})`;
-                    totalPaid += payment.amount;
-                    yearsPaid++;
-                } else {
-                    row[`Status ${year}`] = 'Belum Bayar';
-                }
-            });
-            
-            // Summary columns
-            row['Total Dibayar'] = formatRupiah(totalPaid);
-            row['Jumlah Tahun Lunas'] = yearsPaid;
-            
-            return row;
+        const count = await invoke('count_graves', {
+            query: { search: search || null, block_id: blockId }
         });
+        if (count === 0) {
+            showToast('Tidak ada data untuk diexport', 'error');
+            return;
+        }
         
-        // Create workbook
-        const wb = XLSX.utils.book_new();
-        const ws = XLSX.utils.json_to_sheet(excelData);
-        
-        // Set column widths
-        const colWidths = [
-            { wch: 5 },   // No
-            { wch: 25 },  // Nama Almarhum
-            { wch: 8 },   // Blok
-            { wch: 12 },  // Nomor Makam
-            { wch: 15 },  // Iuran Tahunan
-        ];
-        
-        // Add width for each year column
-        yearsToShow.forEach(() => {
-            colWidths.push({ wch: 20 });  // Status tahun
+        // Workbook is generated in Rust and saved with the save dialog
+        const savedPath = await invoke('export_excel', {
+            request: {
+                sheets,
+                search: search || null,
+                block_id: blockId,
+                start_year: isAll ? null : startYear,
+                end_year: isAll ? null : endYear,
+                report_year: null
+            }
         });
         
-        colWidths.push({ wch: 15 });  // Total Dibayar
-        colWidths.push({ wch: 10 });  // Jumlah Tahun Lunas
-        
-        ws['!cols'] = colWidths;
-        
-        // Add worksheet to workbook
-        XLSX.utils.book_append_sheet(wb, ws, 'Data Pembayaran');
-        
-        // Generate filename with timestamp
-        const now = new Date();
-        const timestamp = `${now.getFullYear()}${String(now.getMonth() + 1).padStart(2, '0')}${String(now.getDate()).padStart(2, '0')}_${String(now.getHours()).padStart(2, '0')}${String(now.getMinutes()).padStart(2, '0')}`;
-        const defaultFilename = `Data_Pembayaran_${timestamp}.xlsx`;
-        
-        // Write to array buffer
-        const excelBuffer = XLSX.write(wb, { bookType: 'xlsx', type: 'array' });
-        const blob = new Blob([excelBuffer], { type: 'application/vnd.openxmlformats-officedocument.spreadsheetml.sheet' });
-        
-        // Check if running in Tauri
-        if (window.__TAURI__) {
-            try {
-                // Convert blob to array
-                const arrayBuffer = await blob.arrayBuffer();
-                const uint8Array = new Uint8Array(arrayBuffer);
-                
-                // Convert to regular array for Tauri
-                const fileData = Array.from(uint8Array);
-                
-                // Use Tauri command to save with dialog
-                const savedPath = await invoke('save_excel_file', {
-                    fileData: fileData,
-                    defaultName: defaultFilename
-                });
-                
-                if (savedPath) {
-                    showToast(`Berhasil export ${exportData.length} data ke:\n${savedPath}`, 'success');
-                } else {
-                    // User cancelled
-                    showToast('Export dibatalkan', 'info');
-                }
-            } catch (tauriError) {
-                console.error('Tauri save failed:', tauriError);
-                // Fallback to browser download
-                fallbackDownload(blob, defaultFilename, exportData.length);
-            }
+        if (savedPath) {
+            showToast(`Berhasil export data ke:\n${savedPath}`, 'success');
         } else {
-            // Browser mode - fallback download
-            fallbackDownload(blob, defaultFilename, exportData.length);
+            // User cancelled
+            showToast('Export dibatalkan', 'info');
         }
         
     } catch (error) {
@@ -762,20 +737,6 @@ async function exportToExcel(startYear, endYear) {
     }
 }
 
-function fallbackDownload(blob, filename, dataCount) {
-    // Create download link
-    const url = URL.createObjectURL(blob);
-    const a = document.createElement('a');
-    a.href = url;
-    a.download = filename;
-    document.body.appendChild(a);
-    a.click();
-    document.body.removeChild(a);
-    URL.revokeObjectURL(url);
-    
-    showToast(`Berhasil export ${dataCount} data ke folder Downloads`, 'success');
-}
-
 // ==================== UTILITIES ====================
 
 function formatDate(dateString) {
@@ -791,6 +752,99 @@ function escapeHtml(text) {
     return div.innerHTML;
 }
 
+// ==================== BAYAR PER AHLI WARIS ====================
+
+let currentPersonId = null;
+let personSearchTimer = null;
+
+function openPersonPaymentModal() {
+    currentPersonId = null;
+    document.getElementById('personSearchInput').value = '';
+    document.getElementById('personSearchResults').innerHTML = '';
+    document.getElementById('personArrears').classList.add('hidden');
+    document.getElementById('personPaymentModal').classList.remove('hidden');
+}
+
+function closePersonPaymentModal() {
+    document.getElementById('personPaymentModal').classList.add('hidden');
+}
+
+function searchPersonsForPayment() {
+    clearTimeout(personSearchTimer);
+    personSearchTimer = setTimeout(async () => {
+        const query = document.getElementById('personSearchInput').value.trim();
+        const results = document.getElementById('personSearchResults');
+        if (query.length < 2) {
+            results.innerHTML = '';
+            return;
+        }
+        try {
+            const persons = await invoke('search_persons', { query, limit: 10 });
+            results.innerHTML = persons.length === 0
+                ? '<p class="py-2 text-sm text-gray-500">Tidak ditemukan</p>'
+                : persons.map(p => `
+                    <button onclick="selectPersonForPayment(${p.id})" class="w-full text-left py-2 px-2 hover:bg-gray-50 text-sm">
+                        <span class="font-medium text-gray-800">${escapeHtml(p.full_name)}</span>
+                        <span class="text-gray-500">${escapeHtml(p.phone_number || '')} · ${p.grave_count} makam</span>
+                    </button>`).join('');
+        } catch (error) {
+            console.error('Failed to search persons:', error);
+        }
+    }, 300);
+}
+
+async function selectPersonForPayment(personId) {
+    try {
+        const arrears = await invoke('get_person_arrears', { personId });
+        currentPersonId = personId;
+        document.getElementById('personSearchResults').innerHTML = '';
+        document.getElementById('personSearchInput').value = arrears.person.full_name;
+        document.getElementById('personArrearsList').innerHTML = arrears.graves.map(g => `
+            <div class="flex items-center justify-between p-3 bg-gray-50 rounded-lg text-sm">
+                <div>
+                    <p class="font-medium text-gray-800">${escapeHtml(g.deceased_name)}</p>
+                    <p class="text-gray-500">Blok ${escapeHtml(g.block_code)} No. ${escapeHtml(g.number)}${g.unpaid_years.length ? ' · ' + g.unpaid_years.map((u) => u.year).join(', ') : ''}</p>
+                </div>
+                <span class="${g.arrears > 0 ? 'text-red-600' : 'text-emerald-600'} font-semibold">${g.arrears > 0 ? formatRupiah(g.arrears) : 'Lunas'}</span>
+            </div>`).join('');
+        document.getElementById('personArrearsTotal').textContent = formatRupiah(arrears.total_arrears);
+        document.getElementById('personArrears').classList.remove('hidden');
+    } catch (error) {
+        console.error('Failed to load person arrears:', error);
+        showToast('Gagal memuat tunggakan: ' + error, 'error');
+    }
+}
+
+async function payPersonArrears() {
+    if (!currentPersonId) return;
+    const total = document.getElementById('personArrearsTotal').textContent;
+    if (!confirm(`Catat pembayaran semua tunggakan (${total})?`)) return;
+
+    try {
+        showLoading(true);
+        const result = await window.astanaApp.invokeLocked('pay_person_arrears', {
+            personId: currentPersonId,
+            payment: {
+                grave_ids: null,
+                payment_date: window.astanaApp.tanggalLokal(),
+                payment_method: 'cash',
+                payment_proof: null,
+                paid_by: null,
+                notes: 'Pembayaran gabungan'
+            }
+        });
+        if (result === undefined) return;
+        closePersonPaymentModal();
+        showToast(`${result.payment_ids.length} pembayaran untuk ${result.graves_paid} makam tercatat (${formatRupiah(result.total_amount)})`, 'success');
+        await loadPayments();
+    } catch (error) {
+        console.error('Failed to pay person arrears:', error);
+        showToast('Gagal mencatat pembayaran: ' + window.astanaApp.errorText(error), 'error');
+    } finally {
+        showLoading(false);
+    }
+}
+
 function showLoading(show) {
     if (show) {
         document.body.style.cursor = 'wait';
@@ -828,3 +882,8 @@ window.setExportRange = setExportRange;
 window.updateYearPreview = updateYearPreview;
 window.confirmExportExcel = confirmExportExcel;
 window.exportToExcel = exportToExcel;
+window.openPersonPaymentModal = openPersonPaymentModal;
+window.closePersonPaymentModal = closePersonPaymentModal;
+window.searchPersonsForPayment = searchPersonsForPayment;
+window.selectPersonForPayment = selectPersonForPayment;
+window.payPersonArrears = payPersonArrears;
@@ -113,12 +113,14 @@ async function loadSettings() {
         const foundationPhone = document.getElementById('foundationPhone');
         const foundationEmail = document.getElementById('foundationEmail');
         const autoBackup = document.getElementById('autoBackup');
+        const timezone = document.getElementById('timezone');
         
         if (foundationName) foundationName.value = settings.foundation_name || '';
         if (foundationAddress) foundationAddress.value = settings.address || '';
         if (foundationPhone) foundationPhone.value = settings.phone || '';
         if (foundationEmail) foundationEmail.value = settings.email || '';
         if (autoBackup) autoBackup.checked = settings.auto_backup || false;
+        if (timezone) timezone.value = settings.timezone || 'Asia/Jakarta';
         
     } catch (error) {
         console.error('Error loading settings:', error);
@@ -240,6 +242,7 @@ async function saveSettings() {
         const foundationPhoneEl = document.getElementById('foundationPhone');
         const foundationEmailEl = document.getElementById('foundationEmail');
         const autoBackupEl = document.getElementById('autoBackup');
+        const timezoneEl = document.getElementById('timezone');
         
         let logoPath = null;
         
@@ -271,7 +274,8 @@ async function saveSettings() {
             email: foundationEmailEl?.value || null,
             logo_path: logoPath,
             active_year: currentYear,  // Use current year from computer
-            auto_backup: autoBackupEl?.checked || false
+            auto_backup: autoBackupEl?.checked || false,
+            timezone: timezoneEl?.value || null
         };
         
         if (!invoke) {
@@ -309,7 +313,12 @@ async function saveSettings() {
         
     } catch (error) {
         console.error('Error saving settings:', error);
-        showNotification('Gagal menyimpan pengaturan: ' + error, 'error');
+        window.astanaApp.showFieldErrors(error, {
+            foundation_name: 'foundationName',
+            email: 'foundationEmail',
+            timezone: 'timezone'
+        });
+        showNotification('Gagal menyimpan pengaturan: ' + window.astanaApp.errorText(error), 'error');
     } finally {
         showLoading(false);
     }
@@ -352,7 +361,7 @@ async function backupNow() {
         
         // Get database path for default filename
         const dbPath = await invoke('get_database_path');
-        const defaultName = 'astana_backup_' + new Date().toISOString().split('T')[0] + '.db';
+        const defaultName = 'astana_backup_' + window.astanaApp.tanggalLokal() + '.db';
         
         // Use dialog:save command
         const savePath = await invoke('dialog:save', {
@@ -391,7 +400,7 @@ async function exportDatabase() {
             return;
         }
         
-        const defaultName = 'astana_backup_' + new Date().toISOString().split('T')[0] + '.db';
+        const defaultName = 'astana_backup_' + window.astanaApp.tanggalLokal() + '.db';
         
         // Use dialog:save command
         const savePath = await invoke('dialog:save', {